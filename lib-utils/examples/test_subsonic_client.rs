@@ -5,7 +5,10 @@
 // 1. 在 conf/config.json 中配置了 Subsonic 服务器信息
 // 2. Subsonic 服务器可访问
 
-use lib_utils::{config::get_config, datasource::subsonic::client::SubsonicClient};
+use lib_utils::{
+    config::get_config,
+    datasource::{subsonic::client::SubsonicClient, types::StreamOptions},
+};
 
 #[tokio::main]
 async fn main() {
@@ -137,7 +140,7 @@ async fn main() {
                             if let Some(songs) = &album.song {
                                 if let Some(first_song) = songs.first() {
                                     println!("\n6. 获取歌曲流式 URL ({})", first_song.title);
-                                    let stream_url = client.get_stream_url(&first_song.id, 320, "mp3");
+                                    let stream_url = client.get_stream_url(&first_song.id, &StreamOptions::from_profile("high").unwrap_or_default());
                                     println!("   {}", stream_url);
 
                                     // 7. 获取单个歌曲信息
@@ -0,0 +1,380 @@
+// 声学指纹
+// 参考 Chromaprint: 重采样到 11025Hz 后以 4096 个样本为一帧、每 1/3 帧计算一次 28Hz~3.5kHz 的 12 维色度向量, 按能量累加, 在时间上平滑并归一化后, 由前后帧、不同音高类别之间的大小关系得到每帧一个 32 位的子指纹。只分析开头 120 秒。同一录音的不同编码 (格式、码率、采样率不同) 对齐后比特错误率很低, 不相关的音频约为 0.5。
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::path::Path;
+
+use anyhow::{Error, Result};
+
+use super::spectrum::{Downmixer, Stft};
+
+/// 分析的采样率, 不同采样率的文件重采样后时间和频率都能对齐
+const RATE: f32 = 11025.0;
+const FRAME: usize = 4096;
+const HOP: usize = FRAME / 3;
+const MIN_FREQ: f32 = 28.0;
+const MAX_FREQ: f32 = 3520.0;
+/// 只分析开头的时长 (秒)
+const MAX_SECONDS: f32 = 120.0;
+/// 色度向量在时间上的平滑系数
+const SMOOTHING: [f32; 5] = [0.25, 0.75, 1.0, 0.75, 0.25];
+/// 时间方向比较的帧间隔
+const LAG: usize = 3;
+/// 相似度达到此值时认为是同一录音
+pub const DUPLICATE_SIMILARITY: f64 = 0.85;
+/// 比较时重叠部分的最少帧数 (约 10 秒)
+const MIN_OVERLAP: usize = 80;
+/// 倒排索引使用子指纹的高 20 位 (音高类别之间的比较, 比前后帧的比较稳定)
+const INDEX_SHIFT: u32 = 12;
+/// 出现次数超过此值的索引键 (如长时间的单音) 不参与匹配
+const MAX_POSTINGS: usize = 500;
+/// 同一偏移上至少有这么多帧的索引键相同时才计算相似度
+const MIN_VOTES: u32 = 3;
+
+/// 每帧一个 32 位子指纹
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint(pub Vec<u32>);
+
+impl Fingerprint {
+    /// 小端序字节, 用于保存到数据库
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.iter().flat_map(|value| value.to_le_bytes()).collect()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Fingerprint> {
+        if bytes.is_empty() || !bytes.len().is_multiple_of(4) {
+            return None;
+        }
+        let values = bytes
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+        Some(Fingerprint(values))
+    }
+
+    /// 把 `other` 的第 `i + offset` 帧与本指纹的第 `i` 帧对齐时的相似度 (1 - 比特错误率)
+    ///
+    /// 两边都是静音 (子指纹为 0) 的帧不计入, 有效的重叠部分太短时为 0
+    pub fn similarity_at(&self, other: &Fingerprint, offset: i64) -> f64 {
+        let (a, b) = (&self.0, &other.0);
+        let start = (-offset).max(0);
+        let end = (a.len() as i64).min(b.len() as i64 - offset);
+        let (mut frames, mut errors) = (0usize, 0u32);
+        for i in start..end {
+            let (x, y) = (a[i as usize], b[(i + offset) as usize]);
+            if x == 0 && y == 0 {
+                continue;
+            }
+            frames += 1;
+            errors += (x ^ y).count_ones();
+        }
+        if frames < MIN_OVERLAP {
+            return 0.0;
+        }
+        1.0 - errors as f64 / (32 * frames) as f64
+    }
+
+    /// 所有偏移中的最高相似度
+    pub fn similarity(&self, other: &Fingerprint) -> f64 {
+        let (a, b) = (self.0.len() as i64, other.0.len() as i64);
+        (-a + 1..b)
+            .map(|offset| self.similarity_at(other, offset))
+            .fold(0.0, f64::max)
+    }
+}
+
+/// 找出一组指纹中相似度不低于 `threshold` 的配对 `(i, j, 相似度)`, 其中 i < j
+///
+/// 先用倒排索引找出同一偏移上有多帧相同的候选, 只对候选计算相似度, 避免两两比较
+pub fn find_matches(prints: &[Fingerprint], threshold: f64) -> Vec<(usize, usize, f64)> {
+    let mut index: HashMap<u32, Vec<(usize, i64)>> = HashMap::new();
+    for (song, print) in prints.iter().enumerate() {
+        for (position, value) in print.0.iter().enumerate() {
+            let key = value >> INDEX_SHIFT;
+            if key != 0 {
+                index.entry(key).or_default().push((song, position as i64));
+            }
+        }
+    }
+
+    let mut matches = Vec::new();
+    for (i, print) in prints.iter().enumerate() {
+        let mut votes: HashMap<(usize, i64), u32> = HashMap::new();
+        for (position, value) in print.0.iter().enumerate() {
+            let Some(postings) = index.get(&(value >> INDEX_SHIFT)) else {
+                continue;
+            };
+            if postings.len() > MAX_POSTINGS {
+                continue;
+            }
+            for &(j, other_position) in postings {
+                if j > i {
+                    *votes.entry((j, other_position - position as i64)).or_default() += 1;
+                }
+            }
+        }
+
+        // 每个候选取票数最多的偏移
+        let mut best: HashMap<usize, (i64, u32)> = HashMap::new();
+        for ((j, offset), count) in votes {
+            let entry = best.entry(j).or_insert((offset, 0));
+            if count > entry.1 || (count == entry.1 && offset < entry.0) {
+                *entry = (offset, count);
+            }
+        }
+        let mut candidates: Vec<(usize, i64)> = best
+            .into_iter()
+            .filter(|(_, (_, count))| *count >= MIN_VOTES)
+            .map(|(j, (offset, _))| (j, offset))
+            .collect();
+        candidates.sort_unstable();
+        for (j, offset) in candidates {
+            let similarity = (offset - 1..=offset + 1)
+                .map(|offset| print.similarity_at(&prints[j], offset))
+                .fold(0.0, f64::max);
+            if similarity >= threshold {
+                matches.push((i, j, similarity));
+            }
+        }
+    }
+    matches
+}
+
+/// 线性插值重采样
+struct Resampler {
+    /// 输出样本之间的间隔 (以输入样本为单位)
+    step: f64,
+    /// 下一个输出样本的位置
+    position: f64,
+    /// 当前输入样本的位置
+    index: f64,
+    previous: f32,
+}
+
+impl Resampler {
+    fn new(from: f32, to: f32) -> Self {
+        Self {
+            step: from as f64 / to as f64,
+            position: 0.0,
+            index: 0.0,
+            previous: 0.0,
+        }
+    }
+
+    fn push(&mut self, sample: f32, mut on_sample: impl FnMut(f32)) {
+        // 输出位于上一个样本和当前样本之间的点
+        while self.position <= self.index {
+            let fraction = (self.position - (self.index - 1.0)) as f32;
+            on_sample(self.previous + (sample - self.previous) * fraction);
+            self.position += self.step;
+        }
+        self.previous = sample;
+        self.index += 1.0;
+    }
+}
+
+/// 流式计算指纹
+pub struct FingerprintBuilder {
+    downmixer: Downmixer,
+    resampler: Resampler,
+    stft: Stft,
+    /// 每个频点对应的音高类别, 超出分析范围时为 None
+    pitch_classes: Vec<Option<usize>>,
+    /// 每帧的色度向量
+    chroma: Vec<[f32; 12]>,
+    max_frames: usize,
+}
+
+impl FingerprintBuilder {
+    pub fn new(channels: usize, rate: u32) -> Self {
+        let downmixer = Downmixer::new(channels, rate);
+        let resampler = Resampler::new(downmixer.rate, RATE);
+        let stft = Stft::new(FRAME, HOP);
+        let pitch_classes = stft.pitch_classes(RATE, MIN_FREQ, MAX_FREQ);
+        let max_frames = (MAX_SECONDS * RATE) as usize / HOP;
+        Self {
+            downmixer,
+            resampler,
+            stft,
+            pitch_classes,
+            chroma: Vec::new(),
+            max_frames,
+        }
+    }
+
+    /// 已经得到足够的帧, 不需要更多样本
+    pub fn is_full(&self) -> bool {
+        self.chroma.len() >= self.max_frames
+    }
+
+    /// 输入交错样本
+    pub fn add_samples(&mut self, samples: &[f32]) {
+        let Self { downmixer, resampler, stft, pitch_classes, chroma, max_frames } = self;
+        downmixer.process(samples, |sample| {
+            if chroma.len() >= *max_frames {
+                return;
+            }
+            resampler.push(sample, |sample| {
+                stft.push(sample, |magnitudes| {
+                    let mut frame = [0.0f32; 12];
+                    for (pitch_class, &magnitude) in pitch_classes.iter().zip(magnitudes) {
+                        if let Some(pitch_class) = pitch_class {
+                            frame[*pitch_class] += magnitude * magnitude;
+                        }
+                    }
+                    chroma.push(frame);
+                })
+            })
+        });
+    }
+
+    /// 生成指纹, 音频太短或全部静音时为 None
+    pub fn finish(&self) -> Option<Fingerprint> {
+        let n = self.chroma.len();
+        if n < MIN_OVERLAP + 2 * LAG {
+            return None;
+        }
+
+        // 时间上平滑后按 L2 范数归一化, 音量很小的帧视为静音
+        let radius = SMOOTHING.len() / 2;
+        let frames: Vec<[f32; 12]> = (0..n)
+            .map(|t| {
+                let mut frame = [0.0f32; 12];
+                for (k, weight) in SMOOTHING.iter().enumerate() {
+                    let Some(source) = (t + k).checked_sub(radius).and_then(|s| self.chroma.get(s)) else {
+                        continue;
+                    };
+                    for (value, &v) in frame.iter_mut().zip(source) {
+                        *value += weight * v;
+                    }
+                }
+                let norm = frame.iter().map(|v| v * v).sum::<f32>().sqrt();
+                if norm < 1e-3 {
+                    return [0.0; 12];
+                }
+                frame.map(|v| v / norm)
+            })
+            .collect();
+
+        let values: Vec<u32> = (LAG..n - LAG)
+            .map(|t| {
+                let (previous, current, next) = (&frames[t - LAG], &frames[t], &frames[t + LAG]);
+                if current.iter().all(|&v| v == 0.0) {
+                    return 0;
+                }
+                let mut value = 0u32;
+                for i in 0..12 {
+                    value |= ((next[i] > previous[i]) as u32) << i;
+                    value |= ((current[i] > current[(i + 1) % 12]) as u32) << (12 + i);
+                }
+                for i in 0..8 {
+                    value |= ((current[i] > current[(i + 3) % 12]) as u32) << (24 + i);
+                }
+                value
+            })
+            .collect();
+        if values.iter().all(|&v| v == 0) {
+            return None;
+        }
+        Some(Fingerprint(values))
+    }
+}
+
+/// 解码本地文件的开头部分并计算指纹, 同时返回采样率; 音频太短或全部静音时指纹为 None
+pub fn analyze_file(file_path: &str) -> Result<(Option<Fingerprint>, u32)> {
+    let file = File::open(file_path)?;
+    let extension = Path::new(file_path).extension().and_then(|e| e.to_str());
+    let mut builder: Option<(FingerprintBuilder, u32)> = None;
+    super::decode_until(Box::new(file), extension, |samples, channels, rate| {
+        let (builder, _) = builder.get_or_insert_with(|| (FingerprintBuilder::new(channels, rate), rate));
+        builder.add_samples(samples);
+        if builder.is_full() {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    })?;
+    let (builder, rate) = builder.ok_or_else(|| Error::msg("no audio samples"))?;
+    Ok((builder.finish(), rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 随机和弦进行加打击声的伪音乐, 单声道; `seed` 相同时内容相同
+    fn music(seed: u32, rate: u32, seconds: f64) -> Vec<f32> {
+        let mut state = seed;
+        let mut random = move || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 8) as f64 / (1u32 << 24) as f64
+        };
+        let mut chords = Vec::new();
+        let mut time = 0.0;
+        while time < seconds {
+            let root = 48 + (random() * 12.0) as u8;
+            let duration = 0.5 + random() * 1.5;
+            chords.push((time, [root, root + 4 - (random() < 0.5) as u8, root + 7]));
+            time += duration;
+        }
+        let mut noise = seed ^ 0x5555;
+        let mut chord = 0;
+        (0..(rate as f64 * seconds) as usize)
+            .map(|i| {
+                let t = i as f64 / rate as f64;
+                while chord + 1 < chords.len() && chords[chord + 1].0 <= t {
+                    chord += 1;
+                }
+                let (start, notes) = chords[chord];
+                let tone: f64 = notes
+                    .iter()
+                    .map(|&note| {
+                        let freq = 440.0 * 2f64.powf((note as f64 - 69.0) / 12.0);
+                        (2.0 * std::f64::consts::PI * freq * t).sin()
+                    })
+                    .sum();
+                noise = noise.wrapping_mul(1103515245).wrapping_add(12345);
+                let white = (noise >> 16) as f64 / 32768.0 - 1.0;
+                let click = (-(t - start) * 40.0).exp() * white;
+                (0.15 * tone + 0.3 * click) as f32
+            })
+            .collect()
+    }
+
+    fn fingerprint(samples: &[f32], rate: u32) -> Fingerprint {
+        let mut builder = FingerprintBuilder::new(1, rate);
+        builder.add_samples(samples);
+        builder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_fingerprint_similarity() {
+        let original = fingerprint(&music(1, 44100, 60.0), 44100);
+        assert_eq!(Fingerprint::from_bytes(&original.to_bytes()), Some(original.clone()));
+
+        // 同一内容: 不同采样率、音量, 加入噪声, 开头多 3 秒静音
+        let mut noise = 99u32;
+        let mut copy = vec![0.0f32; 48000 * 3];
+        copy.extend(music(1, 48000, 60.0).into_iter().map(|s| {
+            noise = noise.wrapping_mul(1103515245).wrapping_add(12345);
+            0.6 * s + 0.02 * ((noise >> 16) as f32 / 32768.0 - 1.0)
+        }));
+        let copy = fingerprint(&copy, 48000);
+        let different = fingerprint(&music(2, 44100, 60.0), 44100);
+
+        let same = original.similarity(&copy);
+        let unrelated = original.similarity(&different);
+        assert!(same >= DUPLICATE_SIMILARITY, "same: {}", same);
+        assert!(unrelated < DUPLICATE_SIMILARITY - 0.1, "unrelated: {}", unrelated);
+
+        let matches = find_matches(&[original, different, copy], DUPLICATE_SIMILARITY);
+        assert_eq!(matches.iter().map(|m| (m.0, m.1)).collect::<Vec<_>>(), vec![(0, 2)]);
+
+        // 静音没有指纹
+        let mut builder = FingerprintBuilder::new(2, 44100);
+        builder.add_samples(&vec![0.0; 44100 * 2 * 30]);
+        assert_eq!(builder.finish(), None);
+    }
+}
@@ -0,0 +1,220 @@
+// 调性估计
+// 把 65Hz~2kHz 的频谱幅度按音高类别 (C, C#, ..., B) 累加为色度向量, 与 Krumhansl-Kessler 大调、小调音级轮廓的 24 种移调分别计算相关系数, 取相关性最高的调。
+
+use std::fmt;
+
+use super::spectrum::{Downmixer, Stft};
+
+const FRAME: usize = 4096;
+const MIN_FREQ: f32 = 65.0;
+const MAX_FREQ: f32 = 2100.0;
+
+const MAJOR_PROFILE: [f64; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f64; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+/// 调性, 显示为 "C"、"F#m" 这样的音名记号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    /// 主音, 0 为 C
+    pub tonic: u8,
+    pub minor: bool,
+}
+
+impl Key {
+    /// 解析调性标签: 音名 ("Am", "F# minor", "Bbmaj") 或 Camelot 记号 ("8A"), 无法识别时为 None
+    pub fn parse(text: &str) -> Option<Key> {
+        let text = text.trim();
+        if let Some(number) = text.strip_suffix(['A', 'a', 'B', 'b']).and_then(|n| n.parse::<u8>().ok()) {
+            if !(1..=12).contains(&number) {
+                return None;
+            }
+            // Camelot 记号每加一相当于上行纯五度, 8B 为 C 大调, 8A 为 A 小调
+            let major = (number as i32 - 8) * 7;
+            let minor = text.ends_with(['A', 'a']);
+            let tonic = if minor { major - 3 } else { major };
+            return Some(Key { tonic: tonic.rem_euclid(12) as u8, minor });
+        }
+
+        let mut chars = text.chars();
+        let letter = chars.next()?.to_ascii_uppercase();
+        let mut tonic = match letter {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            'B' => 11,
+            _ => return None,
+        };
+        let mut rest = chars.as_str();
+        if let Some(r) = rest.strip_prefix(['#', '♯']) {
+            tonic += 1;
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix(['b', '♭']) {
+            tonic += 11;
+            rest = r;
+        }
+        let minor = match rest.trim().to_lowercase().as_str() {
+            "" | "maj" | "major" => false,
+            "m" | "min" | "minor" => true,
+            _ => return None,
+        };
+        Some(Key { tonic: tonic % 12, minor })
+    }
+
+    /// 能识别时转换为音名记号, 用于比较标签和过滤条件; 否则原样返回
+    pub fn normalize(text: &str) -> String {
+        Key::parse(text).map(|k| k.to_string()).unwrap_or_else(|| text.trim().to_string())
+    }
+
+    /// Camelot 记号, 如 A 小调为 "8A", C 大调为 "8B"
+    pub fn camelot(&self) -> String {
+        let major = if self.minor { self.tonic + 3 } else { self.tonic } as usize % 12;
+        let number = (major * 7 + 7) % 12 + 1;
+        format!("{}{}", number, if self.minor { 'A' } else { 'B' })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", NOTE_NAMES[self.tonic as usize % 12], if self.minor { "m" } else { "" })
+    }
+}
+
+/// 流式调性估计
+pub struct KeyDetector {
+    downmixer: Downmixer,
+    stft: Stft,
+    /// 每个频点对应的音高类别, 超出分析范围时为 None
+    pitch_classes: Vec<Option<usize>>,
+    chroma: [f64; 12],
+}
+
+impl KeyDetector {
+    pub fn new(channels: usize, rate: u32) -> Self {
+        let downmixer = Downmixer::new(channels, rate);
+        let stft = Stft::new(FRAME, FRAME / 2);
+        let pitch_classes = stft.pitch_classes(downmixer.rate, MIN_FREQ, MAX_FREQ);
+        Self {
+            downmixer,
+            stft,
+            pitch_classes,
+            chroma: [0.0; 12],
+        }
+    }
+
+    /// 输入交错样本
+    pub fn add_samples(&mut self, samples: &[f32]) {
+        let Self { downmixer, stft, pitch_classes, chroma } = self;
+        downmixer.process(samples, |sample| {
+            stft.push(sample, |magnitudes| {
+                for (pitch_class, &magnitude) in pitch_classes.iter().zip(magnitudes) {
+                    if let Some(pitch_class) = pitch_class {
+                        chroma[*pitch_class] += magnitude as f64;
+                    }
+                }
+            })
+        });
+    }
+
+    /// 估计的调性, 没有可分析的音频 (静音或太短) 时为 None
+    pub fn finish(&self) -> Option<Key> {
+        if self.chroma.iter().sum::<f64>() <= 1e-6 {
+            return None;
+        }
+        (0..12u8)
+            .flat_map(|tonic| [false, true].map(|minor| Key { tonic, minor }))
+            .map(|key| {
+                let profile = if key.minor { &MINOR_PROFILE } else { &MAJOR_PROFILE };
+                let rotated: Vec<f64> = (0..12).map(|pc| profile[(pc + 12 - key.tonic as usize) % 12]).collect();
+                (key, correlation(&self.chroma, &rotated))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(key, _)| key)
+    }
+}
+
+/// 皮尔逊相关系数
+fn correlation(a: &[f64], b: &[f64]) -> f64 {
+    let mean = |v: &[f64]| v.iter().sum::<f64>() / v.len() as f64;
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        cov += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a).powi(2);
+        var_b += (y - mean_b).powi(2);
+    }
+    if var_a <= 0.0 || var_b <= 0.0 {
+        return 0.0;
+    }
+    cov / (var_a * var_b).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 依次演奏的和弦, 每个和弦 2 秒, 音高为 MIDI 编号
+    fn chords(rate: u32, progression: &[&[u8]]) -> Vec<f32> {
+        let frames = rate as usize * 2;
+        progression
+            .iter()
+            .flat_map(|notes| {
+                (0..frames).map(move |i| {
+                    let t = i as f64 / rate as f64;
+                    let sum: f64 = notes
+                        .iter()
+                        .map(|&note| {
+                            let freq = 440.0 * 2f64.powf((note as f64 - 69.0) / 12.0);
+                            (2.0 * std::f64::consts::PI * freq * t).sin()
+                        })
+                        .sum();
+                    (0.2 * sum / notes.len() as f64) as f32
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_key() {
+        // C 大调 I-IV-V-I
+        let mut detector = KeyDetector::new(1, 44100);
+        detector.add_samples(&chords(44100, &[&[60, 64, 67], &[65, 69, 72], &[67, 71, 74], &[60, 64, 67]]));
+        assert_eq!(detector.finish().unwrap().to_string(), "C");
+
+        // A 小调 i-iv-V-i
+        let mut detector = KeyDetector::new(1, 48000);
+        detector.add_samples(&chords(48000, &[&[57, 60, 64], &[62, 65, 69], &[64, 68, 71], &[57, 60, 64]]));
+        assert_eq!(detector.finish().unwrap().to_string(), "Am");
+
+        assert_eq!(KeyDetector::new(2, 44100).finish(), None);
+    }
+
+    #[test]
+    fn test_parse_key() {
+        let key = |text: &str| Key::parse(text).map(|k| k.to_string());
+        assert_eq!(key("Am").as_deref(), Some("Am"));
+        assert_eq!(key(" F# minor ").as_deref(), Some("F#m"));
+        assert_eq!(key("Bbmaj").as_deref(), Some("A#"));
+        assert_eq!(key("Dbm").as_deref(), Some("C#m"));
+        assert_eq!(key("8A").as_deref(), Some("Am"));
+        assert_eq!(key("8B").as_deref(), Some("C"));
+        assert_eq!(key("1B").as_deref(), Some("B"));
+        assert_eq!(key("12a").as_deref(), Some("C#m"));
+        assert_eq!(key("13A"), None);
+        assert_eq!(key("o"), None);
+        assert_eq!(key("H"), None);
+        assert_eq!(Key::normalize("8a"), "Am");
+        assert_eq!(Key::normalize(" x "), "x");
+
+        for tonic in 0..12 {
+            for minor in [false, true] {
+                let k = Key { tonic, minor };
+                assert_eq!(Key::parse(&k.camelot()), Some(k));
+                assert_eq!(Key::parse(&k.to_string()), Some(k));
+            }
+        }
+    }
+}
@@ -0,0 +1,248 @@
+// EBU R128 响度测量
+// 按 ITU-R BS.1770-4 计算: K 加权滤波后以 400ms 为一块 (75% 重叠) 计算均方, 先用 -70 LUFS 的 绝对门限, 再用比平均响度低 10 LU 的相对门限, 剩余块的平均响度即为整体响度。各声道权重均为 1 (不区分环绕声道)。真峰值使用 4 倍过采样估算。
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+use anyhow::{Error, Result};
+
+/// ReplayGain 2.0 的参考响度
+pub const REFERENCE_LUFS: f64 = -18.0;
+
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+/// 过采样倍数和每个相位的滤波器长度
+const OVERSAMPLE: usize = 4;
+const PHASE_TAPS: usize = 12;
+
+/// 测量结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    /// 整体响度 (LUFS)
+    pub integrated: f64,
+    /// 真峰值 (线性幅度)
+    pub true_peak: f64,
+}
+
+impl Loudness {
+    /// 达到参考响度需要的增益 (dB)
+    pub fn gain(&self) -> f64 {
+        REFERENCE_LUFS - self.integrated
+    }
+}
+
+/// 解码本地文件并测量响度, 静音文件返回错误
+pub fn analyze_file(file_path: &str) -> Result<Loudness> {
+    let mut meter: Option<LoudnessMeter> = None;
+    super::decode_file(file_path, |samples, channels, rate| {
+        meter
+            .get_or_insert_with(|| LoudnessMeter::new(channels, rate))
+            .add_samples(samples);
+    })?;
+    meter
+        .and_then(|m| m.finish())
+        .ok_or_else(|| Error::msg("no audible audio"))
+}
+
+/// 由各曲目的增益和时长计算专辑增益: 按时长加权平均各曲目的能量
+pub fn album_gain(tracks: &[(f64, f64)]) -> Option<f64> {
+    let total: f64 = tracks.iter().map(|(_, duration)| duration).sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let energy: f64 = tracks
+        .iter()
+        .map(|(gain, duration)| duration * 10f64.powf((REFERENCE_LUFS - gain) / 10.0))
+        .sum::<f64>()
+        / total;
+    Some(REFERENCE_LUFS - 10.0 * energy.log10())
+}
+
+/// 二阶 IIR 滤波器
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self { b, a, x: [0.0; 2], y: [0.0; 2] }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// K 加权滤波器 (高频搁架 + 高通), 系数按采样率计算
+fn k_weighting(rate: f64) -> [Biquad; 2] {
+    let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new([1.0, -2.0, 1.0], [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0]);
+    [shelf, high_pass]
+}
+
+/// 4 倍过采样的插值滤波器 (Hann 窗 sinc), 按相位存放
+fn interpolation_taps() -> [[f64; PHASE_TAPS]; OVERSAMPLE] {
+    let len = OVERSAMPLE * PHASE_TAPS;
+    let center = (len / 2) as f64;
+    let mut taps = [[0.0; PHASE_TAPS]; OVERSAMPLE];
+    for n in 0..len {
+        let t = (n as f64 - center) / OVERSAMPLE as f64;
+        let sinc = if t == 0.0 { 1.0 } else { (PI * t).sin() / (PI * t) };
+        let window = 0.5 - 0.5 * (2.0 * PI * n as f64 / len as f64).cos();
+        taps[n % OVERSAMPLE][n / OVERSAMPLE] = sinc * window;
+    }
+    taps
+}
+
+/// 流式响度测量
+pub struct LoudnessMeter {
+    channels: usize,
+    filters: Vec<[Biquad; 2]>,
+    /// 100ms 的帧数
+    step_frames: usize,
+    frames_in_step: usize,
+    step_energy: f64,
+    /// 最近 4 个 100ms 的能量
+    recent_steps: VecDeque<f64>,
+    /// 每个 400ms 块的均方 (各声道之和)
+    blocks: Vec<f64>,
+    taps: [[f64; PHASE_TAPS]; OVERSAMPLE],
+    /// 每个声道最近的样本, 用于过采样
+    history: Vec<VecDeque<f64>>,
+    peak: f64,
+}
+
+impl LoudnessMeter {
+    pub fn new(channels: usize, rate: u32) -> Self {
+        let channels = channels.max(1);
+        Self {
+            channels,
+            filters: vec![k_weighting(rate as f64); channels],
+            step_frames: (rate as usize / 10).max(1),
+            frames_in_step: 0,
+            step_energy: 0.0,
+            recent_steps: VecDeque::with_capacity(4),
+            blocks: Vec::new(),
+            taps: interpolation_taps(),
+            history: vec![VecDeque::from(vec![0.0; PHASE_TAPS]); channels],
+            peak: 0.0,
+        }
+    }
+
+    /// 输入交错样本
+    pub fn add_samples(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            for (channel, &sample) in frame.iter().enumerate() {
+                let x = sample as f64;
+                let [shelf, high_pass] = &mut self.filters[channel];
+                let weighted = high_pass.process(shelf.process(x));
+                self.step_energy += weighted * weighted;
+                self.track_peak(channel, x);
+            }
+
+            self.frames_in_step += 1;
+            if self.frames_in_step == self.step_frames {
+                if self.recent_steps.len() == 4 {
+                    self.recent_steps.pop_front();
+                }
+                self.recent_steps.push_back(self.step_energy);
+                if self.recent_steps.len() == 4 {
+                    let sum: f64 = self.recent_steps.iter().sum();
+                    self.blocks.push(sum / (4 * self.step_frames) as f64);
+                }
+                self.frames_in_step = 0;
+                self.step_energy = 0.0;
+            }
+        }
+    }
+
+    fn track_peak(&mut self, channel: usize, x: f64) {
+        let history = &mut self.history[channel];
+        history.pop_back();
+        history.push_front(x);
+        for phase in &self.taps {
+            let y: f64 = phase.iter().zip(history.iter()).map(|(t, s)| t * s).sum();
+            self.peak = self.peak.max(y.abs());
+        }
+    }
+
+    /// 测量结果, 没有高于绝对门限的块 (静音或不足 400ms) 时为 None
+    pub fn finish(&self) -> Option<Loudness> {
+        let loudness = |energy: f64| -0.691 + 10.0 * energy.log10();
+        let mean = |blocks: &[f64]| blocks.iter().sum::<f64>() / blocks.len() as f64;
+
+        let gated: Vec<f64> = self.blocks.iter().copied().filter(|&e| loudness(e) > ABSOLUTE_GATE).collect();
+        if gated.is_empty() {
+            return None;
+        }
+        let threshold = loudness(mean(&gated)) + RELATIVE_GATE;
+        let gated: Vec<f64> = gated.into_iter().filter(|&e| loudness(e) > threshold).collect();
+        Some(Loudness {
+            integrated: loudness(mean(&gated)),
+            true_peak: self.peak,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(rate: u32, channels: usize, freq: f64, amplitude: f64, seconds: f64) -> Vec<f32> {
+        let frames = (rate as f64 * seconds) as usize;
+        (0..frames)
+            .flat_map(|i| {
+                let v = (amplitude * (2.0 * PI * freq * i as f64 / rate as f64).sin()) as f32;
+                std::iter::repeat_n(v, channels)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sine_loudness() {
+        // 双声道 1kHz、-20 dBFS 的正弦波响度约为 -20 LUFS
+        for rate in [44100, 48000] {
+            let mut meter = LoudnessMeter::new(2, rate);
+            meter.add_samples(&sine(rate, 2, 1000.0, 0.1, 3.0));
+            let result = meter.finish().unwrap();
+            assert!((result.integrated + 20.0).abs() < 0.2, "{:?}", result);
+            assert!((result.true_peak - 0.1).abs() < 0.005, "{:?}", result);
+            assert!((result.gain() - 2.0).abs() < 0.2);
+        }
+
+        // 静音不参与计算
+        let mut meter = LoudnessMeter::new(1, 48000);
+        meter.add_samples(&vec![0.0; 48000]);
+        assert_eq!(meter.finish(), None);
+    }
+
+    #[test]
+    fn test_album_gain() {
+        assert_eq!(album_gain(&[]), None);
+        assert!((album_gain(&[(-3.0, 100.0), (-3.0, 200.0)]).unwrap() + 3.0).abs() < 1e-9);
+        // 响的曲目对专辑增益影响更大
+        let gain = album_gain(&[(-8.0, 100.0), (2.0, 100.0)]).unwrap();
+        assert!(gain < -3.0 && gain > -8.0);
+    }
+}
@@ -0,0 +1,103 @@
+// 音频解码与分析
+// 使用 symphonia 将音频解码为交错的 f32 PCM, 供后台分析任务 (响度、波形、节奏和调性、指纹等) 使用。
+
+pub mod fingerprint;
+pub mod key;
+pub mod loudness;
+mod spectrum;
+pub mod tempo;
+pub mod waveform;
+
+use anyhow::{Error, Result};
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::path::Path;
+use symphonia::core::audio::{SampleBuffer, SignalSpec};
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// 解码本地文件, 见 [`decode`]
+pub fn decode_file(file_path: &str, on_samples: impl FnMut(&[f32], usize, u32)) -> Result<()> {
+    let file = File::open(file_path)?;
+    let extension = Path::new(file_path).extension().and_then(|e| e.to_str());
+    decode(Box::new(file), extension, on_samples)
+}
+
+/// 解码第一个音轨, 每解码一个数据包调用一次 `on_samples(交错样本, 声道数, 采样率)`
+///
+/// 个别数据包解码失败时跳过, 与播放器的行为一致
+pub fn decode(
+    media: Box<dyn MediaSource>,
+    extension: Option<&str>,
+    mut on_samples: impl FnMut(&[f32], usize, u32),
+) -> Result<()> {
+    decode_until(media, extension, |samples, channels, rate| {
+        on_samples(samples, channels, rate);
+        ControlFlow::Continue(())
+    })
+}
+
+/// 与 [`decode`] 相同, `on_samples` 返回 `ControlFlow::Break` 时提前结束 (如只分析开头一段)
+pub fn decode_until(
+    media: Box<dyn MediaSource>,
+    extension: Option<&str>,
+    mut on_samples: impl FnMut(&[f32], usize, u32) -> ControlFlow<()>,
+) -> Result<()> {
+    let source = MediaSourceStream::new(media, Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = extension {
+        hint.with_extension(extension);
+    }
+    let probed = symphonia::default::get_probe().format(
+        &hint,
+        source,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| Error::msg("no audio track"))?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    // 复用样本缓冲区, 格式变化或容量不足时重新分配
+    let mut buffer: Option<(SampleBuffer<f32>, u64, SignalSpec)> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(SymphoniaError::ResetRequired) => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        let spec = *decoded.spec();
+        let capacity = decoded.capacity() as u64;
+        let reusable = matches!(&buffer, Some((_, frames, s)) if *frames >= capacity && *s == spec);
+        if !reusable {
+            buffer = Some((SampleBuffer::new(capacity, spec), capacity, spec));
+        }
+        if let Some((buffer, _, _)) = buffer.as_mut() {
+            buffer.copy_interleaved_ref(decoded);
+            if on_samples(buffer.samples(), spec.channels.count(), spec.rate).is_break() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,156 @@
+// 频谱分析的公共部分: 混合为单声道并降采样, 分帧做 FFT
+// 节奏、调性和指纹分析只需要 5kHz 以下的频率, 先降采样到约 11kHz 以减少计算量。
+
+use std::f32::consts::PI;
+
+/// 降采样的目标采样率
+const TARGET_RATE: f32 = 11025.0;
+
+/// 混合为单声道, 按整数倍降采样 (相邻样本取平均, 相当于简单的低通滤波)
+pub struct Downmixer {
+    channels: usize,
+    factor: usize,
+    sum: f32,
+    count: usize,
+    /// 降采样后的采样率
+    pub rate: f32,
+}
+
+impl Downmixer {
+    pub fn new(channels: usize, rate: u32) -> Self {
+        let factor = (rate as f32 / TARGET_RATE).round().max(1.0) as usize;
+        Self {
+            channels: channels.max(1),
+            factor,
+            sum: 0.0,
+            count: 0,
+            rate: rate as f32 / factor as f32,
+        }
+    }
+
+    /// 输入交错样本, 每得到一个降采样后的样本调用一次 `on_sample`
+    pub fn process(&mut self, samples: &[f32], mut on_sample: impl FnMut(f32)) {
+        for frame in samples.chunks_exact(self.channels) {
+            self.sum += frame.iter().sum::<f32>() / self.channels as f32;
+            self.count += 1;
+            if self.count == self.factor {
+                on_sample(self.sum / self.factor as f32);
+                self.sum = 0.0;
+                self.count = 0;
+            }
+        }
+    }
+}
+
+/// 短时傅里叶变换: 每 `hop` 个样本取 `size` 个样本加 Hann 窗, 计算幅度谱
+pub struct Stft {
+    size: usize,
+    hop: usize,
+    window: Vec<f32>,
+    /// 旋转因子 (cos, sin)
+    twiddles: Vec<(f32, f32)>,
+    /// 待处理的样本
+    pending: Vec<f32>,
+    re: Vec<f32>,
+    im: Vec<f32>,
+    magnitudes: Vec<f32>,
+}
+
+impl Stft {
+    /// `size` 必须是 2 的幂
+    pub fn new(size: usize, hop: usize) -> Self {
+        assert!(size.is_power_of_two());
+        let window = (0..size)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / size as f32).cos())
+            .collect();
+        let twiddles = (0..size / 2)
+            .map(|k| {
+                let angle = -2.0 * std::f64::consts::PI * k as f64 / size as f64;
+                (angle.cos() as f32, angle.sin() as f32)
+            })
+            .collect();
+        Self {
+            size,
+            hop,
+            window,
+            twiddles,
+            pending: Vec::with_capacity(size * 2),
+            re: vec![0.0; size],
+            im: vec![0.0; size],
+            magnitudes: vec![0.0; size / 2 + 1],
+        }
+    }
+
+    /// 第 `bin` 个频点的频率
+    pub fn frequency(&self, bin: usize, rate: f32) -> f32 {
+        bin as f32 * rate / self.size as f32
+    }
+
+    /// 每个频点对应的音高类别 (0 为 C), 不在 `min`~`max` Hz 范围内时为 None
+    pub fn pitch_classes(&self, rate: f32, min: f32, max: f32) -> Vec<Option<usize>> {
+        (0..=self.size / 2)
+            .map(|bin| {
+                let freq = self.frequency(bin, rate);
+                if !(min..=max).contains(&freq) {
+                    return None;
+                }
+                let midi = 69.0 + 12.0 * (freq / 440.0).log2();
+                Some((midi.round() as i64).rem_euclid(12) as usize)
+            })
+            .collect()
+    }
+
+    /// 输入样本, 每凑齐一帧调用一次 `on_frame(幅度谱)`
+    pub fn push(&mut self, sample: f32, mut on_frame: impl FnMut(&[f32])) {
+        self.pending.push(sample);
+        if self.pending.len() < self.size {
+            return;
+        }
+        for i in 0..self.size {
+            self.re[i] = self.pending[i] * self.window[i];
+            self.im[i] = 0.0;
+        }
+        self.pending.drain(..self.hop.min(self.size));
+        self.fft();
+        for (bin, magnitude) in self.magnitudes.iter_mut().enumerate() {
+            *magnitude = (self.re[bin] * self.re[bin] + self.im[bin] * self.im[bin]).sqrt();
+        }
+        on_frame(&self.magnitudes);
+    }
+
+    /// 原地基 2 FFT
+    fn fft(&mut self) {
+        let n = self.size;
+        let mut j = 0;
+        for i in 1..n {
+            let mut bit = n >> 1;
+            while j & bit != 0 {
+                j ^= bit;
+                bit >>= 1;
+            }
+            j |= bit;
+            if i < j {
+                self.re.swap(i, j);
+                self.im.swap(i, j);
+            }
+        }
+
+        let mut len = 2;
+        while len <= n {
+            let step = n / len;
+            for start in (0..n).step_by(len) {
+                for k in 0..len / 2 {
+                    let (cos, sin) = self.twiddles[k * step];
+                    let (a, b) = (start + k, start + k + len / 2);
+                    let re = self.re[b] * cos - self.im[b] * sin;
+                    let im = self.re[b] * sin + self.im[b] * cos;
+                    self.re[b] = self.re[a] - re;
+                    self.im[b] = self.im[a] - im;
+                    self.re[a] += re;
+                    self.im[a] += im;
+                }
+            }
+            len <<= 1;
+        }
+    }
+}
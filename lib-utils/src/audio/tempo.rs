@@ -0,0 +1,166 @@
+// 节奏 (BPM) 估计
+// 以频谱通量 (各频点对数幅度的增量之和) 作为起音强度曲线, 减去局部均值后计算自相关, 在 60~200 BPM 范围内按以 120 BPM 为中心的先验加权取最大值, 半周期处也有接近的峰值时取半周期, 再用 4 倍周期处的峰值细化。与其他同类方法一样, 结果可能是实际速度的一半或两倍。
+
+use super::spectrum::{Downmixer, Stft};
+
+const FRAME: usize = 512;
+const HOP: usize = 64;
+const MIN_BPM: f64 = 60.0;
+const MAX_BPM: f64 = 200.0;
+const PRIOR_BPM: f64 = 120.0;
+/// 半周期处的峰值达到此比例时认为是真正的节拍周期
+const OCTAVE_RATIO: f64 = 0.8;
+/// 最短的分析时长 (秒)
+const MIN_SECONDS: f64 = 10.0;
+
+/// 流式节奏估计
+pub struct TempoDetector {
+    downmixer: Downmixer,
+    stft: Stft,
+    /// 上一帧各频点的对数幅度
+    previous: Vec<f32>,
+    /// 每帧的起音强度
+    onsets: Vec<f32>,
+}
+
+impl TempoDetector {
+    pub fn new(channels: usize, rate: u32) -> Self {
+        Self {
+            downmixer: Downmixer::new(channels, rate),
+            stft: Stft::new(FRAME, HOP),
+            previous: vec![0.0; FRAME / 2 + 1],
+            onsets: Vec::new(),
+        }
+    }
+
+    /// 输入交错样本
+    pub fn add_samples(&mut self, samples: &[f32]) {
+        let Self { downmixer, stft, previous, onsets } = self;
+        downmixer.process(samples, |sample| {
+            stft.push(sample, |magnitudes| {
+                let mut flux = 0.0;
+                for (previous, &magnitude) in previous.iter_mut().zip(magnitudes) {
+                    let value = (1.0 + 100.0 * magnitude).ln();
+                    flux += (value - *previous).max(0.0);
+                    *previous = value;
+                }
+                onsets.push(flux);
+            })
+        });
+    }
+
+    /// 估计的 BPM (保留一位小数), 音频太短或没有明显的节奏时为 None
+    pub fn finish(&self) -> Option<f64> {
+        let fps = self.downmixer.rate as f64 / HOP as f64;
+        let n = self.onsets.len();
+        if (n as f64) < fps * MIN_SECONDS {
+            return None;
+        }
+
+        // 减去约 0.5 秒内的局部均值, 只保留突出的起音
+        let radius = (fps / 4.0) as usize;
+        let mut prefix = vec![0.0f64; n + 1];
+        for (i, &onset) in self.onsets.iter().enumerate() {
+            prefix[i + 1] = prefix[i] + onset as f64;
+        }
+        let envelope: Vec<f64> = (0..n)
+            .map(|i| {
+                let (lo, hi) = (i.saturating_sub(radius), (i + radius + 1).min(n));
+                let mean = (prefix[hi] - prefix[lo]) / (hi - lo) as f64;
+                (self.onsets[i] as f64 - mean).max(0.0)
+            })
+            .collect();
+        let autocorrelation = |lag: usize| -> f64 {
+            envelope[..n - lag].iter().zip(&envelope[lag..]).map(|(a, b)| a * b).sum()
+        };
+
+        let min_lag = ((fps * 60.0 / MAX_BPM).floor() as usize).max(2);
+        let max_lag = (fps * 60.0 / MIN_BPM).ceil() as usize;
+        let values: Vec<f64> = (0..=max_lag + 1).map(autocorrelation).collect();
+        let prior = |lag: f64| (-0.5 * (60.0 * fps / lag / PRIOR_BPM).log2().powi(2)).exp();
+        let mut best = (min_lag..=max_lag)
+            .max_by(|&a, &b| (values[a] * prior(a as f64)).total_cmp(&(values[b] * prior(b as f64))))?;
+        if values[best] <= 0.0 {
+            return None;
+        }
+        // 周期信号在周期的整数倍处也有同样高的峰值, 半周期处的峰值接近时取半周期
+        let half = best / 2;
+        if half > min_lag {
+            let peak = (half - 1..=half + 1).max_by(|&a, &b| values[a].total_cmp(&values[b]))?;
+            if values[peak] >= OCTAVE_RATIO * values[best] {
+                best = peak;
+            }
+        }
+
+        let mut period = best as f64 + parabolic_offset(values[best - 1], values[best], values[best + 1]);
+        // 4 倍周期处的峰值位置更精确
+        let far = best * 4;
+        if far + 5 < n / 2 {
+            let far_values: Vec<f64> = (far - 4..=far + 5).map(autocorrelation).collect();
+            let peak = (1..far_values.len() - 1).max_by(|&a, &b| far_values[a].total_cmp(&far_values[b]))?;
+            let offset = parabolic_offset(far_values[peak - 1], far_values[peak], far_values[peak + 1]);
+            period = (far - 4 + peak) as f64 / 4.0 + offset / 4.0;
+        }
+        Some((60.0 * fps / period * 10.0).round() / 10.0)
+    }
+}
+
+/// 抛物线插值得到的峰值偏移 (-0.5~0.5)
+fn parabolic_offset(left: f64, center: f64, right: f64) -> f64 {
+    let denominator = left - 2.0 * center + right;
+    if denominator >= 0.0 {
+        return 0.0;
+    }
+    (0.5 * (left - right) / denominator).clamp(-0.5, 0.5)
+}
+
+/// 解析 BPM 标签, 如 "128" 或 "127.5"
+pub fn parse_bpm(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let number = value.split_whitespace().next()?;
+    number.parse::<f64>().ok().filter(|bpm| bpm.is_finite() && *bpm > 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 每拍一个衰减的 100Hz 正弦波 + 噪声的敲击声, 单声道
+    fn clicks(rate: u32, bpm: f64, seconds: f64) -> Vec<f32> {
+        let beat = 60.0 / bpm;
+        let mut noise = 12345u32;
+        (0..(rate as f64 * seconds) as usize)
+            .map(|i| {
+                let t = i as f64 / rate as f64;
+                let since = t % beat;
+                noise = noise.wrapping_mul(1103515245).wrapping_add(12345);
+                let white = (noise >> 16) as f64 / 32768.0 - 1.0;
+                let envelope = (-since * 30.0).exp();
+                (envelope * (0.5 * (2.0 * std::f64::consts::PI * 100.0 * t).sin() + 0.3 * white)) as f32
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tempo() {
+        for (rate, bpm) in [(44100, 128.0), (48000, 172.0), (44100, 90.0)] {
+            let mut detector = TempoDetector::new(1, rate);
+            detector.add_samples(&clicks(rate, bpm, 30.0));
+            let result = detector.finish().unwrap();
+            assert!((result - bpm).abs() < 1.0, "{} -> {}", bpm, result);
+        }
+
+        // 太短或静音
+        let mut detector = TempoDetector::new(1, 44100);
+        detector.add_samples(&clicks(44100, 120.0, 5.0));
+        assert_eq!(detector.finish(), None);
+        let mut detector = TempoDetector::new(2, 44100);
+        detector.add_samples(&vec![0.0; 44100 * 2 * 20]);
+        assert_eq!(detector.finish(), None);
+
+        assert_eq!(parse_bpm(" 128 "), Some(128.0));
+        assert_eq!(parse_bpm("127.5 BPM"), Some(127.5));
+        assert_eq!(parse_bpm("0"), None);
+        assert_eq!(parse_bpm(""), None);
+    }
+}
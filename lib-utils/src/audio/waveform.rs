@@ -0,0 +1,161 @@
+// 波形峰值数据
+// 解码后按固定帧数分段记录最小值和最大值, 结束时合并为固定数量的区间 (默认 1000 个), 量化为 i8 (-127~127) 保存。缓存文件为每个区间的最小值、最大值交替排列的字节。
+
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Error, Result};
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+
+/// 默认的区间数量
+pub const BUCKETS: usize = 1000;
+/// 分段的帧数
+const CHUNK_FRAMES: usize = 256;
+
+/// 波形峰值, 每个区间的最小值和最大值
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Waveform {
+    pub buckets: usize,
+    pub min: Vec<i8>,
+    pub max: Vec<i8>,
+}
+
+impl Waveform {
+    /// 最小值、最大值交替排列的字节
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.min
+            .iter()
+            .zip(&self.max)
+            .flat_map(|(min, max)| [*min as u8, *max as u8])
+            .collect()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Waveform> {
+        if bytes.is_empty() || !bytes.len().is_multiple_of(2) {
+            return None;
+        }
+        let (min, max) = bytes.chunks_exact(2).map(|pair| (pair[0] as i8, pair[1] as i8)).unzip();
+        Some(Waveform {
+            buckets: bytes.len() / 2,
+            min,
+            max,
+        })
+    }
+}
+
+/// 流式计算波形
+#[derive(Default)]
+pub struct WaveformBuilder {
+    chunks: Vec<(f32, f32)>,
+    current: Option<(f32, f32)>,
+    frames_in_chunk: usize,
+}
+
+impl WaveformBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 输入交错样本, 所有声道一起统计
+    pub fn add_samples(&mut self, samples: &[f32], channels: usize) {
+        for frame in samples.chunks_exact(channels.max(1)) {
+            let (mut min, mut max) = self.current.unwrap_or((f32::MAX, f32::MIN));
+            for &sample in frame {
+                min = min.min(sample);
+                max = max.max(sample);
+            }
+            self.current = Some((min, max));
+
+            self.frames_in_chunk += 1;
+            if self.frames_in_chunk == CHUNK_FRAMES {
+                self.chunks.extend(self.current.take());
+                self.frames_in_chunk = 0;
+            }
+        }
+    }
+
+    /// 合并为 `buckets` 个区间, 音频较短时区间数量更少; 没有样本时为 None
+    pub fn finish(mut self, buckets: usize) -> Option<Waveform> {
+        self.chunks.extend(self.current.take());
+        let total = self.chunks.len();
+        if total == 0 || buckets == 0 {
+            return None;
+        }
+
+        let buckets = buckets.min(total);
+        let quantize = |v: f32| (v.clamp(-1.0, 1.0) * 127.0).round() as i8;
+        let (mut min, mut max) = (Vec::with_capacity(buckets), Vec::with_capacity(buckets));
+        for i in 0..buckets {
+            let range = &self.chunks[i * total / buckets..(i + 1) * total / buckets];
+            let lo = range.iter().map(|c| c.0).fold(f32::MAX, f32::min);
+            let hi = range.iter().map(|c| c.1).fold(f32::MIN, f32::max);
+            min.push(quantize(lo));
+            max.push(quantize(hi));
+        }
+        Some(Waveform { buckets, min, max })
+    }
+}
+
+/// 解码本地文件并计算波形
+pub fn analyze_file(file_path: &str) -> Result<Waveform> {
+    let mut builder = WaveformBuilder::new();
+    super::decode_file(file_path, |samples, channels, _| builder.add_samples(samples, channels))?;
+    builder.finish(BUCKETS).ok_or_else(|| Error::msg("no audio samples"))
+}
+
+/// 解码内存中的音频 (如下载的音频流) 并计算波形
+pub fn analyze_bytes(bytes: Vec<u8>, extension: Option<&str>) -> Result<Waveform> {
+    let mut builder = WaveformBuilder::new();
+    super::decode(Box::new(Cursor::new(bytes)), extension, |samples, channels, _| {
+        builder.add_samples(samples, channels)
+    })?;
+    builder.finish(BUCKETS).ok_or_else(|| Error::msg("no audio samples"))
+}
+
+/// 缓存文件路径, 文件名为 key 的 MD5
+///
+/// 本地文件使用文件路径作为 key, 其他数据源使用 "数据源类型:歌曲 ID"
+pub fn cache_path(cache_dir: &str, key: &str) -> PathBuf {
+    Path::new(cache_dir).join(format!("{}.bin", hex::encode(Md5::digest(key.as_bytes()))))
+}
+
+pub fn load_cached(cache_dir: &str, key: &str) -> Option<Waveform> {
+    let bytes = fs::read(cache_path(cache_dir, key)).ok()?;
+    Waveform::from_bytes(&bytes)
+}
+
+pub fn save_cached(cache_dir: &str, key: &str, waveform: &Waveform) -> Result<()> {
+    fs::create_dir_all(cache_dir)?;
+    fs::write(cache_path(cache_dir, key), waveform.to_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waveform_buckets() {
+        // 前半段静音, 后半段为 ±0.5 的方波, 双声道
+        let mut samples = vec![0.0f32; CHUNK_FRAMES * 10 * 2];
+        for i in 0..CHUNK_FRAMES * 10 {
+            let v = if i % 2 == 0 { 0.5 } else { -0.5 };
+            samples.extend([v, v]);
+        }
+        let mut builder = WaveformBuilder::new();
+        builder.add_samples(&samples, 2);
+        let waveform = builder.finish(4).unwrap();
+        assert_eq!(waveform.buckets, 4);
+        assert_eq!(waveform.min, vec![0, 0, -64, -64]);
+        assert_eq!(waveform.max, vec![0, 0, 64, 64]);
+        assert_eq!(Waveform::from_bytes(&waveform.to_bytes()), Some(waveform));
+
+        // 比区间数量短时每段一个区间
+        let mut builder = WaveformBuilder::new();
+        builder.add_samples(&[0.25, -1.0, 0.0], 1);
+        assert_eq!(builder.finish(BUCKETS).unwrap().max, vec![32]);
+        assert_eq!(WaveformBuilder::new().finish(BUCKETS), None);
+    }
+}
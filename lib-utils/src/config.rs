@@ -10,6 +10,10 @@ pub struct Config {
     pub music_dir: String, // 音乐文件目录 (本地模式使用)
     pub db_path: String,   // 数据库文件路径
     pub debug: bool,       // 是否开启调试模式
+    #[serde(default = "default_waveform_dir")]
+    pub waveform_dir: String, // 波形峰值数据的缓存目录
+    #[serde(default)]
+    pub analyze_tempo: bool, // 是否在后台估计没有标签的歌曲的 BPM 和调性 (较慢, 默认关闭)
 
     // 数据源配置
     #[serde(default)]
@@ -24,13 +28,45 @@ impl Config {
     pub fn is_subsonic_mode(&self) -> bool {
         self.data_source.mode == "subsonic"
     }
-    
+
+    pub fn is_jellyfin_mode(&self) -> bool {
+        self.data_source.mode == "jellyfin"
+    }
+
+    pub fn is_webdav_mode(&self) -> bool {
+        self.data_source.mode == "webdav"
+    }
+
+    pub fn is_aggregate_mode(&self) -> bool {
+        self.data_source.mode == "aggregate"
+    }
+
+    /// 是否使用本地数据库 (本地模式, 或聚合模式中包含本地数据源)
+    pub fn has_local_source(&self) -> bool {
+        self.is_local_mode()
+            || (self.is_aggregate_mode()
+                && self.data_source.sources.iter().any(|s| s.source_type == "local"))
+    }
+
+    /// 是否需要初始化数据库 (使用本地数据源或 WebDAV, 或开启了 Subsonic 镜像/离线下载)
+    pub fn uses_database(&self) -> bool {
+        let subsonic = if self.is_aggregate_mode() {
+            self.data_source
+                .sources
+                .iter()
+                .any(|s| s.subsonic.as_ref().is_some_and(|c| c.uses_database()))
+        } else {
+            self.is_subsonic_mode()
+                && self.data_source.subsonic.as_ref().is_some_and(|c| c.uses_database())
+        };
+        self.has_local_source() || self.is_webdav_mode() || subsonic
+    }
 }
 
 /// 数据源配置
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DataSourceConfig {
-    /// 数据源模式: "local" 或 "subsonic"
+    /// 数据源模式: "local" / "subsonic" / "jellyfin" / "webdav" / "aggregate"
     #[serde(default = "default_mode")]
     pub mode: String,
 
@@ -40,6 +76,18 @@ pub struct DataSourceConfig {
 
     /// Subsonic 服务器配置 (mode = "subsonic" 时使用)
     pub subsonic: Option<SubsonicConfig>,
+
+    /// Jellyfin 服务器配置 (mode = "jellyfin" 时使用)
+    #[serde(default)]
+    pub jellyfin: Option<JellyfinConfig>,
+
+    /// WebDAV 音乐目录配置 (mode = "webdav" 时使用)
+    #[serde(default)]
+    pub webdav: Option<WebDavConfig>,
+
+    /// 聚合模式的数据源列表 (mode = "aggregate" 时使用), 排在前面的优先级更高
+    #[serde(default)]
+    pub sources: Vec<SourceConfig>,
 }
 
 impl Default for DataSourceConfig {
@@ -48,10 +96,40 @@ impl Default for DataSourceConfig {
             mode: "local".to_string(),
             local: Some(LocalConfig::default()),
             subsonic: None,
+            jellyfin: None,
+            webdav: None,
+            sources: vec![],
         }
     }
 }
 
+/// 聚合模式中的单个数据源配置
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SourceConfig {
+    /// 数据源名称, 作为 ID 的命名空间前缀 (不能包含 ':')
+    pub name: String,
+
+    /// 数据源类型: "local" / "subsonic" / "jellyfin"
+    #[serde(rename = "type")]
+    pub source_type: String,
+
+    /// 本地文件配置 (type = "local" 时使用)
+    #[serde(default)]
+    pub local: Option<LocalConfig>,
+
+    /// Subsonic 服务器配置 (type = "subsonic" 时使用)
+    #[serde(default)]
+    pub subsonic: Option<SubsonicConfig>,
+
+    /// Jellyfin 服务器配置 (type = "jellyfin" 时使用)
+    #[serde(default)]
+    pub jellyfin: Option<JellyfinConfig>,
+}
+
+fn default_waveform_dir() -> String {
+    "../data/waveform".to_string()
+}
+
 fn default_mode() -> String {
     "local".to_string()
 }
@@ -106,6 +184,109 @@ pub struct SubsonicConfig {
     /// 缓存 TTL (秒)
     #[serde(default = "default_cache_ttl")]
     pub cache_ttl_seconds: u64,
+
+    /// 每类缓存最多保留的条目数
+    #[serde(default = "default_cache_capacity")]
+    pub cache_capacity: usize,
+
+    /// 是否将音乐库镜像到本地数据库, 服务器不可用时仍可浏览
+    #[serde(default)]
+    pub mirror: bool,
+
+    /// 镜像同步间隔 (分钟)
+    #[serde(default = "default_mirror_interval")]
+    pub mirror_interval_minutes: u64,
+
+    /// 镜像封面的保存目录
+    #[serde(default = "default_cover_cache_dir")]
+    pub cover_cache_dir: String,
+
+    /// 离线下载的保存目录
+    #[serde(default = "default_pin_dir")]
+    pub pin_dir: String,
+
+    /// 离线下载的空间配额 (MB), 为 0 时关闭离线下载
+    #[serde(default)]
+    pub pin_quota_mb: u64,
+
+    /// 单次请求超时 (秒)
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+
+    /// 请求失败后的重试次数, 只重试不会修改数据的请求
+    #[serde(default = "default_retry_count")]
+    pub retry_count: u32,
+
+    /// 第一次重试前的等待时间 (毫秒), 之后每次翻倍
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+
+    /// 连续失败多少次后暂停请求服务器, 为 0 时不熔断
+    #[serde(default = "default_circuit_breaker_threshold")]
+    pub circuit_breaker_threshold: u32,
+
+    /// 暂停请求的时间 (秒)
+    #[serde(default = "default_circuit_breaker_cooldown")]
+    pub circuit_breaker_cooldown_seconds: u64,
+}
+
+/// Jellyfin 服务器配置
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JellyfinConfig {
+    /// Jellyfin 服务器地址
+    pub server_url: String,
+
+    /// 用户名
+    pub username: String,
+
+    /// 密码
+    pub password: String,
+
+    /// 客户端名称, 显示在 Jellyfin 的设备列表中
+    #[serde(default = "default_client_name")]
+    pub client_name: String,
+
+    /// 最大比特率 (kbps)
+    #[serde(default = "default_max_bitrate")]
+    pub max_bitrate: u32,
+
+    /// 首选格式
+    #[serde(default = "default_format")]
+    pub prefer_format: String,
+
+    /// 单次请求超时 (秒)
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+/// WebDAV 音乐目录配置
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebDavConfig {
+    /// 音乐目录地址 (如 "https://nas.example.com/dav/music")
+    pub server_url: String,
+
+    /// 用户名, 为空时不认证
+    #[serde(default)]
+    pub username: String,
+
+    /// 密码
+    #[serde(default)]
+    pub password: String,
+
+    /// 定时扫描间隔 (分钟), 0 表示只在启动时扫描
+    #[serde(default = "default_mirror_interval")]
+    pub scan_interval_minutes: u64,
+
+    /// 单次请求超时 (秒)
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+impl SubsonicConfig {
+    /// 是否需要本地数据库 (开启了镜像或离线下载)
+    pub fn uses_database(&self) -> bool {
+        self.mirror || self.pin_quota_mb > 0
+    }
 }
 
 fn default_use_token() -> bool {
@@ -132,6 +313,42 @@ fn default_cache_ttl() -> u64 {
     3600
 }
 
+fn default_cache_capacity() -> usize {
+    2000
+}
+
+fn default_mirror_interval() -> u64 {
+    60
+}
+
+fn default_cover_cache_dir() -> String {
+    "../data/subsonic_covers".to_string()
+}
+
+fn default_pin_dir() -> String {
+    "../data/subsonic_pinned".to_string()
+}
+
+fn default_timeout_seconds() -> u64 {
+    15
+}
+
+fn default_retry_count() -> u32 {
+    2
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    500
+}
+
+fn default_circuit_breaker_threshold() -> u32 {
+    5
+}
+
+fn default_circuit_breaker_cooldown() -> u64 {
+    30
+}
+
 // 获取 config.json 中的配置信息
 pub fn get_config() -> Config {
     let mut config_path = "./conf/config.json";
@@ -0,0 +1,1032 @@
+// 聚合数据源
+// 将多个数据源(例如本地 FLAC 和远程 Navidrome)合并为一个音乐库
+
+use async_trait::async_trait;
+use futures::future::join_all;
+use rand::seq::SliceRandom;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::datasource::local::service;
+use crate::datasource::error::{DataSourceError, Result};
+use crate::datasource::trait_def::MusicDataSource;
+use crate::datasource::types::*;
+use crate::log;
+
+/// 命名空间分隔符, ID 形如 `<数据源名称>:<原始ID>`
+pub const NAMESPACE_SEPARATOR: char = ':';
+
+const STREAM_PREFIX: &str = "/api/stream/";
+
+/// 聚合中的单个数据源
+pub struct NamedSource {
+    pub name: String,
+    pub data_source: Arc<dyn MusicDataSource>,
+}
+
+/// 聚合数据源
+///
+/// - 列表类接口(搜索、专辑、艺术家、随机歌曲等)并发请求所有数据源, 合并去重,
+///   重复条目保留排在前面的数据源
+/// - ID 带上数据源名称作为命名空间, 按 ID 访问的接口路由回所属数据源
+/// - 单个数据源出错时记录日志并跳过, 全部失败时才返回错误
+pub struct AggregateDataSource {
+    sources: Vec<NamedSource>,
+}
+
+impl AggregateDataSource {
+    pub fn new(sources: Vec<NamedSource>) -> anyhow::Result<Self> {
+        if sources.is_empty() {
+            return Err(anyhow::anyhow!("Aggregate data source requires at least one source"));
+        }
+        let mut names = HashSet::new();
+        for source in &sources {
+            if source.name.is_empty() || source.name.contains(NAMESPACE_SEPARATOR) {
+                return Err(anyhow::anyhow!("Invalid data source name: {:?}", source.name));
+            }
+            if !names.insert(source.name.as_str()) {
+                return Err(anyhow::anyhow!("Duplicate data source name: {}", source.name));
+            }
+        }
+        Ok(Self { sources })
+    }
+
+    /// 优先级最高的数据源
+    fn primary(&self) -> &NamedSource {
+        &self.sources[0]
+    }
+
+    /// 根据带命名空间的 ID 找到所属数据源和原始 ID
+    fn route<'a, 'b>(&'a self, id: &'b str) -> Result<(&'a NamedSource, &'b str)> {
+        let (name, raw_id) = id
+            .split_once(NAMESPACE_SEPARATOR)
+            .ok_or_else(|| DataSourceError::InvalidId(format!("Missing data source namespace in id: {}", id)))?;
+        let source = self
+            .sources
+            .iter()
+            .find(|s| s.name == name)
+            .ok_or_else(|| DataSourceError::InvalidId(format!("Unknown data source: {}", name)))?;
+        Ok((source, raw_id))
+    }
+
+    /// 将一组 ID 路由到同一个数据源, ID 分属不同数据源时报错
+    fn route_all<'a>(&'a self, ids: &[String]) -> Result<Option<(&'a NamedSource, Vec<String>)>> {
+        let mut target: Option<&NamedSource> = None;
+        let mut raw_ids = Vec::with_capacity(ids.len());
+        for id in ids {
+            let (source, raw_id) = self.route(id)?;
+            if let Some(t) = target {
+                if t.name != source.name {
+                    return Err(DataSourceError::InvalidId(format!(
+                        "Songs from different data sources cannot be mixed: {} and {}",
+                        t.name,
+                        source.name
+                    )));
+                }
+            }
+            target = Some(source);
+            raw_ids.push(raw_id.to_string());
+        }
+        Ok(target.map(|t| (t, raw_ids)))
+    }
+
+    /// 并发调用所有数据源, 跳过出错的数据源
+    async fn fan_out<'a, T, F, Fut>(&'a self, f: F) -> Result<Vec<(&'a NamedSource, T)>>
+    where
+        F: Fn(&'a NamedSource) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let results = join_all(self.sources.iter().map(|s| {
+            let fut = f(s);
+            async move { (s, fut.await) }
+        }))
+        .await;
+
+        let mut list = Vec::new();
+        let mut first_err = None;
+        for (source, result) in results {
+            match result {
+                Ok(value) => list.push((source, value)),
+                Err(e) => {
+                    log::log_warn(&format!("[Aggregate] data source {} failed: {}", source.name, e));
+                    first_err.get_or_insert(e);
+                }
+            }
+        }
+
+        match first_err {
+            Some(e) if list.is_empty() => Err(e),
+            _ => Ok(list),
+        }
+    }
+
+    /// 合并多个数据源的歌曲列表
+    fn merge_songs(&self, lists: Vec<(&NamedSource, Vec<UnifiedMetadata>)>) -> Vec<UnifiedMetadata> {
+        let mut seen = HashSet::new();
+        let mut merged = Vec::new();
+        for (source, songs) in lists {
+            for song in namespace_songs(&source.name, songs) {
+                if seen.insert(song_key(&song)) {
+                    merged.push(song);
+                }
+            }
+        }
+        merged
+    }
+
+    fn merge_albums(&self, lists: Vec<(&NamedSource, Vec<AlbumInfo>)>) -> Vec<AlbumInfo> {
+        let mut seen = HashSet::new();
+        let mut merged = Vec::new();
+        for (source, albums) in lists {
+            for album in albums {
+                let album = namespace_album(&source.name, album);
+                if seen.insert(format!("{}|{}", normalize(&album.name), normalize(&album.artist))) {
+                    merged.push(album);
+                }
+            }
+        }
+        merged
+    }
+
+    fn merge_artists(&self, lists: Vec<(&NamedSource, Vec<ArtistInfo>)>) -> Vec<ArtistInfo> {
+        let mut seen = HashSet::new();
+        let mut merged = Vec::new();
+        for (source, artists) in lists {
+            for artist in artists {
+                let artist = namespace_artist(&source.name, artist);
+                if seen.insert(normalize(&artist.name)) {
+                    merged.push(artist);
+                }
+            }
+        }
+        merged
+    }
+
+    /// 收听统计使用的数据源, 播放记录只保存在本地数据库中
+    fn stats_source(&self) -> Result<&NamedSource> {
+        self.local_source().ok_or_else(|| {
+            DataSourceError::unsupported("Listening stats require a local data source")
+        })
+    }
+
+    /// 数据源类型为本地的第一个数据源
+    fn local_source(&self) -> Option<&NamedSource> {
+        self.sources
+            .iter()
+            .find(|s| s.data_source.source_type() == DataSourceType::Local)
+    }
+}
+
+/// 给统计项的 ID 加上命名空间, 风格的 ID 是风格名称, 不需要处理
+fn namespace_stats_items(
+    source: &NamedSource,
+    item_type: StatsItemType,
+    mut items: Vec<StatsItem>,
+) -> Vec<StatsItem> {
+    if item_type != StatsItemType::Genre {
+        for item in &mut items {
+            item.id = namespaced(&source.name, &item.id);
+        }
+    }
+    items
+}
+
+/// 给 ID 加上命名空间
+pub fn namespaced(name: &str, id: &str) -> String {
+    format!("{}{}{}", name, NAMESPACE_SEPARATOR, id)
+}
+
+/// 封面可能是 URL 或 data URL, 这种情况不加命名空间
+fn namespace_cover(name: &str, cover: Option<String>) -> Option<String> {
+    cover.map(|c| {
+        if c.is_empty() || c.starts_with("http://") || c.starts_with("https://") || c.starts_with("data:") {
+            c
+        } else {
+            namespaced(name, &c)
+        }
+    })
+}
+
+fn namespace_songs(name: &str, mut songs: Vec<UnifiedMetadata>) -> Vec<UnifiedMetadata> {
+    fill_local_ids(&mut songs);
+    songs
+        .into_iter()
+        .map(|mut song| {
+            song.id = namespaced(name, &song.id);
+            song.album_id = song.album_id.map(|id| namespaced(name, &id));
+            song.artist_id = song.artist_id.map(|id| namespaced(name, &id));
+            song.cover_art = namespace_cover(name, song.cover_art);
+            // 代理地址 /api/stream/{id} 也要带上命名空间, 才能路由回所属数据源
+            song.stream_url = song.stream_url.map(|url| match url.strip_prefix(STREAM_PREFIX) {
+                Some(rest) => format!("{}{}", STREAM_PREFIX, namespaced(name, rest)),
+                None => url,
+            });
+            song
+        })
+        .collect()
+}
+
+fn namespace_song(name: &str, song: UnifiedMetadata) -> UnifiedMetadata {
+    namespace_songs(name, vec![song]).remove(0)
+}
+
+/// 本地数据源的歌曲不带专辑和艺术家 ID (由上层按歌曲 ID 查询),
+/// 加上命名空间后就查不到了, 所以在这里先补全
+fn fill_local_ids(songs: &mut [UnifiedMetadata]) {
+    let ids: Vec<String> = songs
+        .iter()
+        .filter(|s| s.source == DataSourceType::Local && s.album_id.is_none())
+        .map(|s| s.id.clone())
+        .collect();
+    if ids.is_empty() {
+        return;
+    }
+
+    let album_map: HashMap<String, i64> = service::album_song_by_song_ids(&ids)
+        .map(|list| list.into_iter().map(|a| (a.song_id, a.album_id)).collect())
+        .unwrap_or_default();
+    let artist_map: HashMap<String, i64> = service::artist_song_by_song_ids(&ids)
+        .map(|list| list.into_iter().map(|a| (a.song_id, a.artist_id)).collect())
+        .unwrap_or_default();
+
+    for song in songs.iter_mut().filter(|s| s.source == DataSourceType::Local) {
+        if let Some(album_id) = album_map.get(&song.id) {
+            song.album_id = Some(album_id.to_string());
+            // 本地封面按专辑 ID 存储
+            song.cover_art.get_or_insert(album_id.to_string());
+        }
+        if let Some(artist_id) = artist_map.get(&song.id) {
+            song.artist_id.get_or_insert(artist_id.to_string());
+        }
+    }
+}
+
+fn namespace_album(name: &str, mut album: AlbumInfo) -> AlbumInfo {
+    album.id = namespaced(name, &album.id);
+    album.cover_art = namespace_cover(name, album.cover_art);
+    album
+}
+
+fn namespace_artist(name: &str, mut artist: ArtistInfo) -> ArtistInfo {
+    artist.id = namespaced(name, &artist.id);
+    artist.cover_art = namespace_cover(name, artist.cover_art);
+    artist
+}
+
+fn namespace_playlist(name: &str, mut playlist: PlaylistInfo) -> PlaylistInfo {
+    playlist.id = namespaced(name, &playlist.id);
+    playlist.cover = namespace_cover(name, playlist.cover);
+    playlist
+}
+
+/// 去重用的规范化字符串: 小写, 合并空白
+fn normalize(s: &str) -> String {
+    s.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn song_key(song: &UnifiedMetadata) -> String {
+    format!(
+        "{}|{}|{}",
+        normalize(&song.title),
+        normalize(&song.artist),
+        normalize(&song.album)
+    )
+}
+
+/// 各数据源请求前 page * page_size 条, 合并后再分页
+fn expand_pagination(pagination: &Pagination) -> Pagination {
+    if pagination.is_valid() {
+        Pagination::new(1, pagination.page * pagination.page_size)
+    } else {
+        pagination.clone()
+    }
+}
+
+fn paginate<T>(list: Vec<T>, pagination: &Pagination) -> Vec<T> {
+    if !pagination.is_valid() {
+        return list;
+    }
+    let start = pagination.safe_start(list.len());
+    let end = pagination.end(list.len());
+    list.into_iter().skip(start).take(end - start).collect()
+}
+
+/// 轮流从各数据源的列表中取元素, 用于没有统一排序的列表(随机、最近播放等)
+fn interleave<T>(lists: Vec<Vec<T>>) -> Vec<T> {
+    let mut iters: Vec<_> = lists.into_iter().map(|l| l.into_iter()).collect();
+    let mut merged = Vec::new();
+    loop {
+        let mut progressed = false;
+        for iter in iters.iter_mut() {
+            if let Some(item) = iter.next() {
+                merged.push(item);
+                progressed = true;
+            }
+        }
+        if !progressed {
+            break;
+        }
+    }
+    merged
+}
+
+#[async_trait]
+impl MusicDataSource for AggregateDataSource {
+    async fn get_metadata(&self, id: &str) -> Result<UnifiedMetadata> {
+        let (source, raw_id) = self.route(id)?;
+        let song = source.data_source.get_metadata(raw_id).await?;
+        Ok(namespace_song(&source.name, song))
+    }
+
+    async fn list_metadata(&self, filter: MetadataFilter) -> Result<Vec<UnifiedMetadata>> {
+        let pagination = Pagination::new(filter.page.unwrap_or(0), filter.page_size.unwrap_or(0));
+        let expanded = expand_pagination(&pagination);
+
+        // 按数据源拆分 ID 过滤条件
+        let split_ids = |ids: &Option<Vec<String>>, source: &NamedSource| -> Option<Vec<String>> {
+            ids.as_ref().map(|ids| {
+                ids.iter()
+                    .filter_map(|id| self.route(id).ok())
+                    .filter(|(s, _)| s.name == source.name)
+                    .map(|(_, raw_id)| raw_id.to_string())
+                    .collect()
+            })
+        };
+
+        let lists = self
+            .fan_out(|source| {
+                let mut source_filter = filter.clone();
+                source_filter.artist_ids = split_ids(&filter.artist_ids, source);
+                source_filter.album_ids = split_ids(&filter.album_ids, source);
+                if pagination.is_valid() {
+                    source_filter.page = Some(expanded.page);
+                    source_filter.page_size = Some(expanded.page_size);
+                }
+                // 过滤条件中的 ID 都不属于该数据源时跳过
+                let skip = matches!(&source_filter.artist_ids, Some(ids) if ids.is_empty())
+                    || matches!(&source_filter.album_ids, Some(ids) if ids.is_empty());
+                async move {
+                    if skip {
+                        Ok(vec![])
+                    } else {
+                        source.data_source.list_metadata(source_filter).await
+                    }
+                }
+            })
+            .await?;
+
+        Ok(paginate(self.merge_songs(lists), &pagination))
+    }
+
+    async fn get_cover(&self, link_id: &str, size: CoverSize) -> Result<Vec<u8>> {
+        let (source, raw_id) = self.route(link_id)?;
+        source.data_source.get_cover(raw_id, size).await
+    }
+
+    async fn get_lyrics(&self, song_id: &str) -> Result<Vec<LyricLine>> {
+        let (source, raw_id) = self.route(song_id)?;
+        source.data_source.get_lyrics(raw_id).await
+    }
+
+    async fn get_audio_stream(&self, song_id: &str) -> Result<AudioStream> {
+        let (source, raw_id) = self.route(song_id)?;
+        source.data_source.get_audio_stream(raw_id).await
+    }
+
+    async fn stream_song(
+        &self,
+        song_id: &str,
+        range: Option<String>,
+        options: &StreamOptions,
+    ) -> Result<reqwest::Response> {
+        let (source, raw_id) = self.route(song_id)?;
+        source.data_source.stream_song(raw_id, range, options).await
+    }
+
+    async fn scan_library(&self) -> Result<ScanProgress> {
+        let results = self.fan_out(|s| s.data_source.scan_library()).await?;
+        Ok(combine_scan_progress(results.into_iter().map(|(_, p)| p).collect()))
+    }
+
+    async fn list_albums(
+        &self,
+        pagination: Pagination,
+        filter_text: Option<String>,
+        list_type: Option<AlbumListType>,
+    ) -> Result<Vec<AlbumInfo>> {
+        let expanded = expand_pagination(&pagination);
+        let lists = self
+            .fan_out(|s| {
+                s.data_source
+                    .list_albums(expanded.clone(), filter_text.clone(), list_type.clone())
+            })
+            .await?;
+
+        let mut albums = self.merge_albums(lists);
+        if matches!(list_type, None | Some(AlbumListType::AlphabeticalByName)) {
+            albums.sort_by_key(|a| normalize(&a.name));
+        } else {
+            // 其他排序方式各数据源之间不可比较, 轮流取
+            let mut by_source: Vec<Vec<AlbumInfo>> = vec![vec![]; self.sources.len()];
+            for album in albums {
+                let index = self
+                    .sources
+                    .iter()
+                    .position(|s| album.id.starts_with(&namespaced(&s.name, "")))
+                    .unwrap_or(0);
+                by_source[index].push(album);
+            }
+            albums = interleave(by_source);
+        }
+        Ok(paginate(albums, &pagination))
+    }
+
+    async fn get_album_by_id(&self, album_id: &str) -> Result<AlbumInfo> {
+        let (source, raw_id) = self.route(album_id)?;
+        let album = source.data_source.get_album_by_id(raw_id).await?;
+        Ok(namespace_album(&source.name, album))
+    }
+
+    async fn get_album_songs(&self, album_id: &str) -> Result<Vec<UnifiedMetadata>> {
+        let (source, raw_id) = self.route(album_id)?;
+        let songs = source.data_source.get_album_songs(raw_id).await?;
+        Ok(namespace_songs(&source.name, songs))
+    }
+
+    async fn find_song_by_path(&self, path: &str) -> Result<Option<UnifiedMetadata>> {
+        // 按优先级依次查找
+        for source in &self.sources {
+            if let Ok(Some(song)) = source.data_source.find_song_by_path(path).await {
+                return Ok(Some(namespace_song(&source.name, song)));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn list_artists(&self) -> Result<Vec<ArtistInfo>> {
+        let lists = self.fan_out(|s| s.data_source.list_artists()).await?;
+        let mut artists = self.merge_artists(lists);
+        artists.sort_by_key(|a| normalize(&a.name));
+        Ok(artists)
+    }
+
+    async fn get_artist_by_id(&self, artist_id: &str) -> Result<ArtistInfo> {
+        let (source, raw_id) = self.route(artist_id)?;
+        let artist = source.data_source.get_artist_by_id(raw_id).await?;
+        Ok(namespace_artist(&source.name, artist))
+    }
+
+    async fn get_artist_songs(&self, artist_id: &str) -> Result<Vec<UnifiedMetadata>> {
+        let (source, raw_id) = self.route(artist_id)?;
+        let songs = source.data_source.get_artist_songs(raw_id).await?;
+        Ok(namespace_songs(&source.name, songs))
+    }
+
+    async fn list_genres(&self) -> Result<Vec<GenreInfo>> {
+        let lists = self.fan_out(|s| s.data_source.list_genres()).await?;
+
+        // 同名风格合并计数
+        let mut merged: Vec<GenreInfo> = Vec::new();
+        let mut index_map: HashMap<String, usize> = HashMap::new();
+        for (_, genres) in lists {
+            for genre in genres {
+                let key = normalize(&genre.value);
+                if let Some(&i) = index_map.get(&key) {
+                    merged[i].album_count += genre.album_count;
+                    merged[i].song_count += genre.song_count;
+                } else {
+                    index_map.insert(key, merged.len());
+                    merged.push(genre);
+                }
+            }
+        }
+        Ok(merged)
+    }
+
+    async fn get_genre_songs(&self, genre: &str) -> Result<Vec<UnifiedMetadata>> {
+        let lists = self.fan_out(|s| s.data_source.get_genre_songs(genre)).await?;
+        Ok(self.merge_songs(lists))
+    }
+
+    async fn get_random_songs(
+        &self,
+        size: Option<usize>,
+        genre: Option<&str>,
+        from_year: Option<&str>,
+        to_year: Option<&str>,
+        bpm: BpmRange,
+    ) -> Result<Vec<UnifiedMetadata>> {
+        let lists = self
+            .fan_out(|s| s.data_source.get_random_songs(size, genre, from_year, to_year, bpm))
+            .await?;
+
+        let mut songs = self.merge_songs(lists);
+        songs.shuffle(&mut rand::thread_rng());
+        songs.truncate(size.unwrap_or(150).min(500));
+        Ok(songs)
+    }
+
+    async fn get_similar_songs(
+        &self,
+        seed: &RadioSeed,
+        count: usize,
+        exclude: &[String],
+    ) -> Result<Vec<UnifiedMetadata>> {
+        let (source, raw_seed) = match seed {
+            RadioSeed::Song(id) => {
+                let (source, raw_id) = self.route(id)?;
+                (source, RadioSeed::Song(raw_id.to_string()))
+            }
+            RadioSeed::Artist(id) => {
+                let (source, raw_id) = self.route(id)?;
+                (source, RadioSeed::Artist(raw_id.to_string()))
+            }
+            // 风格电台合并所有数据源的结果
+            RadioSeed::Genre(_) => {
+                let lists = self
+                    .fan_out(|s| async move {
+                        let exclude = self.exclude_for(s, exclude);
+                        s.data_source.get_similar_songs(seed, count, &exclude).await
+                    })
+                    .await?;
+                let mut songs = self.merge_songs(lists);
+                songs.truncate(count);
+                return Ok(songs);
+            }
+        };
+        let exclude = self.exclude_for(source, exclude);
+        let songs = source
+            .data_source
+            .get_similar_songs(&raw_seed, count, &exclude)
+            .await?;
+        Ok(namespace_songs(&source.name, songs))
+    }
+
+    async fn search(&self, query: &str, pagination: Pagination) -> Result<SearchResult> {
+        let expanded = expand_pagination(&pagination);
+        let results = self
+            .fan_out(|s| s.data_source.search(query, expanded.clone()))
+            .await?;
+
+        let mut songs = Vec::new();
+        let mut albums = Vec::new();
+        let mut artists = Vec::new();
+        for (source, result) in results {
+            songs.push((source, result.songs));
+            albums.push((source, result.albums));
+            artists.push((source, result.artists));
+        }
+
+        Ok(SearchResult {
+            songs: paginate(self.merge_songs(songs), &pagination),
+            albums: paginate(self.merge_albums(albums), &pagination),
+            artists: paginate(self.merge_artists(artists), &pagination),
+        })
+    }
+
+    fn source_type(&self) -> DataSourceType {
+        DataSourceType::Aggregate
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let merged = self
+            .sources
+            .iter()
+            .map(|s| s.data_source.capabilities())
+            .fold(Capabilities::default(), Capabilities::union);
+        // 编辑标签、歌词, 按艺术家/专辑过滤和重复检测直接使用本地数据库的原始 ID, 只在本地模式下可用
+        // 收听统计由本地数据源计算, 返回的 ID 加上命名空间
+        Capabilities {
+            tag_editing: false,
+            lyrics_editing: false,
+            metadata_filtering: false,
+            listening_stats: self.local_source().is_some(),
+            duplicate_detection: false,
+            ..merged
+        }
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        let results = join_all(self.sources.iter().map(|s| s.data_source.health_check())).await;
+        let failed: Vec<String> = self
+            .sources
+            .iter()
+            .zip(results)
+            .filter_map(|(s, r)| r.err().map(|e| format!("{}: {}", s.name, e)))
+            .collect();
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(DataSourceError::Upstream(anyhow::anyhow!(
+                "Data source health check failed: {}",
+                failed.join("; ")
+            )))
+        }
+    }
+
+    async fn scan_music(&self) -> Result<()> {
+        self.fan_out(|s| s.data_source.scan_music()).await?;
+        Ok(())
+    }
+
+    async fn clear_cache(&self) -> Result<()> {
+        self.fan_out(|s| s.data_source.clear_cache()).await?;
+        Ok(())
+    }
+
+    async fn sync_library(&self, full: bool) -> Result<()> {
+        self.fan_out(|s| s.data_source.sync_library(full)).await?;
+        Ok(())
+    }
+
+    async fn pin(&self, item_type: PinItemType, id: &str, pin: bool) -> Result<()> {
+        let (source, raw_id) = self.route(id)?;
+        source.data_source.pin(item_type, raw_id, pin).await
+    }
+
+    async fn pin_status(&self) -> Result<PinStatus> {
+        let results = self.fan_out(|s| s.data_source.pin_status()).await?;
+        let mut status = PinStatus::default();
+        for (source, s) in results {
+            status.quota += s.quota;
+            status.used += s.used;
+            status.items.extend(s.items.into_iter().map(|mut item| {
+                item.item_id = namespaced(&source.name, &item.item_id);
+                item
+            }));
+        }
+        Ok(status)
+    }
+
+    async fn scan_status(&self) -> Result<ScanProgress> {
+        let results = self.fan_out(|s| s.data_source.scan_status()).await?;
+        Ok(combine_scan_progress(results.into_iter().map(|(_, p)| p).collect()))
+    }
+
+    async fn list_playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        let lists = self.fan_out(|s| s.data_source.list_playlists()).await?;
+        Ok(lists
+            .into_iter()
+            .flat_map(|(source, list)| {
+                list.into_iter().map(move |p| namespace_playlist(&source.name, p))
+            })
+            .collect())
+    }
+
+    async fn get_playlist(&self, playlist_id: &str) -> Result<PlaylistDetail> {
+        let (source, raw_id) = self.route(playlist_id)?;
+        let mut playlist = source.data_source.get_playlist(raw_id).await?;
+        playlist.id = namespaced(&source.name, &playlist.id);
+        playlist.cover = namespace_cover(&source.name, playlist.cover);
+        playlist.songs = namespace_songs(&source.name, playlist.songs);
+        Ok(playlist)
+    }
+
+    async fn create_playlist(
+        &self,
+        name: &str,
+        description: Option<&str>,
+        song_ids: &[String],
+    ) -> Result<()> {
+        // 歌单只能保存在一个数据源中, 空歌单放在优先级最高的数据源
+        match self.route_all(song_ids)? {
+            Some((source, raw_ids)) => {
+                source
+                    .data_source
+                    .create_playlist(name, description, &raw_ids)
+                    .await
+            }
+            None => {
+                self.primary()
+                    .data_source
+                    .create_playlist(name, description, &[])
+                    .await
+            }
+        }
+    }
+
+    async fn update_playlist(
+        &self,
+        playlist_id: &str,
+        name: Option<&str>,
+        description: Option<&str>,
+        song_ids: Option<&[String]>,
+    ) -> Result<()> {
+        let (source, raw_id) = self.route(playlist_id)?;
+        let raw_song_ids = match song_ids {
+            Some(ids) => Some(self.strip_song_ids(source, ids)?),
+            None => None,
+        };
+        source
+            .data_source
+            .update_playlist(raw_id, name, description, raw_song_ids.as_deref())
+            .await
+    }
+
+    async fn edit_playlist(&self, playlist_id: &str, edit: &PlaylistEdit) -> Result<()> {
+        let (source, raw_id) = self.route(playlist_id)?;
+        let edit = match edit {
+            PlaylistEdit::Append { song_ids } => PlaylistEdit::Append {
+                song_ids: self.strip_song_ids(source, song_ids)?,
+            },
+            PlaylistEdit::Insert { index, song_ids } => PlaylistEdit::Insert {
+                index: *index,
+                song_ids: self.strip_song_ids(source, song_ids)?,
+            },
+            PlaylistEdit::Replace { song_ids } => PlaylistEdit::Replace {
+                song_ids: self.strip_song_ids(source, song_ids)?,
+            },
+            other => other.clone(),
+        };
+        source.data_source.edit_playlist(raw_id, &edit).await
+    }
+
+    async fn set_playlist_public(&self, playlist_id: &str, public: bool) -> Result<()> {
+        let (source, raw_id) = self.route(playlist_id)?;
+        source.data_source.set_playlist_public(raw_id, public).await
+    }
+
+    async fn set_playlist_cover(&self, playlist_id: &str, cover: &str) -> Result<()> {
+        let (source, raw_id) = self.route(playlist_id)?;
+        source.data_source.set_playlist_cover(raw_id, cover).await
+    }
+
+    async fn list_public_playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        let lists = self.fan_out(|s| s.data_source.list_public_playlists()).await?;
+        Ok(lists
+            .into_iter()
+            .flat_map(|(source, list)| {
+                list.into_iter().map(move |p| namespace_playlist(&source.name, p))
+            })
+            .collect())
+    }
+
+    async fn subscribe_playlist(&self, playlist_id: &str, subscribe: bool) -> Result<()> {
+        let (source, raw_id) = self.route(playlist_id)?;
+        source.data_source.subscribe_playlist(raw_id, subscribe).await
+    }
+
+    async fn create_smart_playlist(
+        &self,
+        name: &str,
+        description: Option<&str>,
+        rule: &SmartPlaylistRule,
+    ) -> Result<()> {
+        // 智能歌单依赖本地数据库
+        let source = self.local_source().unwrap_or_else(|| self.primary());
+        source
+            .data_source
+            .create_smart_playlist(name, description, rule)
+            .await
+    }
+
+    async fn update_smart_playlist(
+        &self,
+        playlist_id: &str,
+        rule: &SmartPlaylistRule,
+    ) -> Result<()> {
+        let (source, raw_id) = self.route(playlist_id)?;
+        source.data_source.update_smart_playlist(raw_id, rule).await
+    }
+
+    async fn delete_playlist(&self, playlist_id: &str) -> Result<()> {
+        let (source, raw_id) = self.route(playlist_id)?;
+        source.data_source.delete_playlist(raw_id).await
+    }
+
+    async fn get_play_queue(&self) -> Result<Option<PlayQueueInfo>> {
+        let source = self.primary();
+        let queue = source.data_source.get_play_queue().await?;
+        Ok(queue.map(|q| PlayQueueInfo {
+            songs: namespace_songs(&source.name, q.songs),
+            current_song: q.current_song.map(|s| namespace_song(&source.name, s)),
+            position: q.position,
+        }))
+    }
+
+    async fn save_play_queue(
+        &self,
+        song_ids: Vec<String>,
+        current_song_id: Option<String>,
+        position: Option<u64>,
+    ) -> Result<()> {
+        // 播放队列只能保存在一个数据源中, 其他数据源的歌曲不会被保存
+        let source = self.primary();
+        let own_ids: Vec<String> = song_ids
+            .iter()
+            .filter_map(|id| self.route(id).ok())
+            .filter(|(s, _)| s.name == source.name)
+            .map(|(_, raw_id)| raw_id.to_string())
+            .collect();
+        if own_ids.len() < song_ids.len() {
+            log::log_warn(&format!(
+                "[Aggregate] play queue: {} songs from other data sources are not saved",
+                song_ids.len() - own_ids.len()
+            ));
+        }
+        let current = current_song_id
+            .as_deref()
+            .and_then(|id| self.route(id).ok())
+            .filter(|(s, _)| s.name == source.name)
+            .map(|(_, raw_id)| raw_id.to_string());
+
+        source
+            .data_source
+            .save_play_queue(own_ids, current, position)
+            .await
+    }
+
+    async fn scrobble(
+        &self,
+        song_id: &str,
+        submission: Option<bool>,
+        timestamp: Option<u64>,
+    ) -> Result<()> {
+        let (source, raw_id) = self.route(song_id)?;
+        source.data_source.scrobble(raw_id, submission, timestamp).await
+    }
+
+    async fn star(&self, id: &str, item_type: StarItemType) -> Result<()> {
+        let (source, raw_id) = self.route(id)?;
+        source.data_source.star(raw_id, item_type).await
+    }
+
+    async fn unstar(&self, id: &str, item_type: StarItemType) -> Result<()> {
+        let (source, raw_id) = self.route(id)?;
+        source.data_source.unstar(raw_id, item_type).await
+    }
+
+    async fn get_starred(&self) -> Result<StarredResult> {
+        let results = self.fan_out(|s| s.data_source.get_starred()).await?;
+
+        let mut songs = Vec::new();
+        let mut albums = Vec::new();
+        let mut artists = Vec::new();
+        for (source, result) in results {
+            songs.push((source, result.songs));
+            albums.push((source, result.albums));
+            artists.push((source, result.artists));
+        }
+
+        Ok(StarredResult {
+            songs: self.merge_songs(songs),
+            albums: self.merge_albums(albums),
+            artists: self.merge_artists(artists),
+        })
+    }
+
+    async fn is_starred(&self, id: &str, item_type: StarItemType) -> Result<bool> {
+        let (source, raw_id) = self.route(id)?;
+        source.data_source.is_starred(raw_id, item_type).await
+    }
+
+    async fn set_rating(&self, id: &str, item_type: StarItemType, rating: u8) -> Result<()> {
+        let (source, raw_id) = self.route(id)?;
+        source.data_source.set_rating(raw_id, item_type, rating).await
+    }
+
+    async fn get_rating(&self, id: &str, item_type: StarItemType) -> Result<Option<u8>> {
+        let (source, raw_id) = self.route(id)?;
+        source.data_source.get_rating(raw_id, item_type).await
+    }
+
+    async fn stats_top(
+        &self,
+        item_type: StatsItemType,
+        range: TimeRange,
+        limit: u32,
+    ) -> Result<Vec<StatsItem>> {
+        let source = self.stats_source()?;
+        let items = source.data_source.stats_top(item_type, range, limit).await?;
+        Ok(namespace_stats_items(source, item_type, items))
+    }
+
+    async fn stats_first_listened(
+        &self,
+        item_type: StatsItemType,
+        range: TimeRange,
+        limit: u32,
+    ) -> Result<Vec<StatsItem>> {
+        let source = self.stats_source()?;
+        let items = source
+            .data_source
+            .stats_first_listened(item_type, range, limit)
+            .await?;
+        Ok(namespace_stats_items(source, item_type, items))
+    }
+
+    async fn stats_listening_time(
+        &self,
+        period: StatsPeriod,
+        range: TimeRange,
+    ) -> Result<Vec<ListeningTime>> {
+        self.stats_source()?
+            .data_source
+            .stats_listening_time(period, range)
+            .await
+    }
+
+    async fn stats_streaks(&self) -> Result<Streaks> {
+        self.stats_source()?.data_source.stats_streaks().await
+    }
+
+    async fn stats_heatmap(&self, range: TimeRange) -> Result<Vec<HeatmapCell>> {
+        self.stats_source()?.data_source.stats_heatmap(range).await
+    }
+
+    async fn wrapped(&self, year: i32) -> Result<Wrapped> {
+        let source = self.stats_source()?;
+        let mut report = source.data_source.wrapped(year).await?;
+        report.top_songs = namespace_stats_items(source, StatsItemType::Song, report.top_songs);
+        report.top_albums = namespace_stats_items(source, StatsItemType::Album, report.top_albums);
+        report.top_artists = namespace_stats_items(source, StatsItemType::Artist, report.top_artists);
+        report.new_artists = namespace_stats_items(source, StatsItemType::Artist, report.new_artists);
+        Ok(report)
+    }
+
+    async fn wrapped_card(&self, year: i32) -> Result<Vec<u8>> {
+        self.stats_source()?.data_source.wrapped_card(year).await
+    }
+}
+
+impl AggregateDataSource {
+    /// 属于指定数据源的歌曲 ID (去掉命名空间), 其他数据源的 ID 忽略
+    fn exclude_for(&self, source: &NamedSource, ids: &[String]) -> Vec<String> {
+        ids.iter()
+            .filter_map(|id| id.split_once(NAMESPACE_SEPARATOR))
+            .filter(|(name, _)| *name == source.name)
+            .map(|(_, raw_id)| raw_id.to_string())
+            .collect()
+    }
+
+    /// 去掉歌曲 ID 的命名空间, 要求都属于指定数据源
+    fn strip_song_ids(&self, source: &NamedSource, ids: &[String]) -> Result<Vec<String>> {
+        match self.route_all(ids)? {
+            Some((s, raw_ids)) if s.name == source.name => Ok(raw_ids),
+            Some((s, _)) => Err(DataSourceError::InvalidId(format!(
+                "Songs from {} cannot be added to a playlist in {}",
+                s.name,
+                source.name
+            ))),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+/// 合并扫描进度: 任一数据源扫描中则为扫描中
+fn combine_scan_progress(list: Vec<ScanProgress>) -> ScanProgress {
+    let status = if list.iter().any(|p| p.status == ScanStatus::Scanning) {
+        ScanStatus::Scanning
+    } else if list.iter().any(|p| p.status == ScanStatus::Failed) {
+        ScanStatus::Failed
+    } else if list.iter().all(|p| p.status == ScanStatus::Completed) {
+        ScanStatus::Completed
+    } else {
+        ScanStatus::Idle
+    };
+
+    ScanProgress {
+        status,
+        processed: list.iter().map(|p| p.processed).sum(),
+        total: list.iter().map(|p| p.total).sum(),
+        current_file: list.iter().find_map(|p| p.current_file.clone()),
+        error: list.iter().find_map(|p| p.error.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_namespace_and_dedupe() {
+        let song = |id: &str, title: &str| UnifiedMetadata {
+            id: id.to_string(),
+            title: title.to_string(),
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            source: DataSourceType::Subsonic,
+            album_id: Some("al-1".to_string()),
+            cover_art: Some("https://example.com/cover.jpg".to_string()),
+            ..Default::default()
+        };
+
+        let songs = namespace_songs("remote", vec![song("1", "Song A")]);
+        assert_eq!(songs[0].id, "remote:1");
+        assert_eq!(songs[0].album_id.as_deref(), Some("remote:al-1"));
+        // URL 封面保持不变
+        assert_eq!(songs[0].cover_art.as_deref(), Some("https://example.com/cover.jpg"));
+
+        assert_eq!(song_key(&song("1", "Song  a ")), song_key(&song("2", "song A")));
+        assert_eq!(
+            interleave(vec![vec![1, 2, 3], vec![10], vec![20, 21]]),
+            vec![1, 10, 20, 2, 21, 3]
+        );
+        assert_eq!(paginate((1..=10).collect(), &Pagination::new(2, 4)), vec![5, 6, 7, 8]);
+    }
+}
@@ -0,0 +1,137 @@
+// 数据源错误类型
+// 区分数据不存在、ID 无效、当前模式不支持、远程服务器错误等情况, 方便接口返回对应的状态码
+
+use std::fmt;
+
+/// 数据源操作的返回值
+pub type Result<T, E = DataSourceError> = std::result::Result<T, E>;
+
+/// 数据源错误
+#[derive(Debug)]
+pub enum DataSourceError {
+    /// 数据不存在
+    NotFound(String),
+    /// ID 格式错误或不属于当前数据源
+    InvalidId(String),
+    /// 当前数据源不支持该操作
+    Unsupported(String),
+    /// 远程服务器认证失败或没有权限
+    Unauthorized(String),
+    /// 远程服务器请求超时
+    Timeout(String),
+    /// 远程服务器暂时不可用 (如连续失败后熔断)
+    Unavailable(String),
+    /// 远程服务器不可用或返回错误
+    Upstream(anyhow::Error),
+    /// 本地数据库错误
+    Database(anyhow::Error),
+    /// 其他错误 (文件读写等)
+    Internal(anyhow::Error),
+}
+
+impl DataSourceError {
+    pub fn not_found(msg: impl Into<String>) -> Self {
+        DataSourceError::NotFound(msg.into())
+    }
+
+    pub fn invalid_id(msg: impl Into<String>) -> Self {
+        DataSourceError::InvalidId(msg.into())
+    }
+
+    pub fn unsupported(msg: impl Into<String>) -> Self {
+        DataSourceError::Unsupported(msg.into())
+    }
+}
+
+impl fmt::Display for DataSourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataSourceError::NotFound(msg)
+            | DataSourceError::InvalidId(msg)
+            | DataSourceError::Unsupported(msg)
+            | DataSourceError::Unauthorized(msg)
+            | DataSourceError::Timeout(msg)
+            | DataSourceError::Unavailable(msg) => write!(f, "{}", msg),
+            DataSourceError::Upstream(e) => write!(f, "Upstream error: {:#}", e),
+            DataSourceError::Database(e) => write!(f, "Database error: {:#}", e),
+            DataSourceError::Internal(e) => write!(f, "{:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for DataSourceError {}
+
+impl From<rusqlite::Error> for DataSourceError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::QueryReturnedNoRows => DataSourceError::not_found("Record not found"),
+            e => DataSourceError::Database(e.into()),
+        }
+    }
+}
+
+impl From<reqwest::Error> for DataSourceError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            DataSourceError::Timeout(e.to_string())
+        } else {
+            DataSourceError::Upstream(e.into())
+        }
+    }
+}
+
+impl From<std::io::Error> for DataSourceError {
+    fn from(e: std::io::Error) -> Self {
+        DataSourceError::Internal(e.into())
+    }
+}
+
+impl From<serde_json::Error> for DataSourceError {
+    fn from(e: serde_json::Error) -> Self {
+        DataSourceError::Internal(e.into())
+    }
+}
+
+/// 按错误链中的具体类型分类
+///
+/// 各数据源的客户端先把自己的错误转换为 DataSourceError 再放入 anyhow::Error, 这里直接取出
+impl From<anyhow::Error> for DataSourceError {
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<DataSourceError>() {
+            Ok(e) => return e,
+            Err(e) => e,
+        };
+        if e.chain().any(|c| c.is::<rusqlite::Error>()) {
+            return match e.downcast::<rusqlite::Error>() {
+                Ok(e) => e.into(),
+                Err(e) => DataSourceError::Database(e),
+            };
+        }
+        if e.chain().any(|c| c.is::<reqwest::Error>()) {
+            return DataSourceError::Upstream(e);
+        }
+        DataSourceError::Internal(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_anyhow() {
+        let e: DataSourceError = anyhow::Error::new(DataSourceError::not_found("Song not found"))
+            .context("get song")
+            .into();
+        assert!(matches!(e, DataSourceError::NotFound(_)));
+
+        let e: DataSourceError = anyhow::Error::new(rusqlite::Error::QueryReturnedNoRows).into();
+        assert!(matches!(e, DataSourceError::NotFound(_)));
+
+        let e: DataSourceError = anyhow::Error::new(DataSourceError::invalid_id("Invalid song id: x")).into();
+        assert!(matches!(e, DataSourceError::InvalidId(_)));
+
+        let e: DataSourceError = anyhow::anyhow!("disk full").into();
+        assert!(matches!(e, DataSourceError::Internal(_)));
+    }
+}
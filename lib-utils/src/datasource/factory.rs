@@ -2,10 +2,15 @@
 // 根据配置创建合适的数据源实例
 
 use std::sync::Arc;
+use std::time::Duration;
 use crate::datasource::trait_def::MusicDataSource;
 use crate::datasource::local::datasource::LocalDataSource;
+use crate::datasource::subsonic::policy::ClientPolicy;
 use crate::datasource::subsonic::SubsonicDataSource;
-use crate::config::Config;
+use crate::datasource::jellyfin::JellyfinDataSource;
+use crate::datasource::webdav::{WebDavClient, WebDavLibrary};
+use crate::datasource::aggregate::{AggregateDataSource, NamedSource};
+use crate::config::{Config, JellyfinConfig, LocalConfig, SubsonicConfig, WebDavConfig};
 
 /// 数据源模式
 #[derive(Debug, Clone, PartialEq)]
@@ -14,6 +19,12 @@ pub enum DataSourceMode {
     Local,
     /// Subsonic 服务器模式
     Subsonic,
+    /// Jellyfin 服务器模式
+    Jellyfin,
+    /// WebDAV 音乐目录
+    WebDav,
+    /// 聚合多个数据源
+    Aggregate,
 }
 
 /// 根据配置创建数据源
@@ -35,42 +46,171 @@ pub fn create_data_source(config: &Config) -> Arc<dyn MusicDataSource> {
                 .as_ref()
                 .expect("Subsonic config not found in config.json");
 
-            println!("[DataSource] Creating Subsonic data source...");
-            println!("  Server: {}", subsonic_config.server_url);
-            println!("  Username: {}", subsonic_config.username);
-            println!("  Max Bitrate: {} kbps", subsonic_config.max_bitrate);
-
-            Arc::new(SubsonicDataSource::new(
-                subsonic_config.server_url.clone(),
-                subsonic_config.username.clone(),
-                subsonic_config.password.clone(),
-                subsonic_config.use_token_auth,
-                subsonic_config.max_bitrate,
-                subsonic_config.prefer_format.clone(),
-            ))
+            create_subsonic(config, subsonic_config)
+        }
+        "jellyfin" => {
+            let jellyfin_config = config
+                .data_source
+                .jellyfin
+                .as_ref()
+                .expect("Jellyfin config not found in config.json");
+
+            create_jellyfin(jellyfin_config)
+        }
+        "webdav" => {
+            let webdav_config = config
+                .data_source
+                .webdav
+                .as_ref()
+                .expect("WebDAV config not found in config.json");
+
+            create_webdav(config, webdav_config)
+        }
+        "aggregate" => {
+            // 创建聚合数据源, 按配置顺序决定优先级
+            println!("[DataSource] Creating Aggregate data source...");
+            let sources = config
+                .data_source
+                .sources
+                .iter()
+                .map(|source| {
+                    println!("  Source: {} ({})", source.name, source.source_type);
+                    let data_source = match source.source_type.to_lowercase().as_str() {
+                        "subsonic" => create_subsonic(config, source.subsonic.as_ref().unwrap_or_else(|| {
+                            panic!("Subsonic config not found for source {}", source.name)
+                        })),
+                        "jellyfin" => create_jellyfin(source.jellyfin.as_ref().unwrap_or_else(|| {
+                            panic!("Jellyfin config not found for source {}", source.name)
+                        })),
+                        // WebDAV 和本地数据源共用数据库中的歌曲表, 不能同时使用
+                        "webdav" => panic!(
+                            "WebDAV source {} is not supported in aggregate mode",
+                            source.name
+                        ),
+                        _ => create_local(config, source.local.as_ref()),
+                    };
+                    NamedSource {
+                        name: source.name.clone(),
+                        data_source,
+                    }
+                })
+                .collect();
+
+            Arc::new(AggregateDataSource::new(sources).expect("Invalid aggregate data source config"))
         }
         "local" | _ => {
             // 创建本地文件数据源 (默认)
-            let music_dir = if let Some(local_config) = &config.data_source.local {
-                local_config.music_dir.clone()
-            } else {
-                // 兼容旧配置格式
-                config.music_dir.clone()
-            };
-
-            println!("[DataSource] Creating Local data source...");
-            println!("  Music Dir: {}", music_dir);
-            println!("  DB Path: {}", config.db_path);
-
-            Arc::new(LocalDataSource::new(music_dir, config.db_path.clone()))
+            create_local(config, config.data_source.local.as_ref())
         }
     }
 }
 
+fn create_subsonic(config: &Config, subsonic_config: &SubsonicConfig) -> Arc<dyn MusicDataSource> {
+    println!("[DataSource] Creating Subsonic data source...");
+    println!("  Server: {}", subsonic_config.server_url);
+    println!("  Username: {}", subsonic_config.username);
+    println!("  Max Bitrate: {} kbps", subsonic_config.max_bitrate);
+
+    let data_source = SubsonicDataSource::new(
+        subsonic_config.server_url.clone(),
+        subsonic_config.username.clone(),
+        subsonic_config.password.clone(),
+        subsonic_config.use_token_auth,
+        subsonic_config.max_bitrate,
+        subsonic_config.prefer_format.clone(),
+    )
+    .with_cache(subsonic_config.cache_ttl_seconds, subsonic_config.cache_capacity)
+    .with_client_policy(ClientPolicy {
+        timeout: Duration::from_secs(subsonic_config.timeout_seconds),
+        retry_count: subsonic_config.retry_count,
+        retry_backoff: Duration::from_millis(subsonic_config.retry_backoff_ms),
+        circuit_breaker_threshold: subsonic_config.circuit_breaker_threshold,
+        circuit_breaker_cooldown: Duration::from_secs(subsonic_config.circuit_breaker_cooldown_seconds),
+    });
+
+    let data_source = if subsonic_config.mirror {
+        println!("  Mirror: every {} minutes", subsonic_config.mirror_interval_minutes);
+        data_source.with_mirror(
+            &config.db_path,
+            &subsonic_config.cover_cache_dir,
+            subsonic_config.mirror_interval_minutes,
+        )
+    } else {
+        data_source
+    };
+
+    if subsonic_config.pin_quota_mb > 0 {
+        println!("  Pin Quota: {} MB", subsonic_config.pin_quota_mb);
+        Arc::new(data_source.with_pins(
+            &config.db_path,
+            &subsonic_config.pin_dir,
+            subsonic_config.pin_quota_mb * 1024 * 1024,
+        ))
+    } else {
+        Arc::new(data_source)
+    }
+}
+
+fn create_jellyfin(jellyfin_config: &JellyfinConfig) -> Arc<dyn MusicDataSource> {
+    println!("[DataSource] Creating Jellyfin data source...");
+    println!("  Server: {}", jellyfin_config.server_url);
+    println!("  Username: {}", jellyfin_config.username);
+    println!("  Max Bitrate: {} kbps", jellyfin_config.max_bitrate);
+
+    Arc::new(
+        JellyfinDataSource::new(
+            jellyfin_config.server_url.clone(),
+            jellyfin_config.username.clone(),
+            jellyfin_config.password.clone(),
+            jellyfin_config.client_name.clone(),
+            jellyfin_config.max_bitrate,
+            jellyfin_config.prefer_format.clone(),
+        )
+        .with_timeout(Duration::from_secs(jellyfin_config.timeout_seconds)),
+    )
+}
+
+fn create_webdav(config: &Config, webdav_config: &WebDavConfig) -> Arc<dyn MusicDataSource> {
+    println!("[DataSource] Creating WebDAV data source...");
+    println!("  Server: {}", webdav_config.server_url);
+    println!("  Scan Interval: {} minutes", webdav_config.scan_interval_minutes);
+
+    let mut client = WebDavClient::new(
+        &webdav_config.server_url,
+        webdav_config.username.clone(),
+        webdav_config.password.clone(),
+    )
+    .expect("Invalid WebDAV server_url in config.json");
+    client.set_timeout(Duration::from_secs(webdav_config.timeout_seconds));
+
+    Arc::new(
+        LocalDataSource::new(String::new(), config.db_path.clone())
+            .with_webdav(WebDavLibrary::new(client), webdav_config.scan_interval_minutes),
+    )
+}
+
+fn create_local(config: &Config, local_config: Option<&LocalConfig>) -> Arc<dyn MusicDataSource> {
+    let music_dir = if let Some(local_config) = local_config {
+        local_config.music_dir.clone()
+    } else {
+        // 兼容旧配置格式
+        config.music_dir.clone()
+    };
+
+    println!("[DataSource] Creating Local data source...");
+    println!("  Music Dir: {}", music_dir);
+    println!("  DB Path: {}", config.db_path);
+
+    Arc::new(LocalDataSource::new(music_dir, config.db_path.clone()))
+}
+
 /// 获取数据源模式
 pub fn get_data_source_mode(config: &Config) -> DataSourceMode {
     match config.data_source.mode.to_lowercase().as_str() {
         "subsonic" => DataSourceMode::Subsonic,
+        "jellyfin" => DataSourceMode::Jellyfin,
+        "webdav" => DataSourceMode::WebDav,
+        "aggregate" => DataSourceMode::Aggregate,
         _ => DataSourceMode::Local,
     }
 }
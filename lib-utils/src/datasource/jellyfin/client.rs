@@ -0,0 +1,530 @@
+// Jellyfin HTTP 客户端
+// 用户名密码登录获取 access token, 调用 Jellyfin REST API
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use md5::{Digest, Md5};
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::datasource::error::{DataSourceError, Result};
+
+/// 列表接口需要额外返回的字段
+const ITEM_FIELDS: &str = "Genres,Path,MediaSources,ChildCount,DateCreated,Overview,ItemCounts";
+
+/// 直接播放原始文件时允许的容器格式
+const DIRECT_PLAY_CONTAINERS: &str = "mp3,flac,aac,m4a,m4b,alac,ogg,oga,opus,webm,wav,wma,ape,wv";
+
+/// 登录后的会话
+#[derive(Debug, Clone)]
+struct JellyfinSession {
+    token: String,
+    user_id: String,
+}
+
+/// Jellyfin API 客户端
+pub struct JellyfinClient {
+    base_url: String,
+    username: String,
+    password: String,
+    client_name: String,
+    device_id: String,
+    client: Client,
+    timeout: Duration,
+    // 第一次请求时登录, token 失效后重新登录
+    session: RwLock<Option<JellyfinSession>>,
+}
+
+impl JellyfinClient {
+    /// 创建新的 Jellyfin 客户端
+    ///
+    /// # 参数
+    /// * `base_url` - Jellyfin 服务器地址 (如 "http://jellyfin.example.com:8096")
+    /// * `username` - 用户名
+    /// * `password` - 密码
+    /// * `client_name` - 客户端名称, 显示在 Jellyfin 的设备列表中
+    pub fn new(base_url: String, username: String, password: String, client_name: String) -> Self {
+        let base_url = base_url.trim_end_matches('/').to_string();
+        // 同一个用户和服务器使用固定的设备 ID, 避免每次启动都在服务器上新增一个设备
+        let device_id = hex::encode(Md5::digest(format!("{}@{}", username, base_url)));
+
+        Self {
+            base_url,
+            username,
+            password,
+            client_name,
+            device_id,
+            client: Client::new(),
+            timeout: Duration::from_secs(15),
+            session: RwLock::new(None),
+        }
+    }
+
+    /// 设置单次请求超时 (音频流不受影响)
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn server_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Jellyfin 的认证请求头
+    fn authorization(&self, token: Option<&str>) -> String {
+        let mut header = format!(
+            "MediaBrowser Client=\"{}\", Device=\"{}\", DeviceId=\"{}\", Version=\"{}\"",
+            self.client_name,
+            self.client_name,
+            self.device_id,
+            env!("CARGO_PKG_VERSION")
+        );
+        if let Some(token) = token {
+            header.push_str(&format!(", Token=\"{}\"", token));
+        }
+        header
+    }
+
+    /// 用户名密码登录
+    async fn login(&self) -> Result<JellyfinSession> {
+        let response = self
+            .client
+            .post(format!("{}/Users/AuthenticateByName", self.base_url))
+            .header(AUTHORIZATION, self.authorization(None))
+            .json(&serde_json::json!({
+                "Username": self.username,
+                "Pw": self.password,
+            }))
+            .timeout(self.timeout)
+            .send()
+            .await
+            .map_err(upstream)?;
+
+        match response.status() {
+            status if status.is_success() => {}
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                return Err(DataSourceError::Unauthorized(format!(
+                    "Jellyfin login failed for user {}",
+                    self.username
+                )))
+            }
+            status => {
+                return Err(DataSourceError::Upstream(anyhow::anyhow!(
+                    "Jellyfin login failed: HTTP {}",
+                    status
+                )))
+            }
+        }
+
+        let auth: AuthenticationResult = response.json().await.map_err(upstream)?;
+        let session = JellyfinSession {
+            token: auth.access_token,
+            user_id: auth.user.id,
+        };
+        *self.session.write().await = Some(session.clone());
+        Ok(session)
+    }
+
+    /// 当前会话, 没有登录时先登录
+    async fn session(&self) -> Result<JellyfinSession> {
+        if let Some(session) = self.session.read().await.as_ref() {
+            return Ok(session.clone());
+        }
+        self.login().await
+    }
+
+    /// 当前用户 ID
+    pub async fn user_id(&self) -> Result<String> {
+        Ok(self.session().await?.user_id)
+    }
+
+    /// 发送请求, token 失效 (401) 时重新登录并重试一次
+    async fn send<F>(&self, method: Method, path: &str, build: F) -> Result<Response>
+    where
+        F: Fn(RequestBuilder) -> RequestBuilder,
+    {
+        let mut relogin = true;
+        loop {
+            let session = self.session().await?;
+            let request = self
+                .client
+                .request(method.clone(), format!("{}{}", self.base_url, path))
+                .header(AUTHORIZATION, self.authorization(Some(&session.token)));
+            let response = build(request).send().await.map_err(upstream)?;
+
+            return match response.status() {
+                StatusCode::UNAUTHORIZED if relogin => {
+                    relogin = false;
+                    *self.session.write().await = None;
+                    continue;
+                }
+                status if status.is_success() => Ok(response),
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(DataSourceError::Unauthorized(
+                    format!("Jellyfin rejected request: {}", path),
+                )),
+                StatusCode::NOT_FOUND => Err(DataSourceError::not_found(format!(
+                    "Jellyfin item not found: {}",
+                    path
+                ))),
+                status => Err(DataSourceError::Upstream(anyhow::anyhow!(
+                    "Jellyfin HTTP {} for {}",
+                    status,
+                    path
+                ))),
+            };
+        }
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<T> {
+        let response = self
+            .send(Method::GET, path, |r| r.query(query).timeout(self.timeout))
+            .await?;
+        response.json().await.map_err(upstream)
+    }
+
+    /// 检查服务器是否可用, 以及用户名密码是否正确
+    pub async fn ping(&self) -> Result<()> {
+        let user_id = self.user_id().await?;
+        let _: serde_json::Value = self.get_json(&format!("/Users/{}", user_id), &[]).await?;
+        Ok(())
+    }
+
+    /// 查询媒体项目
+    ///
+    /// # 参数
+    /// * `item_types` - 项目类型, 如 "Audio" / "MusicAlbum" / "Playlist"
+    /// * `params` - 其他查询参数 (SortBy, SearchTerm, ParentId 等)
+    pub async fn get_items(
+        &self,
+        item_types: &str,
+        params: &[(&str, String)],
+    ) -> Result<ItemsResponse> {
+        let user_id = self.user_id().await?;
+        let mut query = vec![
+            ("IncludeItemTypes", item_types.to_string()),
+            ("Recursive", "true".to_string()),
+            ("Fields", ITEM_FIELDS.to_string()),
+            ("EnableUserData", "true".to_string()),
+        ];
+        query.extend(params.iter().cloned());
+        self.get_json(&format!("/Users/{}/Items", user_id), &query).await
+    }
+
+    /// 获取单个项目
+    pub async fn get_item(&self, id: &str) -> Result<JellyfinItem> {
+        let user_id = self.user_id().await?;
+        self.get_json(&format!("/Users/{}/Items/{}", user_id, id), &[])
+            .await
+    }
+
+    /// 获取专辑艺术家列表
+    pub async fn get_album_artists(&self, params: &[(&str, String)]) -> Result<ItemsResponse> {
+        let mut query = vec![
+            ("UserId", self.user_id().await?),
+            ("Fields", ITEM_FIELDS.to_string()),
+        ];
+        query.extend(params.iter().cloned());
+        self.get_json("/Artists/AlbumArtists", &query).await
+    }
+
+    /// 获取音乐风格列表
+    pub async fn get_genres(&self) -> Result<ItemsResponse> {
+        let query = [
+            ("UserId", self.user_id().await?),
+            ("Fields", "ItemCounts".to_string()),
+        ];
+        self.get_json("/MusicGenres", &query).await
+    }
+
+    /// 下载项目的主图
+    pub async fn get_image(&self, id: &str, max_width: Option<u32>) -> Result<Vec<u8>> {
+        let query: Vec<(&str, String)> = max_width
+            .map(|w| vec![("maxWidth", w.to_string())])
+            .unwrap_or_default();
+        let response = self
+            .send(Method::GET, &format!("/Items/{}/Images/Primary", id), |r| {
+                r.query(&query).timeout(self.timeout)
+            })
+            .await?;
+        Ok(response.bytes().await.map_err(upstream)?.to_vec())
+    }
+
+    /// 获取歌词, 没有歌词时返回 None
+    pub async fn get_lyrics(&self, id: &str) -> Result<Option<JellyfinLyrics>> {
+        match self.get_json(&format!("/Audio/{}/Lyrics", id), &[]).await {
+            Ok(lyrics) => Ok(Some(lyrics)),
+            Err(DataSourceError::NotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 获取音频流
+    ///
+    /// # 参数
+    /// * `id` - 歌曲 ID
+    /// * `range` - 转发给服务器的 Range 请求头
+    /// * `max_bitrate` - 最大比特率 (kbps), 0 表示不限制
+    /// * `format` - 转码格式, 为空或 "raw" 时播放原始文件
+    pub async fn stream(
+        &self,
+        id: &str,
+        range: Option<String>,
+        max_bitrate: u32,
+        format: &str,
+    ) -> Result<Response> {
+        let mut query = vec![
+            ("UserId", self.user_id().await?),
+            ("DeviceId", self.device_id.clone()),
+        ];
+        // universal 接口在原始文件满足 Container 和比特率限制时直接返回原始文件, 否则按 AudioCodec 转码
+        if format.is_empty() || format == "raw" {
+            query.push(("Container", DIRECT_PLAY_CONTAINERS.to_string()));
+        } else {
+            query.extend([
+                ("Container", format.to_string()),
+                ("TranscodingContainer", format.to_string()),
+                ("TranscodingProtocol", "http".to_string()),
+                ("AudioCodec", format.to_string()),
+            ]);
+        }
+        if max_bitrate > 0 {
+            query.push(("MaxStreamingBitrate", (max_bitrate * 1000).to_string()));
+        }
+
+        // 音频流可能很长, 不设置整体超时
+        self.send(Method::GET, &format!("/Audio/{}/universal", id), |r| {
+            let r = r.query(&query);
+            match &range {
+                Some(range) => r.header("Range", range),
+                None => r,
+            }
+        })
+        .await
+    }
+
+    /// 收藏 / 取消收藏
+    pub async fn set_favorite(&self, id: &str, favorite: bool) -> Result<()> {
+        let method = if favorite { Method::POST } else { Method::DELETE };
+        let user_id = self.user_id().await?;
+        self.send(method, &format!("/Users/{}/FavoriteItems/{}", user_id, id), |r| {
+            r.timeout(self.timeout)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// 获取歌单中的歌曲 (包含 PlaylistItemId)
+    pub async fn get_playlist_items(&self, id: &str) -> Result<ItemsResponse> {
+        let query = [
+            ("UserId", self.user_id().await?),
+            ("Fields", ITEM_FIELDS.to_string()),
+            ("EnableUserData", "true".to_string()),
+        ];
+        self.get_json(&format!("/Playlists/{}/Items", id), &query)
+            .await
+    }
+
+    /// 创建歌单, 返回新歌单的 ID
+    pub async fn create_playlist(&self, name: &str, song_ids: &[String]) -> Result<String> {
+        let body = serde_json::json!({
+            "Name": name,
+            "Ids": song_ids,
+            "UserId": self.user_id().await?,
+            "MediaType": "Audio",
+        });
+        let response = self
+            .send(Method::POST, "/Playlists", |r| r.json(&body).timeout(self.timeout))
+            .await?;
+        let created: PlaylistCreationResult = response.json().await.map_err(upstream)?;
+        Ok(created.id)
+    }
+
+    /// 追加歌曲到歌单末尾
+    pub async fn add_to_playlist(&self, id: &str, song_ids: &[String]) -> Result<()> {
+        let query = [("Ids", song_ids.join(",")), ("UserId", self.user_id().await?)];
+        self.send(Method::POST, &format!("/Playlists/{}/Items", id), |r| {
+            r.query(&query).timeout(self.timeout)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// 修改歌单名称、歌曲列表和公开状态 (Jellyfin 10.9+)
+    pub async fn update_playlist(&self, id: &str, update: &PlaylistUpdate) -> Result<()> {
+        self.send(Method::POST, &format!("/Playlists/{}", id), |r| {
+            r.json(update).timeout(self.timeout)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// 删除项目 (歌单)
+    pub async fn delete_item(&self, id: &str) -> Result<()> {
+        self.send(Method::DELETE, &format!("/Items/{}", id), |r| r.timeout(self.timeout))
+            .await?;
+        Ok(())
+    }
+
+    /// 标记为已播放
+    ///
+    /// # 参数
+    /// * `date_played` - 播放时间 (ISO 8601), None 表示当前时间
+    pub async fn mark_played(&self, id: &str, date_played: Option<String>) -> Result<()> {
+        let user_id = self.user_id().await?;
+        let query: Vec<(&str, String)> = date_played
+            .map(|d| vec![("DatePlayed", d)])
+            .unwrap_or_default();
+        self.send(Method::POST, &format!("/Users/{}/PlayedItems/{}", user_id, id), |r| {
+            r.query(&query).timeout(self.timeout)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// 上报正在播放
+    pub async fn report_playing(&self, id: &str) -> Result<()> {
+        let body = serde_json::json!({ "ItemId": id });
+        self.send(Method::POST, "/Sessions/Playing", |r| r.json(&body).timeout(self.timeout))
+            .await?;
+        Ok(())
+    }
+
+    /// 触发服务器扫描媒体库 (需要管理员权限)
+    pub async fn refresh_library(&self) -> Result<()> {
+        self.send(Method::POST, "/Library/Refresh", |r| r.timeout(self.timeout))
+            .await?;
+        Ok(())
+    }
+}
+
+fn upstream(e: reqwest::Error) -> DataSourceError {
+    DataSourceError::Upstream(anyhow::Error::new(e).context("Jellyfin request failed"))
+}
+
+// ========== 数据结构定义 ==========
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AuthenticationResult {
+    access_token: String,
+    user: AuthenticatedUser,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AuthenticatedUser {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PlaylistCreationResult {
+    id: String,
+}
+
+/// 修改歌单的请求体, 未设置的字段保持不变
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlaylistUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_public: Option<bool>,
+}
+
+/// 项目列表
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ItemsResponse {
+    #[serde(default)]
+    pub items: Vec<JellyfinItem>,
+    #[serde(default)]
+    pub total_record_count: usize,
+}
+
+/// 媒体项目 (歌曲、专辑、艺术家、风格、歌单)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct JellyfinItem {
+    pub id: String,
+    pub name: Option<String>,
+    #[serde(rename = "Type")]
+    pub item_type: Option<String>,
+    pub album: Option<String>,
+    pub album_id: Option<String>,
+    pub album_artist: Option<String>,
+    pub artists: Option<Vec<String>>,
+    pub artist_items: Option<Vec<NameIdPair>>,
+    pub album_artists: Option<Vec<NameIdPair>>,
+    pub production_year: Option<i32>,
+    /// 时长, 单位为 100 纳秒
+    pub run_time_ticks: Option<i64>,
+    pub genres: Option<Vec<String>>,
+    pub index_number: Option<i32>,
+    pub parent_index_number: Option<i32>,
+    pub container: Option<String>,
+    pub path: Option<String>,
+    pub overview: Option<String>,
+    pub date_created: Option<String>,
+    pub child_count: Option<i32>,
+    pub album_count: Option<i32>,
+    pub song_count: Option<i32>,
+    pub image_tags: Option<HashMap<String, String>>,
+    pub album_primary_image_tag: Option<String>,
+    pub user_data: Option<UserItemData>,
+    pub media_sources: Option<Vec<MediaSource>>,
+    /// 歌单中的条目 ID (同一首歌可以出现多次)
+    pub playlist_item_id: Option<String>,
+    /// 单曲响度增益 (dB, Jellyfin 10.9+)
+    pub normalization_gain: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NameIdPair {
+    pub name: Option<String>,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UserItemData {
+    #[serde(default)]
+    pub is_favorite: bool,
+    #[serde(default)]
+    pub play_count: u32,
+    pub last_played_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MediaSource {
+    /// 比特率 (bps)
+    pub bitrate: Option<u64>,
+}
+
+/// 歌词 (Jellyfin 10.9+)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct JellyfinLyrics {
+    #[serde(default)]
+    pub lyrics: Vec<JellyfinLyricLine>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct JellyfinLyricLine {
+    #[serde(default)]
+    pub text: String,
+    /// 开始时间, 单位为 100 纳秒
+    pub start: Option<i64>,
+}
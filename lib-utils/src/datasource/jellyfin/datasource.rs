@@ -0,0 +1,863 @@
+// Jellyfin 数据源实现
+// 实现 MusicDataSource trait, 连接 Jellyfin 服务器
+
+use async_trait::async_trait;
+use chrono::Datelike;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::client::{JellyfinClient, JellyfinItem, PlaylistUpdate};
+use super::mapper::parse_jellyfin_lyrics;
+use crate::datasource::error::{DataSourceError, Result};
+use crate::datasource::trait_def::MusicDataSource;
+use crate::datasource::types::*;
+
+/// Jellyfin 数据源
+pub struct JellyfinDataSource {
+    client: JellyfinClient,
+    max_bitrate: u32,
+    prefer_format: String,
+}
+
+impl JellyfinDataSource {
+    /// 创建新的 Jellyfin 数据源
+    pub fn new(
+        server_url: String,
+        username: String,
+        password: String,
+        client_name: String,
+        max_bitrate: u32,
+        prefer_format: String,
+    ) -> Self {
+        Self {
+            client: JellyfinClient::new(server_url, username, password, client_name),
+            max_bitrate,
+            prefer_format,
+        }
+    }
+
+    /// 设置单次请求超时
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client.set_timeout(timeout);
+        self
+    }
+
+    /// 转换歌曲并设置流式 URL
+    fn to_songs(&self, items: Vec<JellyfinItem>) -> Vec<UnifiedMetadata> {
+        items
+            .into_iter()
+            .map(|item| {
+                let mut meta: UnifiedMetadata = item.into();
+                // 返回本地代理端点, 由 stream_song 转发到 Jellyfin
+                meta.stream_url = Some(format!("/api/stream/{}", meta.id));
+                meta
+            })
+            .collect()
+    }
+
+    async fn query_songs(&self, params: &[(&str, String)]) -> Result<Vec<UnifiedMetadata>> {
+        let items = self.client.get_items("Audio", params).await?.items;
+        Ok(self.to_songs(items))
+    }
+
+    async fn query_albums(&self, params: &[(&str, String)]) -> Result<Vec<AlbumInfo>> {
+        let items = self.client.get_items("MusicAlbum", params).await?.items;
+        Ok(items.into_iter().map(|a| a.into()).collect())
+    }
+
+    async fn query_artists(&self, params: &[(&str, String)]) -> Result<Vec<ArtistInfo>> {
+        let items = self.client.get_album_artists(params).await?.items;
+        Ok(items.into_iter().map(|a| a.into()).collect())
+    }
+
+    /// 歌单中歌曲的 ID 列表
+    async fn playlist_song_ids(&self, playlist_id: &str) -> Result<Vec<String>> {
+        let items = self.client.get_playlist_items(playlist_id).await?.items;
+        Ok(items.into_iter().map(|s| s.id).collect())
+    }
+
+    async fn set_favorite(&self, id: &str, favorite: bool) -> Result<()> {
+        // Jellyfin 的收藏不区分项目类型
+        self.client.set_favorite(id, favorite).await
+    }
+}
+
+/// 分页参数
+fn page_params(pagination: &Pagination) -> [(&'static str, String); 2] {
+    [
+        ("StartIndex", pagination.start().to_string()),
+        ("Limit", pagination.page_size.to_string()),
+    ]
+}
+
+/// Jellyfin 只接受年份列表, 未指定的起止年份取 1900 年和今年
+fn year_list(from: Option<u32>, to: Option<u32>) -> String {
+    let from = from.unwrap_or(1900);
+    let to = to.unwrap_or_else(|| chrono::Local::now().year() as u32);
+    let (low, high) = (from.min(to), from.max(to));
+    (low..=high)
+        .map(|y| y.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[async_trait]
+impl MusicDataSource for JellyfinDataSource {
+    async fn get_metadata(&self, id: &str) -> Result<UnifiedMetadata> {
+        let item = self.client.get_item(id).await?;
+        Ok(self.to_songs(vec![item]).remove(0))
+    }
+
+    async fn list_metadata(&self, filter: MetadataFilter) -> Result<Vec<UnifiedMetadata>> {
+        let pagination = Pagination::new(filter.page.unwrap_or(1), filter.page_size.unwrap_or(30));
+        // Jellyfin 不记录跳过次数和用户评分, 按名称排序
+        let sort_by = match filter.sort {
+            Some(MetadataSort::Artist) => "Artist,SortName",
+            Some(MetadataSort::Album) => "Album,SortName",
+            Some(MetadataSort::Year) => "ProductionYear,SortName",
+            Some(MetadataSort::Duration) => "Runtime,SortName",
+            Some(MetadataSort::PlayCount) => "PlayCount,SortName",
+            Some(MetadataSort::LastPlayed) => "DatePlayed,SortName",
+            Some(MetadataSort::Title | MetadataSort::SkipCount | MetadataSort::Rating) | None => "SortName",
+        };
+        let sort_order = if filter.descending { "Descending" } else { "Ascending" };
+        let mut params = vec![
+            ("SortBy", sort_by.to_string()),
+            ("SortOrder", sort_order.to_string()),
+        ];
+        params.extend(page_params(&pagination));
+        if let Some(keyword) = filter.keyword.filter(|k| !k.trim().is_empty()) {
+            params.push(("SearchTerm", keyword.trim().to_string()));
+        }
+        self.query_songs(&params).await
+    }
+
+    async fn get_cover(&self, cover_art: &str, size: CoverSize) -> Result<Vec<u8>> {
+        let max_width = match size {
+            CoverSize::Small => Some(300),
+            CoverSize::Medium => Some(600),
+            CoverSize::Large => None,
+        };
+        self.client.get_image(cover_art, max_width).await
+    }
+
+    async fn get_lyrics(&self, song_id: &str) -> Result<Vec<LyricLine>> {
+        match self.client.get_lyrics(song_id).await? {
+            Some(lyrics) => Ok(parse_jellyfin_lyrics(lyrics)),
+            None => Ok(vec![]),
+        }
+    }
+
+    async fn get_audio_stream(&self, song_id: &str) -> Result<AudioStream> {
+        Ok(AudioStream::SubsonicStream {
+            url: format!("/api/stream/{}", song_id),
+            headers: HashMap::new(),
+        })
+    }
+
+    async fn stream_song(
+        &self,
+        song_id: &str,
+        range: Option<String>,
+        options: &StreamOptions,
+    ) -> Result<reqwest::Response> {
+        // 未指定的参数使用配置中的默认值
+        let max_bitrate = options.max_bitrate.unwrap_or(self.max_bitrate);
+        let format = options.format.as_deref().unwrap_or(&self.prefer_format);
+        self.client.stream(song_id, range, max_bitrate, format).await
+    }
+
+    async fn scan_library(&self) -> Result<ScanProgress> {
+        // Jellyfin 的扫描进度在计划任务中, 这里返回一个占位符
+        Ok(ScanProgress {
+            status: ScanStatus::Completed,
+            processed: 0,
+            total: 0,
+            current_file: None,
+            error: Some("Jellyfin scan managed by server".to_string()),
+        })
+    }
+
+    async fn list_albums(
+        &self,
+        pagination: Pagination,
+        filter_text: Option<String>,
+        list_type: Option<AlbumListType>,
+    ) -> Result<Vec<AlbumInfo>> {
+        let mut params: Vec<(&str, String)> = page_params(&pagination).into();
+
+        if let Some(filter_text) = filter_text.filter(|t| !t.trim().is_empty()) {
+            params.push(("SearchTerm", filter_text.trim().to_string()));
+            params.push(("SortBy", "SortName".to_string()));
+            return self.query_albums(&params).await;
+        }
+
+        let (sort_by, descending) = match list_type.unwrap_or_default() {
+            AlbumListType::Random => ("Random", false),
+            AlbumListType::Newest => ("DateCreated", true),
+            // 不支持用户评分, 按社区评分排序
+            AlbumListType::Highest | AlbumListType::ByRating { .. } => ("CommunityRating", true),
+            AlbumListType::Frequent => ("PlayCount", true),
+            AlbumListType::Recent => {
+                params.push(("Filters", "IsPlayed".to_string()));
+                ("DatePlayed", true)
+            }
+            AlbumListType::Starred => {
+                params.push(("Filters", "IsFavorite".to_string()));
+                ("SortName", false)
+            }
+            AlbumListType::ByGenre { genre } => {
+                params.push(("Genres", genre));
+                ("SortName", false)
+            }
+            AlbumListType::ByYear { from_year, to_year } => {
+                params.push(("Years", year_list(from_year, to_year)));
+                // 与 Subsonic 一致, 起始年份大于结束年份时倒序
+                ("ProductionYear,SortName", from_year.zip(to_year).is_some_and(|(f, t)| f > t))
+            }
+            AlbumListType::AlphabeticalByName => ("SortName", false),
+        };
+        params.push(("SortBy", sort_by.to_string()));
+        params.push((
+            "SortOrder",
+            if descending { "Descending" } else { "Ascending" }.to_string(),
+        ));
+        self.query_albums(&params).await
+    }
+
+    async fn get_album_by_id(&self, album_id: &str) -> Result<AlbumInfo> {
+        Ok(self.client.get_item(album_id).await?.into())
+    }
+
+    async fn get_album_songs(&self, album_id: &str) -> Result<Vec<UnifiedMetadata>> {
+        self.query_songs(&[
+            ("ParentId", album_id.to_string()),
+            ("SortBy", "ParentIndexNumber,IndexNumber,SortName".to_string()),
+        ])
+        .await
+    }
+
+    async fn find_song_by_path(&self, path: &str) -> Result<Option<UnifiedMetadata>> {
+        // Jellyfin 没有按路径查询的接口, 用文件名搜索后比对服务器端的绝对路径
+        let path = path.replace('\\', "/");
+        let stem = std::path::Path::new(&path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_string();
+        if stem.is_empty() {
+            return Ok(None);
+        }
+
+        let items = self
+            .client
+            .get_items("Audio", &[("SearchTerm", stem), ("Limit", "50".to_string())])
+            .await?
+            .items;
+        let relative = path.trim_start_matches('/');
+        let matched: Vec<_> = items
+            .into_iter()
+            .filter(|s| {
+                s.path
+                    .as_deref()
+                    .is_some_and(|p| !relative.is_empty() && p.replace('\\', "/").ends_with(relative))
+            })
+            .collect();
+        if matched.len() != 1 {
+            return Ok(None);
+        }
+        Ok(self.to_songs(matched).pop())
+    }
+
+    async fn list_artists(&self) -> Result<Vec<ArtistInfo>> {
+        self.query_artists(&[("SortBy", "SortName".to_string())]).await
+    }
+
+    async fn get_artist_by_id(&self, artist_id: &str) -> Result<ArtistInfo> {
+        Ok(self.client.get_item(artist_id).await?.into())
+    }
+
+    async fn get_artist_songs(&self, artist_id: &str) -> Result<Vec<UnifiedMetadata>> {
+        // 与 Subsonic 的热门歌曲对应, 按播放次数取前 50 首
+        self.query_songs(&[
+            ("ArtistIds", artist_id.to_string()),
+            ("SortBy", "PlayCount,SortName".to_string()),
+            ("SortOrder", "Descending".to_string()),
+            ("Limit", "50".to_string()),
+        ])
+        .await
+    }
+
+    async fn list_genres(&self) -> Result<Vec<GenreInfo>> {
+        let items = self.client.get_genres().await?.items;
+        Ok(items.into_iter().map(|g| g.into()).collect())
+    }
+
+    async fn get_genre_songs(&self, genre: &str) -> Result<Vec<UnifiedMetadata>> {
+        self.query_songs(&[
+            ("Genres", genre.to_string()),
+            ("SortBy", "SortName".to_string()),
+        ])
+        .await
+    }
+
+    async fn get_random_songs(
+        &self,
+        size: Option<usize>,
+        genre: Option<&str>,
+        from_year: Option<&str>,
+        to_year: Option<&str>,
+        bpm: BpmRange,
+    ) -> Result<Vec<UnifiedMetadata>> {
+        // Jellyfin 不提供 BPM
+        if !bpm.is_unbounded() {
+            return Ok(vec![]);
+        }
+        // 与 Subsonic 的默认数量和上限一致
+        let size = size.unwrap_or(10).min(500);
+        let mut params = vec![
+            ("SortBy", "Random".to_string()),
+            ("Limit", size.to_string()),
+        ];
+        if let Some(genre) = genre {
+            params.push(("Genres", genre.to_string()));
+        }
+        let from = from_year.and_then(|y| y.parse::<u32>().ok());
+        let to = to_year.and_then(|y| y.parse::<u32>().ok());
+        if from.is_some() || to.is_some() {
+            params.push(("Years", year_list(from, to)));
+        }
+        self.query_songs(&params).await
+    }
+
+    async fn get_similar_songs(
+        &self,
+        _seed: &RadioSeed,
+        _count: usize,
+        _exclude: &[String],
+    ) -> Result<Vec<UnifiedMetadata>> {
+        Err(DataSourceError::unsupported(
+            "Radio is not supported by the Jellyfin data source",
+        ))
+    }
+
+    async fn search(&self, query: &str, pagination: Pagination) -> Result<SearchResult> {
+        let mut params: Vec<(&str, String)> = page_params(&pagination).into();
+        params.push(("SearchTerm", query.to_string()));
+
+        Ok(SearchResult {
+            songs: self.query_songs(&params).await?,
+            albums: self.query_albums(&params).await?,
+            artists: self.query_artists(&params).await?,
+        })
+    }
+
+    fn source_type(&self) -> DataSourceType {
+        DataSourceType::Jellyfin
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            tag_editing: false,
+            lyrics_editing: false,
+            scanning: true,
+            transcoding: true,
+            // Jellyfin 没有保存播放队列的接口
+            play_queue_sync: false,
+            ratings: false,
+            starred_status: true,
+            metadata_filtering: false,
+            smart_playlists: false,
+            playlist_subscription: false,
+            offline_pinning: false,
+            library_sync: false,
+            listening_stats: false,
+            radio: false,
+            daily_mixes: false,
+            duplicate_detection: false,
+        }
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.client.ping().await
+    }
+
+    async fn scan_music(&self) -> Result<()> {
+        self.client.refresh_library().await
+    }
+
+    async fn clear_cache(&self) -> Result<()> {
+        // 没有本地缓存
+        Ok(())
+    }
+
+    async fn sync_library(&self, _full: bool) -> Result<()> {
+        Err(DataSourceError::unsupported(
+            "Library sync is not supported by the Jellyfin data source",
+        ))
+    }
+
+    async fn pin(&self, _item_type: PinItemType, _id: &str, _pin: bool) -> Result<()> {
+        Err(DataSourceError::unsupported(
+            "Offline pinning is not supported by the Jellyfin data source",
+        ))
+    }
+
+    async fn pin_status(&self) -> Result<PinStatus> {
+        Ok(PinStatus::default())
+    }
+
+    async fn scan_status(&self) -> Result<ScanProgress> {
+        self.scan_library().await
+    }
+
+    async fn list_playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        let items = self
+            .client
+            .get_items("Playlist", &[("SortBy", "SortName".to_string())])
+            .await?
+            .items;
+        Ok(items.into_iter().map(|p| p.into()).collect())
+    }
+
+    async fn get_playlist(&self, playlist_id: &str) -> Result<PlaylistDetail> {
+        let playlist: PlaylistInfo = self.client.get_item(playlist_id).await?.into();
+        let items = self.client.get_playlist_items(playlist_id).await?.items;
+        let songs: Vec<UnifiedMetadata> = self
+            .to_songs(items)
+            .into_iter()
+            .map(|mut meta| {
+                meta.file_url = meta.stream_url.clone();
+                meta
+            })
+            .collect();
+
+        // 歌单没有封面时使用第一首歌的封面
+        let cover = playlist
+            .cover
+            .or_else(|| songs.first().and_then(|s| s.cover_art.clone()));
+
+        Ok(PlaylistDetail {
+            id: playlist.id,
+            name: playlist.name,
+            description: playlist.description,
+            cover,
+            owner: playlist.owner,
+            public: playlist.public,
+            song_count: songs.len(),
+            duration: playlist.duration,
+            created_at: playlist.created_at,
+            updated_at: playlist.updated_at,
+            smart: false,
+            daily_mix: false,
+            songs,
+        })
+    }
+
+    async fn create_playlist(
+        &self,
+        name: &str,
+        _description: Option<&str>,
+        song_ids: &[String],
+    ) -> Result<()> {
+        self.client.create_playlist(name, song_ids).await?;
+        Ok(())
+    }
+
+    async fn update_playlist(
+        &self,
+        playlist_id: &str,
+        name: Option<&str>,
+        _description: Option<&str>,
+        song_ids: Option<&[String]>,
+    ) -> Result<()> {
+        // Jellyfin 的歌单没有描述字段
+        let update = PlaylistUpdate {
+            name: name.map(|n| n.to_string()),
+            ids: song_ids.map(|ids| ids.to_vec()),
+            is_public: None,
+        };
+        self.client.update_playlist(playlist_id, &update).await
+    }
+
+    async fn edit_playlist(&self, playlist_id: &str, edit: &PlaylistEdit) -> Result<()> {
+        let mut song_ids = self.playlist_song_ids(playlist_id).await?;
+
+        // 先在本地校验编辑操作, 越界时不发起请求
+        edit.apply(&mut song_ids)?;
+
+        match edit {
+            PlaylistEdit::Append { song_ids: to_add } => {
+                self.client.add_to_playlist(playlist_id, to_add).await
+            }
+            // 其他操作直接替换整个列表
+            _ => {
+                let update = PlaylistUpdate {
+                    ids: Some(song_ids),
+                    ..Default::default()
+                };
+                self.client.update_playlist(playlist_id, &update).await
+            }
+        }
+    }
+
+    async fn set_playlist_public(&self, playlist_id: &str, public: bool) -> Result<()> {
+        let update = PlaylistUpdate {
+            is_public: Some(public),
+            ..Default::default()
+        };
+        self.client.update_playlist(playlist_id, &update).await
+    }
+
+    async fn set_playlist_cover(&self, _playlist_id: &str, _cover: &str) -> Result<()> {
+        Err(DataSourceError::unsupported(
+            "Custom playlist covers are not supported by the Jellyfin data source",
+        ))
+    }
+
+    async fn list_public_playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        // 其他用户共享的歌单已经包含在 list_playlists 中
+        Ok(vec![])
+    }
+
+    async fn subscribe_playlist(&self, _playlist_id: &str, _subscribe: bool) -> Result<()> {
+        Err(DataSourceError::unsupported(
+            "Playlist subscription is not supported by the Jellyfin data source",
+        ))
+    }
+
+    async fn create_smart_playlist(
+        &self,
+        _name: &str,
+        _description: Option<&str>,
+        _rule: &SmartPlaylistRule,
+    ) -> Result<()> {
+        Err(DataSourceError::unsupported(
+            "Smart playlists are not supported by the Jellyfin data source",
+        ))
+    }
+
+    async fn update_smart_playlist(
+        &self,
+        _playlist_id: &str,
+        _rule: &SmartPlaylistRule,
+    ) -> Result<()> {
+        Err(DataSourceError::unsupported(
+            "Smart playlists are not supported by the Jellyfin data source",
+        ))
+    }
+
+    async fn delete_playlist(&self, playlist_id: &str) -> Result<()> {
+        self.client.delete_item(playlist_id).await
+    }
+
+    async fn get_play_queue(&self) -> Result<Option<PlayQueueInfo>> {
+        Ok(None)
+    }
+
+    async fn save_play_queue(
+        &self,
+        _song_ids: Vec<String>,
+        _current_song_id: Option<String>,
+        _position: Option<u64>,
+    ) -> Result<()> {
+        Err(DataSourceError::unsupported(
+            "Play queue sync is not supported by the Jellyfin data source",
+        ))
+    }
+
+    async fn scrobble(
+        &self,
+        song_id: &str,
+        submission: Option<bool>,
+        timestamp: Option<u64>,
+    ) -> Result<()> {
+        if !submission.unwrap_or(true) {
+            return self.client.report_playing(song_id).await;
+        }
+        let date_played = timestamp
+            .and_then(|ms| chrono::DateTime::from_timestamp_millis(ms as i64))
+            .map(|t| t.to_rfc3339());
+        self.client.mark_played(song_id, date_played).await
+    }
+
+    async fn star(&self, id: &str, _item_type: StarItemType) -> Result<()> {
+        self.set_favorite(id, true).await
+    }
+
+    async fn unstar(&self, id: &str, _item_type: StarItemType) -> Result<()> {
+        self.set_favorite(id, false).await
+    }
+
+    async fn get_starred(&self) -> Result<StarredResult> {
+        let favorite = [
+            ("Filters", "IsFavorite".to_string()),
+            ("SortBy", "SortName".to_string()),
+        ];
+        let artists = self
+            .client
+            .get_items("MusicArtist", &favorite)
+            .await?
+            .items;
+
+        Ok(StarredResult {
+            songs: self.query_songs(&favorite).await?,
+            albums: self.query_albums(&favorite).await?,
+            artists: artists.into_iter().map(|a| a.into()).collect(),
+        })
+    }
+
+    async fn is_starred(&self, id: &str, _item_type: StarItemType) -> Result<bool> {
+        let item = self.client.get_item(id).await?;
+        Ok(item.user_data.is_some_and(|d| d.is_favorite))
+    }
+
+    async fn set_rating(&self, _id: &str, _item_type: StarItemType, _rating: u8) -> Result<()> {
+        Err(DataSourceError::unsupported(
+            "Ratings are not supported by the Jellyfin data source",
+        ))
+    }
+
+    async fn get_rating(&self, _id: &str, _item_type: StarItemType) -> Result<Option<u8>> {
+        Ok(None)
+    }
+
+    async fn stats_top(
+        &self,
+        _item_type: StatsItemType,
+        _range: TimeRange,
+        _limit: u32,
+    ) -> Result<Vec<StatsItem>> {
+        Err(stats_unsupported())
+    }
+
+    async fn stats_first_listened(
+        &self,
+        _item_type: StatsItemType,
+        _range: TimeRange,
+        _limit: u32,
+    ) -> Result<Vec<StatsItem>> {
+        Err(stats_unsupported())
+    }
+
+    async fn stats_listening_time(
+        &self,
+        _period: StatsPeriod,
+        _range: TimeRange,
+    ) -> Result<Vec<ListeningTime>> {
+        Err(stats_unsupported())
+    }
+
+    async fn stats_streaks(&self) -> Result<Streaks> {
+        Err(stats_unsupported())
+    }
+
+    async fn stats_heatmap(&self, _range: TimeRange) -> Result<Vec<HeatmapCell>> {
+        Err(stats_unsupported())
+    }
+
+    async fn wrapped(&self, _year: i32) -> Result<Wrapped> {
+        Err(stats_unsupported())
+    }
+
+    async fn wrapped_card(&self, _year: i32) -> Result<Vec<u8>> {
+        Err(stats_unsupported())
+    }
+}
+
+/// 收听统计依赖本地数据库中的播放记录, 播放记录保存在 Jellyfin 服务器上
+fn stats_unsupported() -> DataSourceError {
+    DataSourceError::unsupported("Listening stats are not supported by the Jellyfin data source")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_subsonic::{MockResponse, MockServer};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn data_source(url: &str) -> JellyfinDataSource {
+        JellyfinDataSource::new(url.into(), "alice".into(), "secret".into(), "MusicFlow".into(), 320, "mp3".into())
+    }
+
+    const LOGIN: &str = r#"{"AccessToken":"token-1","User":{"Id":"u1","Name":"alice"}}"#;
+
+    const SONG: &str = r#"{
+        "Id": "s1", "Name": "Song A", "Type": "Audio",
+        "Album": "Album A", "AlbumId": "al1", "AlbumPrimaryImageTag": "tag",
+        "Artists": ["Artist A"], "ArtistItems": [{"Name": "Artist A", "Id": "ar1"}],
+        "ProductionYear": 2001, "RunTimeTicks": 2150000000, "Genres": ["Rock", "Pop"],
+        "IndexNumber": 3, "ParentIndexNumber": 1, "Path": "/music/Artist A/03 Song A.flac",
+        "MediaSources": [{"Bitrate": 900000}], "UserData": {"IsFavorite": true, "PlayCount": 2, "LastPlayedDate": "2024-03-05T12:00:00.0000000Z"}
+    }"#;
+
+    #[tokio::test]
+    async fn test_map_items_and_favorites() {
+        let server = MockServer::start(|req| match (req.method.as_str(), req.route()) {
+            ("POST", "/Users/AuthenticateByName") => MockResponse::json(200, LOGIN),
+            ("GET", "/Users/u1/Items/s1") => MockResponse::json(200, SONG),
+            ("GET", "/Users/u1/Items") => {
+                MockResponse::json(200, format!(r#"{{"Items":[{}],"TotalRecordCount":1}}"#, SONG))
+            }
+            ("POST", "/Users/u1/FavoriteItems/s1") => MockResponse::json(200, "{}"),
+            _ => MockResponse::status(404),
+        });
+        let source = data_source(&server.url);
+
+        let song = source.get_metadata("s1").await.unwrap();
+        assert_eq!(song.title, "Song A");
+        assert_eq!(song.artist, "Artist A");
+        assert_eq!(song.artist_id.as_deref(), Some("ar1"));
+        assert_eq!(song.duration, 215.0);
+        assert_eq!(song.genre, "Rock,Pop");
+        assert_eq!(song.bitrate, "900");
+        assert_eq!(song.file_name.as_deref(), Some("03 Song A.flac"));
+        assert_eq!(song.play_count, Some(2));
+        assert_eq!(song.last_played, Some(1_709_640_000_000));
+        // 歌曲没有自己的封面时使用专辑封面
+        assert_eq!(song.cover_art.as_deref(), Some("al1"));
+        assert_eq!(song.stream_url.as_deref(), Some("/api/stream/s1"));
+        assert_eq!(song.source, DataSourceType::Jellyfin);
+
+        let found = source.find_song_by_path("Artist A/03 Song A.flac").await.unwrap();
+        assert_eq!(found.map(|s| s.id), Some("s1".to_string()));
+
+        source.star("s1", StarItemType::Song).await.unwrap();
+        assert!(matches!(
+            source.get_album_by_id("missing").await,
+            Err(DataSourceError::NotFound(_))
+        ));
+
+        // 只登录一次
+        assert_eq!(server.count("POST /Users/AuthenticateByName"), 1);
+        assert_eq!(server.count("POST /Users/u1/FavoriteItems/s1"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stream_forwards_range() {
+        let audio: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        let data = audio.clone();
+        let server = MockServer::start(move |req| match (req.method.as_str(), req.route()) {
+            ("POST", "/Users/AuthenticateByName") => MockResponse::json(200, LOGIN),
+            ("GET", "/Audio/s1/universal") => match req.header("range") {
+                Some("bytes=100-199") => MockResponse::bytes(206, data[100..200].to_vec())
+                    .with_header("Content-Range", "bytes 100-199/1000"),
+                _ => MockResponse::bytes(200, data.clone()),
+            },
+            _ => MockResponse::status(404),
+        });
+        let source = data_source(&server.url);
+
+        let response = source
+            .stream_song("s1", Some("bytes=100-199".to_string()), &StreamOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 206);
+        assert_eq!(
+            response.headers().get("Content-Range").unwrap(),
+            "bytes 100-199/1000"
+        );
+        assert_eq!(response.bytes().await.unwrap().to_vec(), audio[100..200].to_vec());
+
+        // 默认转码为配置中的格式和比特率
+        let request = server.requests().pop().unwrap();
+        assert_eq!(request.param("UserId").as_deref(), Some("u1"));
+        assert_eq!(request.param("AudioCodec").as_deref(), Some("mp3"));
+        assert_eq!(request.param("MaxStreamingBitrate").as_deref(), Some("320000"));
+
+        // 请求原始格式时不转码
+        let options = StreamOptions {
+            format: Some("raw".to_string()),
+            ..Default::default()
+        };
+        let response = source.stream_song("s1", None, &options).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().len(), 1000);
+        let request = server.requests().pop().unwrap();
+        assert!(request.header("range").is_none());
+        assert_eq!(request.param("AudioCodec"), None);
+        assert!(request.param("Container").unwrap().contains("flac"));
+    }
+
+    #[tokio::test]
+    async fn test_playlists() {
+        const PLAYLIST: &str = r#"{
+            "Id": "pl1", "Name": "Road Trip", "Type": "Playlist", "Overview": "Summer",
+            "ChildCount": 2, "RunTimeTicks": 4300000000, "DateCreated": "2024-05-01T08:00:00.0000000Z"
+        }"#;
+        let server = MockServer::start(|req| match (req.method.as_str(), req.route()) {
+            ("POST", "/Users/AuthenticateByName") => MockResponse::json(200, LOGIN),
+            ("GET", "/Users/u1/Items") => MockResponse::json(
+                200,
+                format!(r#"{{"Items":[{}],"TotalRecordCount":1}}"#, PLAYLIST),
+            ),
+            ("GET", "/Users/u1/Items/pl1") => MockResponse::json(200, PLAYLIST),
+            ("GET", "/Playlists/pl1/Items") => {
+                let second = SONG.replace(r#""Id": "s1""#, r#""Id": "s2""#);
+                MockResponse::json(
+                    200,
+                    format!(r#"{{"Items":[{},{}],"TotalRecordCount":2}}"#, SONG, second),
+                )
+            }
+            ("POST", "/Playlists/pl1") => MockResponse::status(204),
+            _ => MockResponse::status(404),
+        });
+        let source = data_source(&server.url);
+
+        let playlists = source.list_playlists().await.unwrap();
+        assert_eq!(playlists.len(), 1);
+        assert_eq!(playlists[0].id, "pl1");
+        assert_eq!(playlists[0].name, "Road Trip");
+        assert_eq!(playlists[0].description.as_deref(), Some("Summer"));
+        assert_eq!(playlists[0].song_count, 2);
+        assert_eq!(playlists[0].duration, Some(430));
+        let request = server.requests().pop().unwrap();
+        assert_eq!(request.param("IncludeItemTypes").as_deref(), Some("Playlist"));
+
+        let detail = source.get_playlist("pl1").await.unwrap();
+        assert_eq!(detail.name, "Road Trip");
+        assert_eq!(detail.song_count, 2);
+        let ids: Vec<&str> = detail.songs.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["s1", "s2"]);
+        assert_eq!(detail.songs[1].file_url.as_deref(), Some("/api/stream/s2"));
+        // 歌单没有封面时使用第一首歌的封面
+        assert_eq!(detail.cover.as_deref(), Some("al1"));
+
+        // 删除歌曲时提交删除后的完整列表
+        source
+            .edit_playlist("pl1", &PlaylistEdit::Remove { positions: vec![0] })
+            .await
+            .unwrap();
+        let request = server.requests().pop().unwrap();
+        assert_eq!(request.route(), "/Playlists/pl1");
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["Ids"], serde_json::json!(["s2"]));
+    }
+
+    #[tokio::test]
+    async fn test_relogin_when_token_expires() {
+        let expired = AtomicBool::new(false);
+        let server = MockServer::start(move |req| match (req.method.as_str(), req.route()) {
+            ("POST", "/Users/AuthenticateByName") => MockResponse::json(200, LOGIN),
+            // 第一次请求返回 401, 模拟 token 过期
+            ("GET", "/Users/u1/Items/s1") if expired.swap(true, Ordering::Relaxed) => {
+                MockResponse::json(200, SONG)
+            }
+            ("GET", "/Users/u1/Items/s1") => MockResponse::status(401),
+            _ => MockResponse::status(404),
+        });
+        let source = data_source(&server.url);
+
+        assert_eq!(source.get_metadata("s1").await.unwrap().id, "s1");
+        assert_eq!(server.count("POST /Users/AuthenticateByName"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_login_failure() {
+        let server = MockServer::start(|_| MockResponse::status(401));
+        assert!(matches!(
+            data_source(&server.url).health_check().await,
+            Err(DataSourceError::Unauthorized(_))
+        ));
+    }
+}
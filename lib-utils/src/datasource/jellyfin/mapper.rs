@@ -0,0 +1,181 @@
+// Jellyfin 数据格式映射
+// 将 Jellyfin 的 Items 转换为统一的数据结构
+
+use super::client::{JellyfinItem, JellyfinLyrics};
+use crate::datasource::types::*;
+
+/// Jellyfin 时长单位 (100 纳秒) 与秒的换算
+const TICKS_PER_SECOND: f64 = 10_000_000.0;
+
+impl JellyfinItem {
+    fn has_primary_image(&self) -> bool {
+        self.image_tags
+            .as_ref()
+            .is_some_and(|tags| tags.contains_key("Primary"))
+    }
+
+    /// 封面 ID: 有自己的主图时为项目 ID, 否则使用专辑的主图
+    fn cover_art(&self) -> Option<String> {
+        if self.has_primary_image() {
+            Some(self.id.clone())
+        } else if self.album_primary_image_tag.is_some() {
+            self.album_id.clone()
+        } else {
+            None
+        }
+    }
+
+    fn starred(&self) -> Option<bool> {
+        self.user_data.as_ref().map(|d| d.is_favorite)
+    }
+
+    fn play_count(&self) -> Option<u32> {
+        self.user_data.as_ref().map(|d| d.play_count)
+    }
+
+    fn last_played(&self) -> Option<i64> {
+        self.user_data
+            .as_ref()
+            .and_then(|d| d.last_played_date.as_deref())
+            .and_then(parse_timestamp_millis)
+    }
+}
+
+impl From<JellyfinItem> for UnifiedMetadata {
+    fn from(item: JellyfinItem) -> Self {
+        let cover_art = item.cover_art();
+        let starred = item.starred();
+        let (play_count, last_played) = (item.play_count(), item.last_played());
+        let artist = match &item.artists {
+            Some(artists) if !artists.is_empty() => artists.join(", "),
+            _ => item.album_artist.clone().unwrap_or_default(),
+        };
+        let artist_id = item
+            .artist_items
+            .as_ref()
+            .or(item.album_artists.as_ref())
+            .and_then(|a| a.first())
+            .map(|a| a.id.clone());
+        let bitrate = item
+            .media_sources
+            .as_ref()
+            .and_then(|s| s.first())
+            .and_then(|s| s.bitrate)
+            .map(|b| (b / 1000).to_string())
+            .unwrap_or_default();
+        let file_name = item.path.as_deref().and_then(|p| {
+            p.rsplit(['/', '\\']).next().map(|name| name.to_string())
+        });
+
+        UnifiedMetadata {
+            id: item.id,
+            title: item.name.unwrap_or_default(),
+            artist,
+            album: item.album.unwrap_or_default(),
+            year: item.production_year.map(|y| y.to_string()).unwrap_or_default(),
+            duration: item.run_time_ticks.unwrap_or(0) as f64 / TICKS_PER_SECOND,
+            genre: item.genres.unwrap_or_default().join(","),
+            track: item.index_number.map(|t| t.to_string()).unwrap_or_default(),
+            disc: item.parent_index_number.map(|d| d.to_string()).unwrap_or_default(),
+            language: String::new(),
+            comment: String::new(),
+            bitrate,
+            samplerate: String::new(),
+            source: DataSourceType::Jellyfin,
+            file_name,
+            file_path: item.path,
+            file_url: None,
+            subsonic_id: None,
+            stream_url: None, // 会在 DataSource 中设置
+            cover_art,
+            album_id: item.album_id,
+            artist_id,
+            starred,
+            play_count,
+            last_played,
+            skip_count: None,
+            rating: None,
+            replay_gain: item.normalization_gain.map(|gain| ReplayGain {
+                track_gain: Some(gain),
+                ..Default::default()
+            }),
+            bpm: None,
+            musical_key: None,
+        }
+    }
+}
+
+impl From<JellyfinItem> for AlbumInfo {
+    fn from(item: JellyfinItem) -> Self {
+        AlbumInfo {
+            cover_art: item.cover_art(),
+            play_count: item.play_count(),
+            last_played: item.last_played(),
+            skip_count: None,
+            rating: None,
+            artist: item
+                .album_artist
+                .or_else(|| item.artists.map(|a| a.join(", ")))
+                .unwrap_or_default(),
+            year: item.production_year.map(|y| y.to_string()).unwrap_or_default(),
+            song_count: item.child_count.or(item.song_count).unwrap_or(0) as usize,
+            name: item.name.unwrap_or_default(),
+            id: item.id,
+        }
+    }
+}
+
+impl From<JellyfinItem> for ArtistInfo {
+    fn from(item: JellyfinItem) -> Self {
+        ArtistInfo {
+            cover_art: item.cover_art(),
+            album_count: item.album_count.or(item.child_count).unwrap_or(0) as usize,
+            name: item.name.unwrap_or_default(),
+            id: item.id,
+        }
+    }
+}
+
+impl From<JellyfinItem> for GenreInfo {
+    fn from(item: JellyfinItem) -> Self {
+        GenreInfo {
+            value: item.name.unwrap_or_default(),
+            album_count: item.album_count.unwrap_or(0) as usize,
+            song_count: item.song_count.unwrap_or(0) as usize,
+        }
+    }
+}
+
+impl From<JellyfinItem> for PlaylistInfo {
+    fn from(item: JellyfinItem) -> Self {
+        PlaylistInfo {
+            cover: item.cover_art(),
+            song_count: item.child_count.unwrap_or(0) as usize,
+            duration: item
+                .run_time_ticks
+                .map(|t| (t as f64 / TICKS_PER_SECOND) as u32),
+            id: item.id,
+            name: item.name.unwrap_or_default(),
+            description: item.overview,
+            // Jellyfin 的歌单列表只包含当前用户可见的歌单, 不返回所有者和公开状态
+            owner: None,
+            public: None,
+            created_at: item.date_created,
+            updated_at: None,
+            smart: false,
+            daily_mix: false,
+        }
+    }
+}
+
+/// 解析 Jellyfin 歌词为 LyricLine 列表
+pub fn parse_jellyfin_lyrics(lyrics: JellyfinLyrics) -> Vec<LyricLine> {
+    lyrics
+        .lyrics
+        .into_iter()
+        .map(|line| LyricLine {
+            time: line.start.unwrap_or(0) as f64 / TICKS_PER_SECOND,
+            text: line.text,
+        })
+        .collect()
+}
@@ -0,0 +1,9 @@
+// Jellyfin 客户端模块
+// 实现 Jellyfin REST API 的调用和数据源适配
+
+pub mod client;
+pub mod mapper;
+pub mod datasource;
+
+// 重新导出核心类型
+pub use datasource::JellyfinDataSource;
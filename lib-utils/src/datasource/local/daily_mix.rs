@@ -0,0 +1,344 @@
+// 每日推荐歌单 (Daily Mix)
+// 每个用户每天生成几个歌单: 按常听的风格聚类, 每类一个风格歌单; 很久没听过的歌曲 (重温); 最近添加的歌曲 (新歌)。随机种子由用户、日期和歌单类型决定, 同一天重复生成的结果相同。生成的歌单保存在 song_list 表中 (mix_key 不为空), 歌曲列表只读, 第二天访问时重新生成。
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDate};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rusqlite::{params, Connection, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+
+use super::radio::{genre_tokens, jaccard};
+
+/// 每个歌单的歌曲数量
+const MIX_SIZE: usize = 50;
+/// 风格歌单的最大数量
+const MAX_GENRE_MIXES: usize = 3;
+/// 参与聚类的常听风格数量
+const TOP_GENRES: usize = 12;
+/// 两个风格的歌曲集合相似度不低于该值时归为同一类
+const CLUSTER_THRESHOLD: f64 = 0.3;
+/// 超过多久没听过的歌曲进入重温歌单
+const REDISCOVER_MILLIS: i64 = 90 * 24 * 60 * 60 * 1000;
+/// 多少天内添加的歌曲进入新歌歌单
+const NEW_ADDITION_DAYS: i64 = 30;
+/// 同一歌单中同一艺术家最多的歌曲数量, 不够时再补充
+const MAX_PER_ARTIST: usize = 3;
+
+pub const REDISCOVER_KEY: &str = "rediscover";
+pub const NEW_ADDITIONS_KEY: &str = "new";
+
+/// 生成的每日推荐歌单
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DailyMix {
+    /// 歌单类型, 风格歌单为 genre-1、genre-2 ..., 跨天保持不变, 用于复用歌单 ID
+    pub key: String,
+    pub name: String,
+    pub description: String,
+    pub song_ids: Vec<String>,
+}
+
+struct Song {
+    id: String,
+    artist: String,
+    genres: HashSet<String>,
+    created_at: String,
+    plays: i64,
+    last_played: Option<i64>,
+}
+
+/// 生成某一天的每日推荐歌单, 没有歌曲的歌单不返回
+///
+/// # 参数
+/// * `now` - 当前时间 (毫秒), 用于判断很久没听过的歌曲
+pub fn generate(conn: &Connection, user_id: i64, date: NaiveDate, now: i64) -> Result<Vec<DailyMix>> {
+    let songs = load_songs(conn, user_id)?;
+    let mut mixes = Vec::new();
+
+    // 风格歌单之间不重复
+    let mut used = HashSet::new();
+    for (index, cluster) in genre_clusters(&songs).into_iter().enumerate() {
+        let key = format!("genre-{}", index + 1);
+        let candidates: Vec<(&Song, f64)> = songs
+            .iter()
+            .filter(|s| !used.contains(&s.id) && !s.genres.is_disjoint(&cluster.genres))
+            .map(|s| (s, 1.0 + (1.0 + s.plays as f64).ln()))
+            .collect();
+        let song_ids = pick(candidates, &mut rng(user_id, date, &key));
+        used.extend(song_ids.iter().cloned());
+        mixes.push(DailyMix {
+            name: format!("每日推荐 {}", index + 1),
+            description: cluster.names.join(", "),
+            key,
+            song_ids,
+        });
+    }
+
+    // 听过但很久没听的歌曲, 以前听得越多越优先
+    let candidates: Vec<(&Song, f64)> = songs
+        .iter()
+        .filter(|s| matches!(s.last_played, Some(t) if t < now - REDISCOVER_MILLIS))
+        .map(|s| (s, s.plays as f64))
+        .collect();
+    mixes.push(DailyMix {
+        key: REDISCOVER_KEY.to_string(),
+        name: "每日推荐: 重温".to_string(),
+        description: "很久没听过的歌曲".to_string(),
+        song_ids: pick(candidates, &mut rng(user_id, date, REDISCOVER_KEY)),
+    });
+
+    // 最近添加的歌曲, 没听过的优先
+    let since = (date - Duration::days(NEW_ADDITION_DAYS)).format("%Y-%m-%d").to_string();
+    let candidates: Vec<(&Song, f64)> = songs
+        .iter()
+        .filter(|s| !s.created_at.is_empty() && s.created_at >= since)
+        .map(|s| (s, if s.plays == 0 { 2.0 } else { 1.0 }))
+        .collect();
+    mixes.push(DailyMix {
+        key: NEW_ADDITIONS_KEY.to_string(),
+        name: "每日推荐: 新歌".to_string(),
+        description: format!("最近 {} 天添加的歌曲", NEW_ADDITION_DAYS),
+        song_ids: pick(candidates, &mut rng(user_id, date, NEW_ADDITIONS_KEY)),
+    });
+
+    mixes.retain(|m| !m.song_ids.is_empty());
+    Ok(mixes)
+}
+
+/// 确保用户今天的每日推荐歌单已生成, 已生成时不做任何修改
+///
+/// 同一类型的歌单复用原来的歌单 ID, 今天没有生成的类型删除对应的歌单。
+/// 检查和生成在同一个 IMMEDIATE 事务中, 并发请求不会重复生成。
+pub fn refresh(conn: &mut Connection, user_id: i64, date: NaiveDate, now: i64) -> Result<()> {
+    let date_str = date.format("%Y-%m-%d").to_string();
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let generated: i64 = tx.query_row(
+        "SELECT COUNT(*) FROM song_list WHERE user_id = ?1 AND mix_key != '' AND mix_date = ?2",
+        params![user_id, date_str],
+        |row| row.get(0),
+    )?;
+    if generated > 0 {
+        return Ok(());
+    }
+
+    let mixes = generate(&tx, user_id, date, now)?;
+    let now_str = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let mut existing: HashMap<String, i64> = HashMap::new();
+    {
+        let mut stmt = tx.prepare("SELECT mix_key, id FROM song_list WHERE user_id = ?1 AND mix_key != ''")?;
+        for row in stmt.query_map([user_id], |row| Ok((row.get(0)?, row.get(1)?)))? {
+            let (key, id) = row?;
+            existing.insert(key, id);
+        }
+    }
+
+    for mix in &mixes {
+        let id = match existing.remove(&mix.key) {
+            Some(id) => {
+                tx.execute(
+                    "UPDATE song_list SET name = ?1, description = ?2, mix_date = ?3, updated_at = ?4 WHERE id = ?5",
+                    params![mix.name, mix.description, date_str, now_str, id],
+                )?;
+                tx.execute("DELETE FROM song_list_song WHERE song_list_id = ?1", [id])?;
+                id
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO song_list (user_id, name, description, cover, created_at, updated_at, mix_key, mix_date) \
+                     VALUES (?1, ?2, ?3, '', ?4, ?4, ?5, ?6)",
+                    params![user_id, mix.name, mix.description, now_str, mix.key, date_str],
+                )?;
+                tx.last_insert_rowid()
+            }
+        };
+        let mut stmt = tx.prepare(
+            "INSERT INTO song_list_song (user_id, song_list_id, song_id, order_num) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for (order, song_id) in mix.song_ids.iter().enumerate() {
+            stmt.execute(params![user_id, id, song_id, order as i64])?;
+        }
+    }
+
+    for id in existing.into_values() {
+        tx.execute("DELETE FROM song_list_song WHERE song_list_id = ?1", [id])?;
+        tx.execute("DELETE FROM song_list_subscribe WHERE song_list_id = ?1", [id])?;
+        tx.execute("DELETE FROM song_list WHERE id = ?1", [id])?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn load_songs(conn: &Connection, user_id: i64) -> Result<Vec<Song>> {
+    let mut plays: HashMap<String, (i64, i64)> = HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT song_id, COUNT(*), MAX(timestamp) FROM scrobble WHERE user_id = ?1 AND submission = 1 GROUP BY song_id",
+    )?;
+    for row in stmt.query_map([user_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))? {
+        let (song_id, count, last) = row?;
+        plays.insert(song_id, (count, last));
+    }
+
+    // 按 ID 排序, 保证同一个种子得到相同的结果
+    let mut stmt = conn.prepare("SELECT id, artist, genre, created_at FROM metadata ORDER BY id")?;
+    let songs = stmt
+        .query_map([], |row| {
+            let id: String = row.get(0)?;
+            let genre: String = row.get(2)?;
+            let (plays, last_played) = match plays.get(&id) {
+                Some((count, last)) => (*count, Some(*last)),
+                None => (0, None),
+            };
+            Ok(Song {
+                id,
+                artist: row.get(1)?,
+                genres: genre_tokens(&genre),
+                created_at: row.get(3)?,
+                plays,
+                last_played,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(songs)
+}
+
+/// 风格聚类
+struct Cluster {
+    /// 类中的风格, 按收听次数排序
+    names: Vec<String>,
+    genres: HashSet<String>,
+    songs: HashSet<String>,
+}
+
+/// 按收听次数取常听的风格 (没有收听记录时按歌曲数量), 歌曲集合相近的风格归为一类
+fn genre_clusters(songs: &[Song]) -> Vec<Cluster> {
+    let has_plays = songs.iter().any(|s| s.plays > 0);
+    let mut weights: HashMap<&str, i64> = HashMap::new();
+    let mut members: HashMap<&str, HashSet<String>> = HashMap::new();
+    for song in songs {
+        for genre in &song.genres {
+            *weights.entry(genre).or_default() += if has_plays { song.plays } else { 1 };
+            members.entry(genre).or_default().insert(song.id.clone());
+        }
+    }
+
+    let mut ranked: Vec<(&str, i64)> = weights.into_iter().filter(|(_, w)| *w > 0).collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    let mut clusters: Vec<Cluster> = Vec::new();
+    for (genre, _) in ranked.into_iter().take(TOP_GENRES) {
+        let genre_songs = members.remove(genre).unwrap_or_default();
+        if let Some(cluster) = clusters.iter_mut().find(|c| jaccard(&c.songs, &genre_songs) >= CLUSTER_THRESHOLD) {
+            cluster.names.push(genre.to_string());
+            cluster.genres.insert(genre.to_string());
+            cluster.songs.extend(genre_songs);
+        } else if clusters.len() < MAX_GENRE_MIXES {
+            clusters.push(Cluster {
+                names: vec![genre.to_string()],
+                genres: HashSet::from([genre.to_string()]),
+                songs: genre_songs,
+            });
+        }
+    }
+    clusters
+}
+
+/// 按权重不放回随机抽取歌曲 (权重越大越靠前), 同一艺术家的歌曲不要太集中
+fn pick(candidates: Vec<(&Song, f64)>, rng: &mut StdRng) -> Vec<String> {
+    let mut keyed: Vec<(f64, &Song)> = candidates
+        .into_iter()
+        .filter(|(_, weight)| *weight > 0.0)
+        .map(|(song, weight)| (rng.gen::<f64>().powf(1.0 / weight), song))
+        .collect();
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.id.cmp(&b.1.id)));
+
+    let mut per_artist: HashMap<String, usize> = HashMap::new();
+    let (mut picked, mut skipped) = (Vec::new(), Vec::new());
+    for (_, song) in keyed {
+        if picked.len() >= MIX_SIZE {
+            break;
+        }
+        let count = per_artist.entry(song.artist.to_lowercase()).or_default();
+        if *count < MAX_PER_ARTIST {
+            *count += 1;
+            picked.push(song.id.clone());
+        } else {
+            skipped.push(song.id.clone());
+        }
+    }
+    let missing = MIX_SIZE.saturating_sub(picked.len());
+    picked.extend(skipped.into_iter().take(missing));
+    picked
+}
+
+/// 由用户、日期和歌单类型得到的随机数生成器 (FNV-1a, 不依赖标准库哈希算法的实现)
+fn rng(user_id: i64, date: NaiveDate, key: &str) -> StdRng {
+    let seed = format!("{}:{}:{}", user_id, date.format("%Y-%m-%d"), key)
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3));
+    StdRng::seed_from_u64(seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daily_mixes() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&crate::datasource::local::table::sql()).unwrap();
+        conn.execute_batch(
+            "INSERT INTO metadata (id, file_name, file_path, file_url, title, artist, duration, genre, created_at) VALUES
+                ('s1', '', '', '', 'So What', 'Miles Davis', 60, 'Jazz, Modal', '2024-01-01 00:00:00'),
+                ('s2', '', '', '', 'Blue in Green', 'Miles Davis', 60, 'Jazz', '2024-01-01 00:00:00'),
+                ('s3', '', '', '', 'Take Five', 'Dave Brubeck', 60, 'Jazz, Cool', '2024-01-01 00:00:00'),
+                ('s4', '', '', '', 'Thriller', 'Michael Jackson', 60, 'Pop', '2024-01-01 00:00:00'),
+                ('s5', '', '', '', 'Billie Jean', 'Michael Jackson', 60, 'Pop', '2024-06-10 00:00:00');",
+        )
+        .unwrap();
+        // s4 很久以前听过, 其余歌曲最近听过
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+        let now = 1_718_409_600_000i64;
+        for (song_id, timestamp) in [("s1", now - 1000), ("s2", now - 2000), ("s1", now - 3000), ("s4", 1_000_000)] {
+            conn.execute(
+                "INSERT INTO scrobble (user_id, song_id, submission, timestamp, created_at) VALUES (1, ?1, 1, ?2, '')",
+                params![song_id, timestamp],
+            )
+            .unwrap();
+        }
+
+        let mixes = generate(&conn, 1, date, now).unwrap();
+        let keys: Vec<&str> = mixes.iter().map(|m| m.key.as_str()).collect();
+        assert_eq!(keys, vec!["genre-1", "genre-2", REDISCOVER_KEY, NEW_ADDITIONS_KEY]);
+        assert!(mixes[0].description.starts_with("jazz"));
+        assert_eq!(mixes[2].song_ids, vec!["s4"]);
+        assert_eq!(mixes[3].song_ids, vec!["s5"]);
+        // 同一天结果相同
+        assert_eq!(generate(&conn, 1, date, now).unwrap(), mixes);
+
+        refresh(&mut conn, 1, date, now).unwrap();
+        let ids: Vec<i64> = conn
+            .prepare("SELECT id FROM song_list WHERE mix_key != '' ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(ids.len(), 4);
+
+        // 第二天重新生成, 复用原来的歌单 ID; s5 不再是新歌
+        let next = date + Duration::days(30);
+        refresh(&mut conn, 1, next, now + 30 * 24 * 60 * 60 * 1000).unwrap();
+        let rows: Vec<(i64, String)> = conn
+            .prepare("SELECT id, mix_date FROM song_list WHERE mix_key != '' ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(rows.len(), 3);
+        assert!(rows.iter().all(|(id, d)| ids.contains(id) && d == "2024-07-15"));
+    }
+}
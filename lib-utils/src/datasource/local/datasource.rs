@@ -1,16 +1,21 @@
 // 本地文件数据源实现
 // 封装现有的本地文件系统访问逻辑
 
-use anyhow::Result;
 use async_trait::async_trait;
 use base64::{engine::general_purpose, Engine};
 use std::collections::HashSet;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use crate::config::get_config;
-use crate::datasource::local::service;
+use crate::datasource::local::{stats, wrapped};
+use crate::datasource::local::{daily_mix, radio, service};
+use crate::datasource::error::{DataSourceError, Result};
 use crate::datasource::trait_def::MusicDataSource;
 use crate::datasource::types::*;
+use crate::datasource::webdav::library::spawn_scan_job;
+use crate::datasource::webdav::WebDavLibrary;
+use crate::audio::key::Key;
 use crate::{datasource::local, log, readmeta};
 
 /// 本地文件数据源
@@ -18,16 +23,88 @@ pub struct LocalDataSource {
     music_dir: String,
     #[allow(dead_code)]
     db_path: String,
+    // 音乐文件在 WebDAV 上时使用, 此时 music_dir 不使用
+    webdav: Option<Arc<WebDavLibrary>>,
 }
 
 impl LocalDataSource {
     /// 创建新的本地文件数据源
     pub fn new(music_dir: String, db_path: String) -> Self {
-        Self { music_dir, db_path }
+        Self {
+            music_dir,
+            db_path,
+            webdav: None,
+        }
+    }
+
+    /// 使用 WebDAV 上的音乐目录, 并启动定时扫描
+    ///
+    /// 需要先初始化数据库
+    pub fn with_webdav(mut self, library: WebDavLibrary, scan_interval_minutes: u64) -> Self {
+        let library = Arc::new(library);
+        spawn_scan_job(library.clone(), scan_interval_minutes);
+        self.webdav = Some(library);
+        self
+    }
+
+    /// 生成今天的每日推荐歌单, 失败时只记录日志, 不影响歌单列表
+    ///
+    /// 与其他歌单一样只为默认用户 (user_id = 1) 生成, 本地数据源目前只有一个用户。
+    /// 生成时会在事务中写数据库, 放到阻塞线程中执行, 不占用异步运行时
+    async fn refresh_daily_mixes(&self) {
+        let result = tokio::task::spawn_blocking(|| {
+            let now = chrono::Local::now();
+            let mut conn = local::connect_db()?;
+            daily_mix::refresh(&mut conn, 1, now.date_naive(), now.timestamp_millis())
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|result| result);
+        if let Err(e) = result {
+            log::log_err(&format!("refresh daily mixes error: {}", e));
+        }
+    }
+
+    /// 将数据库歌单统计信息转换为 PlaylistInfo
+    fn convert_song_list_summary(&self, summary: service::SongListSummary) -> Result<PlaylistInfo> {
+        let sl = summary.song_list;
+        let smart = !sl.smart_rule.is_empty();
+
+        // 智能歌单没有 song_list_song 记录, 需要按规则计算
+        let (song_count, duration) = if smart {
+            let songs = service::get_smart_song_list_songs(1, &parse_smart_rule(&sl.smart_rule)?)?;
+            (songs.len(), songs.iter().map(|m| m.duration).sum::<f64>())
+        } else {
+            (summary.song_count as usize, summary.duration)
+        };
+
+        let non_empty = |s: String| if s.is_empty() { None } else { Some(s) };
+        Ok(PlaylistInfo {
+            id: sl.id.to_string(),
+            name: sl.name,
+            description: non_empty(sl.description),
+            cover: non_empty(sl.cover),
+            owner: summary.owner,
+            public: Some(sl.public),
+            song_count,
+            duration: Some(duration.round() as u32),
+            created_at: non_empty(sl.created_at),
+            updated_at: non_empty(sl.updated_at),
+            smart,
+            daily_mix: !sl.mix_key.is_empty(),
+        })
     }
 
     /// 将数据库 Metadata 转换为 UnifiedMetadata
     fn convert_metadata(&self, metadata: service::Metadata) -> UnifiedMetadata {
+        // WebDAV 上的文件通过 stream_song 代理
+        let (file_url, stream_url) = match self.webdav {
+            Some(_) => {
+                let url = format!("/api/stream/{}", metadata.id);
+                (url.clone(), Some(url))
+            }
+            None => (format!("/music{}", metadata.file_url), None),
+        };
         UnifiedMetadata {
             id: metadata.id.clone(),
             title: metadata.title,
@@ -45,14 +122,76 @@ impl LocalDataSource {
             source: DataSourceType::Local,
             file_name: Some(metadata.file_name),
             file_path: Some(metadata.file_path),
-            file_url: Some(format!("/music{}", metadata.file_url)),
+            file_url: Some(file_url),
             subsonic_id: None,
-            stream_url: None,
+            stream_url,
             cover_art: None,
             album_id: None,
             artist_id: None,
             starred: None,
+            play_count: None,
+            last_played: None,
+            skip_count: None,
+            rating: None,
+            replay_gain: None,
+            bpm: None,
+            musical_key: None,
+        }
+    }
+
+    /// 补充歌曲的播放次数、最后播放时间、跳过次数、评分和响度归一化信息
+    fn attach_play_stats(&self, songs: &mut [UnifiedMetadata]) -> Result<()> {
+        if songs.is_empty() {
+            return Ok(());
+        }
+        let song_ids: Vec<String> = songs.iter().map(|song| song.id.clone()).collect();
+        let play_stats = stats::song_play_stats(&local::connect_db()?, 1, &song_ids)?;
+        let ratings = service::get_ratings_by_ids(1, StarItemType::Song.as_str(), &song_ids)?;
+        let replay_gains = service::get_replay_gains(&song_ids)?;
+        let bpm_keys = service::get_bpm_keys(&song_ids)?;
+        for song in songs {
+            song.replay_gain = replay_gains.get(&song.id).copied();
+            if let Some((bpm, key)) = bpm_keys.get(&song.id) {
+                song.bpm = *bpm;
+                song.musical_key = key.clone();
+            }
+            let s = play_stats.get(&song.id).copied().unwrap_or_default();
+            song.play_count = Some(s.play_count);
+            song.last_played = s.last_played;
+            song.skip_count = Some(s.skip_count);
+            song.rating = ratings.get(&song.id).copied();
         }
+        Ok(())
+    }
+
+    /// 转换专辑并补充播放统计和评分
+    fn convert_albums(&self, albums: Vec<service::Album>) -> Result<Vec<AlbumInfo>> {
+        if albums.is_empty() {
+            return Ok(Vec::new());
+        }
+        let album_ids: Vec<i64> = albums.iter().map(|album| album.id).collect();
+        let album_keys: Vec<String> = album_ids.iter().map(|id| id.to_string()).collect();
+        let play_stats = stats::album_play_stats(&local::connect_db()?, 1, &album_ids)?;
+        let ratings = service::get_ratings_by_ids(1, StarItemType::Album.as_str(), &album_keys)?;
+        Ok(albums
+            .into_iter()
+            .map(|album| {
+                let s = play_stats.get(&album.id.to_string()).copied().unwrap_or_default();
+                let rating = ratings.get(&album.id.to_string()).copied();
+                AlbumInfo {
+                    id: album.id.to_string(),
+                    name: album.name,
+                    artist: album.artist,
+                    year: album.year,
+                    cover_art: Some(album.id.to_string()),
+                    song_count: 0, // TODO: 查询歌曲数量
+                    play_count: Some(s.play_count),
+                    last_played: s.last_played,
+                    skip_count: Some(s.skip_count),
+                    rating,
+                }
+            })
+            .collect())
     }
 
     /// 将数据库 Lyric 转换为 LyricLine
@@ -68,9 +207,11 @@ impl LocalDataSource {
 impl MusicDataSource for LocalDataSource {
     async fn get_metadata(&self, id: &str) -> Result<UnifiedMetadata> {
         let metadata = service::get_metadata_by_id(id)?
-            .ok_or_else(|| anyhow::anyhow!("Metadata not found: {}", id))?;
+            .ok_or_else(|| DataSourceError::NotFound(format!("Metadata not found: {}", id)))?;
 
-        Ok(self.convert_metadata(metadata))
+        let mut song = self.convert_metadata(metadata);
+        self.attach_play_stats(std::slice::from_mut(&mut song))?;
+        Ok(song)
     }
 
     async fn list_metadata(&self, filter: MetadataFilter) -> Result<Vec<UnifiedMetadata>> {
@@ -103,6 +244,17 @@ impl MusicDataSource for LocalDataSource {
             .map(|m| self.convert_metadata(m))
             .collect();
 
+        self.attach_play_stats(&mut filtered)?;
+        // BPM 和调性过滤, 数据来自标签或后台分析
+        filtered.retain(|m| filter.bpm.contains(m.bpm));
+        if let Some(key) = filter.musical_key.as_deref().filter(|k| !k.trim().is_empty()) {
+            let key = Key::normalize(key);
+            filtered.retain(|m| m.musical_key.as_deref() == Some(key.as_str()));
+        }
+        if let Some(sort) = filter.sort {
+            sort_metadata(&mut filtered, sort, filter.descending);
+        }
+
         // 分页
         if let (Some(page), Some(page_size)) = (filter.page, filter.page_size) {
             let start = ((page - 1) * page_size) as usize;
@@ -131,7 +283,7 @@ impl MusicDataSource for LocalDataSource {
             let url = String::from_utf8(url).unwrap_or_default();
             // println!("url={:?}", url);
             if url.is_empty() {
-                return Err(anyhow::anyhow!("Invalid cover URL: {}", link_id));
+                return Err(DataSourceError::InvalidId(format!("Invalid cover URL: {}", link_id)));
             }
 
             // 下载图片
@@ -142,7 +294,7 @@ impl MusicDataSource for LocalDataSource {
         }
 
         let cover = service::get_cover(id, "album", size.as_str())?
-            .ok_or_else(|| anyhow::anyhow!("Cover not found for song: {}", link_id))?;
+            .ok_or_else(|| DataSourceError::NotFound(format!("Cover not found for song: {}", link_id)))?;
 
         // println!("cover={:?}", cover);
 
@@ -162,12 +314,21 @@ impl MusicDataSource for LocalDataSource {
 
     async fn get_audio_stream(&self, song_id: &str) -> Result<AudioStream> {
         let metadata = service::get_metadata_by_id(song_id)?
-            .ok_or_else(|| anyhow::anyhow!("Song not found: {}", song_id))?;
+            .ok_or_else(|| DataSourceError::NotFound(format!("Song not found: {}", song_id)))?;
 
+        if self.webdav.is_some() {
+            return Ok(AudioStream::SubsonicStream {
+                url: format!("/api/stream/{}", song_id),
+                headers: Default::default(),
+            });
+        }
         Ok(AudioStream::LocalFile(PathBuf::from(metadata.file_path)))
     }
 
     async fn scan_library(&self) -> Result<ScanProgress> {
+        if let Some(webdav) = &self.webdav {
+            return Ok(webdav.progress());
+        }
         // TODO: 实现本地库扫描
         // 需要调用 readmeta 模块的逻辑
         // 这部分比较复杂,需要单独处理
@@ -187,7 +348,7 @@ impl MusicDataSource for LocalDataSource {
         list_type: Option<AlbumListType>,
     ) -> Result<Vec<AlbumInfo>> {
         // 使用排序逻辑
-        let mut albums = service::get_album_list(list_type.as_ref())?;
+        let mut albums = service::get_album_list(1, list_type.as_ref())?;
 
         // 文本过滤
         if let Some(filter_text) = filter_text {
@@ -211,48 +372,36 @@ impl MusicDataSource for LocalDataSource {
             albums = vec![];
         }
 
-        Ok(albums
-            .into_iter()
-            .map(|album| AlbumInfo {
-                id: album.id.to_string(),
-                name: album.name,
-                artist: album.artist,
-                year: album.year,
-                cover_art: Some(album.id.to_string()),
-                song_count: 0,
-            })
-            .collect())
+        self.convert_albums(albums)
     }
 
     async fn get_album_by_id(&self, album_id: &str) -> Result<AlbumInfo> {
         let id = album_id
             .parse::<i64>()
-            .map_err(|_| anyhow::anyhow!("Invalid album ID: {}", album_id))?;
+            .map_err(|_| DataSourceError::InvalidId(format!("Invalid album ID: {}", album_id)))?;
 
         let album = service::album_by_id(id)?
-            .ok_or_else(|| anyhow::anyhow!("Album not found: {}", album_id))?;
-
-        Ok(AlbumInfo {
-            id: album.id.to_string(),
-            name: album.name,
-            artist: album.artist,
-            year: album.year,
-            cover_art: Some(album.id.to_string()),
-            song_count: 0, // TODO: 查询歌曲数量
-        })
+            .ok_or_else(|| DataSourceError::NotFound(format!("Album not found: {}", album_id)))?;
+
+        Ok(self.convert_albums(vec![album])?.remove(0))
     }
 
     async fn get_album_songs(&self, album_id: &str) -> Result<Vec<UnifiedMetadata>> {
         let id = album_id
             .parse::<i64>()
-            .map_err(|_| anyhow::anyhow!("Invalid album ID: {}", album_id))?;
-
-        let songs = service::album_songs(id)?;
+            .map_err(|_| DataSourceError::InvalidId(format!("Invalid album ID: {}", album_id)))?;
 
-        Ok(songs
+        let mut songs: Vec<_> = service::album_songs(id)?
             .into_iter()
             .map(|s| self.convert_metadata(s))
-            .collect())
+            .collect();
+        self.attach_play_stats(&mut songs)?;
+        Ok(songs)
+    }
+
+    async fn find_song_by_path(&self, path: &str) -> Result<Option<UnifiedMetadata>> {
+        let metadata = service::get_metadata_by_path(path)?;
+        Ok(metadata.map(|m| self.convert_metadata(m)))
     }
 
     async fn list_artists(&self) -> Result<Vec<ArtistInfo>> {
@@ -276,10 +425,10 @@ impl MusicDataSource for LocalDataSource {
     async fn get_artist_by_id(&self, artist_id: &str) -> Result<ArtistInfo> {
         let id = artist_id
             .parse::<i64>()
-            .map_err(|_| anyhow::anyhow!("Invalid artist ID: {}", artist_id))?;
+            .map_err(|_| DataSourceError::InvalidId(format!("Invalid artist ID: {}", artist_id)))?;
 
         let artist = service::artist_by_id(id)?
-            .ok_or_else(|| anyhow::anyhow!("Artist not found: {}", artist_id))?;
+            .ok_or_else(|| DataSourceError::NotFound(format!("Artist not found: {}", artist_id)))?;
 
         Ok(ArtistInfo {
             id: artist.id.to_string(),
@@ -296,14 +445,14 @@ impl MusicDataSource for LocalDataSource {
     async fn get_artist_songs(&self, artist_id: &str) -> Result<Vec<UnifiedMetadata>> {
         let id = artist_id
             .parse::<i64>()
-            .map_err(|_| anyhow::anyhow!("Invalid artist ID: {}", artist_id))?;
-
-        let songs = service::artist_songs(id)?;
+            .map_err(|_| DataSourceError::InvalidId(format!("Invalid artist ID: {}", artist_id)))?;
 
-        Ok(songs
+        let mut songs: Vec<_> = service::artist_songs(id)?
             .into_iter()
             .map(|s| self.convert_metadata(s))
-            .collect())
+            .collect();
+        self.attach_play_stats(&mut songs)?;
+        Ok(songs)
     }
 
     async fn list_genres(&self) -> Result<Vec<GenreInfo>> {
@@ -365,15 +514,37 @@ impl MusicDataSource for LocalDataSource {
         genre: Option<&str>,
         from_year: Option<&str>,
         to_year: Option<&str>,
+        bpm: BpmRange,
     ) -> Result<Vec<UnifiedMetadata>> {
         // 调用数据库层的 get_random_songs
-        let metadata_list = service::get_random_songs(size, genre, from_year, to_year)?;
+        let metadata_list = service::get_random_songs(size, genre, from_year, to_year, bpm)?;
 
         // 转换为 UnifiedMetadata
-        Ok(metadata_list
+        let mut songs: Vec<UnifiedMetadata> = metadata_list
             .into_iter()
             .map(|m| self.convert_metadata(m))
-            .collect())
+            .collect();
+        self.attach_play_stats(&mut songs)?;
+        Ok(songs)
+    }
+
+    async fn get_similar_songs(
+        &self,
+        seed: &RadioSeed,
+        count: usize,
+        exclude: &[String],
+    ) -> Result<Vec<UnifiedMetadata>> {
+        let now = chrono::Local::now().timestamp_millis();
+        let scored = radio::similar_songs(&local::connect_db()?, 1, seed, count, exclude, now)?;
+
+        let mut songs = Vec::with_capacity(scored.len());
+        for item in scored {
+            if let Some(metadata) = service::get_metadata_by_id(&item.id)? {
+                songs.push(self.convert_metadata(metadata));
+            }
+        }
+        self.attach_play_stats(&mut songs)?;
+        Ok(songs)
     }
 
     async fn search(&self, query: &str, pagination: Pagination) -> Result<SearchResult> {
@@ -406,26 +577,78 @@ impl MusicDataSource for LocalDataSource {
         // 检查数据库连接
         local::connect_db()?;
 
+        if let Some(webdav) = &self.webdav {
+            webdav.client().list(webdav.client().root()).await?;
+            return Ok(());
+        }
+
         // 检查音乐目录
         let music_path = PathBuf::from(&self.music_dir);
         if !music_path.exists() {
-            return Err(anyhow::anyhow!(
+            return Err(DataSourceError::NotFound(format!(
                 "Music directory not found: {}",
                 self.music_dir
-            ));
+            )));
         }
 
         Ok(())
     }
 
+    fn capabilities(&self) -> Capabilities {
+        // WebDAV 上的文件只读, 标签编辑等接口只在本地模式开放
+        let local_files = self.webdav.is_none();
+        Capabilities {
+            tag_editing: local_files,
+            lyrics_editing: local_files,
+            scanning: true,
+            transcoding: false,
+            play_queue_sync: true,
+            ratings: true,
+            starred_status: true,
+            metadata_filtering: local_files,
+            smart_playlists: true,
+            playlist_subscription: true,
+            offline_pinning: false,
+            library_sync: false,
+            listening_stats: true,
+            radio: true,
+            daily_mixes: true,
+            duplicate_detection: local_files,
+        }
+    }
+
     async fn scan_music(&self) -> Result<()> {
+        if let Some(webdav) = &self.webdav {
+            webdav.scan(true).await?;
+            return Ok(());
+        }
         let config = get_config();
         readmeta::check_lost_file(&config.music_dir).await;
         log::log_info("start scan music");
         Ok(())
     }
 
+    async fn clear_cache(&self) -> Result<()> {
+        // 本地模式直接读数据库, 没有缓存
+        Ok(())
+    }
+
+    async fn sync_library(&self, _full: bool) -> Result<()> {
+        Ok(())
+    }
+
+    async fn pin(&self, _item_type: PinItemType, _id: &str, _pin: bool) -> Result<()> {
+        Err(DataSourceError::unsupported("Local songs are always available offline"))
+    }
+
+    async fn pin_status(&self) -> Result<PinStatus> {
+        Ok(PinStatus::default())
+    }
+
     async fn scan_status(&self) -> Result<ScanProgress> {
+        if let Some(webdav) = &self.webdav {
+            return Ok(webdav.progress());
+        }
         // 本地模式暂时返回空闲状态
         // TODO: 实现真正的扫描进度跟踪
         Ok(ScanProgress {
@@ -439,80 +662,68 @@ impl MusicDataSource for LocalDataSource {
 
     async fn stream_song(
         &self,
-        _song_id: &str,
-        _range: Option<String>,
+        song_id: &str,
+        range: Option<String>,
+        _options: &StreamOptions,
     ) -> Result<reqwest::Response> {
+        // WebDAV 上的文件原样转发, 不转码
+        if let Some(webdav) = &self.webdav {
+            return webdav.stream(song_id, range).await;
+        }
         // 本地数据源不支持流式传输,应该使用静态文件服务
-        Err(anyhow::anyhow!(
-            "Local data source does not support streaming. Use file_url instead."
+        Err(DataSourceError::unsupported(
+            "Local data source does not support streaming. Use file_url instead.",
         ))
     }
 
     async fn list_playlists(&self) -> Result<Vec<PlaylistInfo>> {
-        let song_lists = service::get_song_list()?;
+        self.refresh_daily_mixes().await;
+        let song_lists = service::get_user_song_list_summaries(1)?;
 
-        Ok(song_lists
+        song_lists
             .into_iter()
-            .map(|sl| PlaylistInfo {
-                id: sl.id.to_string(),
-                name: sl.name,
-                description: if sl.description.is_empty() {
-                    None
-                } else {
-                    Some(sl.description)
-                },
-                cover: if sl.cover.is_empty() {
-                    None
-                } else {
-                    Some(sl.cover)
-                },
-                owner: None,
-                public: None,
-                song_count: 0, // 需要额外查询
-                duration: None,
-                created_at: Some(sl.created_at),
-                updated_at: None,
-            })
-            .collect())
+            .map(|sl| self.convert_song_list_summary(sl))
+            .collect()
     }
 
     async fn get_playlist(&self, playlist_id: &str) -> Result<PlaylistDetail> {
         let playlist_id_i64 = playlist_id
             .parse::<i64>()
-            .map_err(|_| anyhow::anyhow!("Invalid playlist id: {}", playlist_id))?;
+            .map_err(|_| DataSourceError::InvalidId(format!("Invalid playlist id: {}", playlist_id)))?;
+        self.refresh_daily_mixes().await;
 
         // 获取歌单信息
-        let song_list = service::get_song_list()?
-            .into_iter()
-            .find(|sl| sl.id == playlist_id_i64)
-            .ok_or_else(|| anyhow::anyhow!("Playlist not found: {}", playlist_id))?;
-
-        // 获取歌曲列表 (返回的直接是 Vec<Metadata>)
-        let metadata_list = service::get_song_list_songs(playlist_id_i64)?;
-        let songs: Vec<UnifiedMetadata> = metadata_list
+        let summary = service::get_song_list_summary(playlist_id_i64)?
+            .ok_or_else(|| DataSourceError::NotFound(format!("Playlist not found: {}", playlist_id)))?;
+        let smart_rule = summary.song_list.smart_rule.clone();
+        let info = self.convert_song_list_summary(summary)?;
+
+        // 获取歌曲列表 (返回的直接是 Vec<Metadata>), 智能歌单按规则实时计算
+        let metadata_list = if info.smart {
+            let rule = parse_smart_rule(&smart_rule)?;
+            service::get_smart_song_list_songs(1, &rule)?
+        } else {
+            service::get_song_list_songs(playlist_id_i64)?
+        };
+        let mut songs: Vec<UnifiedMetadata> = metadata_list
             .into_iter()
             .map(|m| self.convert_metadata(m))
             .collect();
+        self.attach_play_stats(&mut songs)?;
 
         Ok(PlaylistDetail {
-            id: song_list.id.to_string(),
-            name: song_list.name,
-            description: if song_list.description.is_empty() {
-                None
-            } else {
-                Some(song_list.description)
-            },
-            cover: if song_list.cover.is_empty() {
-                None
-            } else {
-                Some(song_list.cover)
-            },
-            owner: None,
-            public: None,
+            id: info.id,
+            name: info.name,
+            description: info.description,
+            cover: info.cover,
+            owner: info.owner,
+            public: info.public,
             song_count: songs.len(),
-            duration: None,
-            created_at: Some(song_list.created_at),
-            updated_at: None,
+            duration: info.duration,
+            created_at: info.created_at,
+            updated_at: info.updated_at,
+            smart: info.smart,
+            daily_mix: info.daily_mix,
             songs,
         })
     }
@@ -530,32 +741,12 @@ impl MusicDataSource for LocalDataSource {
             description: description.unwrap_or("").to_string(),
             cover: String::new(),
             created_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            smart_rule: String::new(),
+            ..Default::default()
         };
 
-        service::add_song_list(&song_list)?;
-
-        // 获取刚创建的歌单
-        let created_playlist = service::get_song_list()?
-            .into_iter()
-            .find(|sl| sl.name == name)
-            .ok_or_else(|| anyhow::anyhow!("Failed to find created playlist"))?;
-
-        // 添加歌曲
-        if !song_ids.is_empty() {
-            let song_list_songs: Vec<_> = song_ids
-                .iter()
-                .enumerate()
-                .map(|(i, id)| service::SongListSong {
-                    user_id: 1,
-                    song_list_id: created_playlist.id,
-                    song_id: id.clone(),
-                    order_num: i as i64,
-                })
-                .collect();
-
-            service::add_song_list_song(created_playlist.id, &song_list_songs)?;
-        }
-
+        // 使用自增 id, 允许同名歌单; 歌单和歌曲在同一个事务中写入
+        service::add_song_list(&song_list, song_ids)?;
         Ok(())
     }
 
@@ -568,13 +759,29 @@ impl MusicDataSource for LocalDataSource {
     ) -> Result<()> {
         let playlist_id_i64 = playlist_id
             .parse::<i64>()
-            .map_err(|_| anyhow::anyhow!("Invalid playlist id: {}", playlist_id))?;
+            .map_err(|_| DataSourceError::InvalidId(format!("Invalid playlist id: {}", playlist_id)))?;
 
         // 获取现有歌单
         let mut song_list = service::get_song_list()?
             .into_iter()
             .find(|sl| sl.id == playlist_id_i64)
-            .ok_or_else(|| anyhow::anyhow!("Playlist not found: {}", playlist_id))?;
+            .ok_or_else(|| DataSourceError::NotFound(format!("Playlist not found: {}", playlist_id)))?;
+
+        // 每日推荐歌单每天重新生成, 不允许修改
+        if !song_list.mix_key.is_empty() {
+            return Err(DataSourceError::Unsupported(format!(
+                "Daily mix is read-only: {}",
+                playlist_id
+            )));
+        }
+
+        // 智能歌单的歌曲由规则生成, 不允许直接修改
+        if !song_list.smart_rule.is_empty() && song_ids.is_some() {
+            return Err(DataSourceError::Unsupported(format!(
+                "Smart playlist songs are read-only: {}",
+                playlist_id
+            )));
+        }
 
         // 更新名称和描述
         if let Some(n) = name {
@@ -588,27 +795,137 @@ impl MusicDataSource for LocalDataSource {
 
         // 更新歌曲列表(如果提供)
         if let Some(ids) = song_ids {
-            let song_list_songs: Vec<_> = ids
-                .iter()
-                .enumerate()
-                .map(|(i, id)| service::SongListSong {
-                    user_id: 1,
-                    song_list_id: playlist_id_i64,
-                    song_id: id.clone(),
-                    order_num: i as i64,
-                })
-                .collect();
+            service::edit_song_list_songs(1, playlist_id_i64, |songs| {
+                PlaylistEdit::Replace {
+                    song_ids: ids.to_vec(),
+                }
+                .apply(songs)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    async fn edit_playlist(&self, playlist_id: &str, edit: &PlaylistEdit) -> Result<()> {
+        let playlist_id_i64 = playlist_id
+            .parse::<i64>()
+            .map_err(|_| DataSourceError::InvalidId(format!("Invalid playlist id: {}", playlist_id)))?;
+
+        let song_list = service::get_song_list()?
+            .into_iter()
+            .find(|sl| sl.id == playlist_id_i64)
+            .ok_or_else(|| DataSourceError::NotFound(format!("Playlist not found: {}", playlist_id)))?;
+
+        if !song_list.smart_rule.is_empty() || !song_list.mix_key.is_empty() {
+            return Err(DataSourceError::Unsupported(format!(
+                "Smart playlist and daily mix songs are read-only: {}",
+                playlist_id
+            )));
+        }
+
+        service::edit_song_list_songs(1, playlist_id_i64, |songs| edit.apply(songs))?;
+        Ok(())
+    }
+
+    async fn set_playlist_public(&self, playlist_id: &str, public: bool) -> Result<()> {
+        let playlist_id_i64 = playlist_id
+            .parse::<i64>()
+            .map_err(|_| DataSourceError::InvalidId(format!("Invalid playlist id: {}", playlist_id)))?;
+
+        if service::set_song_list_public(playlist_id_i64, public)? == 0 {
+            return Err(DataSourceError::NotFound(format!("Playlist not found: {}", playlist_id)));
+        }
+        Ok(())
+    }
+
+    async fn set_playlist_cover(&self, playlist_id: &str, cover: &str) -> Result<()> {
+        let playlist_id_i64 = playlist_id
+            .parse::<i64>()
+            .map_err(|_| DataSourceError::InvalidId(format!("Invalid playlist id: {}", playlist_id)))?;
+
+        if service::set_song_list_cover(playlist_id_i64, cover)? == 0 {
+            return Err(DataSourceError::NotFound(format!("Playlist not found: {}", playlist_id)));
+        }
+        Ok(())
+    }
+
+    async fn list_public_playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        service::get_public_song_list_summaries(1)?
+            .into_iter()
+            .map(|sl| self.convert_song_list_summary(sl))
+            .collect()
+    }
+
+    async fn subscribe_playlist(&self, playlist_id: &str, subscribe: bool) -> Result<()> {
+        let playlist_id_i64 = playlist_id
+            .parse::<i64>()
+            .map_err(|_| DataSourceError::InvalidId(format!("Invalid playlist id: {}", playlist_id)))?;
+
+        if subscribe {
+            if service::add_song_list_subscribe(1, playlist_id_i64)? == 0
+                && !service::get_user_song_list_summaries(1)?
+                    .iter()
+                    .any(|sl| sl.song_list.id == playlist_id_i64)
+            {
+                return Err(DataSourceError::NotFound(format!(
+                    "Playlist not found or not public: {}",
+                    playlist_id
+                )));
+            }
+        } else {
+            service::delete_song_list_subscribe(1, playlist_id_i64)?;
+        }
+        Ok(())
+    }
+
+    async fn create_smart_playlist(
+        &self,
+        name: &str,
+        description: Option<&str>,
+        rule: &SmartPlaylistRule,
+    ) -> Result<()> {
+        let song_list = service::SongList {
+            id: 0, // 数据库自动生成
+            user_id: 1,
+            name: name.to_string(),
+            description: description.unwrap_or("").to_string(),
+            cover: String::new(),
+            created_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            smart_rule: serde_json::to_string(rule)?,
+            ..Default::default()
+        };
 
-            service::add_song_list_song(playlist_id_i64, &song_list_songs)?;
+        service::add_song_list(&song_list, &[])?;
+        Ok(())
+    }
+
+    async fn update_smart_playlist(
+        &self,
+        playlist_id: &str,
+        rule: &SmartPlaylistRule,
+    ) -> Result<()> {
+        let playlist_id_i64 = playlist_id
+            .parse::<i64>()
+            .map_err(|_| DataSourceError::InvalidId(format!("Invalid playlist id: {}", playlist_id)))?;
+
+        let mut song_list = service::get_song_list()?
+            .into_iter()
+            .find(|sl| sl.id == playlist_id_i64)
+            .ok_or_else(|| DataSourceError::NotFound(format!("Playlist not found: {}", playlist_id)))?;
+
+        if song_list.smart_rule.is_empty() {
+            return Err(DataSourceError::Unsupported(format!("Playlist is not a smart playlist: {}", playlist_id)));
         }
 
+        song_list.smart_rule = serde_json::to_string(rule)?;
+        service::update_song_list(&song_list)?;
         Ok(())
     }
 
     async fn delete_playlist(&self, playlist_id: &str) -> Result<()> {
         let playlist_id_i64 = playlist_id
             .parse::<i64>()
-            .map_err(|_| anyhow::anyhow!("Invalid playlist id: {}", playlist_id))?;
+            .map_err(|_| DataSourceError::InvalidId(format!("Invalid playlist id: {}", playlist_id)))?;
 
         service::delete_song_list(playlist_id_i64)?;
         Ok(())
@@ -765,7 +1082,74 @@ impl MusicDataSource for LocalDataSource {
     }
 
     async fn is_starred(&self, id: &str, item_type: StarItemType) -> Result<bool> {
-        service::is_favorited(1, id, item_type.as_str())
-            .map_err(|e| anyhow::anyhow!("Failed to check starred status: {}", e))
+        Ok(service::is_favorited(1, id, item_type.as_str())?)
     }
+
+    async fn set_rating(&self, id: &str, item_type: StarItemType, rating: u8) -> Result<()> {
+        service::set_rating(1, id, item_type.as_str(), rating)?;
+        log::log_info(&format!(
+            "Rated: id={}, type={}, rating={}",
+            id,
+            item_type.as_str(),
+            rating
+        ));
+        Ok(())
+    }
+
+    async fn get_rating(&self, id: &str, item_type: StarItemType) -> Result<Option<u8>> {
+        Ok(service::get_rating(1, id, item_type.as_str())?)
+    }
+
+    async fn stats_top(
+        &self,
+        item_type: StatsItemType,
+        range: TimeRange,
+        limit: u32,
+    ) -> Result<Vec<StatsItem>> {
+        Ok(stats::top(&local::connect_db()?, 1, item_type, range, limit)?)
+    }
+
+    async fn stats_first_listened(
+        &self,
+        item_type: StatsItemType,
+        range: TimeRange,
+        limit: u32,
+    ) -> Result<Vec<StatsItem>> {
+        Ok(stats::first_listened(&local::connect_db()?, 1, item_type, range, limit)?)
+    }
+
+    async fn stats_listening_time(
+        &self,
+        period: StatsPeriod,
+        range: TimeRange,
+    ) -> Result<Vec<ListeningTime>> {
+        Ok(stats::listening_time(&local::connect_db()?, 1, period, range)?)
+    }
+
+    async fn stats_streaks(&self) -> Result<Streaks> {
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        Ok(stats::streaks(&local::connect_db()?, 1, &today)?)
+    }
+
+    async fn stats_heatmap(&self, range: TimeRange) -> Result<Vec<HeatmapCell>> {
+        Ok(stats::heatmap(&local::connect_db()?, 1, range)?)
+    }
+
+    async fn wrapped(&self, year: i32) -> Result<Wrapped> {
+        Ok(wrapped::build(&local::connect_db()?, 1, year)?)
+    }
+
+    async fn wrapped_card(&self, year: i32) -> Result<Vec<u8>> {
+        let conn = local::connect_db()?;
+        let report = wrapped::build(&conn, 1, year)?;
+        let covers = wrapped::album_covers(&conn, &report.top_albums)?;
+        Ok(wrapped::render_card(&report, &covers)?)
+    }
+}
+
+/// 解析存储在数据库中的智能歌单规则
+fn parse_smart_rule(rule: &str) -> Result<SmartPlaylistRule> {
+    // 规则是保存在数据库中的数据, 解析失败说明数据已损坏, 不是请求参数的问题
+    serde_json::from_str(rule)
+        .map_err(|e| DataSourceError::Internal(anyhow::anyhow!("Invalid stored smart playlist rule: {}", e)))
 }
@@ -0,0 +1,287 @@
+// 重复歌曲检测
+// 后台分析任务为每首歌计算声学指纹 (见 audio::fingerprint), 这里按指纹相似度把歌曲分组, 每组按音质排序。保留其中一首时, 其他副本的播放记录、收藏、评分和歌单引用转到保留的歌曲上, 然后从音乐库移除: 文件本身不删除, 路径记入 excluded_file 表, 以后扫描时跳过。
+
+use std::collections::HashMap;
+
+use anyhow::{Error, Result};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use super::service::delete_song_rows;
+use crate::audio::fingerprint::{self, Fingerprint};
+
+/// 无损格式, 音质比较时优先
+const LOSSLESS_FORMATS: [&str; 7] = ["flac", "wav", "ape", "alac", "aiff", "aif", "wv"];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DuplicateSong {
+    pub id: String,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub file_path: String,
+    pub duration: f64,
+    /// 文件格式 (扩展名)
+    pub format: String,
+    pub lossless: bool,
+    /// 平均码率 (kbps)
+    pub bitrate: u32,
+    pub samplerate: u32,
+    /// 与组内第一首的相似度, 第一首为 1
+    pub similarity: f64,
+}
+
+impl DuplicateSong {
+    fn quality(&self) -> (bool, u32, u32) {
+        (self.lossless, self.bitrate, self.samplerate)
+    }
+}
+
+/// 一组重复的歌曲, 按音质从高到低排序, 第一首为建议保留的版本
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DuplicateGroup {
+    pub songs: Vec<DuplicateSong>,
+}
+
+/// 保留一首歌、移除其他副本的结果
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MergeResult {
+    pub kept: String,
+    pub removed: Vec<String>,
+}
+
+pub fn is_lossless(format: &str) -> bool {
+    LOSSLESS_FORMATS.contains(&format.to_lowercase().as_str())
+}
+
+/// 按指纹相似度把歌曲分组, 只返回有两首以上的组, 按第一首的标题排序
+pub fn find_duplicates(conn: &Connection, threshold: f64) -> Result<Vec<DuplicateGroup>> {
+    let mut stmt = conn.prepare(
+        "SELECT f.song_id, f.data, f.format, f.bitrate, f.samplerate, m.title, m.artist, COALESCE(m.album, ''), \
+         m.file_path, COALESCE(m.duration, 0) FROM fingerprint f JOIN metadata m ON m.id = f.song_id \
+         WHERE f.failed = 0 ORDER BY f.song_id",
+    )?;
+    let mut songs = Vec::new();
+    let mut prints = Vec::new();
+    for row in stmt.query_map([], |row| {
+        let data: Vec<u8> = row.get(1)?;
+        let format: String = row.get(2)?;
+        let song = DuplicateSong {
+            id: row.get(0)?,
+            lossless: is_lossless(&format),
+            format,
+            bitrate: row.get(3)?,
+            samplerate: row.get(4)?,
+            title: row.get(5)?,
+            artist: row.get(6)?,
+            album: row.get(7)?,
+            file_path: row.get(8)?,
+            duration: row.get(9)?,
+            similarity: 1.0,
+        };
+        Ok((song, data))
+    })? {
+        let (song, data) = row?;
+        if let Some(print) = Fingerprint::from_bytes(&data) {
+            songs.push(song);
+            prints.push(print);
+        }
+    }
+
+    // 并查集合并相似的歌曲, 相似度具有传递性
+    let matches = fingerprint::find_matches(&prints, threshold);
+    let mut parent: Vec<usize> = (0..songs.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let mut similarities = HashMap::new();
+    for &(i, j, similarity) in &matches {
+        let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+        parent[a.max(b)] = a.min(b);
+        similarities.insert((i, j), similarity);
+    }
+    let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..songs.len() {
+        let r = root(&mut parent, i);
+        members.entry(r).or_default().push(i);
+    }
+
+    let mut groups: Vec<DuplicateGroup> = members
+        .into_values()
+        .filter(|indexes| indexes.len() > 1)
+        .map(|mut indexes| {
+            indexes.sort_by(|&a, &b| songs[b].quality().cmp(&songs[a].quality()).then(a.cmp(&b)));
+            let best = indexes[0];
+            let songs = indexes
+                .iter()
+                .map(|&i| {
+                    let mut song = songs[i].clone();
+                    if i != best {
+                        let key = (i.min(best), i.max(best));
+                        song.similarity = similarities
+                            .get(&key)
+                            .copied()
+                            .unwrap_or_else(|| prints[best].similarity(&prints[i]));
+                        song.similarity = (song.similarity * 1000.0).round() / 1000.0;
+                    }
+                    song
+                })
+                .collect();
+            DuplicateGroup { songs }
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        let (a, b) = (&a.songs[0], &b.songs[0]);
+        (&a.title, &a.artist, &a.id).cmp(&(&b.title, &b.artist, &b.id))
+    });
+    Ok(groups)
+}
+
+/// 保留一首歌, 从音乐库移除其他副本
+///
+/// # 参数
+/// * `song_ids` - 同一组的歌曲
+/// * `keep` - 保留的歌曲, 为 None 时保留音质最好的 (无损优先, 其次码率、采样率)
+pub fn merge(conn: &mut Connection, song_ids: &[String], keep: Option<&str>) -> Result<MergeResult> {
+    if song_ids.len() < 2 {
+        return Err(Error::msg("at least two songs are required"));
+    }
+    let kept = match keep {
+        Some(keep) if song_ids.iter().any(|id| id == keep) => keep.to_string(),
+        Some(keep) => return Err(Error::msg(format!("song {keep} is not in the group"))),
+        None => best_quality(conn, song_ids)?,
+    };
+
+    let tx = conn.transaction()?;
+    let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let mut removed = Vec::new();
+    for song_id in song_ids.iter().filter(|id| **id != kept) {
+        let file_path: String = tx
+            .query_row("SELECT file_path FROM metadata WHERE id = ?", [song_id], |row| row.get(0))
+            .map_err(|_| Error::msg(format!("song {song_id} not found")))?;
+
+        tx.execute("UPDATE scrobble SET song_id = ?1 WHERE song_id = ?2", params![kept, song_id])?;
+        tx.execute("UPDATE playlist SET song_id = ?1 WHERE song_id = ?2", params![kept, song_id])?;
+        tx.execute(
+            "INSERT OR IGNORE INTO user_favorite (user_id, item_id, item_type, created_at) \
+             SELECT user_id, ?1, item_type, created_at FROM user_favorite WHERE item_id = ?2 AND item_type = 'song'",
+            params![kept, song_id],
+        )?;
+        tx.execute(
+            "INSERT OR IGNORE INTO user_rating (user_id, item_type, item_id, rating, updated_at) \
+             SELECT user_id, item_type, ?1, rating, updated_at FROM user_rating WHERE item_id = ?2 AND item_type = 'song'",
+            params![kept, song_id],
+        )?;
+        // 歌单中已经有保留的歌曲时只删除副本, 否则替换为保留的歌曲
+        tx.execute(
+            "UPDATE song_list_song SET song_id = ?1 WHERE song_id = ?2 AND song_list_id NOT IN \
+             (SELECT song_list_id FROM song_list_song WHERE song_id = ?1)",
+            params![kept, song_id],
+        )?;
+
+        delete_song_rows(&tx, song_id)?;
+        tx.execute(
+            "INSERT OR IGNORE INTO excluded_file (file_path, created_at) VALUES (?1, ?2)",
+            params![file_path, now],
+        )?;
+        removed.push(song_id.clone());
+    }
+    tx.commit()?;
+    Ok(MergeResult { kept, removed })
+}
+
+/// 音质最好的歌曲, 没有指纹记录 (音质未知) 的排在最后
+fn best_quality(conn: &Connection, song_ids: &[String]) -> Result<String> {
+    let mut stmt = conn.prepare("SELECT format, bitrate, samplerate FROM fingerprint WHERE song_id = ?")?;
+    let (mut best, mut best_quality) = (None, (false, false, 0, 0));
+    for song_id in song_ids {
+        let quality = stmt
+            .query_row([song_id], |row| {
+                let format: String = row.get(0)?;
+                Ok((true, is_lossless(&format), row.get(1)?, row.get(2)?))
+            })
+            .unwrap_or((false, false, 0, 0));
+        if best.is_none() || quality > best_quality {
+            best = Some(song_id.clone());
+            best_quality = quality;
+        }
+    }
+    best.ok_or_else(|| Error::msg("no songs"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_and_merge_duplicates() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&crate::datasource::local::table::sql()).unwrap();
+        conn.execute_batch(
+            "INSERT INTO metadata (id, file_name, file_path, file_url, title, artist, duration, created_at) VALUES
+                ('s1', '', '/music/a.mp3', '', 'Song A', 'Artist', 200, ''),
+                ('s2', '', '/music/a.flac', '', 'Song A (Remaster)', 'Artist', 201, ''),
+                ('s3', '', '/music/b.mp3', '', 'Song A', 'Artist', 180, ''),
+                ('s4', '', '/music/a.wav', '', 'Song A', 'Artist', 200, '');",
+        )
+        .unwrap();
+
+        // s2 与 s1 相同, 只有少量低位不同; s3 是另一首同名的歌
+        let mut state = 7u32;
+        let mut random = move || {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            state ^ (state >> 15)
+        };
+        let a: Vec<u32> = (0..300).map(|_| random()).collect();
+        let b: Vec<u32> = a.iter().enumerate().map(|(i, v)| if i % 3 == 0 { v ^ 0b101 } else { *v }).collect();
+        let c: Vec<u32> = (0..300).map(|_| random()).collect();
+        for (id, data, format, bitrate) in [("s1", a, "mp3", 320), ("s2", b, "flac", 900), ("s3", c, "mp3", 320)] {
+            conn.execute(
+                "INSERT INTO fingerprint (song_id, data, format, bitrate, samplerate, created_at) VALUES (?1, ?2, ?3, ?4, 44100, '')",
+                params![id, Fingerprint(data).to_bytes(), format, bitrate],
+            )
+            .unwrap();
+        }
+        // s4 的指纹计算失败, 不参与重复检测
+        conn.execute(
+            "INSERT INTO fingerprint (song_id, data, format, bitrate, samplerate, failed, created_at) VALUES ('s4', x'', 'wav', 0, 0, 1, '')",
+            [],
+        )
+        .unwrap();
+
+        let groups = find_duplicates(&conn, fingerprint::DUPLICATE_SIMILARITY).unwrap();
+        assert_eq!(groups.len(), 1);
+        let ids: Vec<&str> = groups[0].songs.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["s2", "s1"]);
+        assert!(groups[0].songs[0].lossless);
+        assert!(groups[0].songs[1].similarity > 0.95);
+
+        // s1 的播放记录、收藏、评分和歌单引用转到 s2
+        conn.execute_batch(
+            "INSERT INTO scrobble (user_id, song_id, submission, timestamp, created_at) VALUES (1, 's1', 1, 1, '');
+             INSERT INTO user_favorite (user_id, item_id, item_type, created_at) VALUES (1, 's1', 'song', '');
+             INSERT INTO user_rating (user_id, item_type, item_id, rating, updated_at) VALUES (1, 'song', 's1', 5, '');
+             INSERT INTO song_list_song (user_id, song_list_id, song_id, order_num) VALUES (1, 1, 's1', 0), (1, 2, 's1', 0), (1, 2, 's2', 1);",
+        )
+        .unwrap();
+        let ids: Vec<String> = vec!["s1".into(), "s2".into()];
+        assert!(merge(&mut conn, &ids, Some("s3")).is_err());
+        let result = merge(&mut conn, &ids, None).unwrap();
+        assert_eq!(result, MergeResult { kept: "s2".into(), removed: vec!["s1".into()] });
+
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM metadata WHERE id = 's1'"), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM fingerprint WHERE song_id = 's1'"), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM scrobble WHERE song_id = 's2'"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM user_favorite WHERE item_id = 's2'"), 1);
+        assert_eq!(count("SELECT rating FROM user_rating WHERE item_id = 's2'"), 5);
+        assert_eq!(count("SELECT COUNT(*) FROM song_list_song WHERE song_id = 's2'"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM song_list_song WHERE song_id = 's1'"), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM excluded_file WHERE file_path = '/music/a.mp3'"), 1);
+        assert!(find_duplicates(&conn, fingerprint::DUPLICATE_SIMILARITY).unwrap().is_empty());
+    }
+}
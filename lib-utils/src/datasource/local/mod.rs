@@ -2,6 +2,12 @@ pub mod covert;
 pub mod service;
 pub mod table;
 pub mod datasource;
+pub mod smart;
+pub mod radio;
+pub mod daily_mix;
+pub mod duplicates;
+pub mod stats;
+pub mod wrapped;
 
 use crate::config::get_config;
 use rusqlite::Connection;
@@ -0,0 +1,311 @@
+// 相似歌曲电台
+// 不依赖外部服务, 根据本地数据为候选歌曲打分: 共同的风格标签、共同的艺术家、年份接近程度、在同一次收听会话中一起播放的次数, 以及在同一歌单中出现的次数。最近播放过的歌曲不推荐。
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use super::stats::SESSION_GAP_MILLIS;
+use crate::datasource::error::DataSourceError;
+use crate::datasource::types::{split_genre, RadioSeed};
+
+/// 最近多久内播放过的歌曲不推荐
+const RECENT_MILLIS: i64 = 6 * 60 * 60 * 1000;
+/// 同一批结果中同一艺术家最多的歌曲数量, 不够时再补充
+const MAX_PER_ARTIST: usize = 3;
+/// metadata.genre 的默认值, 不作为风格标签
+const UNKNOWN_GENRE: &str = "未知风格";
+
+const GENRE_WEIGHT: f64 = 3.0;
+const ARTIST_WEIGHT: f64 = 2.0;
+const YEAR_WEIGHT: f64 = 1.0;
+/// 年份相差超过该值时不加分
+const YEAR_SPAN: f64 = 10.0;
+const SESSION_WEIGHT: f64 = 2.5;
+const PLAYLIST_WEIGHT: f64 = 1.5;
+
+/// 推荐结果
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScoredSong {
+    pub id: String,
+    pub score: f64,
+}
+
+struct Song {
+    id: String,
+    artist: String,
+    genres: HashSet<String>,
+    year: Option<i32>,
+    artists: HashSet<i64>,
+}
+
+/// 种子的特征
+#[derive(Default)]
+struct Profile {
+    songs: HashSet<String>,
+    genres: HashSet<String>,
+    artists: HashSet<i64>,
+    year: Option<f64>,
+}
+
+/// 根据种子推荐歌曲, 按分数倒序
+///
+/// # 参数
+/// * `exclude` - 不推荐的歌曲 (如播放队列中已有的歌曲)
+/// * `now` - 当前时间 (毫秒), 用于排除最近播放过的歌曲
+pub fn similar_songs(
+    conn: &Connection,
+    user_id: i64,
+    seed: &RadioSeed,
+    limit: usize,
+    exclude: &[String],
+    now: i64,
+) -> Result<Vec<ScoredSong>> {
+    let songs = load_songs(conn)?;
+    let profile = profile(&songs, seed)?;
+
+    let mut excluded: HashSet<String> = exclude.iter().cloned().collect();
+    let mut stmt = conn.prepare("SELECT DISTINCT song_id FROM scrobble WHERE user_id = ?1 AND timestamp >= ?2")?;
+    for id in stmt.query_map(params![user_id, now - RECENT_MILLIS], |row| row.get(0))? {
+        excluded.insert(id?);
+    }
+    if let RadioSeed::Song(id) = seed {
+        excluded.insert(id.clone());
+    }
+
+    let sessions = normalize(session_counts(conn, user_id, &profile.songs)?);
+    let playlists = normalize(playlist_counts(conn, user_id, &profile.songs)?);
+
+    let mut scored: Vec<(f64, &Song)> = songs
+        .iter()
+        .filter(|s| !excluded.contains(&s.id))
+        .map(|s| {
+            let mut score = GENRE_WEIGHT * jaccard(&profile.genres, &s.genres);
+            if !profile.artists.is_disjoint(&s.artists) {
+                score += ARTIST_WEIGHT;
+            }
+            if let (Some(a), Some(b)) = (profile.year, s.year) {
+                score += YEAR_WEIGHT * (1.0 - (a - b as f64).abs() / YEAR_SPAN).max(0.0);
+            }
+            score += SESSION_WEIGHT * sessions.get(&s.id).unwrap_or(&0.0);
+            score += PLAYLIST_WEIGHT * playlists.get(&s.id).unwrap_or(&0.0);
+            (score, s)
+        })
+        .filter(|(score, _)| *score > 0.0)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.id.cmp(&b.1.id)));
+
+    // 同一艺术家的歌曲不要太集中, 先跳过超出数量的歌曲, 不够时再按顺序补充
+    let mut per_artist: HashMap<String, usize> = HashMap::new();
+    let (mut picked, mut skipped) = (Vec::new(), Vec::new());
+    for (score, song) in scored {
+        if picked.len() >= limit {
+            break;
+        }
+        let count = per_artist.entry(song.artist.to_lowercase()).or_default();
+        let item = ScoredSong {
+            id: song.id.clone(),
+            score,
+        };
+        if *count < MAX_PER_ARTIST {
+            *count += 1;
+            picked.push(item);
+        } else {
+            skipped.push(item);
+        }
+    }
+    let missing = limit.saturating_sub(picked.len());
+    picked.extend(skipped.into_iter().take(missing));
+    Ok(picked)
+}
+
+fn load_songs(conn: &Connection) -> Result<Vec<Song>> {
+    let mut artists: HashMap<String, HashSet<i64>> = HashMap::new();
+    let mut stmt = conn.prepare("SELECT song_id, artist_id FROM artist_song")?;
+    for row in stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))? {
+        let (song_id, artist_id) = row?;
+        artists.entry(song_id).or_default().insert(artist_id);
+    }
+
+    let mut stmt = conn.prepare("SELECT id, artist, genre, year FROM metadata")?;
+    let songs = stmt
+        .query_map([], |row| {
+            let id: String = row.get(0)?;
+            let genre: String = row.get(2)?;
+            let year: String = row.get(3)?;
+            Ok(Song {
+                artists: artists.remove(&id).unwrap_or_default(),
+                id,
+                artist: row.get(1)?,
+                genres: genre_tokens(&genre),
+                year: year.trim().parse().ok(),
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(songs)
+}
+
+fn profile(songs: &[Song], seed: &RadioSeed) -> Result<Profile> {
+    let matched: Vec<&Song> = match seed {
+        RadioSeed::Song(id) => songs.iter().filter(|s| &s.id == id).collect(),
+        RadioSeed::Artist(id) => {
+            let artist_id: i64 = id
+                .parse()
+                .map_err(|_| DataSourceError::invalid_id(format!("Invalid artist ID: {}", id)))?;
+            songs.iter().filter(|s| s.artists.contains(&artist_id)).collect()
+        }
+        // 风格电台只按风格标签打分
+        RadioSeed::Genre(genre) => {
+            return Ok(Profile {
+                genres: genre_tokens(genre),
+                ..Default::default()
+            })
+        }
+    };
+    if matched.is_empty() {
+        return Err(DataSourceError::not_found(format!("Radio seed not found: {:?}", seed)).into());
+    }
+
+    let mut profile = Profile::default();
+    for song in &matched {
+        profile.songs.insert(song.id.clone());
+        profile.genres.extend(song.genres.iter().cloned());
+    }
+    match seed {
+        RadioSeed::Artist(id) => profile.artists.extend(id.parse::<i64>().ok()),
+        _ => profile.artists.extend(matched.iter().flat_map(|s| s.artists.iter().copied())),
+    }
+    let years: Vec<f64> = matched.iter().filter_map(|s| s.year).map(f64::from).collect();
+    if !years.is_empty() {
+        profile.year = Some(years.iter().sum::<f64>() / years.len() as f64);
+    }
+    Ok(profile)
+}
+
+/// 与种子歌曲在同一次收听会话中播放过的会话数
+fn session_counts(conn: &Connection, user_id: i64, seeds: &HashSet<String>) -> Result<HashMap<String, usize>> {
+    if seeds.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let mut stmt = conn.prepare(
+        "WITH gaps AS (
+            SELECT s.song_id, s.timestamp,
+                CASE WHEN s.timestamp - LAG(s.timestamp + CAST(COALESCE(m.duration, 0) * 1000 AS INTEGER))
+                    OVER (ORDER BY s.timestamp) <= ?2 THEN 0 ELSE 1 END AS new_session
+            FROM scrobble s LEFT JOIN metadata m ON m.id = s.song_id
+            WHERE s.user_id = ?1 AND s.submission = 1
+        )
+        SELECT song_id, SUM(new_session) OVER (ORDER BY timestamp ROWS UNBOUNDED PRECEDING) FROM gaps",
+    )?;
+    let mut sessions: HashMap<i64, HashSet<String>> = HashMap::new();
+    for row in stmt.query_map(params![user_id, SESSION_GAP_MILLIS], |row| Ok((row.get(0)?, row.get(1)?)))? {
+        let (song_id, session) = row?;
+        sessions.entry(session).or_default().insert(song_id);
+    }
+    Ok(co_occurrence(sessions.into_values(), seeds))
+}
+
+/// 与种子歌曲出现在同一歌单 (自己的歌单和公开歌单) 中的次数
+fn playlist_counts(conn: &Connection, user_id: i64, seeds: &HashSet<String>) -> Result<HashMap<String, usize>> {
+    if seeds.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let mut stmt = conn.prepare(
+        "SELECT sls.song_list_id, sls.song_id FROM song_list_song sls
+        JOIN song_list sl ON sl.id = sls.song_list_id
+        WHERE sl.user_id = ?1 OR sl.public = 1",
+    )?;
+    let mut lists: HashMap<i64, HashSet<String>> = HashMap::new();
+    for row in stmt.query_map(params![user_id], |row| Ok((row.get(0)?, row.get(1)?)))? {
+        let (list_id, song_id) = row?;
+        lists.entry(list_id).or_default().insert(song_id);
+    }
+    Ok(co_occurrence(lists.into_values(), seeds))
+}
+
+/// 统计每首歌曲和种子歌曲出现在同一分组中的次数
+fn co_occurrence(
+    groups: impl Iterator<Item = HashSet<String>>,
+    seeds: &HashSet<String>,
+) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for group in groups.filter(|g| !g.is_disjoint(seeds)) {
+        for song_id in group {
+            *counts.entry(song_id).or_default() += 1;
+        }
+    }
+    counts
+}
+
+/// 按最大值归一化到 [0, 1]
+fn normalize(counts: HashMap<String, usize>) -> HashMap<String, f64> {
+    let max = counts.values().copied().max().unwrap_or(0).max(1) as f64;
+    counts
+        .into_iter()
+        .map(|(id, count)| (id, count as f64 / max))
+        .collect()
+}
+
+pub(super) fn genre_tokens(genre: &str) -> HashSet<String> {
+    split_genre(genre)
+        .into_iter()
+        .filter(|g| g != UNKNOWN_GENRE)
+        .map(|g| g.to_lowercase())
+        .collect()
+}
+
+pub(super) fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similar_songs() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&crate::datasource::local::table::sql()).unwrap();
+        conn.execute_batch(
+            "INSERT INTO metadata (id, file_name, file_path, file_url, title, artist, duration, genre, year) VALUES
+                ('s1', '', '', '', 'So What', 'Miles Davis', 60, 'Jazz, Modal', '1959'),
+                ('s2', '', '', '', 'Blue in Green', 'Miles Davis', 60, 'Jazz', '1959'),
+                ('s3', '', '', '', 'Take Five', 'Dave Brubeck', 60, 'Jazz, Cool', '1959'),
+                ('s4', '', '', '', 'Thriller', 'Michael Jackson', 60, 'Pop', '1982'),
+                ('s5', '', '', '', 'Billie Jean', 'Michael Jackson', 60, 'Pop', '1982'),
+                ('s6', '', '', '', 'Naima', 'John Coltrane', 60, 'Jazz', '1960');
+             INSERT INTO artist_song VALUES (1, 's1'), (1, 's2'), (2, 's3'), (3, 's4'), (3, 's5'), (4, 's6');
+             INSERT INTO song_list (id, user_id, name, created_at) VALUES (1, 1, 'Mix', '');
+             INSERT INTO song_list_song VALUES (1, 1, 's1', 0), (1, 1, 's4', 1);",
+        )
+        .unwrap();
+        // s1 和 s5 在同一次收听会话中, s6 刚刚播放过
+        let now = 1_700_000_000_000i64;
+        for (song_id, timestamp) in [("s1", 1_000_000), ("s5", 1_060_000), ("s6", now - 1000)] {
+            conn.execute(
+                "INSERT INTO scrobble (user_id, song_id, submission, timestamp, created_at) VALUES (1, ?1, 1, ?2, '')",
+                params![song_id, timestamp],
+            )
+            .unwrap();
+        }
+
+        let result = similar_songs(&conn, 1, &RadioSeed::Song("s1".into()), 10, &[], now).unwrap();
+        let ids: Vec<&str> = result.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids[0], "s2");
+        assert!(!ids.contains(&"s1"));
+        assert!(!ids.contains(&"s6"));
+        // 不同风格的歌曲通过会话和歌单得分
+        assert!(ids.contains(&"s5") && ids.contains(&"s4"));
+
+        let result = similar_songs(&conn, 1, &RadioSeed::Genre("pop".into()), 10, &["s4".into()], now).unwrap();
+        assert_eq!(result.iter().map(|s| s.id.as_str()).collect::<Vec<_>>(), vec!["s5"]);
+
+        assert!(similar_songs(&conn, 1, &RadioSeed::Artist("9".into()), 10, &[], now).is_err());
+    }
+}
@@ -1,12 +1,19 @@
 #![allow(dead_code)]
 
-use rusqlite::{params_from_iter, Result, Row};
+use rusqlite::{params_from_iter, OptionalExtension, Result, Row};
 use serde::{Deserialize, Serialize};
 
 use super::connect_db;
-use crate::datasource::types::AlbumListType;
+use crate::datasource::types::{AlbumListType, BpmRange, ReplayGain};
 
-fn repeat_vars(count: usize) -> String {
+fn now_str() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// IN 查询每批的 ID 数量, 避免超过 SQLite 的参数数量上限
+pub(super) const ID_BATCH: usize = 500;
+
+pub(super) fn repeat_vars(count: usize) -> String {
     assert_ne!(count, 0);
     let mut s = "?,".repeat(count);
     // Remove trailing comma
@@ -78,6 +85,20 @@ fn covert_row_to_song_list(row: &rusqlite::Row) -> Result<SongList> {
         description: row.get(3)?,
         cover: row.get(4)?,
         created_at: row.get(5)?,
+        smart_rule: row.get(6)?,
+        updated_at: row.get(7)?,
+        public: row.get(8)?,
+        mix_key: row.get(9)?,
+        mix_date: row.get(10)?,
+    })
+}
+
+fn covert_row_to_song_list_summary(row: &rusqlite::Row) -> Result<SongListSummary> {
+    Ok(SongListSummary {
+        song_list: covert_row_to_song_list(row)?,
+        song_count: row.get(11)?,
+        duration: row.get(12)?,
+        owner: row.get(13)?,
     })
 }
 
@@ -217,12 +238,14 @@ pub fn get_metadata_list() -> Result<Vec<Metadata>> {
 /// - `genre`: 可选,按流派筛选
 /// - `from_year`: 可选,只返回此年份之后(含)发布的歌曲
 /// - `to_year`: 可选,只返回此年份之前(含)发布的歌曲
+/// - `bpm`: BPM 范围, 有限制时只返回已知 BPM 的歌曲
 /// - `music_folder_id`: 可选,按音乐文件夹筛选(暂不支持)
 pub fn get_random_songs(
     size: Option<usize>,
     genre: Option<&str>,
     from_year: Option<&str>,
     to_year: Option<&str>,
+    bpm: BpmRange,
 ) -> Result<Vec<Metadata>> {
     let conn = connect_db()?;
 
@@ -233,10 +256,10 @@ pub fn get_random_songs(
     let mut sql = String::from("SELECT * FROM metadata WHERE 1=1");
     let mut params: Vec<String> = Vec::new();
 
-    // 添加流派筛选
+    // 添加流派筛选 (不区分大小写, 匹配多风格中的任一个)
     if let Some(g) = genre {
         if !g.is_empty() {
-            sql.push_str(" AND genre = ?");
+            sql.push_str(" AND instr(LOWER(genre), LOWER(?)) > 0");
             params.push(g.to_string());
         }
     }
@@ -256,6 +279,19 @@ pub fn get_random_songs(
         }
     }
 
+    // 添加 BPM 筛选, 没有 BPM 的歌曲不匹配
+    if !bpm.is_unbounded() {
+        sql.push_str(" AND bpm IS NOT NULL");
+    }
+    if let Some(min) = bpm.min {
+        sql.push_str(" AND bpm >= ?");
+        params.push(min.to_string());
+    }
+    if let Some(max) = bpm.max {
+        sql.push_str(" AND bpm <= ?");
+        params.push(max.to_string());
+    }
+
     // 添加随机排序和限制
     sql.push_str(" ORDER BY RANDOM() LIMIT ?");
     params.push(limit.to_string());
@@ -275,10 +311,19 @@ pub fn get_random_songs(
     Ok(list)
 }
 
-pub fn get_metadata_by_title_artist(title: &str, artist: &str) -> Result<Option<Metadata>> {
+/// 标题和艺术家相同的所有歌曲
+pub fn get_metadata_list_by_title_artist(title: &str, artist: &str) -> Result<Vec<Metadata>> {
+    let conn = connect_db()?;
+    let mut stmt = conn.prepare("SELECT * FROM metadata WHERE title = ? AND artist = ? ORDER BY id")?;
+    let rows = stmt.query_map([title, artist], covert_row_to_metadata)?;
+    rows.collect()
+}
+
+/// 按文件路径精确查找歌曲
+pub fn get_metadata_by_file_path(file_path: &str) -> Result<Option<Metadata>> {
     let conn = connect_db()?;
-    let mut stmt = conn.prepare("SELECT * FROM metadata WHERE title = ? AND artist = ?")?;
-    let mut rows = stmt.query([title, artist])?;
+    let mut stmt = conn.prepare("SELECT * FROM metadata WHERE file_path = ?")?;
+    let mut rows = stmt.query([file_path])?;
 
     let metadata = rows
         .next()
@@ -288,9 +333,41 @@ pub fn get_metadata_by_title_artist(title: &str, artist: &str) -> Result<Option<
     Ok(metadata)
 }
 
+/// 根据文件路径查找歌曲
+/// 先按 file_path / file_url 精确匹配, 失败时按路径后缀匹配(仅在唯一命中时返回)
+pub fn get_metadata_by_path(path: &str) -> Result<Option<Metadata>> {
+    let conn = connect_db()?;
+    let mut stmt = conn.prepare("SELECT * FROM metadata WHERE file_path = ? OR file_url = ?")?;
+    let mut rows = stmt.query([path, path])?;
+    let metadata = rows
+        .next()
+        .map(|row| convert_single(row, covert_row_to_metadata))
+        .unwrap_or(None);
+    if metadata.is_some() {
+        return Ok(metadata);
+    }
+
+    let tail = path.trim_start_matches('/');
+    if tail.is_empty() {
+        return Ok(None);
+    }
+    let pattern = format!(
+        "%/{}",
+        tail.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+    );
+    let mut stmt = conn.prepare("SELECT * FROM metadata WHERE file_path LIKE ? ESCAPE '\\' LIMIT 2")?;
+    let rows = stmt.query_map([pattern], covert_row_to_metadata)?;
+    let mut list = rows.filter_map(|r| r.ok()).collect::<Vec<_>>();
+    if list.len() == 1 {
+        Ok(list.pop())
+    } else {
+        Ok(None)
+    }
+}
+
 pub fn add_metadata(metadata: &Metadata) -> Result<()> {
     let conn = connect_db()?;
-    let mut stmt = conn.prepare("INSERT INTO metadata (id, file_name, file_path, file_url, title, artist, album, year, duration, bitrate, samplerate, language, genre, track, disc, comment) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")?;
+    let mut stmt = conn.prepare("INSERT INTO metadata (id, file_name, file_path, file_url, title, artist, album, year, duration, bitrate, samplerate, language, genre, track, disc, comment, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")?;
     let _ = stmt.execute([
         metadata.id.clone(),
         metadata.file_name.clone(),
@@ -308,6 +385,7 @@ pub fn add_metadata(metadata: &Metadata) -> Result<()> {
         metadata.track.to_string(),
         metadata.disc.to_string(),
         metadata.comment.clone(),
+        now_str(),
     ])?;
     Ok(())
 }
@@ -347,6 +425,13 @@ pub fn del_metadata_by_id(song_id: &str) -> Result<usize> {
     let mut conn = connect_db()?;
     // 开启事务
     let tx = conn.transaction()?;
+    let count_size = delete_song_rows(&tx, song_id)?;
+    tx.commit()?;
+    Ok(count_size)
+}
+
+/// 删除歌曲及关联的收藏、评分、歌单等记录, 事务由调用方管理
+pub(crate) fn delete_song_rows(tx: &rusqlite::Connection, song_id: &str) -> Result<usize> {
     let mut count_size = 0;
 
     let size = tx.execute("DELETE FROM metadata WHERE id = ?", [song_id])?;
@@ -357,10 +442,14 @@ pub fn del_metadata_by_id(song_id: &str) -> Result<usize> {
     println!("del cover size: {size}");
     count_size += size;
 
-    let size = tx.execute("DELETE FROM user_favorite WHERE song_id = ?", [song_id])?;
+    let size = tx.execute("DELETE FROM user_favorite WHERE item_id = ? AND item_type = 'song'", [song_id])?;
     println!("del favorite size: {size}");
     count_size += size;
 
+    let size = tx.execute("DELETE FROM user_rating WHERE item_id = ? AND item_type = 'song'", [song_id])?;
+    println!("del rating size: {size}");
+    count_size += size;
+
     let size = tx.execute("DELETE FROM album_song WHERE song_id = ?", [song_id])?;
     println!("del album_song size: {size}");
     count_size += size;
@@ -380,8 +469,11 @@ pub fn del_metadata_by_id(song_id: &str) -> Result<usize> {
     let size = tx.execute("DELETE FROM playlist WHERE song_id = ?", [song_id])?;
     println!("del playlist size: {size}");
     count_size += size;
-    
-    tx.commit()?;
+
+    let size = tx.execute("DELETE FROM fingerprint WHERE song_id = ?", [song_id])?;
+    println!("del fingerprint size: {size}");
+    count_size += size;
+
     Ok(count_size)
 }
 
@@ -504,34 +596,122 @@ pub fn get_song_list() -> Result<Vec<SongList>> {
     Ok(song_list_list)
 }
 
-pub fn add_song_list(song_list: &SongList) -> Result<i64> {
-    let conn = connect_db()?;
-    let mut stmt = conn.prepare("INSERT INTO song_list (user_id, name, description, cover, created_at) VALUES (?, ?, ?, ?, ?)")?;
-    let _ = stmt.execute([
-        &song_list.user_id.to_string(),
-        &song_list.name.clone(),
-        &song_list.description.clone(),
-        &song_list.cover.clone(),
-        &song_list.created_at.clone(),
-    ])?;
+/// 创建歌单并写入歌曲, 在同一个事务中完成, 失败时不会留下空歌单
+pub fn add_song_list(song_list: &SongList, song_ids: &[String]) -> Result<i64> {
+    let mut conn = connect_db()?;
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO song_list (user_id, name, description, cover, created_at, smart_rule, updated_at, public) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        rusqlite::params![
+            song_list.user_id,
+            song_list.name,
+            song_list.description,
+            song_list.cover,
+            song_list.created_at,
+            song_list.smart_rule,
+            song_list.created_at,
+            song_list.public,
+        ],
+    )?;
     // 拿到自增id
-    let id = conn.last_insert_rowid();
+    let id = tx.last_insert_rowid();
+    {
+        let mut stmt = tx.prepare("INSERT INTO song_list_song (user_id, song_list_id, song_id, order_num) VALUES (?, ?, ?, ?)")?;
+        for (i, song_id) in song_ids.iter().enumerate() {
+            stmt.execute(rusqlite::params![song_list.user_id, id, song_id, i as i64])?;
+        }
+    }
+    tx.commit()?;
     Ok(id)
 }
 
 pub fn update_song_list(song_list: &SongList) -> Result<usize> {
     let conn = connect_db()?;
     let mut stmt =
-        conn.prepare("UPDATE song_list SET name = ?, description = ?, cover = ? WHERE id = ?")?;
-    let size = stmt.execute([
-        &song_list.name.clone(),
-        &song_list.description.clone(),
-        &song_list.cover.clone(),
-        &song_list.id.to_string(),
+        conn.prepare("UPDATE song_list SET name = ?, description = ?, cover = ?, smart_rule = ?, public = ?, updated_at = ? WHERE id = ?")?;
+    let size = stmt.execute(rusqlite::params![
+        song_list.name,
+        song_list.description,
+        song_list.cover,
+        song_list.smart_rule,
+        song_list.public,
+        now_str(),
+        song_list.id,
     ])?;
     Ok(size)
 }
 
+pub fn set_song_list_cover(id: i64, cover: &str) -> Result<usize> {
+    let conn = connect_db()?;
+    let mut stmt = conn.prepare("UPDATE song_list SET cover = ?, updated_at = ? WHERE id = ?")?;
+    let size = stmt.execute(rusqlite::params![cover, now_str(), id])?;
+    Ok(size)
+}
+
+pub fn set_song_list_public(id: i64, public: bool) -> Result<usize> {
+    let conn = connect_db()?;
+    let mut stmt = conn.prepare("UPDATE song_list SET public = ?, updated_at = ? WHERE id = ?")?;
+    let size = stmt.execute(rusqlite::params![public, now_str(), id])?;
+    Ok(size)
+}
+
+/// 查询歌单及其统计信息(歌曲数量、总时长、创建者)
+fn get_song_list_summaries(
+    condition: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<SongListSummary>> {
+    let conn = connect_db()?;
+    let sql = format!(
+        "SELECT sl.id, sl.user_id, sl.name, sl.description, sl.cover, sl.created_at, sl.smart_rule, sl.updated_at, sl.public, sl.mix_key, sl.mix_date, \
+         COUNT(m.id), COALESCE(SUM(m.duration), 0), u.name \
+         FROM song_list sl \
+         LEFT JOIN song_list_song sls ON sls.song_list_id = sl.id \
+         LEFT JOIN metadata m ON m.id = sls.song_id \
+         LEFT JOIN user u ON u.id = sl.user_id \
+         WHERE {} GROUP BY sl.id ORDER BY sl.id",
+        condition
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params, covert_row_to_song_list_summary)?;
+    rows.collect()
+}
+
+/// 用户自己的歌单和已订阅的歌单
+pub fn get_user_song_list_summaries(user_id: i64) -> Result<Vec<SongListSummary>> {
+    get_song_list_summaries(
+        "sl.user_id = ?1 OR sl.id IN (SELECT song_list_id FROM song_list_subscribe WHERE user_id = ?1)",
+        [user_id],
+    )
+}
+
+/// 其他用户公开的歌单
+pub fn get_public_song_list_summaries(user_id: i64) -> Result<Vec<SongListSummary>> {
+    get_song_list_summaries("sl.public = 1 AND sl.user_id != ?1", [user_id])
+}
+
+pub fn get_song_list_summary(id: i64) -> Result<Option<SongListSummary>> {
+    Ok(get_song_list_summaries("sl.id = ?1", [id])?.into_iter().next())
+}
+
+/// 订阅公开歌单
+pub fn add_song_list_subscribe(user_id: i64, song_list_id: i64) -> Result<usize> {
+    let conn = connect_db()?;
+    let mut stmt = conn.prepare(
+        "INSERT OR IGNORE INTO song_list_subscribe (user_id, song_list_id, created_at) \
+         SELECT ?1, id, ?3 FROM song_list WHERE id = ?2 AND public = 1 AND user_id != ?1",
+    )?;
+    let size = stmt.execute(rusqlite::params![user_id, song_list_id, now_str()])?;
+    Ok(size)
+}
+
+pub fn delete_song_list_subscribe(user_id: i64, song_list_id: i64) -> Result<usize> {
+    let conn = connect_db()?;
+    let mut stmt =
+        conn.prepare("DELETE FROM song_list_subscribe WHERE user_id = ? AND song_list_id = ?")?;
+    let size = stmt.execute([user_id, song_list_id])?;
+    Ok(size)
+}
+
 pub fn delete_song_list(id: i64) -> Result<(usize, usize)> {
     let mut conn = connect_db()?;
     let tx = conn.transaction()?; // 事务
@@ -573,6 +753,51 @@ pub fn add_song_list_song(song_list_id: i64, list: &Vec<SongListSong>) -> Result
     Ok(list.len())
 }
 
+/// 获取歌单中的歌曲 ID (按 order_num 排序)
+pub fn get_song_list_song_ids(song_list_id: i64) -> Result<Vec<String>> {
+    let conn = connect_db()?;
+    let mut stmt = conn.prepare(
+        "SELECT song_id FROM song_list_song WHERE song_list_id = ? ORDER BY order_num",
+    )?;
+    let rows = stmt.query_map([song_list_id], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// 在同一事务中读取歌单歌曲、执行编辑并重写 order_num
+///
+/// 编辑函数返回错误时事务回滚, 歌单保持不变
+pub fn edit_song_list_songs<F>(user_id: i64, song_list_id: i64, edit: F) -> anyhow::Result<usize>
+where
+    F: FnOnce(&mut Vec<String>) -> anyhow::Result<()>,
+{
+    let mut conn = connect_db()?;
+    let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+
+    let mut song_ids: Vec<String> = {
+        let mut stmt = tx.prepare(
+            "SELECT song_id FROM song_list_song WHERE song_list_id = ? ORDER BY order_num",
+        )?;
+        let rows = stmt.query_map([song_list_id], |row| row.get(0))?;
+        rows.collect::<Result<_>>()?
+    };
+
+    edit(&mut song_ids)?;
+
+    tx.execute("DELETE FROM song_list_song WHERE song_list_id = ?", [song_list_id])?;
+    tx.execute(
+        "UPDATE song_list SET updated_at = ? WHERE id = ?",
+        rusqlite::params![now_str(), song_list_id],
+    )?;
+    {
+        let mut stmt = tx.prepare("INSERT INTO song_list_song (user_id, song_list_id, song_id, order_num) VALUES (?, ?, ?, ?)")?;
+        for (i, song_id) in song_ids.iter().enumerate() {
+            stmt.execute(rusqlite::params![user_id, song_list_id, song_id, i as i64])?;
+        }
+    }
+    tx.commit()?;
+    Ok(song_ids.len())
+}
+
 pub fn delete_song_list_song(song_list_id: i64, song_id: &str) -> Result<usize> {
     let conn = connect_db()?;
     let mut stmt =
@@ -582,7 +807,7 @@ pub fn delete_song_list_song(song_list_id: i64, song_id: &str) -> Result<usize>
 
 pub fn get_song_list_songs(song_list_id: i64) -> Result<Vec<Metadata>> {
     let conn = connect_db()?;
-    let mut stmt = conn.prepare("SELECT metadata.id, metadata.file_name, metadata.file_path, metadata.file_url, metadata.title, metadata.artist, metadata.album, metadata.year, metadata.duration, metadata.bitrate, metadata.samplerate, metadata.language, metadata.genre, metadata.track, metadata.disc, metadata.comment FROM metadata INNER JOIN song_list_song ON metadata.id = song_list_song.song_id WHERE song_list_song.song_list_id = ?")?;
+    let mut stmt = conn.prepare("SELECT metadata.id, metadata.file_name, metadata.file_path, metadata.file_url, metadata.title, metadata.artist, metadata.album, metadata.year, metadata.duration, metadata.bitrate, metadata.samplerate, metadata.language, metadata.genre, metadata.track, metadata.disc, metadata.comment FROM metadata INNER JOIN song_list_song ON metadata.id = song_list_song.song_id WHERE song_list_song.song_list_id = ? ORDER BY song_list_song.order_num")?;
     let rows = stmt.query_map([&song_list_id.to_string()], |row| {
         covert_row_to_metadata(row)
     })?;
@@ -598,9 +823,26 @@ pub fn get_song_list_songs(song_list_id: i64) -> Result<Vec<Metadata>> {
     Ok(song_list)
 }
 
+/// 按智能歌单规则查询歌曲
+pub fn get_smart_song_list_songs(
+    user_id: i64,
+    rule: &crate::datasource::types::SmartPlaylistRule,
+) -> Result<Vec<Metadata>> {
+    let conn = connect_db()?;
+    let (sql, params) = super::smart::build_query(user_id, rule);
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params_from_iter(params), covert_row_to_metadata)?;
+
+    let mut list = Vec::new();
+    for song in rows {
+        list.push(song?);
+    }
+    Ok(list)
+}
+
 pub fn get_song_song_list(song_id: &str) -> Result<Vec<SongList>> {
     let conn = connect_db()?;
-    let mut stmt = conn.prepare("SELECT song_list.id, song_list.user_id, song_list.name, song_list.description, song_list.cover, song_list.created_at FROM song_list INNER JOIN song_list_song ON song_list.id = song_list_song.song_list_id WHERE song_list_song.song_id = ?")?;
+    let mut stmt = conn.prepare("SELECT song_list.id, song_list.user_id, song_list.name, song_list.description, song_list.cover, song_list.created_at, song_list.smart_rule, song_list.updated_at, song_list.public, song_list.mix_key, song_list.mix_date FROM song_list INNER JOIN song_list_song ON song_list.id = song_list_song.song_list_id WHERE song_list_song.song_id = ?")?;
     let rows = stmt.query_map([song_id], |row| covert_row_to_song_list(row))?;
 
     let mut song_list_list = Vec::new();
@@ -641,48 +883,66 @@ pub fn add_album(album: &Album) -> Result<i64> {
     Ok(id)
 }
 
-pub fn get_album_list(list_type: Option<&AlbumListType>) -> Result<Vec<Album>> {
+pub fn get_album_list(user_id: i64, list_type: Option<&AlbumListType>) -> Result<Vec<Album>> {
     let conn = connect_db()?;
 
-    // 根据排序类型构建 SQL
-    let sql = match list_type {
+    // 按播放统计和评分排序, 通过 album_song 关联歌曲的播放记录
+    let by_play_stats = |condition: &str, order: &str| {
+        format!(
+            "SELECT a.id, a.name, a.description, a.year, a.artist, a.created_at
+            FROM album a LEFT JOIN ({}) ps ON ps.album_id = a.id
+            LEFT JOIN user_rating r ON r.user_id = ?1 AND r.item_type = 'album' AND r.item_id = a.id
+            WHERE {}
+            ORDER BY {}",
+            super::stats::album_play_stats_sql("1 = 1"),
+            condition,
+            order
+        )
+    };
+
+    // 根据排序类型构建 SQL 和参数, 按播放统计排序时 ?1 为用户 ID
+    let (sql, params): (String, Vec<i64>) = match list_type {
         // 最新添加 - 按 created_at 倒序
         Some(AlbumListType::Newest) => {
-            "SELECT * FROM album ORDER BY created_at DESC".to_string()
+            ("SELECT * FROM album ORDER BY created_at DESC".to_string(), vec![])
         }
 
         // 最常播放 - 按播放次数倒序
-        Some(AlbumListType::Frequent) => {
-            r#"
-            SELECT a.id, a.name, a.description, a.year, a.artist, a.created_at,
-                   COUNT(s.id) as play_count
-            FROM album a
-            LEFT JOIN scrobble s ON a.id = s.album_id AND s.submission = 1
-            GROUP BY a.id
-            ORDER BY play_count DESC, a.name ASC
-            "#
-            .to_string()
-        }
-
-        // 最近播放 - 按最后播放时间倒序
-        Some(AlbumListType::Recent) => {
-            r#"
-            SELECT a.id, a.name, a.description, a.year, a.artist, a.created_at,
-                   MAX(s.timestamp) as last_played
-            FROM album a
-            LEFT JOIN scrobble s ON a.id = s.album_id
-            GROUP BY a.id
-            ORDER BY last_played DESC, a.created_at DESC
-            "#
-            .to_string()
-        }
+        Some(AlbumListType::Frequent) => (
+            by_play_stats("1 = 1", "COALESCE(ps.play_count, 0) DESC, a.name ASC"),
+            vec![user_id],
+        ),
+
+        // 最近播放 - 按最后播放时间倒序, 没有播放过的排在最后
+        Some(AlbumListType::Recent) => (
+            by_play_stats("1 = 1", "ps.last_played IS NULL, ps.last_played DESC, a.created_at DESC"),
+            vec![user_id],
+        ),
+
+        // 评分最高 - 按用户评分倒序, 评分相同 (或未评分) 时按播放完成的次数 (播放次数减去跳过次数) 倒序
+        Some(AlbumListType::Highest) => (
+            by_play_stats(
+                "1 = 1",
+                "COALESCE(r.rating, 0) DESC, COALESCE(ps.play_count, 0) - COALESCE(ps.skip_count, 0) DESC, COALESCE(ps.play_count, 0) DESC, a.name ASC",
+            ),
+            vec![user_id],
+        ),
+
+        // 评分不低于指定值 - 按评分倒序
+        Some(AlbumListType::ByRating { min_rating }) => (
+            by_play_stats(
+                "r.rating >= ?2",
+                "r.rating DESC, COALESCE(ps.play_count, 0) DESC, a.name ASC",
+            ),
+            vec![user_id, *min_rating as i64],
+        ),
 
         // 默认: 按名称字母排序
-        _ => "SELECT * FROM album ORDER BY name ASC".to_string(),
+        _ => ("SELECT * FROM album ORDER BY name ASC".to_string(), vec![]),
     };
 
     let mut stmt = conn.prepare(&sql)?;
-    let rows = stmt.query_map([], |row| covert_row_to_album(row))?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| covert_row_to_album(row))?;
 
     let mut album_list = Vec::new();
     for album in rows {
@@ -1054,6 +1314,27 @@ pub struct SongList {
     pub description: String,
     pub cover: String,
     pub created_at: String,
+    /// 智能歌单规则(JSON), 为空表示普通歌单
+    pub smart_rule: String,
+    pub updated_at: String,
+    /// 是否公开, 公开的歌单可被其他用户浏览和订阅
+    pub public: bool,
+    /// 每日推荐歌单的类型, 为空表示普通歌单
+    pub mix_key: String,
+    /// 每日推荐歌单的生成日期
+    pub mix_date: String,
+}
+
+/// 歌单及统计信息
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct SongListSummary {
+    #[serde(flatten)]
+    pub song_list: SongList,
+    pub song_count: i64,
+    /// 总时长(秒)
+    pub duration: f64,
+    /// 创建者用户名
+    pub owner: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
@@ -1297,3 +1578,254 @@ pub fn get_all_favorites(user_id: i64) -> Result<Vec<Favorite>> {
 
     Ok(favorites)
 }
+
+// ==================== Rating 评分相关函数 ====================
+
+/// 设置评分, rating 为 0 时删除评分
+pub fn set_rating(user_id: i64, item_id: &str, item_type: &str, rating: u8) -> Result<()> {
+    let conn = connect_db()?;
+
+    if rating == 0 {
+        conn.execute(
+            "DELETE FROM user_rating WHERE user_id = ?1 AND item_type = ?2 AND item_id = ?3",
+            rusqlite::params![user_id, item_type, item_id],
+        )?;
+    } else {
+        conn.execute(
+            "INSERT OR REPLACE INTO user_rating (user_id, item_type, item_id, rating, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![user_id, item_type, item_id, rating, now_str()],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// 获取评分, 未评分时为 None
+pub fn get_rating(user_id: i64, item_id: &str, item_type: &str) -> Result<Option<u8>> {
+    let conn = connect_db()?;
+
+    let rating = conn
+        .query_row(
+            "SELECT rating FROM user_rating WHERE user_id = ?1 AND item_type = ?2 AND item_id = ?3",
+            rusqlite::params![user_id, item_type, item_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    Ok(rating)
+}
+
+/// 获取用户对指定项目的评分, item_id -> rating, 未评分的项目不在结果中
+pub fn get_ratings_by_ids(
+    user_id: i64,
+    item_type: &str,
+    item_ids: &[String],
+) -> Result<std::collections::HashMap<String, u8>> {
+    let conn = connect_db()?;
+
+    let mut ratings = std::collections::HashMap::new();
+    for ids in item_ids.chunks(ID_BATCH) {
+        let sql = format!(
+            "SELECT item_id, rating FROM user_rating WHERE user_id = ? AND item_type = ? AND item_id IN ({})",
+            repeat_vars(ids.len())
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let params = [&user_id as &dyn rusqlite::ToSql, &item_type]
+            .into_iter()
+            .chain(ids.iter().map(|id| id as &dyn rusqlite::ToSql));
+        for row in stmt.query_map(params_from_iter(params), |row| Ok((row.get(0)?, row.get(1)?)))? {
+            let (id, rating) = row?;
+            ratings.insert(id, rating);
+        }
+    }
+
+    Ok(ratings)
+}
+
+/// 写入歌曲的响度归一化信息, 为 None 的字段保留原值
+pub fn set_replay_gain(song_id: &str, gain: &ReplayGain) -> Result<usize> {
+    let conn = connect_db()?;
+    conn.execute(
+        "UPDATE metadata SET track_gain = COALESCE(?1, track_gain), track_peak = COALESCE(?2, track_peak), \
+         album_gain = COALESCE(?3, album_gain), album_peak = COALESCE(?4, album_peak) WHERE id = ?5",
+        rusqlite::params![gain.track_gain, gain.track_peak, gain.album_gain, gain.album_peak, song_id],
+    )
+}
+
+/// 标记响度分析失败, 避免每次分析任务重复处理; 计算专辑增益时忽略这类歌曲
+pub fn set_loudness_failed(song_id: &str) -> Result<usize> {
+    let conn = connect_db()?;
+    conn.execute("UPDATE metadata SET loudness_failed = 1 WHERE id = ?1", [song_id])
+}
+
+/// 没有单曲增益的歌曲 (id, file_path), 需要分析响度
+pub fn get_songs_without_replay_gain() -> Result<Vec<(String, String)>> {
+    let conn = connect_db()?;
+    let mut stmt = conn.prepare("SELECT id, file_path FROM metadata WHERE track_gain IS NULL AND loudness_failed = 0 ORDER BY id")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// 指定歌曲的响度归一化信息, 专辑增益和峰值使用按专辑计算的值
+pub fn get_replay_gains(song_ids: &[String]) -> Result<std::collections::HashMap<String, ReplayGain>> {
+    let conn = connect_db()?;
+    let mut gains = std::collections::HashMap::new();
+    for ids in song_ids.chunks(ID_BATCH) {
+        let sql = format!(
+            "SELECT m.id, m.track_gain, m.track_peak, COALESCE(a.gain, m.album_gain), COALESCE(a.peak, m.album_peak) \
+             FROM metadata m \
+             LEFT JOIN album_song s ON s.song_id = m.id \
+             LEFT JOIN album a ON a.id = s.album_id \
+             WHERE m.id IN ({}) AND (m.track_gain IS NOT NULL OR m.album_gain IS NOT NULL)",
+            repeat_vars(ids.len())
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(ids), |row| {
+            Ok((
+                row.get(0)?,
+                ReplayGain {
+                    track_gain: row.get(1)?,
+                    track_peak: row.get(2)?,
+                    album_gain: row.get(3)?,
+                    album_peak: row.get(4)?,
+                },
+            ))
+        })?;
+        for row in rows {
+            let (id, gain) = row?;
+            gains.insert(id, gain);
+        }
+    }
+    Ok(gains)
+}
+
+/// 按专辑中的歌曲计算 album.gain 和 album.peak, 返回更新的专辑数量
+///
+/// 歌曲带有专辑增益标签时使用标签的值; 否则所有歌曲都有单曲增益后按时长加权计算。
+pub fn update_album_replay_gains() -> Result<usize> {
+    let conn = connect_db()?;
+    let mut stmt = conn.prepare(
+        "SELECT s.album_id, m.track_gain, m.track_peak, m.album_gain, m.album_peak, COALESCE(m.duration, 0) \
+         FROM album_song s JOIN metadata m ON m.id = s.song_id \
+         WHERE m.loudness_failed = 0 ORDER BY s.album_id, m.id",
+    )?;
+    type SongGain = (Option<f64>, Option<f64>, Option<f64>, Option<f64>, f64);
+    let mut albums: std::collections::BTreeMap<i64, Vec<SongGain>> = std::collections::BTreeMap::new();
+    for row in stmt.query_map([], |row| {
+        Ok((row.get(0)?, (row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)))
+    })? {
+        let (album_id, song) = row?;
+        albums.entry(album_id).or_default().push(song);
+    }
+
+    let mut updated = 0;
+    for (album_id, songs) in albums {
+        let tagged = songs.iter().find_map(|s| s.2);
+        let gain = tagged.or_else(|| {
+            let tracks: Option<Vec<(f64, f64)>> = songs.iter().map(|s| s.0.map(|g| (g, s.4))).collect();
+            tracks.and_then(|t| crate::audio::loudness::album_gain(&t))
+        });
+        let Some(gain) = gain else { continue };
+        let peak = songs
+            .iter()
+            .filter_map(|s| s.3.or(s.1))
+            .fold(None, |max: Option<f64>, p| Some(max.map_or(p, |m| m.max(p))));
+        updated += conn.execute(
+            "UPDATE album SET gain = ?1, peak = ?2 WHERE id = ?3",
+            rusqlite::params![(gain * 100.0).round() / 100.0, peak, album_id],
+        )?;
+    }
+    Ok(updated)
+}
+
+/// 写入 BPM 和调性, 为 None 的字段保持原值
+pub fn set_bpm_key(song_id: &str, bpm: Option<f64>, key: Option<&str>) -> Result<usize> {
+    let conn = connect_db()?;
+    conn.execute(
+        "UPDATE metadata SET bpm = COALESCE(?1, bpm), musical_key = COALESCE(?2, musical_key) WHERE id = ?3",
+        rusqlite::params![bpm, key, song_id],
+    )
+}
+
+/// 标记节奏和调性分析无法估计缺少的字段, 避免每次分析任务重复处理
+pub fn set_tempo_failed(song_id: &str) -> Result<usize> {
+    let conn = connect_db()?;
+    conn.execute("UPDATE metadata SET tempo_failed = 1 WHERE id = ?1", [song_id])
+}
+
+/// 没有 BPM 或调性的歌曲 (id, file_path, 是否有 BPM, 是否有调性), 需要分析节奏和调性
+pub fn get_songs_without_bpm_key() -> Result<Vec<(String, String, bool, bool)>> {
+    let conn = connect_db()?;
+    let mut stmt = conn.prepare(
+        "SELECT id, file_path, bpm IS NOT NULL, musical_key IS NOT NULL FROM metadata \
+         WHERE (bpm IS NULL OR musical_key IS NULL) AND tempo_failed = 0 ORDER BY id",
+    )?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?;
+    rows.collect()
+}
+
+/// 歌曲的 BPM 和调性
+pub type BpmKey = (Option<f64>, Option<String>);
+
+/// 指定歌曲的 BPM 和调性
+pub fn get_bpm_keys(song_ids: &[String]) -> Result<std::collections::HashMap<String, BpmKey>> {
+    let conn = connect_db()?;
+    let mut bpm_keys = std::collections::HashMap::new();
+    for ids in song_ids.chunks(ID_BATCH) {
+        let sql = format!(
+            "SELECT id, bpm, musical_key FROM metadata \
+             WHERE id IN ({}) AND (bpm IS NOT NULL OR musical_key IS NOT NULL)",
+            repeat_vars(ids.len())
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        for row in stmt.query_map(params_from_iter(ids), |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))? {
+            let (id, bpm_key) = row?;
+            bpm_keys.insert(id, bpm_key);
+        }
+    }
+    Ok(bpm_keys)
+}
+
+/// 没有指纹的歌曲 (id, file_path, duration), 需要计算指纹
+pub fn get_songs_without_fingerprint() -> Result<Vec<(String, String, f64)>> {
+    let conn = connect_db()?;
+    let mut stmt = conn.prepare(
+        "SELECT m.id, m.file_path, COALESCE(m.duration, 0) FROM metadata m \
+         LEFT JOIN fingerprint f ON f.song_id = m.id WHERE f.song_id IS NULL ORDER BY m.id",
+    )?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+    rows.collect()
+}
+
+/// 写入指纹和音质信息
+///
+/// 无法计算指纹时 data 为 None, 记录为失败, 不再重复分析, 也不参与重复检测
+pub fn set_fingerprint(
+    song_id: &str,
+    data: Option<&[u8]>,
+    format: &str,
+    bitrate: u32,
+    samplerate: u32,
+) -> Result<usize> {
+    let conn = connect_db()?;
+    conn.execute(
+        "INSERT OR REPLACE INTO fingerprint (song_id, data, format, bitrate, samplerate, failed, created_at) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![song_id, data.unwrap_or_default(), format, bitrate, samplerate, data.is_none(), now_str()],
+    )
+}
+
+/// 从音乐库移除的文件路径
+pub fn get_excluded_files() -> Result<std::collections::HashSet<String>> {
+    let conn = connect_db()?;
+    let mut stmt = conn.prepare("SELECT file_path FROM excluded_file")?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect()
+}
+
+pub fn is_excluded_file(file_path: &str) -> Result<bool> {
+    let conn = connect_db()?;
+    conn.query_row("SELECT COUNT(*) FROM excluded_file WHERE file_path = ?", [file_path], |row| {
+        row.get::<_, i64>(0).map(|count| count > 0)
+    })
+}
@@ -0,0 +1,297 @@
+// 智能歌单规则 -> SQL
+// 规则树编译为带参数的 WHERE 子句, 每次获取歌单时重新执行查询
+
+use rusqlite::types::Value;
+
+use crate::audio::key::Key;
+use crate::datasource::types::{SmartPlaylistRule, SmartRule, SmartSort};
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+/// 构建完整查询语句和参数
+pub fn build_query(user_id: i64, rule: &SmartPlaylistRule) -> (String, Vec<Value>) {
+    let mut params = Vec::new();
+    let condition = build_condition(user_id, &rule.rule, &mut params);
+
+    let order = match rule.sort {
+        SmartSort::Random => "RANDOM()".to_string(),
+        SmartSort::Title => "m.title".to_string(),
+        SmartSort::Artist => "m.artist".to_string(),
+        SmartSort::Album => "m.album".to_string(),
+        SmartSort::Year => "CAST(m.year AS INTEGER)".to_string(),
+        SmartSort::Duration => "m.duration".to_string(),
+        SmartSort::Added => "m.created_at".to_string(),
+        SmartSort::PlayCount => {
+            params.push(Value::Integer(user_id));
+            "(SELECT COUNT(*) FROM scrobble s WHERE s.song_id = m.id AND s.user_id = ? AND s.submission = 1)".to_string()
+        }
+        SmartSort::LastPlayed => {
+            params.push(Value::Integer(user_id));
+            "(SELECT MAX(s.timestamp) FROM scrobble s WHERE s.song_id = m.id AND s.user_id = ? AND s.submission = 1)".to_string()
+        }
+        SmartSort::Rating => {
+            params.push(Value::Integer(user_id));
+            "COALESCE((SELECT r.rating FROM user_rating r WHERE r.item_id = m.id AND r.user_id = ? AND r.item_type = 'song'), 0)".to_string()
+        }
+    };
+    let direction = match (rule.sort, rule.descending) {
+        (SmartSort::Random, _) => "",
+        (_, true) => " DESC",
+        (_, false) => " ASC",
+    };
+
+    // LIMIT -1 表示不限制
+    let limit = rule.limit.map(|l| l as i64).unwrap_or(-1);
+    params.push(Value::Integer(limit));
+
+    let sql = format!(
+        "SELECT m.* FROM metadata m WHERE {} ORDER BY {}{} LIMIT ?",
+        condition, order, direction
+    );
+    (sql, params)
+}
+
+/// 递归构建 WHERE 条件
+fn build_condition(user_id: i64, rule: &SmartRule, params: &mut Vec<Value>) -> String {
+    match rule {
+        SmartRule::All(rules) => join_conditions(user_id, rules, " AND ", "1 = 1", params),
+        SmartRule::Any(rules) => join_conditions(user_id, rules, " OR ", "1 = 0", params),
+        SmartRule::Not(rule) => format!("NOT ({})", build_condition(user_id, rule, params)),
+        SmartRule::GenreContains(genre) => {
+            params.push(Value::Text(format!("%{}%", escape_like(genre))));
+            "m.genre LIKE ? ESCAPE '\\'".to_string()
+        }
+        SmartRule::YearRange { from, to } => {
+            let mut parts = Vec::new();
+            if let Some(from) = from {
+                params.push(Value::Integer(*from as i64));
+                parts.push("CAST(m.year AS INTEGER) >= ?");
+            }
+            if let Some(to) = to {
+                params.push(Value::Integer(*to as i64));
+                parts.push("CAST(m.year AS INTEGER) <= ?");
+            }
+            range_condition(parts)
+        }
+        SmartRule::ArtistIn(artists) => {
+            if artists.is_empty() {
+                return "1 = 0".to_string();
+            }
+            let vars = vec!["?"; artists.len()].join(", ");
+            for artist in artists.iter().chain(artists.iter()) {
+                params.push(Value::Text(artist.clone()));
+            }
+            format!(
+                "(m.artist IN ({vars}) OR m.id IN (SELECT ars.song_id FROM artist_song ars INNER JOIN artist ar ON ar.id = ars.artist_id WHERE ar.name IN ({vars})))"
+            )
+        }
+        SmartRule::PlayCountGt(count) => {
+            params.push(Value::Integer(user_id));
+            params.push(Value::Integer(*count as i64));
+            "(SELECT COUNT(*) FROM scrobble s WHERE s.song_id = m.id AND s.user_id = ? AND s.submission = 1) > ?".to_string()
+        }
+        SmartRule::NotPlayedInDays(days) => {
+            params.push(Value::Integer(user_id));
+            params.push(Value::Integer(now_millis() - *days as i64 * DAY_MILLIS));
+            "NOT EXISTS (SELECT 1 FROM scrobble s WHERE s.song_id = m.id AND s.user_id = ? AND s.submission = 1 AND s.timestamp >= ?)".to_string()
+        }
+        SmartRule::Starred => {
+            params.push(Value::Integer(user_id));
+            "EXISTS (SELECT 1 FROM user_favorite f WHERE f.item_id = m.id AND f.user_id = ? AND f.item_type = 'song')".to_string()
+        }
+        SmartRule::RatingAtLeast(rating) => {
+            params.push(Value::Integer(user_id));
+            params.push(Value::Integer(*rating as i64));
+            "EXISTS (SELECT 1 FROM user_rating r WHERE r.item_id = m.id AND r.user_id = ? AND r.item_type = 'song' AND r.rating >= ?)".to_string()
+        }
+        SmartRule::AddedInLastDays(days) => {
+            let since = chrono::Local::now() - chrono::Duration::days(*days as i64);
+            params.push(Value::Text(since.format("%Y-%m-%d %H:%M:%S").to_string()));
+            "m.created_at >= ?".to_string()
+        }
+        SmartRule::DurationRange { min, max } => {
+            let mut parts = Vec::new();
+            if let Some(min) = min {
+                params.push(Value::Real(*min));
+                parts.push("m.duration >= ?");
+            }
+            if let Some(max) = max {
+                params.push(Value::Real(*max));
+                parts.push("m.duration <= ?");
+            }
+            range_condition(parts)
+        }
+        SmartRule::BitrateRange { min, max } => {
+            let mut parts = Vec::new();
+            if let Some(min) = min {
+                params.push(Value::Integer(*min as i64));
+                parts.push("CAST(m.bitrate AS REAL) >= ?");
+            }
+            if let Some(max) = max {
+                params.push(Value::Integer(*max as i64));
+                parts.push("CAST(m.bitrate AS REAL) <= ?");
+            }
+            range_condition(parts)
+        }
+        SmartRule::BpmRange { min, max } => {
+            // 没有 BPM 的歌曲不匹配
+            let mut parts = Vec::new();
+            if let Some(min) = min {
+                params.push(Value::Real(*min));
+                parts.push("m.bpm >= ?");
+            }
+            if let Some(max) = max {
+                params.push(Value::Real(*max));
+                parts.push("m.bpm <= ?");
+            }
+            if !parts.is_empty() {
+                parts.push("m.bpm IS NOT NULL");
+            }
+            range_condition(parts)
+        }
+        SmartRule::KeyIn(keys) => {
+            if keys.is_empty() {
+                return "1 = 0".to_string();
+            }
+            let vars = vec!["?"; keys.len()].join(", ");
+            for key in keys {
+                params.push(Value::Text(Key::normalize(key)));
+            }
+            format!("m.musical_key IN ({vars})")
+        }
+    }
+}
+
+fn join_conditions(
+    user_id: i64,
+    rules: &[SmartRule],
+    separator: &str,
+    empty: &str,
+    params: &mut Vec<Value>,
+) -> String {
+    if rules.is_empty() {
+        return empty.to_string();
+    }
+    let parts: Vec<String> = rules
+        .iter()
+        .map(|r| format!("({})", build_condition(user_id, r, params)))
+        .collect();
+    parts.join(separator)
+}
+
+fn range_condition(parts: Vec<&str>) -> String {
+    if parts.is_empty() {
+        "1 = 1".to_string()
+    } else {
+        parts.join(" AND ")
+    }
+}
+
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn now_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_query() {
+        let rule: SmartPlaylistRule = serde_json::from_str(
+            r#"{
+                "rule": {"all": [
+                    {"genre_contains": "Rock"},
+                    {"year_range": {"from": 1990, "to": 1999}},
+                    {"any": ["starred", {"play_count_gt": 3}]}
+                ]},
+                "sort": "play_count",
+                "descending": true,
+                "limit": 50
+            }"#,
+        )
+        .unwrap();
+
+        let (sql, params) = build_query(1, &rule);
+        assert_eq!(sql.matches('?').count(), params.len());
+        assert!(sql.contains("m.genre LIKE ?"));
+        assert!(sql.ends_with("DESC LIMIT ?"));
+        assert_eq!(params[0], Value::Text("%Rock%".to_string()));
+        assert_eq!(params.last(), Some(&Value::Integer(50)));
+    }
+
+    #[test]
+    fn test_empty_rules() {
+        let mut params = Vec::new();
+        assert_eq!(build_condition(1, &SmartRule::All(vec![]), &mut params), "1 = 1");
+        assert_eq!(build_condition(1, &SmartRule::Any(vec![]), &mut params), "1 = 0");
+        assert_eq!(
+            build_condition(1, &SmartRule::ArtistIn(vec![]), &mut params),
+            "1 = 0"
+        );
+        assert!(params.is_empty());
+    }
+
+    #[test]
+    fn test_rating_rule_and_sort() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(&crate::datasource::local::table::sql()).unwrap();
+        conn.execute_batch(
+            "INSERT INTO metadata (id, file_name, file_path, file_url, title) VALUES
+                ('s1', '', '', '', 'A'), ('s2', '', '', '', 'B'), ('s3', '', '', '', 'C');
+             INSERT INTO user_rating VALUES
+                (1, 'song', 's1', 3, ''), (1, 'song', 's2', 5, ''), (1, 'song', 's3', 1, ''),
+                (2, 'song', 's3', 5, '');",
+        )
+        .unwrap();
+        let rule: SmartPlaylistRule =
+            serde_json::from_str(r#"{"rule": {"rating_at_least": 3}, "sort": "rating", "descending": true}"#)
+                .unwrap();
+
+        let (sql, params) = build_query(1, &rule);
+        let mut stmt = conn.prepare(&sql).unwrap();
+        let ids: Vec<String> = stmt
+            .query_map(rusqlite::params_from_iter(params), |row| row.get("id"))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(ids, vec!["s2", "s1"]);
+    }
+
+    #[test]
+    fn test_bpm_and_key_rules() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(&crate::datasource::local::table::sql()).unwrap();
+        conn.execute_batch(
+            "INSERT INTO metadata (id, file_name, file_path, file_url, title, bpm, musical_key, tempo_failed) VALUES
+                ('s1', '', '', '', 'A', 165, 'Am', 0), ('s2', '', '', '', 'B', 128, 'C', 0),
+                ('s3', '', '', '', 'C', NULL, NULL, 1), ('s4', '', '', '', 'D', NULL, NULL, 0);",
+        )
+        .unwrap();
+        let query = |json: &str| -> Vec<String> {
+            let rule: SmartPlaylistRule = serde_json::from_str(json).unwrap();
+            let (sql, params) = build_query(1, &rule);
+            let mut stmt = conn.prepare(&sql).unwrap();
+            let mut ids: Vec<String> = stmt
+                .query_map(rusqlite::params_from_iter(params), |row| row.get("id"))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(query(r#"{"rule": {"bpm_range": {"min": 160, "max": 175}}}"#), vec!["s1"]);
+        // 没有 BPM 或无法估计的歌曲不匹配
+        assert_eq!(query(r#"{"rule": {"bpm_range": {"max": 140}}}"#), vec!["s2"]);
+        assert_eq!(query(r#"{"rule": {"key_in": ["8A", "8B"]}}"#), vec!["s1", "s2"]);
+    }
+}
@@ -0,0 +1,550 @@
+// 收听统计
+// 所有统计都基于 scrobble 表 (只统计 submission = 1 的记录) 在 SQL 中计算, 通过 album_song / artist_song 关联到专辑和艺术家, 按用户隔离。时间参数和返回的时间戳均为毫秒, 按服务器本地时区划分日期。
+
+use anyhow::Result;
+use std::collections::HashMap;
+
+use rusqlite::{params, params_from_iter, Connection, ToSql};
+use serde::{Deserialize, Serialize};
+
+use super::service::{repeat_vars, ID_BATCH};
+use crate::datasource::types::{
+    HeatmapCell, ListeningSession, ListeningTime, MonthGenres, StatsItem, StatsItemType, StatsPeriod, Streaks,
+    TimeRange,
+};
+
+/// 每次播放对应到统计对象, 输出列: play_id, timestamp, duration, key, name, artist
+fn plays_cte(item_type: StatsItemType) -> &'static str {
+    match item_type {
+        StatsItemType::Song => {
+            "plays AS (
+                SELECT s.id AS play_id, s.timestamp, m.duration, m.id AS key, m.title AS name, m.artist
+                FROM scrobble s JOIN metadata m ON m.id = s.song_id
+                WHERE s.user_id = ?1 AND s.submission = 1
+            )"
+        }
+        StatsItemType::Album => {
+            "plays AS (
+                SELECT s.id AS play_id, s.timestamp, m.duration, CAST(a.id AS TEXT) AS key, a.name, a.artist
+                FROM scrobble s
+                JOIN album_song als ON als.song_id = s.song_id
+                JOIN album a ON a.id = als.album_id
+                LEFT JOIN metadata m ON m.id = s.song_id
+                WHERE s.user_id = ?1 AND s.submission = 1
+            )"
+        }
+        StatsItemType::Artist => {
+            "plays AS (
+                SELECT s.id AS play_id, s.timestamp, m.duration, CAST(ar.id AS TEXT) AS key, ar.name, NULL AS artist
+                FROM scrobble s
+                JOIN artist_song ars ON ars.song_id = s.song_id
+                JOIN artist ar ON ar.id = ars.artist_id
+                LEFT JOIN metadata m ON m.id = s.song_id
+                WHERE s.user_id = ?1 AND s.submission = 1
+            )"
+        }
+        // genre 为逗号分隔的字符串, 用递归 CTE 拆分
+        StatsItemType::Genre => {
+            "song_plays AS (
+                SELECT s.id AS play_id, s.timestamp, m.duration, m.genre || ',' AS rest
+                FROM scrobble s JOIN metadata m ON m.id = s.song_id
+                WHERE s.user_id = ?1 AND s.submission = 1
+            ),
+            split(play_id, timestamp, duration, genre, rest) AS (
+                SELECT play_id, timestamp, duration, '', rest FROM song_plays
+                UNION ALL
+                SELECT play_id, timestamp, duration,
+                    TRIM(substr(rest, 1, instr(rest, ',') - 1)),
+                    substr(rest, instr(rest, ',') + 1)
+                FROM split WHERE rest <> ''
+            ),
+            plays AS (
+                SELECT DISTINCT play_id, timestamp, duration, genre AS key, genre AS name, NULL AS artist
+                FROM split WHERE genre <> ''
+            )"
+        }
+    }
+}
+
+fn covert_row_to_stats_item(row: &rusqlite::Row) -> rusqlite::Result<StatsItem> {
+    Ok(StatsItem {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        artist: row.get(2)?,
+        plays: row.get(3)?,
+        seconds: row.get(4)?,
+        first_listened: row.get(5)?,
+        last_listened: row.get(6)?,
+    })
+}
+
+const ITEM_COLUMNS: &str = "key, name, artist, COUNT(DISTINCT play_id) AS plays,
+    COALESCE(SUM(duration), 0.0) AS seconds, MIN(timestamp) AS first, MAX(timestamp) AS last";
+
+/// 时间窗口内播放次数最多的歌曲 / 专辑 / 艺术家 / 风格
+pub fn top(
+    conn: &Connection,
+    user_id: i64,
+    item_type: StatsItemType,
+    range: TimeRange,
+    limit: u32,
+) -> Result<Vec<StatsItem>> {
+    let sql = format!(
+        "WITH RECURSIVE {} SELECT {} FROM plays
+        WHERE timestamp >= ?2 AND timestamp < ?3
+        GROUP BY key ORDER BY plays DESC, seconds DESC, last DESC, name LIMIT ?4",
+        plays_cte(item_type),
+        ITEM_COLUMNS
+    );
+    let (from, to) = range.bounds();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![user_id, from, to, limit], covert_row_to_stats_item)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// 第一次收听发生在时间窗口内的歌曲 / 专辑 / 艺术家 / 风格, 按首次收听时间倒序
+///
+/// 播放次数和时长统计的是全部历史
+pub fn first_listened(
+    conn: &Connection,
+    user_id: i64,
+    item_type: StatsItemType,
+    range: TimeRange,
+    limit: u32,
+) -> Result<Vec<StatsItem>> {
+    let sql = format!(
+        "WITH RECURSIVE {} SELECT {} FROM plays
+        GROUP BY key HAVING first >= ?2 AND first < ?3
+        ORDER BY first DESC LIMIT ?4",
+        plays_cte(item_type),
+        ITEM_COLUMNS
+    );
+    let (from, to) = range.bounds();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![user_id, from, to, limit], covert_row_to_stats_item)?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// 周期的标识: 日期 "2024-03-05", 周一的日期 "2024-03-04", 月份 "2024-03"
+fn bucket_sql(period: StatsPeriod) -> &'static str {
+    match period {
+        StatsPeriod::Day => "date(s.timestamp / 1000, 'unixepoch', 'localtime')",
+        StatsPeriod::Week => "date(s.timestamp / 1000, 'unixepoch', 'localtime', '-6 days', 'weekday 1')",
+        StatsPeriod::Month => "strftime('%Y-%m', s.timestamp / 1000, 'unixepoch', 'localtime')",
+    }
+}
+
+/// 按天 / 周 / 月统计收听时长
+pub fn listening_time(
+    conn: &Connection,
+    user_id: i64,
+    period: StatsPeriod,
+    range: TimeRange,
+) -> Result<Vec<ListeningTime>> {
+    let sql = format!(
+        "SELECT {} AS period, COUNT(*), COALESCE(SUM(m.duration), 0.0)
+        FROM scrobble s LEFT JOIN metadata m ON m.id = s.song_id
+        WHERE s.user_id = ?1 AND s.submission = 1 AND s.timestamp >= ?2 AND s.timestamp < ?3
+        GROUP BY period ORDER BY period",
+        bucket_sql(period)
+    );
+    let (from, to) = range.bounds();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![user_id, from, to], |row| {
+        Ok(ListeningTime {
+            period: row.get(0)?,
+            plays: row.get(1)?,
+            seconds: row.get(2)?,
+        })
+    })?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// 计算连续收听天数
+///
+/// # 参数
+/// * `today` - 当前日期 ("YYYY-MM-DD"), 最后一次收听是今天或昨天时当前连续天数才有效
+pub fn streaks(conn: &Connection, user_id: i64, today: &str) -> Result<Streaks> {
+    // 日期减去序号, 连续的日期得到相同的分组
+    let mut stmt = conn.prepare(
+        "WITH days AS (
+            SELECT DISTINCT date(timestamp / 1000, 'unixepoch', 'localtime') AS day
+            FROM scrobble WHERE user_id = ?1 AND submission = 1
+        ),
+        islands AS (
+            SELECT day, julianday(day) - ROW_NUMBER() OVER (ORDER BY day) AS grp FROM days
+        )
+        SELECT MIN(day), MAX(day), COUNT(*), julianday(?2) - julianday(MAX(day))
+        FROM islands GROUP BY grp ORDER BY MIN(day)",
+    )?;
+    let rows = stmt.query_map(params![user_id, today], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+            row.get::<_, f64>(3)?,
+        ))
+    })?;
+
+    let mut streaks = Streaks::default();
+    let mut last = None;
+    for row in rows {
+        let (start, end, length, age) = row?;
+        streaks.days += length;
+        // 长度相同时取最近的一次
+        if length >= streaks.longest {
+            streaks.longest = length;
+            streaks.longest_start = Some(start.clone());
+            streaks.longest_end = Some(end);
+        }
+        last = Some((start, length, age));
+    }
+    if let Some((start, length, age)) = last {
+        if (0.0..=1.0).contains(&age) {
+            streaks.current = length;
+            streaks.current_start = Some(start);
+        }
+    }
+    Ok(streaks)
+}
+
+/// 按星期和小时统计收听次数, 只返回有记录的格子
+pub fn heatmap(conn: &Connection, user_id: i64, range: TimeRange) -> Result<Vec<HeatmapCell>> {
+    let mut stmt = conn.prepare(
+        "SELECT
+            CAST(strftime('%w', s.timestamp / 1000, 'unixepoch', 'localtime') AS INTEGER) AS weekday,
+            CAST(strftime('%H', s.timestamp / 1000, 'unixepoch', 'localtime') AS INTEGER) AS hour,
+            COUNT(*), COALESCE(SUM(m.duration), 0.0)
+        FROM scrobble s LEFT JOIN metadata m ON m.id = s.song_id
+        WHERE s.user_id = ?1 AND s.submission = 1 AND s.timestamp >= ?2 AND s.timestamp < ?3
+        GROUP BY weekday, hour ORDER BY weekday, hour",
+    )?;
+    let (from, to) = range.bounds();
+    let rows = stmt.query_map(params![user_id, from, to], |row| {
+        Ok(HeatmapCell {
+            weekday: row.get(0)?,
+            hour: row.get(1)?,
+            plays: row.get(2)?,
+            seconds: row.get(3)?,
+        })
+    })?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// 每个月播放次数最多的 `per_month` 个风格, 用于观察风格随时间的变化
+pub fn genres_by_month(
+    conn: &Connection,
+    user_id: i64,
+    range: TimeRange,
+    per_month: u32,
+) -> Result<Vec<MonthGenres>> {
+    let sql = format!(
+        "WITH RECURSIVE {},
+        monthly AS (
+            SELECT strftime('%Y-%m', timestamp / 1000, 'unixepoch', 'localtime') AS month, {}
+            FROM plays WHERE timestamp >= ?2 AND timestamp < ?3
+            GROUP BY month, key
+        ),
+        ranked AS (
+            SELECT *, ROW_NUMBER() OVER (
+                PARTITION BY month ORDER BY plays DESC, seconds DESC, last DESC, name
+            ) AS pos
+            FROM monthly
+        )
+        SELECT key, name, artist, plays, seconds, first, last, month
+        FROM ranked WHERE pos <= ?4 ORDER BY month, pos",
+        plays_cte(StatsItemType::Genre),
+        ITEM_COLUMNS
+    );
+    let (from, to) = range.bounds();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![user_id, from, to, per_month], |row| {
+        Ok((row.get::<_, String>(7)?, covert_row_to_stats_item(row)?))
+    })?;
+
+    let mut months: Vec<MonthGenres> = Vec::new();
+    for row in rows {
+        let (month, item) = row?;
+        match months.last_mut() {
+            Some(last) if last.month == month => last.genres.push(item),
+            _ => months.push(MonthGenres {
+                month,
+                genres: vec![item],
+            }),
+        }
+    }
+    Ok(months)
+}
+
+/// 两次播放间隔超过该值时视为新的收听会话
+pub(crate) const SESSION_GAP_MILLIS: i64 = 30 * 60 * 1000;
+
+/// 收听时长最长的一次会话
+///
+/// 上一首歌结束 (播放时间 + 时长) 后 30 分钟内开始的播放属于同一会话
+pub fn longest_session(
+    conn: &Connection,
+    user_id: i64,
+    range: TimeRange,
+) -> Result<Option<ListeningSession>> {
+    let mut stmt = conn.prepare(
+        "WITH plays AS (
+            SELECT s.timestamp, COALESCE(m.duration, 0.0) AS duration
+            FROM scrobble s LEFT JOIN metadata m ON m.id = s.song_id
+            WHERE s.user_id = ?1 AND s.submission = 1 AND s.timestamp >= ?2 AND s.timestamp < ?3
+        ),
+        gaps AS (
+            SELECT timestamp, duration,
+                CASE WHEN timestamp - LAG(timestamp + CAST(duration * 1000 AS INTEGER))
+                    OVER (ORDER BY timestamp) <= ?4 THEN 0 ELSE 1 END AS new_session
+            FROM plays
+        ),
+        sessions AS (
+            SELECT timestamp, duration,
+                SUM(new_session) OVER (ORDER BY timestamp ROWS UNBOUNDED PRECEDING) AS session
+            FROM gaps
+        )
+        SELECT MIN(timestamp), MAX(timestamp), COUNT(*), SUM(duration) AS seconds
+        FROM sessions GROUP BY session ORDER BY seconds DESC, COUNT(*) DESC LIMIT 1",
+    )?;
+    let (from, to) = range.bounds();
+    let mut rows = stmt.query_map(params![user_id, from, to, SESSION_GAP_MILLIS], |row| {
+        Ok(ListeningSession {
+            start: row.get(0)?,
+            end: row.get(1)?,
+            plays: row.get(2)?,
+            seconds: row.get(3)?,
+        })
+    })?;
+    Ok(rows.next().transpose()?)
+}
+
+/// "正在播放" 之前多久的已播放记录也算作播放完成 (客户端可能提交开始播放的时间)
+const SKIP_TOLERANCE_MILLIS: i64 = 60 * 1000;
+
+/// 每首歌曲的播放统计, 参数 ?1 为用户 ID, song_filter 为对 `s.song_id` 的过滤条件
+///
+/// 跳过: 一条"正在播放" (submission = 0) 到下一条"正在播放"之间没有该歌曲的已播放记录。
+/// 最后一条"正在播放"可能还在播放中, 不计入跳过。
+/// 下一条"正在播放"通过 idx_scrobble_user_timestamp 查找, 过滤歌曲后不需要扫描用户的全部记录
+pub fn song_play_stats_sql(song_filter: &str) -> String {
+    format!(
+        "SELECT song_id,
+            SUM(submission = 1) AS play_count,
+            MAX(CASE WHEN submission = 1 THEN timestamp END) AS last_played,
+            SUM(skipped) AS skip_count
+        FROM (
+            SELECT sc.song_id, sc.submission, sc.timestamp,
+                sc.submission = 0 AND sc.next_now_playing IS NOT NULL AND NOT EXISTS (
+                    SELECT 1 FROM scrobble p
+                    WHERE p.user_id = sc.user_id AND p.song_id = sc.song_id AND p.submission = 1
+                        AND p.timestamp >= sc.timestamp - {} AND p.timestamp < sc.next_now_playing
+                ) AS skipped
+            FROM (
+                SELECT s.user_id, s.song_id, s.submission, s.timestamp,
+                    CASE WHEN s.submission = 0 THEN (
+                        SELECT MIN(n.timestamp) FROM scrobble n
+                        WHERE n.user_id = s.user_id AND n.submission = 0 AND n.timestamp > s.timestamp
+                    ) END AS next_now_playing
+                FROM scrobble s WHERE s.user_id = ?1 AND {}
+            ) sc
+        ) GROUP BY song_id",
+        SKIP_TOLERANCE_MILLIS, song_filter
+    )
+}
+
+/// 每张专辑的播放统计 (专辑中歌曲的统计之和), 参数 ?1 为用户 ID, song_filter 同 [`song_play_stats_sql`]
+pub fn album_play_stats_sql(song_filter: &str) -> String {
+    format!(
+        "SELECT als.album_id, SUM(ps.play_count) AS play_count,
+            MAX(ps.last_played) AS last_played, SUM(ps.skip_count) AS skip_count
+        FROM ({}) ps JOIN album_song als ON als.song_id = ps.song_id
+        GROUP BY als.album_id",
+        song_play_stats_sql(song_filter)
+    )
+}
+
+/// 播放次数、最后播放时间和跳过次数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayStats {
+    pub play_count: u32,
+    pub last_played: Option<i64>,
+    pub skip_count: u32,
+}
+
+/// 分批查询 ids 的播放统计, sql 根据 ID 数量生成, ?1 为用户 ID, 之后的参数为 ID
+fn query_play_stats<T: ToSql>(
+    conn: &Connection,
+    sql: impl Fn(usize) -> String,
+    user_id: i64,
+    ids: &[T],
+) -> Result<HashMap<String, PlayStats>> {
+    let mut stats = HashMap::new();
+    for ids in ids.chunks(ID_BATCH) {
+        let mut stmt = conn.prepare(&sql(ids.len()))?;
+        let params = std::iter::once(&user_id as &dyn ToSql).chain(ids.iter().map(|id| id as &dyn ToSql));
+        let rows = stmt.query_map(params_from_iter(params), |row| {
+            Ok((
+                row.get::<_, rusqlite::types::Value>(0)?,
+                PlayStats {
+                    play_count: row.get(1)?,
+                    last_played: row.get(2)?,
+                    skip_count: row.get(3)?,
+                },
+            ))
+        })?;
+        for row in rows {
+            let (id, play_stats) = row?;
+            let id = match id {
+                rusqlite::types::Value::Integer(id) => id.to_string(),
+                rusqlite::types::Value::Text(id) => id,
+                _ => continue,
+            };
+            stats.insert(id, play_stats);
+        }
+    }
+    Ok(stats)
+}
+
+/// 指定歌曲的播放统计, key 为歌曲 ID
+pub fn song_play_stats(conn: &Connection, user_id: i64, song_ids: &[String]) -> Result<HashMap<String, PlayStats>> {
+    let sql = |count| song_play_stats_sql(&format!("s.song_id IN ({})", repeat_vars(count)));
+    query_play_stats(conn, sql, user_id, song_ids)
+}
+
+/// 指定专辑的播放统计, key 为专辑 ID
+pub fn album_play_stats(conn: &Connection, user_id: i64, album_ids: &[i64]) -> Result<HashMap<String, PlayStats>> {
+    let sql = |count| {
+        album_play_stats_sql(&format!(
+            "s.song_id IN (SELECT song_id FROM album_song WHERE album_id IN ({}))",
+            repeat_vars(count)
+        ))
+    };
+    query_play_stats(conn, sql, user_id, album_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+    // 2024-03-04 12:00 UTC, 周一
+    const BASE: i64 = 1_709_553_600_000;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&crate::datasource::local::table::sql()).unwrap();
+        conn.execute_batch(
+            "INSERT INTO metadata (id, file_name, file_path, file_url, title, artist, duration, genre)
+                VALUES ('s1', '', '', '', 'So What', 'Miles Davis', 540, 'Jazz, Modal'),
+                       ('s2', '', '', '', '晴天', '周杰伦', 270, 'Pop,Pop');
+             INSERT INTO album (id, name, artist) VALUES (1, 'Kind of Blue', 'Miles Davis'), (2, '叶惠美', '周杰伦');
+             INSERT INTO album_song VALUES (1, 's1', '', '', ''), (2, 's2', '', '', '');
+             INSERT INTO artist (id, name, cover, description) VALUES (1, 'Miles Davis', '', ''), (2, '周杰伦', '', '');
+             INSERT INTO artist_song VALUES (1, 's1'), (2, 's2');",
+        )
+        .unwrap();
+        let plays = [
+            (1, "s1", 0),
+            (1, "s2", 0),
+            (1, "s2", 1),
+            (1, "s2", 2),
+            (1, "s1", 5),
+            // 其他用户和正在播放的记录不计入
+            (2, "s1", 1),
+        ];
+        for (user_id, song_id, day) in plays {
+            conn.execute(
+                "INSERT INTO scrobble (user_id, song_id, submission, timestamp, created_at) VALUES (?1, ?2, 1, ?3, '')",
+                params![user_id, song_id, BASE + day * DAY_MILLIS],
+            )
+            .unwrap();
+        }
+        conn.execute(
+            "INSERT INTO scrobble (user_id, song_id, submission, timestamp, created_at) VALUES (1, 's1', 0, ?1, '')",
+            params![BASE],
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_top_and_first_listened() {
+        let conn = setup();
+        let all = TimeRange::default();
+
+        let songs = top(&conn, 1, StatsItemType::Song, all, 10).unwrap();
+        assert_eq!(songs.len(), 2);
+        assert_eq!((songs[0].id.as_str(), songs[0].plays), ("s2", 3));
+        assert_eq!(songs[1].seconds, 1080.0);
+
+        let albums = top(&conn, 1, StatsItemType::Album, all, 1).unwrap();
+        assert_eq!((albums[0].name.as_str(), albums[0].plays), ("叶惠美", 3));
+
+        let window = TimeRange { from: Some(BASE + 3 * DAY_MILLIS), to: None };
+        let artists = top(&conn, 1, StatsItemType::Artist, window, 10).unwrap();
+        assert_eq!(artists.len(), 1);
+        assert_eq!(artists[0].name, "Miles Davis");
+
+        let genres = top(&conn, 1, StatsItemType::Genre, all, 10).unwrap();
+        let genres: Vec<_> = genres.iter().map(|g| (g.name.as_str(), g.plays)).collect();
+        assert_eq!(genres, vec![("Pop", 3), ("Jazz", 2), ("Modal", 2)]);
+
+        let window = TimeRange { from: Some(BASE), to: Some(BASE + 1) };
+        let first = first_listened(&conn, 1, StatsItemType::Song, window, 10).unwrap();
+        assert_eq!(first.len(), 2);
+        let window = TimeRange { from: Some(BASE + DAY_MILLIS), to: None };
+        assert!(first_listened(&conn, 1, StatsItemType::Song, window, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_listening_time_and_streaks() {
+        let conn = setup();
+        let days = listening_time(&conn, 1, StatsPeriod::Day, TimeRange::default()).unwrap();
+        assert_eq!(days.len(), 4);
+        assert_eq!(days[0].plays, 2);
+        assert_eq!(days[0].seconds, 810.0);
+
+        let weeks = listening_time(&conn, 1, StatsPeriod::Week, TimeRange::default()).unwrap();
+        assert_eq!(weeks.iter().map(|w| w.plays).sum::<i64>(), 5);
+
+        let streaks = streaks(&conn, 1, "2024-03-10").unwrap();
+        assert_eq!(streaks.longest, 3);
+        assert_eq!(streaks.current, 1);
+        assert_eq!(streaks.days, 4);
+        assert_eq!(streaks.current_start.as_deref(), Some("2024-03-09"));
+
+        let cells = heatmap(&conn, 1, TimeRange::default()).unwrap();
+        assert_eq!(cells.iter().map(|c| c.plays).sum::<i64>(), 5);
+    }
+
+    #[test]
+    fn test_play_stats() {
+        let conn = setup();
+        // s2 开始播放后切到 s1 (跳过), s1 播放完成, 最后一条正在播放的 s2 不算跳过
+        let day = BASE + 10 * DAY_MILLIS;
+        for (song_id, submission, offset) in
+            [("s2", 0, 0), ("s1", 0, 20_000), ("s1", 1, 500_000), ("s2", 0, 560_000)]
+        {
+            conn.execute(
+                "INSERT INTO scrobble (user_id, song_id, submission, timestamp, created_at) VALUES (1, ?1, ?2, ?3, '')",
+                params![song_id, submission, day + offset],
+            )
+            .unwrap();
+        }
+
+        let songs = song_play_stats(&conn, 1, &["s1".to_string(), "s2".to_string()]).unwrap();
+        assert_eq!(songs["s1"], PlayStats { play_count: 3, last_played: Some(day + 500_000), skip_count: 0 });
+        assert_eq!(songs["s2"].play_count, 3);
+        assert_eq!(songs["s2"].skip_count, 1);
+
+        let albums = album_play_stats(&conn, 1, &[1, 2]).unwrap();
+        assert_eq!(albums["2"].skip_count, 1);
+        assert_eq!(albums["1"].play_count, 3);
+
+        // 只查询 s2 时, 下一条"正在播放"仍然是 s1 的记录
+        let songs = song_play_stats(&conn, 1, &["s2".to_string()]).unwrap();
+        assert_eq!(songs.len(), 1);
+        assert_eq!(songs["s2"].skip_count, 1);
+    }
+}
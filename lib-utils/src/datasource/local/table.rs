@@ -91,10 +91,109 @@ pub fn migrate() -> Result<()> {
         conn.pragma_update(None, "user_version", 2)?;
     }
 
+    // 版本 2 -> 版本 3: 智能歌单规则 song_list.smart_rule, 歌曲添加时间 metadata.created_at
+    if version < 3 {
+        if !has_column(&conn, "song_list", "smart_rule")? {
+            conn.execute("ALTER TABLE song_list ADD COLUMN smart_rule TEXT NOT NULL DEFAULT ''", [])?;
+        }
+
+        if !has_column(&conn, "metadata", "created_at")? {
+            conn.execute("ALTER TABLE metadata ADD COLUMN created_at TEXT NOT NULL DEFAULT ''", [])?;
+
+            // 为现有歌曲填充添加时间 (使用当前时间)
+            let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+            conn.execute("UPDATE metadata SET created_at = ? WHERE created_at = ''", [&now])?;
+        }
+
+        conn.pragma_update(None, "user_version", 3)?;
+    }
+
+    // 版本 3 -> 版本 4: 歌单更新时间 song_list.updated_at, 公开标记 song_list.public
+    if version < 4 {
+        if !has_column(&conn, "song_list", "updated_at")? {
+            conn.execute("ALTER TABLE song_list ADD COLUMN updated_at TEXT NOT NULL DEFAULT ''", [])?;
+            conn.execute("UPDATE song_list SET updated_at = created_at WHERE updated_at = ''", [])?;
+        }
+
+        if !has_column(&conn, "song_list", "public")? {
+            conn.execute("ALTER TABLE song_list ADD COLUMN public INTEGER NOT NULL DEFAULT 0", [])?;
+        }
+
+        conn.pragma_update(None, "user_version", 4)?;
+    }
+
+    // 版本 4 -> 版本 5: 每日推荐歌单 song_list.mix_key, song_list.mix_date
+    if version < 5 {
+        if !has_column(&conn, "song_list", "mix_key")? {
+            conn.execute("ALTER TABLE song_list ADD COLUMN mix_key TEXT NOT NULL DEFAULT ''", [])?;
+        }
+
+        if !has_column(&conn, "song_list", "mix_date")? {
+            conn.execute("ALTER TABLE song_list ADD COLUMN mix_date TEXT NOT NULL DEFAULT ''", [])?;
+        }
+
+        conn.pragma_update(None, "user_version", 5)?;
+    }
+
+    // 版本 5 -> 版本 6: 响度归一化 metadata.track_gain 等, album.gain, album.peak, 分析失败标记 metadata.loudness_failed
+    if version < 6 {
+        for column in ["track_gain", "track_peak", "album_gain", "album_peak"] {
+            if !has_column(&conn, "metadata", column)? {
+                conn.execute(&format!("ALTER TABLE metadata ADD COLUMN {} REAL", column), [])?;
+            }
+        }
+        if !has_column(&conn, "metadata", "loudness_failed")? {
+            conn.execute("ALTER TABLE metadata ADD COLUMN loudness_failed INTEGER NOT NULL DEFAULT 0", [])?;
+        }
+
+        for column in ["gain", "peak"] {
+            if !has_column(&conn, "album", column)? {
+                conn.execute(&format!("ALTER TABLE album ADD COLUMN {} REAL", column), [])?;
+            }
+        }
+
+        conn.pragma_update(None, "user_version", 6)?;
+    }
+
+    // 版本 6 -> 版本 7: 节奏和调性 metadata.bpm, metadata.musical_key, 分析失败标记 metadata.tempo_failed
+    if version < 7 {
+        if !has_column(&conn, "metadata", "bpm")? {
+            conn.execute("ALTER TABLE metadata ADD COLUMN bpm REAL", [])?;
+        }
+        if !has_column(&conn, "metadata", "musical_key")? {
+            conn.execute("ALTER TABLE metadata ADD COLUMN musical_key TEXT", [])?;
+        }
+        if !has_column(&conn, "metadata", "tempo_failed")? {
+            conn.execute("ALTER TABLE metadata ADD COLUMN tempo_failed INTEGER NOT NULL DEFAULT 0", [])?;
+        }
+
+        conn.pragma_update(None, "user_version", 7)?;
+    }
+
+    // 版本 7 -> 版本 8: Subsonic 镜像保存评分 subsonic_song.rating, subsonic_album.rating
+    if version < 8 {
+        for table in ["subsonic_song", "subsonic_album"] {
+            if !has_column(&conn, table, "rating")? {
+                conn.execute(&format!("ALTER TABLE {} ADD COLUMN rating INTEGER", table), [])?;
+            }
+        }
+
+        conn.pragma_update(None, "user_version", 8)?;
+    }
+
     Ok(())
 }
 
-fn sql() -> String {
+/// 检查表中是否存在指定字段
+fn has_column(conn: &rusqlite::Connection, table: &str, column: &str) -> Result<bool> {
+    conn.prepare("SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?")?
+        .query_row([table, column], |row| {
+            let count: i32 = row.get(0)?;
+            Ok(count > 0)
+        })
+}
+
+pub(crate) fn sql() -> String {
     return r#"
   BEGIN TRANSACTION;
 
@@ -115,7 +214,16 @@ fn sql() -> String {
       genre TEXT NOT NULL DEFAULT '未知风格',
       track TEXT NOT NULL DEFAULT '未知曲目',
       disc TEXT NOT NULL DEFAULT '未知碟片',
-      comment TEXT NOT NULL DEFAULT ''
+      comment TEXT NOT NULL DEFAULT '',
+      created_at TEXT NOT NULL DEFAULT '',
+      track_gain REAL,
+      track_peak REAL,
+      album_gain REAL,
+      album_peak REAL,
+      loudness_failed INTEGER NOT NULL DEFAULT 0,
+      bpm REAL,
+      musical_key TEXT,
+      tempo_failed INTEGER NOT NULL DEFAULT 0
     );
   
   CREATE TABLE
@@ -146,7 +254,20 @@ fn sql() -> String {
       name TEXT NOT NULL DEFAULT '',
       description TEXT NOT NULL DEFAULT '',
       cover TEXT NOT NULL DEFAULT '',
-      created_at TEXT NOT NULL
+      created_at TEXT NOT NULL,
+      smart_rule TEXT NOT NULL DEFAULT '',
+      updated_at TEXT NOT NULL DEFAULT '',
+      public INTEGER NOT NULL DEFAULT 0,
+      mix_key TEXT NOT NULL DEFAULT '',
+      mix_date TEXT NOT NULL DEFAULT ''
+    );
+  
+  CREATE TABLE
+    IF NOT EXISTS song_list_subscribe (
+      user_id INTEGER NOT NULL,
+      song_list_id INTEGER NOT NULL,
+      created_at TEXT NOT NULL,
+      PRIMARY KEY (user_id, song_list_id)
     );
   
   CREATE TABLE
@@ -178,8 +299,21 @@ fn sql() -> String {
   CREATE TABLE
     IF NOT EXISTS user_favorite (
       user_id INTEGER NOT NULL,
-      song_id TEXT NOT NULL,
-      created_at TEXT NOT NULL
+      item_id TEXT NOT NULL,
+      item_type TEXT NOT NULL DEFAULT 'song',
+      created_at TEXT NOT NULL,
+      PRIMARY KEY (user_id, item_id, item_type)
+    );
+
+  -- 用户评分, item_type 为 song / album, rating 为 1-5
+  CREATE TABLE
+    IF NOT EXISTS user_rating (
+      user_id INTEGER NOT NULL,
+      item_type TEXT NOT NULL,
+      item_id TEXT NOT NULL,
+      rating INTEGER NOT NULL,
+      updated_at TEXT NOT NULL,
+      PRIMARY KEY (user_id, item_type, item_id)
     );
   
   CREATE TABLE
@@ -200,7 +334,9 @@ fn sql() -> String {
       description TEXT NOT NULL DEFAULT '',
       year TEXT NOT NULL DEFAULT '',
       artist TEXT NOT NULL DEFAULT '',
-      created_at TEXT NOT NULL DEFAULT ''
+      created_at TEXT NOT NULL DEFAULT '',
+      gain REAL,
+      peak REAL
     );
   
   CREATE TABLE
@@ -234,6 +370,162 @@ fn sql() -> String {
   CREATE INDEX IF NOT EXISTS idx_scrobble_song_id ON scrobble(song_id);
   CREATE INDEX IF NOT EXISTS idx_scrobble_album_id ON scrobble(album_id);
   CREATE INDEX IF NOT EXISTS idx_scrobble_timestamp ON scrobble(timestamp DESC);
+  CREATE INDEX IF NOT EXISTS idx_scrobble_user_timestamp ON scrobble(user_id, timestamp);
+
+  -- Subsonic 音乐库镜像, server 区分不同的 Subsonic 服务器
+  CREATE TABLE
+    IF NOT EXISTS subsonic_artist (
+      server TEXT NOT NULL,
+      id TEXT NOT NULL,
+      name TEXT NOT NULL,
+      album_count INTEGER NOT NULL DEFAULT 0,
+      cover_art TEXT,
+      starred INTEGER NOT NULL DEFAULT 0,
+      PRIMARY KEY (server, id)
+    );
+
+  CREATE TABLE
+    IF NOT EXISTS subsonic_album (
+      server TEXT NOT NULL,
+      id TEXT NOT NULL,
+      name TEXT NOT NULL,
+      artist TEXT NOT NULL DEFAULT '',
+      artist_id TEXT,
+      year TEXT NOT NULL DEFAULT '',
+      genre TEXT NOT NULL DEFAULT '',
+      cover_art TEXT,
+      song_count INTEGER NOT NULL DEFAULT 0,
+      duration INTEGER NOT NULL DEFAULT 0,
+      created TEXT NOT NULL DEFAULT '',
+      starred INTEGER NOT NULL DEFAULT 0,
+      rating INTEGER,
+      PRIMARY KEY (server, id)
+    );
+
+  CREATE TABLE
+    IF NOT EXISTS subsonic_song (
+      server TEXT NOT NULL,
+      id TEXT NOT NULL,
+      title TEXT NOT NULL,
+      artist TEXT NOT NULL DEFAULT '',
+      artist_id TEXT,
+      album TEXT NOT NULL DEFAULT '',
+      album_id TEXT,
+      year TEXT NOT NULL DEFAULT '',
+      genre TEXT NOT NULL DEFAULT '',
+      track TEXT NOT NULL DEFAULT '',
+      duration REAL NOT NULL DEFAULT 0,
+      bitrate TEXT NOT NULL DEFAULT '',
+      suffix TEXT,
+      path TEXT,
+      cover_art TEXT,
+      starred INTEGER NOT NULL DEFAULT 0,
+      rating INTEGER,
+      PRIMARY KEY (server, id)
+    );
+
+  CREATE INDEX IF NOT EXISTS idx_subsonic_song_album ON subsonic_song(server, album_id);
+
+  CREATE TABLE
+    IF NOT EXISTS subsonic_genre (
+      server TEXT NOT NULL,
+      value TEXT NOT NULL,
+      album_count INTEGER NOT NULL DEFAULT 0,
+      song_count INTEGER NOT NULL DEFAULT 0,
+      PRIMARY KEY (server, value)
+    );
+
+  CREATE TABLE
+    IF NOT EXISTS subsonic_playlist (
+      server TEXT NOT NULL,
+      id TEXT NOT NULL,
+      name TEXT NOT NULL,
+      comment TEXT,
+      owner TEXT,
+      public INTEGER,
+      song_count INTEGER NOT NULL DEFAULT 0,
+      duration INTEGER,
+      created TEXT,
+      changed TEXT,
+      cover_art TEXT,
+      PRIMARY KEY (server, id)
+    );
+
+  CREATE TABLE
+    IF NOT EXISTS subsonic_playlist_song (
+      server TEXT NOT NULL,
+      playlist_id TEXT NOT NULL,
+      song_id TEXT NOT NULL,
+      order_num INTEGER NOT NULL
+    );
+
+  CREATE TABLE
+    IF NOT EXISTS subsonic_sync_state (
+      server TEXT NOT NULL,
+      key TEXT NOT NULL,
+      value TEXT NOT NULL,
+      PRIMARY KEY (server, key)
+    );
+
+  -- Subsonic 离线下载, item_type 为 song / album / playlist
+  CREATE TABLE
+    IF NOT EXISTS subsonic_pin (
+      server TEXT NOT NULL,
+      item_type TEXT NOT NULL,
+      item_id TEXT NOT NULL,
+      created_at TEXT NOT NULL,
+      PRIMARY KEY (server, item_type, item_id)
+    );
+
+  CREATE TABLE
+    IF NOT EXISTS subsonic_pin_song (
+      server TEXT NOT NULL,
+      item_type TEXT NOT NULL,
+      item_id TEXT NOT NULL,
+      song_id TEXT NOT NULL
+    );
+
+  -- status: 0 等待下载, 1 已下载, 2 下载失败
+  CREATE TABLE
+    IF NOT EXISTS subsonic_pin_file (
+      server TEXT NOT NULL,
+      song_id TEXT NOT NULL,
+      file_name TEXT NOT NULL,
+      status INTEGER NOT NULL DEFAULT 0,
+      size INTEGER NOT NULL DEFAULT 0,
+      error TEXT,
+      updated_at TEXT NOT NULL,
+      PRIMARY KEY (server, song_id)
+    );
+
+  -- WebDAV 文件索引, version 为 ETag (或大小和修改时间), 用于判断文件是否变化
+  CREATE TABLE
+    IF NOT EXISTS webdav_file (
+      path TEXT PRIMARY KEY,
+      version TEXT NOT NULL,
+      song_id TEXT NOT NULL,
+      updated_at TEXT NOT NULL
+    );
+
+  -- 声学指纹, data 为每帧 32 位子指纹 (小端序); 无法计算时 failed 为 1, data 为空;
+  -- bitrate 为按文件大小估算的平均码率 (kbps)
+  CREATE TABLE
+    IF NOT EXISTS fingerprint (
+      song_id TEXT PRIMARY KEY,
+      data BLOB NOT NULL,
+      format TEXT NOT NULL,
+      bitrate INTEGER NOT NULL DEFAULT 0,
+      samplerate INTEGER NOT NULL DEFAULT 0,
+      failed INTEGER NOT NULL DEFAULT 0,
+      created_at TEXT NOT NULL
+    );
+
+  -- 从音乐库移除的文件 (如重复歌曲的副本), 扫描时跳过
+  CREATE TABLE
+    IF NOT EXISTS excluded_file (
+      file_path TEXT PRIMARY KEY,
+      created_at TEXT NOT NULL
+    );
 
   COMMIT;
   "#
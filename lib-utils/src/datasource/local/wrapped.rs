@@ -0,0 +1,205 @@
+// 年度报告
+// 基于收听统计汇总一年的数据, 并生成可分享的 PNG 卡片
+
+use std::io::Cursor;
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine};
+use chrono::{Local, TimeZone};
+use image::{DynamicImage, Rgba, RgbaImage};
+use rusqlite::{params, Connection};
+
+use super::stats;
+use crate::datasource::types::{StatsItem, StatsItemType, StatsPeriod, TimeRange, Wrapped};
+use crate::image::{draw_text, fill_rect, text_width};
+
+/// 各排行榜的数量
+const TOP_COUNT: u32 = 5;
+
+/// 一年的时间窗口 (本地时区)
+pub fn year_range(year: i32) -> Result<TimeRange> {
+    let start = |year| {
+        Local
+            .with_ymd_and_hms(year, 1, 1, 0, 0, 0)
+            .earliest()
+            .map(|t| t.timestamp_millis())
+            .ok_or_else(|| anyhow!("Invalid year: {}", year))
+    };
+    Ok(TimeRange {
+        from: Some(start(year)?),
+        to: Some(start(year + 1)?),
+    })
+}
+
+/// 生成用户的年度报告
+pub fn build(conn: &Connection, user_id: i64, year: i32) -> Result<Wrapped> {
+    let range = year_range(year)?;
+    let top = |item_type| stats::top(conn, user_id, item_type, range, TOP_COUNT);
+
+    let monthly = stats::listening_time(conn, user_id, StatsPeriod::Month, range)?;
+    let most_played_day = stats::listening_time(conn, user_id, StatsPeriod::Day, range)?
+        .into_iter()
+        .max_by(|a, b| a.plays.cmp(&b.plays).then(b.period.cmp(&a.period)));
+
+    let mut new_artists =
+        stats::first_listened(conn, user_id, StatsItemType::Artist, range, u32::MAX)?;
+    let new_artist_count = new_artists.len();
+    new_artists.sort_by_key(|a| std::cmp::Reverse(a.plays));
+    new_artists.truncate(TOP_COUNT as usize);
+
+    Ok(Wrapped {
+        year,
+        total_plays: monthly.iter().map(|m| m.plays).sum(),
+        total_minutes: (monthly.iter().map(|m| m.seconds).sum::<f64>() / 60.0).round() as i64,
+        top_songs: top(StatsItemType::Song)?,
+        top_albums: top(StatsItemType::Album)?,
+        top_artists: top(StatsItemType::Artist)?,
+        top_genres: top(StatsItemType::Genre)?,
+        new_artist_count,
+        new_artists,
+        most_played_day,
+        monthly,
+        genre_shift: stats::genres_by_month(conn, user_id, range, 3)?,
+        longest_session: stats::longest_session(conn, user_id, range)?,
+    })
+}
+
+/// 读取专辑封面 (cover 表中的中等尺寸), 没有封面的专辑跳过
+pub fn album_covers(conn: &Connection, albums: &[StatsItem]) -> Result<Vec<Vec<u8>>> {
+    let mut stmt = conn.prepare(
+        "SELECT base64 FROM cover WHERE type = 'album' AND link_id = ?1 AND size = 'medium' LIMIT 1",
+    )?;
+    let mut covers = Vec::new();
+    for album in albums {
+        let Ok(album_id) = album.id.parse::<i64>() else {
+            continue;
+        };
+        let cover: Option<String> = stmt
+            .query_row(params![album_id], |row| row.get(0))
+            .ok();
+        if let Some(bytes) = cover.and_then(|c| general_purpose::STANDARD.decode(c).ok()) {
+            covers.push(bytes);
+        }
+    }
+    Ok(covers)
+}
+
+const CARD_WIDTH: u32 = 1080;
+const CARD_HEIGHT: u32 = 1350;
+const TEXT_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const LABEL_COLOR: Rgba<u8> = Rgba([165, 180, 252, 255]);
+const ACCENT_COLOR: Rgba<u8> = Rgba([34, 211, 238, 255]);
+
+/// 生成年度报告卡片 (PNG)
+///
+/// 包含前五张专辑封面、总收听时长等数字和每月收听时长柱状图。
+/// 内置点阵字体只支持英文和数字, 歌曲和艺术家名称不绘制在卡片上
+pub fn render_card(wrapped: &Wrapped, covers: &[Vec<u8>]) -> Result<Vec<u8>> {
+    // 背景: 从深紫到深蓝的渐变
+    let mut canvas = RgbaImage::from_fn(CARD_WIDTH, CARD_HEIGHT, |_, y| {
+        let t = y as f32 / CARD_HEIGHT as f32;
+        let mix = |a: f32, b: f32| (a + (b - a) * t) as u8;
+        Rgba([mix(49.0, 15.0), mix(27.0, 23.0), mix(75.0, 42.0), 255])
+    });
+
+    let title = format!("{} WRAPPED", wrapped.year);
+    draw_text(&mut canvas, &title, (CARD_WIDTH - text_width(&title, 10)) / 2, 60, 10, TEXT_COLOR);
+
+    // 前五张专辑封面
+    let images: Vec<DynamicImage> = covers
+        .iter()
+        .filter_map(|c| image::load_from_memory(c).ok())
+        .take(TOP_COUNT as usize)
+        .collect();
+    let (size, gap) = (180, 24);
+    let row_width = images.len() as u32 * (size + gap);
+    let mut x = (CARD_WIDTH + gap).saturating_sub(row_width) / 2;
+    for img in &images {
+        let tile = img.resize_to_fill(size, size, image::imageops::FilterType::Triangle);
+        image::imageops::overlay(&mut canvas, &tile.to_rgba8(), x as i64, 190);
+        x += size + gap;
+    }
+
+    // 数字
+    let mut lines = vec![
+        ("MINUTES", wrapped.total_minutes.to_string()),
+        ("PLAYS", wrapped.total_plays.to_string()),
+        ("NEW ARTISTS", wrapped.new_artist_count.to_string()),
+    ];
+    if let Some(day) = &wrapped.most_played_day {
+        lines.push(("TOP DAY", day.period.clone()));
+    }
+    if let Some(session) = &wrapped.longest_session {
+        lines.push(("LONGEST SESSION", format!("{} MIN", (session.seconds / 60.0).round())));
+    }
+    for (i, (label, value)) in lines.iter().enumerate() {
+        let y = 440 + i as u32 * 80;
+        draw_text(&mut canvas, label, 60, y + 8, 4, LABEL_COLOR);
+        draw_text(&mut canvas, value, CARD_WIDTH - 60 - text_width(value, 6), y, 6, TEXT_COLOR);
+    }
+
+    // 每月收听时长
+    let mut minutes = [0f64; 12];
+    for month in &wrapped.monthly {
+        if let Some(index) = month.period.get(5..7).and_then(|m| m.parse::<usize>().ok()) {
+            if (1..=12).contains(&index) {
+                minutes[index - 1] = month.seconds / 60.0;
+            }
+        }
+    }
+    let max = minutes.iter().cloned().fold(1.0, f64::max);
+    let (bar_width, bar_gap, bottom, max_height) = (60, 24, 1240, 280);
+    draw_text(&mut canvas, "MINUTES PER MONTH", 48, bottom - max_height - 60, 3, LABEL_COLOR);
+    for (i, value) in minutes.iter().enumerate() {
+        let x = 48 + i as u32 * (bar_width + bar_gap);
+        let height = ((value / max) * max_height as f64).round().max(4.0) as u32;
+        fill_rect(&mut canvas, x, bottom - height, bar_width, height, ACCENT_COLOR);
+        let label = (i + 1).to_string();
+        let label_x = x + (bar_width - text_width(&label, 3)) / 2;
+        draw_text(&mut canvas, &label, label_x, bottom + 16, 3, LABEL_COLOR);
+    }
+
+    let mut buf = Vec::new();
+    DynamicImage::ImageRgba8(canvas).write_to(&mut Cursor::new(&mut buf), image::ImageFormat::Png)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_and_render() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&crate::datasource::local::table::sql()).unwrap();
+        conn.execute_batch(
+            "INSERT INTO metadata (id, file_name, file_path, file_url, title, artist, duration, genre)
+                VALUES ('s1', '', '', '', 'So What', 'Miles Davis', 600, 'Jazz');
+             INSERT INTO artist (id, name, cover, description) VALUES (1, 'Miles Davis', '', '');
+             INSERT INTO artist_song VALUES (1, 's1');",
+        )
+        .unwrap();
+        let range = year_range(2024).unwrap();
+        let start = range.from.unwrap() + 10 * 60 * 60 * 1000;
+        // 同一会话中的三次播放, 另一年的播放不计入
+        for timestamp in [start, start + 600_000, start + 1_200_000, range.to.unwrap() + 1] {
+            conn.execute(
+                "INSERT INTO scrobble (user_id, song_id, submission, timestamp, created_at) VALUES (1, 's1', 1, ?1, '')",
+                params![timestamp],
+            )
+            .unwrap();
+        }
+
+        let wrapped = build(&conn, 1, 2024).unwrap();
+        assert_eq!(wrapped.total_plays, 3);
+        assert_eq!(wrapped.total_minutes, 30);
+        assert_eq!(wrapped.new_artist_count, 1);
+        assert_eq!(wrapped.most_played_day.as_ref().unwrap().plays, 3);
+        assert_eq!(wrapped.genre_shift[0].genres[0].name, "Jazz");
+        assert_eq!(wrapped.longest_session.as_ref().unwrap().plays, 3);
+
+        let png = render_card(&wrapped, &[]).unwrap();
+        let card = image::load_from_memory(&png).unwrap();
+        assert_eq!((card.width(), card.height()), (CARD_WIDTH, CARD_HEIGHT));
+    }
+}
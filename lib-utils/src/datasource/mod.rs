@@ -1,13 +1,19 @@
 // 数据源抽象层模块
-// 提供统一的接口来访问不同的音乐数据源(本地文件或Subsonic服务器)
+// 提供统一的接口来访问不同的音乐数据源(本地文件、Subsonic 或 Jellyfin 服务器)
 
+pub mod error;
 pub mod trait_def;
 pub mod types;
 pub mod local;
 pub mod subsonic;
+pub mod jellyfin;
+pub mod webdav;
+pub mod aggregate;
 pub mod factory;
+pub mod playlist_io;
 
 // 重新导出核心类型和trait
+pub use error::DataSourceError;
 pub use trait_def::MusicDataSource;
 pub use types::{
     UnifiedMetadata, DataSourceType, AudioStream, MetadataFilter,
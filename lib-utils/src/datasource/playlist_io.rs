@@ -0,0 +1,730 @@
+// 歌单导入导出
+// 支持扩展 M3U8 (#EXTINF) 和 XSPF 两种格式, 通过 MusicDataSource 读写, 与具体数据源无关
+
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+use super::trait_def::MusicDataSource;
+use super::types::*;
+
+/// 本地模式下静态音乐文件的映射路径(见 server 中的 music_path)
+const LOCAL_MUSIC_PREFIX: &str = "/music/";
+
+/// 模糊匹配的最低得分
+const MATCH_THRESHOLD: f64 = 0.75;
+
+/// 歌单文件格式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistFormat {
+    /// 扩展 M3U (UTF-8)
+    M3u8,
+    /// XML Shareable Playlist Format
+    Xspf,
+}
+
+impl PlaylistFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "m3u" | "m3u8" => Some(PlaylistFormat::M3u8),
+            "xspf" => Some(PlaylistFormat::Xspf),
+            _ => None,
+        }
+    }
+
+    /// 根据内容猜测格式
+    pub fn detect(content: &str) -> Self {
+        let head = content.trim_start_matches('\u{feff}').trim_start();
+        if head.starts_with("<?xml") || head.starts_with("<playlist") {
+            PlaylistFormat::Xspf
+        } else {
+            PlaylistFormat::M3u8
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u8 => "audio/x-mpegurl; charset=utf-8",
+            PlaylistFormat::Xspf => "application/xspf+xml; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u8 => "m3u8",
+            PlaylistFormat::Xspf => "xspf",
+        }
+    }
+}
+
+/// 导出时歌曲位置的写法
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PathMode {
+    /// 相对音乐库根目录的路径
+    #[default]
+    Relative,
+    /// 文件绝对路径
+    Absolute,
+    /// 可直接播放的流地址
+    Stream,
+}
+
+impl PathMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "relative" => Some(PathMode::Relative),
+            "absolute" => Some(PathMode::Absolute),
+            "stream" | "url" => Some(PathMode::Stream),
+            _ => None,
+        }
+    }
+}
+
+/// 歌单文件中的一个条目
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlaylistEntry {
+    /// 条目位置: M3U 为行号, XSPF 为曲目序号 (均从 1 开始)
+    pub line: usize,
+    pub location: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    /// 时长(秒)
+    pub duration: Option<f64>,
+}
+
+/// 解析后的歌单文件
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedPlaylist {
+    pub title: Option<String>,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+/// 未能匹配到歌曲的条目
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UnmatchedEntry {
+    pub line: usize,
+    pub location: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+}
+
+/// 导入结果
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImportReport {
+    pub name: String,
+    pub total: usize,
+    pub matched: usize,
+    pub song_ids: Vec<String>,
+    pub unmatched: Vec<UnmatchedEntry>,
+}
+
+// ==================== 导出 ====================
+
+/// 将歌单导出为指定格式
+///
+/// # 参数
+/// * `playlist` - 歌单详情
+/// * `format` - 导出格式
+/// * `mode` - 歌曲位置写法
+/// * `base_url` - 流地址前缀(如 "http://host:9090"), 仅 `PathMode::Stream` 使用
+pub fn export_playlist(
+    playlist: &PlaylistDetail,
+    format: PlaylistFormat,
+    mode: PathMode,
+    base_url: &str,
+) -> String {
+    match format {
+        PlaylistFormat::M3u8 => export_m3u8(playlist, mode, base_url),
+        PlaylistFormat::Xspf => export_xspf(playlist, mode, base_url),
+    }
+}
+
+fn export_m3u8(playlist: &PlaylistDetail, mode: PathMode, base_url: &str) -> String {
+    let mut out = String::from("#EXTM3U\n");
+    out.push_str(&format!("#PLAYLIST:{}\n", one_line(&playlist.name)));
+    for song in &playlist.songs {
+        let display = if song.artist.is_empty() {
+            one_line(&song.title)
+        } else {
+            format!("{} - {}", one_line(&song.artist), one_line(&song.title))
+        };
+        out.push_str(&format!("#EXTINF:{},{}\n", song.duration.round() as i64, display));
+        out.push_str(&song_location(song, mode, base_url));
+        out.push('\n');
+    }
+    out
+}
+
+fn export_xspf(playlist: &PlaylistDetail, mode: PathMode, base_url: &str) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n");
+    out.push_str(&format!("  <title>{}</title>\n", xml_escape(&playlist.name)));
+    if let Some(desc) = playlist.description.as_deref().filter(|d| !d.is_empty()) {
+        out.push_str(&format!("  <annotation>{}</annotation>\n", xml_escape(desc)));
+    }
+    out.push_str("  <trackList>\n");
+    for song in &playlist.songs {
+        let location = match mode {
+            PathMode::Stream => song_location(song, mode, base_url),
+            PathMode::Absolute => {
+                let path = song_location(song, mode, base_url);
+                if path.starts_with('/') {
+                    format!("file://{}", percent_encode_path(&path))
+                } else {
+                    percent_encode_path(&path)
+                }
+            }
+            PathMode::Relative => percent_encode_path(&song_location(song, mode, base_url)),
+        };
+        out.push_str("    <track>\n");
+        out.push_str(&format!("      <location>{}</location>\n", xml_escape(&location)));
+        out.push_str(&format!("      <title>{}</title>\n", xml_escape(&song.title)));
+        if !song.artist.is_empty() {
+            out.push_str(&format!("      <creator>{}</creator>\n", xml_escape(&song.artist)));
+        }
+        if !song.album.is_empty() {
+            out.push_str(&format!("      <album>{}</album>\n", xml_escape(&song.album)));
+        }
+        if let Ok(track) = song.track.trim().parse::<u32>() {
+            out.push_str(&format!("      <trackNum>{}</trackNum>\n", track));
+        }
+        out.push_str(&format!(
+            "      <duration>{}</duration>\n",
+            (song.duration * 1000.0).round() as i64
+        ));
+        out.push_str("    </track>\n");
+    }
+    out.push_str("  </trackList>\n");
+    out.push_str("</playlist>\n");
+    out
+}
+
+/// 计算歌曲在歌单文件中的位置
+fn song_location(song: &UnifiedMetadata, mode: PathMode, base_url: &str) -> String {
+    match mode {
+        PathMode::Absolute => song.file_path.clone().unwrap_or_default(),
+        PathMode::Relative => match song.source {
+            // 本地模式 file_url 形如 /music/<相对路径>
+            DataSourceType::Local => song
+                .file_url
+                .as_deref()
+                .and_then(|u| u.strip_prefix(LOCAL_MUSIC_PREFIX))
+                .map(|s| s.to_string())
+                .or_else(|| song.file_path.clone())
+                .unwrap_or_default(),
+            // Subsonic 返回的 path 本身就是相对音乐库的路径
+            _ => song.file_path.clone().unwrap_or_default(),
+        },
+        PathMode::Stream => {
+            // stream_url 由本服务生成, 无需再编码; file_url 是文件路径, 需要编码
+            let path = match (&song.stream_url, &song.file_url) {
+                (Some(stream_url), _) => stream_url.clone(),
+                (None, Some(file_url)) => percent_encode_path(file_url),
+                (None, None) => format!("/api/stream/{}", song.id),
+            };
+            format!("{}{}", base_url.trim_end_matches('/'), path)
+        }
+    }
+}
+
+// ==================== 解析 ====================
+
+/// 解析歌单文件
+pub fn parse_playlist(content: &str, format: PlaylistFormat) -> ParsedPlaylist {
+    match format {
+        PlaylistFormat::M3u8 => parse_m3u(content),
+        PlaylistFormat::Xspf => parse_xspf(content),
+    }
+}
+
+/// 解析 M3U / 扩展 M3U
+pub fn parse_m3u(content: &str) -> ParsedPlaylist {
+    let mut playlist = ParsedPlaylist::default();
+    let mut pending: Option<(Option<f64>, Option<String>, Option<String>)> = None;
+
+    for (i, raw) in content.lines().enumerate() {
+        let line = raw.trim_start_matches('\u{feff}').trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            pending = Some(parse_extinf(info));
+            continue;
+        }
+        if let Some(title) = line.strip_prefix("#PLAYLIST:") {
+            let title = title.trim();
+            if !title.is_empty() {
+                playlist.title = Some(title.to_string());
+            }
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let (duration, artist, title) = pending.take().unwrap_or((None, None, None));
+        playlist.entries.push(PlaylistEntry {
+            line: i + 1,
+            location: line.to_string(),
+            title,
+            artist,
+            duration,
+        });
+    }
+    playlist
+}
+
+/// 解析 #EXTINF 行: `时长[ 属性...],艺术家 - 标题`
+fn parse_extinf(info: &str) -> (Option<f64>, Option<String>, Option<String>) {
+    let (head, display) = match info.find(',') {
+        Some(pos) => (&info[..pos], info[pos + 1..].trim()),
+        None => (info, ""),
+    };
+    let duration = head
+        .split_whitespace()
+        .next()
+        .and_then(|d| d.parse::<f64>().ok())
+        .filter(|d| *d > 0.0);
+
+    if display.is_empty() {
+        return (duration, None, None);
+    }
+    match display.split_once(" - ") {
+        Some((artist, title)) => (
+            duration,
+            Some(artist.trim().to_string()),
+            Some(title.trim().to_string()),
+        ),
+        None => (duration, None, Some(display.to_string())),
+    }
+}
+
+/// 解析 XSPF
+pub fn parse_xspf(content: &str) -> ParsedPlaylist {
+    let mut playlist = ParsedPlaylist::default();
+
+    // 歌单标题在 trackList 之前
+    let header = content.split("<trackList").next().unwrap_or_default();
+    playlist.title = xml_tag(header, "title");
+
+    for (i, caps) in TRACK_RE.captures_iter(content).enumerate() {
+        let body = caps.get(1).map(|m| m.as_str()).unwrap_or_default();
+        let location = xml_tag(body, "location").unwrap_or_default();
+        playlist.entries.push(PlaylistEntry {
+            line: i + 1,
+            location,
+            title: xml_tag(body, "title"),
+            artist: xml_tag(body, "creator"),
+            duration: xml_tag(body, "duration")
+                .and_then(|d| d.parse::<f64>().ok())
+                .map(|ms| ms / 1000.0),
+        });
+    }
+    playlist
+}
+
+static TRACK_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<track>(.*?)</track>").unwrap());
+
+/// XSPF 中读取的元素, 开始和结束标签相同时才匹配
+static XSPF_TAG_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)<(title|location|creator|duration)(?:\s[^>]*)?>(.*?)</(title|location|creator|duration)>")
+        .unwrap()
+});
+
+fn xml_tag(body: &str, tag: &str) -> Option<String> {
+    XSPF_TAG_RE
+        .captures_iter(body)
+        .find(|c| &c[1] == tag && &c[3] == tag)
+        .map(|c| xml_unescape(c[2].trim()))
+        .filter(|s| !s.is_empty())
+}
+
+// ==================== 导入 ====================
+
+/// 导入歌单
+///
+/// 依次按路径、流地址、标题/艺术家/时长模糊匹配解析每个条目,
+/// 然后通过 `MusicDataSource::create_playlist` 创建歌单
+///
+/// # 参数
+/// * `data_source` - 数据源
+/// * `name` - 歌单名称, 为空时使用文件中的标题
+/// * `content` - 歌单文件内容
+/// * `format` - 文件格式, None 时自动检测
+pub async fn import_playlist(
+    data_source: &dyn MusicDataSource,
+    name: Option<&str>,
+    content: &str,
+    format: Option<PlaylistFormat>,
+) -> Result<ImportReport> {
+    let format = format.unwrap_or_else(|| PlaylistFormat::detect(content));
+    let parsed = parse_playlist(content, format);
+
+    let name = name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .or(parsed.title.clone())
+        .unwrap_or_else(|| "导入的歌单".to_string());
+
+    let mut song_ids = Vec::new();
+    let mut unmatched = Vec::new();
+    for entry in &parsed.entries {
+        match resolve_entry(data_source, entry).await {
+            Some(song) => song_ids.push(song.id),
+            None => unmatched.push(UnmatchedEntry {
+                line: entry.line,
+                location: entry.location.clone(),
+                title: entry.title.clone(),
+                artist: entry.artist.clone(),
+            }),
+        }
+    }
+
+    data_source.create_playlist(&name, None, &song_ids).await?;
+
+    Ok(ImportReport {
+        name,
+        total: parsed.entries.len(),
+        matched: song_ids.len(),
+        song_ids,
+        unmatched,
+    })
+}
+
+async fn resolve_entry(
+    data_source: &dyn MusicDataSource,
+    entry: &PlaylistEntry,
+) -> Option<UnifiedMetadata> {
+    let location = entry.location.trim();
+
+    // 1. 本服务导出的流地址
+    if let Some(id) = stream_id(location) {
+        if let Ok(song) = data_source.get_metadata(&id).await {
+            return Some(song);
+        }
+    }
+
+    // 2. 文件路径
+    let path = location_to_path(location);
+    if !path.is_empty() {
+        if let Ok(Some(song)) = data_source.find_song_by_path(&path).await {
+            return Some(song);
+        }
+    }
+
+    // 3. 标题/艺术家/时长模糊匹配
+    let (artist, title) = match (&entry.artist, &entry.title) {
+        (artist, Some(title)) => (artist.clone(), title.clone()),
+        (_, None) => guess_from_file_name(&path),
+    };
+    if title.is_empty() {
+        return None;
+    }
+
+    let mut candidates = data_source
+        .search(&title, Pagination::new(1, 20))
+        .await
+        .map(|r| r.songs)
+        .unwrap_or_default();
+    if candidates.is_empty() {
+        if let Some(artist) = artist.as_deref().filter(|a| !a.is_empty()) {
+            candidates = data_source
+                .search(artist, Pagination::new(1, 50))
+                .await
+                .map(|r| r.songs)
+                .unwrap_or_default();
+        }
+    }
+
+    candidates
+        .into_iter()
+        .map(|song| {
+            let score = match_score(&song, &title, artist.as_deref(), entry.duration);
+            (score, song)
+        })
+        .filter(|(score, _)| *score >= MATCH_THRESHOLD)
+        .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(_, song)| song)
+}
+
+static STREAM_ID_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"/api/stream/([^/?#]+)").unwrap());
+
+/// 从 `/api/stream/{id}` 形式的地址中提取歌曲 ID
+fn stream_id(location: &str) -> Option<String> {
+    STREAM_ID_RE
+        .captures(location)
+        .and_then(|c| c.get(1))
+        .map(|m| percent_decode(m.as_str()))
+}
+
+/// 将条目位置转换为文件路径
+/// - `file://` URI 转为绝对路径
+/// - http(s) 地址取路径部分, 并去掉本地静态文件前缀 `/music`
+/// - 统一使用 `/` 作为分隔符
+fn location_to_path(location: &str) -> String {
+    let path = if let Some(rest) = location.strip_prefix("file://") {
+        // file:///C:/x 或 file:///home/x
+        let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+        percent_decode(rest)
+    } else if location.starts_with("http://") || location.starts_with("https://") {
+        match url::Url::parse(location) {
+            Ok(url) => {
+                let path = percent_decode(url.path());
+                match path.strip_prefix(LOCAL_MUSIC_PREFIX) {
+                    Some(rel) => format!("/{}", rel),
+                    None => path,
+                }
+            }
+            Err(_) => return String::new(),
+        }
+    } else if location.contains('%') {
+        percent_decode(location)
+    } else {
+        location.to_string()
+    };
+
+    let path = path.replace('\\', "/");
+    // Windows 盘符路径 /C:/x -> C:/x
+    let path = match path.as_bytes() {
+        [b'/', _, b':', ..] => path[1..].to_string(),
+        _ => path,
+    };
+    path.trim_start_matches("./").to_string()
+}
+
+static TRACK_NUMBER_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d{1,3}\s*[.\-_]\s*").unwrap());
+
+/// 从文件名猜测艺术家和标题, 如 "周杰伦 - 晴天.mp3"
+fn guess_from_file_name(path: &str) -> (Option<String>, String) {
+    let stem = std::path::Path::new(path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    // 去掉常见的曲目编号前缀 "01. " / "01 - "
+    let stem = TRACK_NUMBER_RE.replace(stem, "").to_string();
+    match stem.split_once(" - ") {
+        Some((artist, title)) => (Some(artist.trim().to_string()), title.trim().to_string()),
+        None => (None, stem.trim().to_string()),
+    }
+}
+
+/// 计算候选歌曲与条目的匹配得分 (0.0 ~ 1.0)
+fn match_score(
+    song: &UnifiedMetadata,
+    title: &str,
+    artist: Option<&str>,
+    duration: Option<f64>,
+) -> f64 {
+    let title_score = similarity(&song.title, title);
+
+    let mut weights = vec![(title_score, 0.6)];
+    if let Some(artist) = artist.filter(|a| !a.is_empty()) {
+        weights.push((similarity(&song.artist, artist), 0.3));
+    }
+    if let Some(duration) = duration.filter(|d| *d > 0.0 && song.duration > 0.0) {
+        let delta = (song.duration - duration).abs();
+        let duration_score = if delta <= 3.0 {
+            1.0
+        } else if delta <= 10.0 {
+            0.5
+        } else {
+            0.0
+        };
+        weights.push((duration_score, 0.1));
+    }
+
+    let total: f64 = weights.iter().map(|(_, w)| w).sum();
+    weights.iter().map(|(s, w)| s * w).sum::<f64>() / total
+}
+
+/// 文本相似度: 忽略大小写和标点, 基于编辑距离
+fn similarity(a: &str, b: &str) -> f64 {
+    let a = normalize(a);
+    let b = normalize(b);
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a == b {
+        return 1.0;
+    }
+    let distance = levenshtein(&a, &b) as f64;
+    let len = a.len().max(b.len()) as f64;
+    let score = 1.0 - distance / len;
+    // 一方包含另一方(如 "晴天" 与 "晴天 (Live)")时给较高的分
+    if contains_chars(&a, &b) || contains_chars(&b, &a) {
+        score.max(0.85)
+    } else {
+        score
+    }
+}
+
+fn normalize(s: &str) -> Vec<char> {
+    s.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j + 1] + 1).min(cur[j] + 1).min(prev[j] + cost);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+fn contains_chars(haystack: &[char], needle: &[char]) -> bool {
+    needle.len() <= haystack.len() && haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+// ==================== 工具函数 ====================
+
+fn one_line(s: &str) -> String {
+    s.replace(['\r', '\n'], " ")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// 对路径做百分号编码, 保留 `/` 和非保留字符
+fn percent_encode_path(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn hex_value(b: u8) -> u8 {
+    match b {
+        b'0'..=b'9' => b - b'0',
+        b'a'..=b'f' => b - b'a' + 10,
+        _ => b - b'A' + 10,
+    }
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        // 只解码后面跟着两个十六进制数字的 %, 不能按字节切分 str (后面可能是多字节字符)
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit()
+        {
+            out.push(hex_value(bytes[i + 1]) << 4 | hex_value(bytes[i + 2]));
+            i += 3;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(title: &str, artist: &str, duration: f64) -> UnifiedMetadata {
+        UnifiedMetadata {
+            id: "abc".to_string(),
+            title: title.to_string(),
+            artist: artist.to_string(),
+            duration,
+            source: DataSourceType::Local,
+            file_path: Some("/data/music/Pop/晴天.mp3".to_string()),
+            file_url: Some("/music/Pop/晴天.mp3".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_m3u() {
+        let text = "#EXTM3U\n#PLAYLIST:My List\n#EXTINF:269,周杰伦 - 晴天\nPop/晴天.mp3\n\nother.flac\n";
+        let parsed = parse_m3u(text);
+        assert_eq!(parsed.title.as_deref(), Some("My List"));
+        assert_eq!(parsed.entries.len(), 2);
+        assert_eq!(parsed.entries[0].line, 4);
+        assert_eq!(parsed.entries[0].artist.as_deref(), Some("周杰伦"));
+        assert_eq!(parsed.entries[0].title.as_deref(), Some("晴天"));
+        assert_eq!(parsed.entries[0].duration, Some(269.0));
+        assert_eq!(parsed.entries[1].title, None);
+    }
+
+    #[test]
+    fn test_xspf_round_trip() {
+        let playlist = PlaylistDetail {
+            id: "1".to_string(),
+            name: "R&B".to_string(),
+            description: None,
+            cover: None,
+            owner: None,
+            public: None,
+            song_count: 1,
+            duration: None,
+            created_at: None,
+            updated_at: None,
+            smart: false,
+            daily_mix: false,
+            songs: vec![song("晴天", "周杰伦", 269.0)],
+        };
+        let xml = export_playlist(&playlist, PlaylistFormat::Xspf, PathMode::Relative, "");
+        assert_eq!(PlaylistFormat::detect(&xml), PlaylistFormat::Xspf);
+
+        let parsed = parse_xspf(&xml);
+        assert_eq!(parsed.title.as_deref(), Some("R&B"));
+        assert_eq!(parsed.entries.len(), 1);
+        assert_eq!(location_to_path(&parsed.entries[0].location), "Pop/晴天.mp3");
+        assert_eq!(parsed.entries[0].duration, Some(269.0));
+    }
+
+    #[test]
+    fn test_location_to_path() {
+        assert_eq!(location_to_path("file:///home/a%20b.mp3"), "/home/a b.mp3");
+        assert_eq!(location_to_path("http://host:9090/music/Pop/x.mp3"), "/Pop/x.mp3");
+        assert_eq!(location_to_path(".\\Pop\\x.mp3"), "Pop/x.mp3");
+        assert_eq!(stream_id("http://host/api/stream/abc?x=1").as_deref(), Some("abc"));
+        // % 后面不是十六进制数字 (包括多字节字符) 时保留原样
+        assert_eq!(location_to_path("Pop/100%晴天.mp3"), "Pop/100%晴天.mp3");
+        assert_eq!(location_to_path("Pop/%E6%99%B4%e5%a4%a9 50%.mp3"), "Pop/晴天 50%.mp3");
+    }
+
+    #[test]
+    fn test_match_score() {
+        let s = song("晴天", "周杰伦", 269.0);
+        assert!(match_score(&s, "晴天", Some("周杰伦"), Some(270.0)) > 0.99);
+        assert!(match_score(&s, "晴天 (Live)", Some("Jay Chou"), None) < MATCH_THRESHOLD);
+        assert!(match_score(&s, "七里香", Some("周杰伦"), None) < MATCH_THRESHOLD);
+    }
+}
@@ -12,6 +12,11 @@ pub struct SubsonicAuth {
 }
 
 impl SubsonicAuth {
+    /// 用户名
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
     /// 创建新的认证管理器
     ///
     /// # 参数
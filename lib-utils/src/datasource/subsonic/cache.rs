@@ -0,0 +1,226 @@
+// Subsonic 元数据缓存
+// 带过期时间 (TTL) 和容量上限 (LRU 淘汰) 的内存缓存
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::datasource::types::*;
+
+struct CacheEntry<V> {
+    value: V,
+    expires_at: Instant,
+    // 最近一次访问的序号, 用于 LRU 淘汰
+    tick: u64,
+}
+
+struct CacheInner<V> {
+    entries: HashMap<String, CacheEntry<V>>,
+    // 访问序号 -> key, 序号最小的是最久未使用的
+    order: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+/// 带 TTL 的 LRU 缓存
+///
+/// `ttl` 或 `capacity` 为 0 时禁用缓存
+pub struct TtlCache<V> {
+    inner: Mutex<CacheInner<V>>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+impl<V: Clone> TtlCache<V> {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(CacheInner {
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+                tick: 0,
+            }),
+            ttl,
+            capacity,
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.capacity > 0
+    }
+
+    /// 获取缓存, 过期的条目会被删除
+    pub fn get(&self, key: &str) -> Option<V> {
+        if !self.enabled() {
+            return None;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        let (old_tick, expired) = match inner.entries.get(key) {
+            Some(entry) => (entry.tick, entry.expires_at <= Instant::now()),
+            None => return None,
+        };
+        inner.order.remove(&old_tick);
+        if expired {
+            inner.entries.remove(key);
+            return None;
+        }
+
+        inner.order.insert(tick, key.to_string());
+        let entry = inner.entries.get_mut(key)?;
+        entry.tick = tick;
+        Some(entry.value.clone())
+    }
+
+    /// 写入缓存, 超出容量时淘汰最久未使用的条目
+    pub fn insert(&self, key: &str, value: V) {
+        if !self.enabled() {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        let entry = CacheEntry {
+            value,
+            expires_at: Instant::now() + self.ttl,
+            tick,
+        };
+        if let Some(old) = inner.entries.insert(key.to_string(), entry) {
+            inner.order.remove(&old.tick);
+        }
+        inner.order.insert(tick, key.to_string());
+
+        while inner.entries.len() > self.capacity {
+            let Some((_, oldest)) = inner.order.pop_first() else {
+                break;
+            };
+            inner.entries.remove(&oldest);
+        }
+    }
+
+    pub fn remove(&self, key: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(entry) = inner.entries.remove(key) {
+            inner.order.remove(&entry.tick);
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.order.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Subsonic 数据源使用的各类缓存
+pub struct SubsonicCache {
+    /// 单曲, key 为歌曲 ID
+    pub songs: TtlCache<UnifiedMetadata>,
+    /// 专辑信息, key 为专辑 ID
+    pub albums: TtlCache<AlbumInfo>,
+    /// 专辑歌曲, key 为专辑 ID
+    pub album_songs: TtlCache<Vec<UnifiedMetadata>>,
+    /// 艺术家信息, key 为艺术家 ID
+    pub artists: TtlCache<ArtistInfo>,
+    /// 艺术家列表
+    pub artist_list: TtlCache<Vec<ArtistInfo>>,
+    /// 流派列表
+    pub genres: TtlCache<Vec<GenreInfo>>,
+    /// 专辑列表, key 为列表类型和分页参数
+    pub album_lists: TtlCache<Vec<AlbumInfo>>,
+    /// 歌单列表
+    pub playlists: TtlCache<Vec<PlaylistInfo>>,
+}
+
+impl SubsonicCache {
+    pub fn new(ttl_seconds: u64, capacity: usize) -> Self {
+        let ttl = Duration::from_secs(ttl_seconds);
+        Self {
+            songs: TtlCache::new(ttl, capacity),
+            albums: TtlCache::new(ttl, capacity),
+            album_songs: TtlCache::new(ttl, capacity),
+            artists: TtlCache::new(ttl, capacity),
+            // 列表类缓存条目较大, 只保留少量
+            artist_list: TtlCache::new(ttl, 1),
+            genres: TtlCache::new(ttl, 1),
+            album_lists: TtlCache::new(ttl, capacity.min(100)),
+            playlists: TtlCache::new(ttl, 1),
+        }
+    }
+
+    /// 收藏状态变化后失效相关缓存
+    ///
+    /// 列表中也带有收藏状态, 无法精确定位, 一并清空
+    pub fn invalidate_starred(&self, id: &str, item_type: StarItemType) {
+        match item_type {
+            StarItemType::Song => self.songs.remove(id),
+            StarItemType::Album => {
+                self.albums.remove(id);
+                self.album_songs.remove(id);
+            }
+            StarItemType::Artist => self.artists.remove(id),
+        }
+        self.album_songs.clear();
+        self.album_lists.clear();
+        self.artist_list.clear();
+    }
+
+    /// 歌单变化后失效歌单缓存
+    pub fn invalidate_playlists(&self) {
+        self.playlists.clear();
+    }
+
+    pub fn clear(&self) {
+        self.songs.clear();
+        self.albums.clear();
+        self.album_songs.clear();
+        self.artists.clear();
+        self.artist_list.clear();
+        self.genres.clear();
+        self.album_lists.clear();
+        self.playlists.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_eviction() {
+        let cache = TtlCache::new(Duration::from_secs(60), 2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        // 访问 a 之后 b 成为最久未使用的
+        assert_eq!(cache.get("a"), Some(1));
+        cache.insert("c", 3);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("c"), Some(3));
+    }
+
+    #[test]
+    fn test_ttl_expire() {
+        let cache = TtlCache::new(Duration::from_millis(20), 10);
+        cache.insert("a", 1);
+        assert_eq!(cache.get("a"), Some(1));
+        std::thread::sleep(Duration::from_millis(40));
+        assert_eq!(cache.get("a"), None);
+        assert!(cache.is_empty());
+
+        let disabled = TtlCache::new(Duration::ZERO, 10);
+        disabled.insert("a", 1);
+        assert_eq!(disabled.get("a"), None);
+    }
+}
@@ -1,11 +1,24 @@
 // Subsonic HTTP 客户端
 // 实现 Subsonic REST API 的调用
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
 use super::auth::SubsonicAuth;
+use super::error::SubsonicClientError;
+use super::policy::{CircuitBreaker, ClientPolicy};
+use crate::datasource::error::DataSourceError;
+use crate::datasource::types::StreamOptions;
+use crate::log;
+
+/// 非幂等的接口, 失败后不重试
+const NON_IDEMPOTENT_ENDPOINTS: &[&str] = &[
+    "rest/createPlaylist",
+    "rest/updatePlaylist",
+    "rest/scrobble",
+    "rest/startScan",
+];
 
 /// Subsonic API 客户端
 pub struct SubsonicClient {
@@ -14,6 +27,8 @@ pub struct SubsonicClient {
     client: Client,
     api_version: String,
     client_name: String,
+    policy: ClientPolicy,
+    breaker: CircuitBreaker,
 }
 
 impl SubsonicClient {
@@ -36,6 +51,7 @@ impl SubsonicClient {
     ) -> Self {
         // 移除 base_url 末尾的斜杠
         let base_url = base_url.trim_end_matches('/').to_string();
+        let policy = ClientPolicy::default();
 
         Self {
             base_url,
@@ -43,9 +59,23 @@ impl SubsonicClient {
             client: Client::new(),
             api_version,
             client_name,
+            breaker: CircuitBreaker::new(
+                policy.circuit_breaker_threshold,
+                policy.circuit_breaker_cooldown,
+            ),
+            policy,
         }
     }
 
+    /// 设置超时、重试和熔断策略
+    pub fn set_policy(&mut self, policy: ClientPolicy) {
+        self.breaker = CircuitBreaker::new(
+            policy.circuit_breaker_threshold,
+            policy.circuit_breaker_cooldown,
+        );
+        self.policy = policy;
+    }
+
     /// Ping 服务器,检查连接状态
     pub async fn ping(&self) -> Result<()> {
         let response: SubsonicResponse<BaseResponse> = self.get("rest/ping", vec![]).await?;
@@ -160,6 +190,25 @@ impl SubsonicClient {
         Ok(artists)
     }
 
+    /// 获取音乐库索引的最后修改时间 (毫秒)
+    ///
+    /// 传入 `if_modified_since` 时, 服务器在音乐库没有变化的情况下不会返回索引内容
+    pub async fn get_indexes_last_modified(&self, if_modified_since: Option<i64>) -> Result<i64> {
+        let mut params = vec![];
+        if let Some(since) = if_modified_since {
+            params.push(("ifModifiedSince", since.to_string()));
+        }
+        let response: SubsonicResponse<IndexesWrapper> =
+            self.get("rest/getIndexes", params).await?;
+
+        Ok(response
+            .subsonic_response
+            .indexes
+            .and_then(|i| i.last_modified)
+            .or(if_modified_since)
+            .unwrap_or(0))
+    }
+
     /// 获取单个艺术家信息
     pub async fn get_artist(&self, id: &str) -> Result<SubsonicArtistDetail> {
         let params = vec![("id", id.to_string())];
@@ -186,6 +235,19 @@ impl SubsonicClient {
             .ok_or_else(|| anyhow::anyhow!("top songs not found: {}", artist_name))
     }
 
+    /// 获取与艺术家相似的歌曲 (服务器通常通过 Last.fm 获取相似艺术家)
+    pub async fn get_similar_songs2(&self, artist_id: &str, count: usize) -> Result<Vec<SubsonicSong>> {
+        let params = vec![("id", artist_id.to_string()), ("count", count.to_string())];
+        let response: SubsonicResponse<SimilarSongs2Wrapper> =
+            self.get("rest/getSimilarSongs2", params).await?;
+
+        Ok(response
+            .subsonic_response
+            .similar_songs2
+            .and_then(|s| s.song)
+            .unwrap_or_default())
+    }
+
     /// 获取风格列表
     pub async fn get_genres(&self) -> Result<Vec<Genre>> {
         let response: SubsonicResponse<GenreWrapper> = self.get("rest/getGenres", vec![]).await?;
@@ -310,14 +372,26 @@ impl SubsonicClient {
     }
 
     /// 获取音频流(返回 Response 供代理使用)
-    pub async fn stream_song(&self, id: &str, range: Option<String>) -> Result<reqwest::Response> {
+    pub async fn stream_song(
+        &self,
+        id: &str,
+        range: Option<String>,
+        max_bitrate: u32,
+        format: &str,
+    ) -> Result<reqwest::Response> {
         let mut params = self.auth.get_auth_params();
         params.extend(vec![
             ("v", self.api_version.clone()),
             ("c", self.client_name.clone()),
             ("f", "json".to_string()),
             ("id", id.to_string()),
+            ("maxBitRate", max_bitrate.to_string()),
+            // 转码后的流没有准确长度, 让服务端估算 Content-Length 以支持拖动进度
+            ("estimateContentLength", "true".to_string()),
         ]);
+        if !format.is_empty() {
+            params.push(("format", format.to_string()));
+        }
 
         let url = format!("{}/rest/stream", self.base_url);
 
@@ -328,18 +402,54 @@ impl SubsonicClient {
             request = request.header("Range", range);
         }
 
-        let response = request
+        // 音频流可能很长, 不设置整体超时
+        self.breaker.check().map_err(DataSourceError::from)?;
+        let response = self
+            .record(request.send().await.map_err(|e| SubsonicClientError::from_reqwest(&e)))
+            .map_err(DataSourceError::from)?;
+
+        Ok(response)
+    }
+
+    /// 下载原始文件 (不转码)
+    pub async fn download(&self, id: &str) -> Result<reqwest::Response> {
+        let mut params = self.auth.get_auth_params();
+        params.extend(vec![
+            ("v", self.api_version.clone()),
+            ("c", self.client_name.clone()),
+            ("id", id.to_string()),
+        ]);
+
+        let url = format!("{}/rest/download", self.base_url);
+        self.breaker.check().map_err(DataSourceError::from)?;
+        let response = self
+            .client
+            .get(&url)
+            .query(&params)
             .send()
             .await
-            .context("Failed to stream from Subsonic server")?;
-
+            .map_err(|e| SubsonicClientError::from_reqwest(&e))
+            .and_then(|response| match response.status() {
+                status if status.is_success() => Ok(response),
+                status => Err(SubsonicClientError::Http(status.as_u16())),
+            });
+        let response = self.record(response).map_err(DataSourceError::from)?;
+        // 出错时 Subsonic 返回 200 和 XML/JSON 错误信息, 而不是音频
+        let is_error = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|t| t.contains("xml") || t.contains("json"));
+        if is_error {
+            return Err(anyhow::anyhow!("Subsonic download failed: {}", response.text().await?));
+        }
         Ok(response)
     }
 
     /// 构建流式URL(返回本地代理URL)
-    pub fn get_stream_url(&self, id: &str, _max_bitrate: u32, _format: &str) -> String {
+    pub fn get_stream_url(&self, id: &str, options: &StreamOptions) -> String {
         // 返回本地代理端点,而不是直接的 Subsonic URL
-        format!("/api/stream/{}", id)
+        format!("/api/stream/{}{}", id, options.to_query())
     }
 
     /// 获取所有播放列表
@@ -347,9 +457,11 @@ impl SubsonicClient {
         let response: SubsonicResponse<PlaylistsWrapper> =
             self.get("rest/getPlaylists", vec![]).await?;
 
-        Ok(response
-            .subsonic_response
-            .playlists)
+        Ok(match response.subsonic_response.playlists {
+            Some(Playlists::List(playlists)) => playlists,
+            Some(Playlists::Object { playlist }) => playlist.unwrap_or_default(),
+            None => vec![],
+        })
     }
 
     /// 获取播放列表详情
@@ -364,6 +476,15 @@ impl SubsonicClient {
             .ok_or_else(|| anyhow::anyhow!("Playlist not found: {}", id))
     }
 
+    /// 当前登录的用户名
+    pub fn server_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn username(&self) -> &str {
+        self.auth.username()
+    }
+
     /// 创建播放列表
     pub async fn create_playlist(&self, name: &str, song_ids: &[String]) -> Result<()> {
         let mut params = vec![("name", name.to_string())];
@@ -382,6 +503,22 @@ impl SubsonicClient {
         Ok(())
     }
 
+    /// 替换播放列表中的全部歌曲 (createPlaylist 带 playlistId 时为更新)
+    pub async fn replace_playlist_songs(&self, playlist_id: &str, song_ids: &[String]) -> Result<()> {
+        let mut params = vec![("playlistId", playlist_id.to_string())];
+        for song_id in song_ids {
+            params.push(("songId", song_id.clone()));
+        }
+
+        let response: SubsonicResponse<BaseResponse> =
+            self.get("rest/createPlaylist", params).await?;
+
+        if response.subsonic_response.status != "ok" {
+            return Err(anyhow::anyhow!("Failed to replace playlist songs: {:?}", response.subsonic_response.error));
+        }
+        Ok(())
+    }
+
     /// 更新播放列表
     pub async fn update_playlist(
         &self,
@@ -620,6 +757,28 @@ impl SubsonicClient {
         }
     }
 
+    /// 设置评分（歌曲、专辑、艺术家）
+    ///
+    /// # 参数
+    /// * `id` - 项目 ID
+    /// * `rating` - 评分 1-5, 0 表示取消评分
+    ///
+    /// # 返回
+    /// * `Ok(())` - 设置成功
+    pub async fn set_rating(&self, id: &str, rating: u8) -> Result<()> {
+        let params = vec![("id", id.to_string()), ("rating", rating.to_string())];
+        let response: SubsonicResponse<BaseResponse> = self.get("rest/setRating", params).await?;
+
+        if response.subsonic_response.status == "ok" {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Set rating failed: {:?}",
+                response.subsonic_response.error
+            ))
+        }
+    }
+
     /// 获取收藏列表（getStarred2）
     ///
     /// # 返回
@@ -638,7 +797,18 @@ impl SubsonicClient {
             }))
     }
 
+    /// 记录请求结果, 服务器不可用时计入熔断
+    fn record<T>(&self, result: Result<T, SubsonicClientError>) -> Result<T, SubsonicClientError> {
+        match &result {
+            Err(e) if e.is_transient() => self.breaker.record_failure(),
+            _ => self.breaker.record_success(),
+        }
+        result
+    }
+
     /// 发送 GET 请求
+    ///
+    /// 幂等接口在网络错误、超时和 5xx 时按指数退避重试
     async fn get<T>(&self, endpoint: &str, mut params: Vec<(&str, String)>) -> Result<T>
     where
         T: for<'de> Deserialize<'de>,
@@ -653,48 +823,77 @@ impl SubsonicClient {
         params.push(("f", "json".to_string()));
 
         let url = format!("{}/{}", self.base_url, endpoint);
-        println!(
-            "send {}?{}",
-            url,
-            params
-                .iter()
-                .map(|(k, v)| format!("{}={}", k, v))
-                .collect::<Vec<_>>()
-                .join("&")
-        );
+        let retry_count = match NON_IDEMPOTENT_ENDPOINTS.contains(&endpoint) {
+            true => 0,
+            false => self.policy.retry_count,
+        };
+
+        let mut attempt = 0;
+        let text = loop {
+            self.breaker.check().map_err(DataSourceError::from)?;
+            let result = self.record(self.send(&url, &params).await);
+            match result {
+                Err(e) if e.is_transient() && attempt < retry_count => {
+                    attempt += 1;
+                    let backoff = self.policy.backoff(attempt);
+                    log::log_warn(&format!(
+                        "Subsonic request {} failed: {}, retry {} in {:?}",
+                        endpoint, e, attempt, backoff
+                    ));
+                    tokio::time::sleep(backoff).await;
+                }
+                result => break result.map_err(DataSourceError::from)?,
+            }
+        };
+
+        let value: serde_json::Value = serde_json::from_str(&text).map_err(|e| {
+            log::log_err(&format!(
+                "Invalid Subsonic response from {}: {} body={}",
+                endpoint,
+                e,
+                text.chars().take(500).collect::<String>()
+            ));
+            DataSourceError::from(SubsonicClientError::InvalidResponse(e.to_string()))
+        })?;
+
+        // 错误响应中只有 status 和 error, 先转换为具体的错误
+        let base = &value["subsonic-response"];
+        if base["status"] == "failed" {
+            let code = base["error"]["code"].as_i64().unwrap_or(0) as i32;
+            let message = base["error"]["message"].as_str().unwrap_or_default().to_string();
+            return Err(DataSourceError::from(SubsonicClientError::from_code(code, message)).into());
+        }
+
+        serde_json::from_value(value).map_err(|e| {
+            log::log_err(&format!(
+                "Unexpected Subsonic response from {}: {} body={}",
+                endpoint,
+                e,
+                text.chars().take(500).collect::<String>()
+            ));
+            DataSourceError::from(SubsonicClientError::InvalidResponse(e.to_string())).into()
+        })
+    }
 
+    /// 发送一次请求并读取响应内容
+    async fn send(&self, url: &str, params: &[(&str, String)]) -> Result<String, SubsonicClientError> {
         let response = self
             .client
-            .get(&url)
-            .query(&params)
+            .get(url)
+            .query(params)
+            .timeout(self.policy.timeout)
             .send()
             .await
-            .context("Failed to send request to Subsonic server")?;
+            .map_err(|e| SubsonicClientError::from_reqwest(&e))?;
 
         let status = response.status();
         if !status.is_success() {
-            return Err(anyhow::anyhow!("HTTP error: {}", status));
-        }
-
-        let response = response.json::<T>().await;
-
-        match response {
-            Ok(json) => Ok(json),
-            Err(e) => {
-                println!("url={} response={:?}", url, e);
-
-                let response = self
-                    .client
-                    .get(&url)
-                    .query(&params)
-                    .send()
-                    .await
-                    .context("Failed to send request to Subsonic server")?;
-                println!("json fail={:?}", response.text().await);
-
-                return Err(anyhow::anyhow!("Failed to parse Subsonic response"));
-            }
+            return Err(SubsonicClientError::Http(status.as_u16()));
         }
+        response
+            .text()
+            .await
+            .map_err(|e| SubsonicClientError::from_reqwest(&e))
     }
 }
 
@@ -788,15 +987,33 @@ pub struct SubsonicSong {
     // pub created: Option<String>,
     pub suffix: Option<String>,
     pub path: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_starred")]
     pub starred: Option<bool>,
+    pub play_count: Option<u32>,
+    /// 最后播放时间 (OpenSubsonic)
+    pub played: Option<String>,
     // pub media_type: Option<String>,
     // pub sort_name: Option<String>,
-    // pub user_rating: Option<u32>,
+    pub user_rating: Option<u32>,
+    /// 响度归一化信息 (OpenSubsonic)
+    pub replay_gain: Option<SubsonicReplayGain>,
+    /// 每分钟节拍数 (OpenSubsonic), 0 表示未知
+    pub bpm: Option<u32>,
     // pub r#type: Option<String>,
     // pub is_dir: Option<bool>,
     // pub is_video: Option<bool>,
 }
 
+/// OpenSubsonic 响度归一化信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubsonicReplayGain {
+    pub track_gain: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub track_peak: Option<f64>,
+    pub album_peak: Option<f64>,
+}
+
 /// Subsonic 专辑信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -817,6 +1034,9 @@ pub struct SubsonicAlbum {
     pub song: Option<Vec<SubsonicSong>>,
     pub sort_name: Option<String>,
     pub user_rating: Option<u32>,
+    pub play_count: Option<u32>,
+    /// 最后播放时间 (OpenSubsonic)
+    pub played: Option<String>,
 }
 
 /// 专辑列表响应包装
@@ -884,6 +1104,21 @@ struct ArtistWrapper {
     artist: Option<SubsonicArtistDetail>,
 }
 
+/// 索引响应包装
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct IndexesWrapper {
+    #[serde(flatten)]
+    base: BaseResponse,
+    indexes: Option<Indexes>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Indexes {
+    last_modified: Option<i64>,
+}
+
 #[derive(Debug, Deserialize)]
 struct Artists {
     index: Vec<ArtistIndex>,
@@ -974,6 +1209,21 @@ struct RandomSongs {
     song: Option<Vec<SubsonicSong>>,
 }
 
+/// 相似歌曲响应
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+#[serde(rename_all = "camelCase")]
+struct SimilarSongs2Wrapper {
+    #[serde(flatten)]
+    base: BaseResponse,
+    similar_songs2: Option<SimilarSongs2>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SimilarSongs2 {
+    song: Option<Vec<SubsonicSong>>,
+}
+
 // 自定义反序列化函数，同时支持 String 和 u32 类型的 year 字段
 fn deserialize_year<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
 where
@@ -1001,6 +1251,25 @@ where
     }
 }
 
+// Subsonic 规范中 starred 为收藏时间, 部分服务器返回 bool
+fn deserialize_starred<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StarredValue {
+        Bool(bool),
+        Time(String),
+    }
+
+    Ok(match Option::<StarredValue>::deserialize(deserializer)? {
+        None => None,
+        Some(StarredValue::Bool(b)) => Some(b),
+        Some(StarredValue::Time(t)) => Some(!t.is_empty()),
+    })
+}
+
 // 添加 urlencoding 辅助函数
 mod urlencoding {
     pub fn encode(s: &str) -> String {
@@ -1049,7 +1318,15 @@ pub struct SubsonicPlaylist {
 struct PlaylistsWrapper {
     #[serde(flatten)]
     base: BaseResponse,
-    playlists: Vec<SubsonicPlaylist>,
+    playlists: Option<Playlists>,
+}
+
+/// 规范中为 `{"playlist": [...]}`, 兼容直接返回数组的服务器
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Playlists {
+    List(Vec<SubsonicPlaylist>),
+    Object { playlist: Option<Vec<SubsonicPlaylist>> },
 }
 
 /// 单个播放列表响应包装
@@ -1,24 +1,32 @@
 // Subsonic 数据源实现
 // 实现 MusicDataSource trait,连接 Subsonic 服务器
 
-use anyhow::Result;
 use async_trait::async_trait;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
+use super::cache::SubsonicCache;
 use super::client::SubsonicClient;
 use super::mapper::parse_subsonic_lyrics;
+use super::mirror::{spawn_sync_job, SubsonicMirror};
+use super::pin::{spawn_download_job, SubsonicPinStore};
+use super::policy::ClientPolicy;
+use crate::datasource::error::{DataSourceError, Result};
 use crate::datasource::trait_def::MusicDataSource;
 use crate::datasource::types::*;
+use crate::log;
 
 /// Subsonic 数据源
 pub struct SubsonicDataSource {
-    client: SubsonicClient,
+    client: Arc<SubsonicClient>,
     max_bitrate: u32,
     prefer_format: String,
-    // 简单的内存缓存
-    cache: Arc<RwLock<HashMap<String, UnifiedMetadata>>>,
+    // 元数据缓存
+    cache: Arc<SubsonicCache>,
+    // 本地数据库中的音乐库镜像
+    mirror: Option<Arc<SubsonicMirror>>,
+    // 离线下载
+    pins: Option<Arc<SubsonicPinStore>>,
 }
 
 impl SubsonicDataSource {
@@ -32,17 +40,138 @@ impl SubsonicDataSource {
         prefer_format: String,
     ) -> Self {
         Self {
-            client: SubsonicClient::new(
+            client: Arc::new(SubsonicClient::new(
                 server_url,
                 username,
                 password,
                 use_token,
                 "1.16.1".to_string(),
                 "MusicFlow".to_string(),
-            ),
+            )),
             max_bitrate,
             prefer_format,
-            cache: Arc::new(RwLock::new(HashMap::new())),
+            cache: Arc::new(SubsonicCache::new(3600, 2000)),
+            mirror: None,
+            pins: None,
+        }
+    }
+
+    /// 设置缓存过期时间 (秒) 和每类缓存的容量, 任一为 0 时关闭缓存
+    pub fn with_cache(mut self, ttl_seconds: u64, capacity: usize) -> Self {
+        self.cache = Arc::new(SubsonicCache::new(ttl_seconds, capacity));
+        self
+    }
+
+    /// 设置请求的超时、重试和熔断策略
+    ///
+    /// 需要在 `with_mirror` 和 `with_pins` 之前调用
+    pub fn with_client_policy(mut self, policy: ClientPolicy) -> Self {
+        Arc::get_mut(&mut self.client)
+            .expect("with_client_policy must be called before the client is shared")
+            .set_policy(policy);
+        self
+    }
+
+    /// 开启音乐库镜像, 并启动定时同步任务
+    ///
+    /// 需要先初始化数据库
+    pub fn with_mirror(mut self, db_path: &str, cover_dir: &str, interval_minutes: u64) -> Self {
+        let mirror = Arc::new(SubsonicMirror::new(self.server_key(), db_path, cover_dir));
+
+        let cache = self.cache.clone();
+        spawn_sync_job(mirror.clone(), self.client.clone(), interval_minutes, move || {
+            cache.clear()
+        });
+        self.mirror = Some(mirror);
+        self
+    }
+
+    /// 开启离线下载, 并继续下载上次未完成的歌曲
+    ///
+    /// 需要先初始化数据库
+    pub fn with_pins(mut self, db_path: &str, dir: &str, quota_bytes: u64) -> Self {
+        let pins = Arc::new(SubsonicPinStore::new(self.server_key(), db_path, dir, quota_bytes));
+        spawn_download_job(pins.clone(), self.client.clone());
+        self.pins = Some(pins);
+        self
+    }
+
+    /// 评分不低于 `min_rating` 的专辑
+    ///
+    /// Subsonic 没有按评分过滤的接口, 按 `highest` (评分倒序) 逐页请求并过滤,
+    /// 直到凑够当前页或者遇到评分更低的专辑
+    async fn albums_by_rating(
+        &self,
+        min_rating: u8,
+        pagination: &Pagination,
+    ) -> anyhow::Result<Vec<AlbumInfo>> {
+        let size = pagination.page_size.max(1);
+        let wanted = pagination.start() + pagination.page_size;
+        let mut matched: Vec<AlbumInfo> = vec![];
+        let mut offset = 0;
+        loop {
+            let page = self
+                .client
+                .get_album_list2("highest", size, offset, None, None, None)
+                .await?;
+            let count = page.len();
+            offset += count;
+            let mut lower = false;
+            for album in page {
+                let album: AlbumInfo = album.into();
+                if album.rating.is_some_and(|r| r >= min_rating) {
+                    matched.push(album);
+                } else {
+                    lower = true;
+                }
+            }
+            if lower || count < size || matched.len() >= wanted {
+                break;
+            }
+        }
+        Ok(matched
+            .into_iter()
+            .skip(pagination.start())
+            .take(pagination.page_size)
+            .collect())
+    }
+
+    /// 区分不同服务器的本地数据
+    fn server_key(&self) -> String {
+        format!("{}@{}", self.client.username(), self.client.server_url())
+    }
+
+    /// 镜像已经同步过时返回镜像
+    fn mirror(&self) -> Option<&SubsonicMirror> {
+        self.mirror.as_deref().filter(|m| m.is_ready())
+    }
+
+    fn with_stream_url(&self, mut songs: Vec<UnifiedMetadata>) -> Vec<UnifiedMetadata> {
+        for meta in &mut songs {
+            meta.stream_url = Some(self.client.get_stream_url(&meta.id, &StreamOptions::default()));
+        }
+        songs
+    }
+
+    async fn get_top_songs(&self, artist_id: &str) -> Result<Vec<super::client::SubsonicSong>> {
+        let artist_detail = self.client.get_artist(artist_id).await?;
+        Ok(self
+            .client
+            .get_top_songs(&artist_detail.name.unwrap_or_default())
+            .await?)
+    }
+
+    /// 歌单修改后同步镜像中的歌单
+    async fn refresh_mirror_playlist(&self, playlist_id: Option<&str>) {
+        let Some(mirror) = &self.mirror else {
+            return;
+        };
+        let result = match playlist_id {
+            Some(id) => mirror.refresh_playlist(&self.client, id).await,
+            None => mirror.refresh_playlists(&self.client).await,
+        };
+        if let Err(e) = result {
+            log::log_warn(&format!("Refresh mirror playlist failed: {}", e));
         }
     }
 }
@@ -51,11 +180,15 @@ impl SubsonicDataSource {
 impl MusicDataSource for SubsonicDataSource {
     async fn get_metadata(&self, id: &str) -> Result<UnifiedMetadata> {
         // 检查缓存
-        {
-            let cache = self.cache.read().await;
-            if let Some(cached) = cache.get(id) {
-                return Ok(cached.clone());
-            }
+        if let Some(cached) = self.cache.songs.get(id) {
+            return Ok(cached);
+        }
+
+        // 优先读取镜像
+        if let Some(song) = self.mirror().and_then(|m| m.get_song(id).ok().flatten()) {
+            let song = self.with_stream_url(vec![song]).remove(0);
+            self.cache.songs.insert(id, song.clone());
+            return Ok(song);
         }
 
         // 从服务器获取
@@ -64,17 +197,10 @@ impl MusicDataSource for SubsonicDataSource {
         let mut metadata: UnifiedMetadata = song.into();
 
         // 设置流式URL
-        metadata.stream_url = Some(self.client.get_stream_url(
-            id,
-            self.max_bitrate,
-            &self.prefer_format,
-        ));
+        metadata.stream_url = Some(self.client.get_stream_url(id, &StreamOptions::default()));
 
         // 更新缓存
-        {
-            let mut cache = self.cache.write().await;
-            cache.insert(id.to_string(), metadata.clone());
-        }
+        self.cache.songs.insert(id, metadata.clone());
 
         Ok(metadata)
     }
@@ -84,6 +210,11 @@ impl MusicDataSource for SubsonicDataSource {
 
         let pagination = Pagination::new(filter.page.unwrap_or(1), filter.page_size.unwrap_or(30));
 
+        if let Some(mirror) = self.mirror() {
+            let songs = mirror.search(&keyword, &pagination)?.songs;
+            return Ok(self.with_stream_url(songs));
+        }
+
         // 如果有关键字,使用搜索
         let search_result = self
             .client
@@ -95,11 +226,7 @@ impl MusicDataSource for SubsonicDataSource {
 
         // 为每个歌曲设置流式 URL
         for meta in &mut metadata_list {
-            meta.stream_url = Some(self.client.get_stream_url(
-                &meta.id,
-                self.max_bitrate,
-                &self.prefer_format,
-            ));
+            meta.stream_url = Some(self.client.get_stream_url(&meta.id, &StreamOptions::default()));
         }
 
         Ok(metadata_list)
@@ -119,6 +246,12 @@ impl MusicDataSource for SubsonicDataSource {
             CoverSize::Medium => "600",
             CoverSize::Large => "1200",
         };
+
+        // 优先读取镜像保存在磁盘上的封面
+        if let Some(data) = self.mirror.as_ref().and_then(|m| m.read_cover(cover_art, size)) {
+            return Ok(data);
+        }
+
         // 获取封面 URL
         let cover_url = self.client.get_cover_art_url(&cover_art, size);
         // println!("Downloading cover art from {}", cover_url);
@@ -126,11 +259,14 @@ impl MusicDataSource for SubsonicDataSource {
         // 下载图片
         let response = reqwest::get(&cover_url).await?;
         if response.status() != 200 {
-            return Err(anyhow::anyhow!("Failed to download cover art"));
+            return Err(DataSourceError::not_found("Failed to download cover art"));
         }
 
         let bytes = response.bytes().await?;
 
+        if let Some(mirror) = &self.mirror {
+            mirror.save_cover(cover_art, size, &bytes);
+        }
         Ok(bytes.to_vec())
     }
 
@@ -157,9 +293,14 @@ impl MusicDataSource for SubsonicDataSource {
     }
 
     async fn get_audio_stream(&self, song_id: &str) -> Result<AudioStream> {
+        // 已离线下载的歌曲直接读取本地文件
+        if let Some(path) = self.pins.as_ref().and_then(|p| p.local_path(song_id)) {
+            return Ok(AudioStream::LocalFile(path));
+        }
+
         let stream_url = self
             .client
-            .get_stream_url(song_id, self.max_bitrate, &self.prefer_format);
+            .get_stream_url(song_id, &StreamOptions::default());
 
         Ok(AudioStream::SubsonicStream {
             url: stream_url,
@@ -186,6 +327,19 @@ impl MusicDataSource for SubsonicDataSource {
         filter_text: Option<String>,
         list_type: Option<AlbumListType>,
     ) -> Result<Vec<AlbumInfo>> {
+        // 镜像中没有播放记录, 这几种列表仍然请求服务器; 按评分的列表以服务器为准, 服务器不可用时使用镜像中的评分
+        let album_type = list_type.unwrap_or_default();
+        let needs_remote = matches!(
+            album_type,
+            AlbumListType::Highest
+                | AlbumListType::Frequent
+                | AlbumListType::Recent
+                | AlbumListType::ByRating { .. }
+        ) && filter_text.as_deref().is_none_or(|t| t.trim().is_empty());
+        if let Some(mirror) = self.mirror().filter(|_| !needs_remote) {
+            return Ok(mirror.list_albums(&album_type, filter_text.as_deref(), &pagination)?);
+        }
+
         // 如果有搜索关键字，使用 search3 API
         if let Some(filter_text) = filter_text {
             let filter_text = filter_text.trim();
@@ -200,7 +354,17 @@ impl MusicDataSource for SubsonicDataSource {
         }
 
         // 使用 getAlbumList2 API
-        let album_type = list_type.unwrap_or_default();
+        // 随机、最近播放、最常播放的结果随时变化, 不缓存
+        let cache_key = match album_type {
+            AlbumListType::Random | AlbumListType::Recent | AlbumListType::Frequent => None,
+            _ => Some(format!(
+                "{:?}:{}:{}",
+                album_type, pagination.page, pagination.page_size
+            )),
+        };
+        if let Some(cached) = cache_key.as_deref().and_then(|k| self.cache.album_lists.get(k)) {
+            return Ok(cached);
+        }
 
         // 提取 genre 和 year 参数
         let (genre, from_year, to_year) = match &album_type {
@@ -209,27 +373,63 @@ impl MusicDataSource for SubsonicDataSource {
             _ => (None, None, None),
         };
 
-        let albums = self
-            .client
-            .get_album_list2(
-                album_type.to_subsonic_type(),
-                pagination.page_size,
-                pagination.start(),
-                genre,
-                from_year,
-                to_year,
-            )
-            .await?;
+        let albums = match &album_type {
+            AlbumListType::ByRating { min_rating } => {
+                self.albums_by_rating(*min_rating, &pagination).await
+            }
+            _ => self
+                .client
+                .get_album_list2(
+                    album_type.to_subsonic_type(),
+                    pagination.page_size,
+                    pagination.start(),
+                    genre,
+                    from_year,
+                    to_year,
+                )
+                .await
+                .map(|albums| albums.into_iter().map(|a| a.into()).collect()),
+        };
+        let albums: Vec<AlbumInfo> = match albums {
+            Ok(albums) => albums,
+            // 服务器不可用时退回镜像
+            Err(e) => match self.mirror() {
+                Some(mirror) => {
+                    log::log_warn(&format!("getAlbumList2 failed, use mirror: {}", e));
+                    return Ok(mirror.list_albums(&album_type, None, &pagination)?);
+                }
+                None => return Err(e.into()),
+            },
+        };
 
-        Ok(albums.into_iter().map(|a| a.into()).collect())
+        if let Some(key) = cache_key {
+            self.cache.album_lists.insert(&key, albums.clone());
+        }
+        Ok(albums)
     }
 
     async fn get_album_by_id(&self, album_id: &str) -> Result<AlbumInfo> {
-        let album = self.client.get_album(album_id).await?;
-        Ok(album.into())
+        if let Some(cached) = self.cache.albums.get(album_id) {
+            return Ok(cached);
+        }
+        if let Some(album) = self.mirror().and_then(|m| m.get_album(album_id).ok().flatten()) {
+            return Ok(album);
+        }
+        let album: AlbumInfo = self.client.get_album(album_id).await?.into();
+        self.cache.albums.insert(album_id, album.clone());
+        Ok(album)
     }
 
     async fn get_album_songs(&self, album_id: &str) -> Result<Vec<UnifiedMetadata>> {
+        if let Some(cached) = self.cache.album_songs.get(album_id) {
+            return Ok(cached);
+        }
+        if let Some(mirror) = self.mirror() {
+            let songs = mirror.get_album_songs(album_id)?;
+            if !songs.is_empty() {
+                return Ok(self.with_stream_url(songs));
+            }
+        }
         let album = self.client.get_album(album_id).await?;
 
         let songs = album.song.unwrap_or_default();
@@ -237,51 +437,103 @@ impl MusicDataSource for SubsonicDataSource {
 
         // 为每个歌曲设置流式 URL
         for meta in &mut metadata_list {
-            meta.stream_url = Some(self.client.get_stream_url(
-                &meta.id,
-                self.max_bitrate,
-                &self.prefer_format,
-            ));
+            meta.stream_url = Some(self.client.get_stream_url(&meta.id, &StreamOptions::default()));
         }
 
+        self.cache.album_songs.insert(album_id, metadata_list.clone());
         Ok(metadata_list)
     }
 
+    async fn find_song_by_path(&self, path: &str) -> Result<Option<UnifiedMetadata>> {
+        // Subsonic 没有按路径查询的接口, 用文件名搜索后比对服务器端的相对路径
+        let path = path.replace('\\', "/");
+        let stem = std::path::Path::new(&path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_string();
+        if stem.is_empty() {
+            return Ok(None);
+        }
+
+        let result = self.client.search3(&stem, 0, 50).await?;
+        let matched: Vec<_> = result
+            .song
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|s| {
+                s.path.as_deref().is_some_and(|p| {
+                    let p = p.trim_start_matches('/');
+                    !p.is_empty() && (path.ends_with(p) || p.ends_with(path.trim_start_matches('/')))
+                })
+            })
+            .collect();
+        if matched.len() != 1 {
+            return Ok(None);
+        }
+
+        let mut meta: UnifiedMetadata = matched.into_iter().next().unwrap().into();
+        meta.stream_url = Some(self.client.get_stream_url(&meta.id, &StreamOptions::default()));
+        Ok(Some(meta))
+    }
+
     async fn list_artists(&self) -> Result<Vec<ArtistInfo>> {
+        if let Some(mirror) = self.mirror() {
+            return Ok(mirror.list_artists()?);
+        }
+        if let Some(cached) = self.cache.artist_list.get("") {
+            return Ok(cached);
+        }
         let artists = self.client.get_artists().await?;
 
-        Ok(artists.into_iter().map(|a| a.into()).collect())
+        let artists: Vec<ArtistInfo> = artists.into_iter().map(|a| a.into()).collect();
+        self.cache.artist_list.insert("", artists.clone());
+        Ok(artists)
     }
 
     async fn get_artist_by_id(&self, artist_id: &str) -> Result<ArtistInfo> {
-        let artist = self.client.get_artist(artist_id).await?;
-        Ok(artist.into())
+        if let Some(cached) = self.cache.artists.get(artist_id) {
+            return Ok(cached);
+        }
+        if let Some(artist) = self.mirror().and_then(|m| m.get_artist(artist_id).ok().flatten()) {
+            return Ok(artist);
+        }
+        let artist: ArtistInfo = self.client.get_artist(artist_id).await?.into();
+        self.cache.artists.insert(artist_id, artist.clone());
+        Ok(artist)
     }
 
     async fn get_artist_songs(&self, artist_id: &str) -> Result<Vec<UnifiedMetadata>> {
-        let artist_detail = self.client.get_artist(artist_id).await?;
-
-        let top_songs = self
-            .client
-            .get_top_songs(&artist_detail.name.unwrap_or_default())
-            .await?;
+        // 热门歌曲由服务器提供, 请求失败时退回镜像中该艺术家的全部歌曲
+        let top_songs = match self.get_top_songs(artist_id).await {
+            Ok(songs) => songs,
+            Err(e) => match self.mirror() {
+                Some(mirror) => {
+                    log::log_warn(&format!("getTopSongs failed, use mirror: {}", e));
+                    return Ok(self.with_stream_url(mirror.get_artist_songs(artist_id)?));
+                }
+                None => return Err(e),
+            },
+        };
 
         let mut metadata_list: Vec<UnifiedMetadata> =
             top_songs.into_iter().map(|s| s.into()).collect();
 
         // 为每个歌曲设置流式 URL
         for meta in &mut metadata_list {
-            meta.stream_url = Some(self.client.get_stream_url(
-                &meta.id,
-                self.max_bitrate,
-                &self.prefer_format,
-            ));
+            meta.stream_url = Some(self.client.get_stream_url(&meta.id, &StreamOptions::default()));
         }
 
         Ok(metadata_list)
     }
 
     async fn list_genres(&self) -> Result<Vec<GenreInfo>> {
+        if let Some(mirror) = self.mirror() {
+            return Ok(mirror.list_genres()?);
+        }
+        if let Some(cached) = self.cache.genres.get("") {
+            return Ok(cached);
+        }
         let artist_detail = self.client.get_genres().await?;
         let genres: Vec<GenreInfo> = artist_detail
             .into_iter()
@@ -292,20 +544,20 @@ impl MusicDataSource for SubsonicDataSource {
             })
             .collect();
 
+        self.cache.genres.insert("", genres.clone());
         Ok(genres)
     }
 
     async fn get_genre_songs(&self, genre: &str) -> Result<Vec<UnifiedMetadata>> {
+        if let Some(mirror) = self.mirror() {
+            return Ok(self.with_stream_url(mirror.get_genre_songs(genre)?));
+        }
         let songs = self.client.get_genre_songs(genre).await?;
         let mut metadata_list: Vec<UnifiedMetadata> = songs.into_iter().map(|s| s.into()).collect();
 
         // 为每个歌曲设置流式 URL
         for meta in &mut metadata_list {
-            meta.stream_url = Some(self.client.get_stream_url(
-                &meta.id,
-                self.max_bitrate,
-                &self.prefer_format,
-            ));
+            meta.stream_url = Some(self.client.get_stream_url(&meta.id, &StreamOptions::default()));
         }
 
         Ok(metadata_list)
@@ -317,26 +569,76 @@ impl MusicDataSource for SubsonicDataSource {
         genre: Option<&str>,
         from_year: Option<&str>,
         to_year: Option<&str>,
+        bpm: BpmRange,
     ) -> Result<Vec<UnifiedMetadata>> {
+        // 镜像不保存 BPM, 按 BPM 筛选时请求服务器
+        if let Some(mirror) = self.mirror().filter(|_| bpm.is_unbounded()) {
+            // 与 Subsonic 的默认数量一致
+            let songs = mirror.get_random_songs(size.unwrap_or(10), genre, from_year, to_year)?;
+            return Ok(self.with_stream_url(songs));
+        }
+        // 服务器不支持按 BPM 筛选, 取最大数量后过滤
+        let fetch = if bpm.is_unbounded() { size } else { Some(500) };
         let songs = self
             .client
-            .get_random_songs(size, genre, from_year, to_year)
+            .get_random_songs(fetch, genre, from_year, to_year)
             .await?;
         let mut metadata_list: Vec<UnifiedMetadata> = songs.into_iter().map(|s| s.into()).collect();
+        if !bpm.is_unbounded() {
+            metadata_list.retain(|m| bpm.contains(m.bpm));
+            metadata_list.truncate(size.unwrap_or(10));
+        }
 
         // 为每个歌曲设置流式 URL
         for meta in &mut metadata_list {
-            meta.stream_url = Some(self.client.get_stream_url(
-                &meta.id,
-                self.max_bitrate,
-                &self.prefer_format,
-            ));
+            meta.stream_url = Some(self.client.get_stream_url(&meta.id, &StreamOptions::default()));
         }
 
         Ok(metadata_list)
     }
 
+    async fn get_similar_songs(
+        &self,
+        seed: &RadioSeed,
+        count: usize,
+        exclude: &[String],
+    ) -> Result<Vec<UnifiedMetadata>> {
+        let (artist_id, genre) = match seed {
+            RadioSeed::Song(id) => {
+                let song = self.get_metadata(id).await?;
+                (song.artist_id, split_genre(&song.genre).into_iter().next())
+            }
+            RadioSeed::Artist(id) => (Some(id.clone()), None),
+            RadioSeed::Genre(genre) => (None, Some(genre.clone())),
+        };
+        // 多取一些, 去掉需要排除的歌曲后仍然够数
+        let fetch = (count + exclude.len() + 1).min(500);
+
+        let mut songs = Vec::new();
+        if let Some(artist_id) = artist_id {
+            match self.client.get_similar_songs2(&artist_id, fetch).await {
+                Ok(list) => songs = self.with_stream_url(list.into_iter().map(|s| s.into()).collect()),
+                // 服务器不支持 (或没有配置 Last.fm) 时退回同风格的随机歌曲
+                Err(e) => log::log_warn(&format!("getSimilarSongs2 failed: {}", e)),
+            }
+        }
+        if songs.is_empty() {
+            if let Some(genre) = genre.filter(|g| !g.is_empty()) {
+                songs = self.get_random_songs(Some(fetch), Some(&genre), None, None, BpmRange::default()).await?;
+            }
+        }
+
+        songs.retain(|s| !exclude.contains(&s.id) && !matches!(seed, RadioSeed::Song(id) if *id == s.id));
+        songs.truncate(count);
+        Ok(songs)
+    }
+
     async fn search(&self, query: &str, pagination: Pagination) -> Result<SearchResult> {
+        if let Some(mirror) = self.mirror() {
+            let mut result = mirror.search(query, &pagination)?;
+            result.songs = self.with_stream_url(result.songs);
+            return Ok(result);
+        }
         let result = self
             .client
             .search3(query, pagination.start(), pagination.page_size)
@@ -348,11 +650,7 @@ impl MusicDataSource for SubsonicDataSource {
             .into_iter()
             .map(|s| {
                 let mut meta: UnifiedMetadata = s.into();
-                meta.stream_url = Some(self.client.get_stream_url(
-                    &meta.id,
-                    self.max_bitrate,
-                    &self.prefer_format,
-                ));
+                meta.stream_url = Some(self.client.get_stream_url(&meta.id, &StreamOptions::default()));
                 meta
             })
             .collect();
@@ -383,11 +681,59 @@ impl MusicDataSource for SubsonicDataSource {
     }
 
     async fn health_check(&self) -> Result<()> {
-        self.client.ping().await
+        match self.client.ping().await {
+            Ok(_) => Ok(()),
+            // 服务器不可用时仍然可以浏览镜像
+            Err(e) if self.mirror().is_some() => {
+                log::log_warn(&format!("Subsonic server unreachable, serving from mirror: {}", e));
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            tag_editing: false,
+            lyrics_editing: false,
+            scanning: true,
+            transcoding: true,
+            play_queue_sync: true,
+            ratings: true,
+            // 没有单独的查询接口, 只能从镜像中查询
+            starred_status: self.mirror.is_some(),
+            metadata_filtering: false,
+            smart_playlists: false,
+            playlist_subscription: false,
+            offline_pinning: self.pins.is_some(),
+            library_sync: self.mirror.is_some(),
+            listening_stats: false,
+            radio: true,
+            daily_mixes: false,
+            duplicate_detection: false,
+        }
     }
 
     async fn scan_music(&self) -> Result<()> {
-        self.client.scan_music().await
+        self.client.scan_music().await?;
+        // 服务器重新扫描后元数据可能变化
+        self.cache.clear();
+        Ok(())
+    }
+
+    async fn clear_cache(&self) -> Result<()> {
+        self.cache.clear();
+        Ok(())
+    }
+
+    async fn sync_library(&self, full: bool) -> Result<()> {
+        let mirror = self
+            .mirror
+            .as_ref()
+            .ok_or_else(|| DataSourceError::Unsupported("Subsonic mirror is not enabled".to_string()))?;
+        mirror.sync(&self.client, full).await?;
+        self.cache.clear();
+        Ok(())
     }
 
     async fn scan_status(&self) -> Result<ScanProgress> {
@@ -406,31 +752,68 @@ impl MusicDataSource for SubsonicDataSource {
         })
     }
 
-    async fn stream_song(&self, song_id: &str, range: Option<String>) -> Result<reqwest::Response> {
-        self.client.stream_song(song_id, range).await
+    async fn stream_song(
+        &self,
+        song_id: &str,
+        range: Option<String>,
+        options: &StreamOptions,
+    ) -> Result<reqwest::Response> {
+        // 未指定的参数使用配置中的默认值
+        let max_bitrate = options.max_bitrate.unwrap_or(self.max_bitrate);
+        let format = options.format.as_deref().unwrap_or(&self.prefer_format);
+        Ok(self
+            .client
+            .stream_song(song_id, range, max_bitrate, format)
+            .await?)
+    }
+
+    async fn pin(&self, item_type: PinItemType, id: &str, pin: bool) -> Result<()> {
+        let pins = self
+            .pins
+            .as_ref()
+            .ok_or_else(|| DataSourceError::Unsupported("Offline pinning is not enabled".to_string()))?;
+        if !pin {
+            return Ok(pins.unpin(item_type, id)?);
+        }
+
+        let songs = match item_type {
+            PinItemType::Song => vec![self.client.get_song(id).await?],
+            PinItemType::Album => self.client.get_album(id).await?.song.unwrap_or_default(),
+            PinItemType::Playlist => self.client.get_playlist(id).await?.entry.unwrap_or_default(),
+        };
+        pins.pin(item_type, id, &songs)?;
+        spawn_download_job(pins.clone(), self.client.clone());
+        Ok(())
+    }
+
+    async fn pin_status(&self) -> Result<PinStatus> {
+        match &self.pins {
+            Some(pins) => Ok(pins.status()?),
+            None => Ok(PinStatus::default()),
+        }
     }
 
     async fn list_playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        if let Some(mirror) = self.mirror() {
+            return Ok(mirror.list_playlists()?);
+        }
+        if let Some(cached) = self.cache.playlists.get("") {
+            return Ok(cached);
+        }
         let playlists = self.client.get_playlists().await?;
 
-        Ok(playlists
-            .into_iter()
-            .map(|p| PlaylistInfo {
-                id: p.id,
-                name: p.name,
-                description: p.comment,
-                cover: p.cover_art,
-                owner: p.owner,
-                public: p.public,
-                song_count: p.song_count.unwrap_or(0) as usize,
-                duration: p.duration,
-                created_at: p.created,
-                updated_at: p.changed,
-            })
-            .collect())
+        let playlists: Vec<PlaylistInfo> = playlists.into_iter().map(convert_playlist_info).collect();
+        self.cache.playlists.insert("", playlists.clone());
+        Ok(playlists)
     }
 
     async fn get_playlist(&self, playlist_id: &str) -> Result<PlaylistDetail> {
+        if let Some(mut playlist) = self.mirror().and_then(|m| m.get_playlist(playlist_id).ok().flatten()) {
+            for meta in &mut playlist.songs {
+                meta.file_url = Some(self.client.get_stream_url(&meta.id, &StreamOptions::default()));
+            }
+            return Ok(playlist);
+        }
         let playlist = self.client.get_playlist(playlist_id).await?;
 
         // 转换歌曲列表
@@ -440,11 +823,7 @@ impl MusicDataSource for SubsonicDataSource {
             .into_iter()
             .map(|s| {
                 let mut meta: UnifiedMetadata = s.into();
-                meta.file_url = Some(self.client.get_stream_url(
-                    &meta.id,
-                    self.max_bitrate,
-                    &self.prefer_format,
-                ));
+                meta.file_url = Some(self.client.get_stream_url(&meta.id, &StreamOptions::default()));
                 meta
             })
             .collect();
@@ -467,6 +846,8 @@ impl MusicDataSource for SubsonicDataSource {
             duration: playlist.duration,
             created_at: playlist.created,
             updated_at: playlist.changed,
+            smart: false,
+            daily_mix: false,
             songs,
         })
     }
@@ -477,7 +858,10 @@ impl MusicDataSource for SubsonicDataSource {
         _description: Option<&str>,
         song_ids: &[String],
     ) -> Result<()> {
-        self.client.create_playlist(name, song_ids).await
+        self.client.create_playlist(name, song_ids).await?;
+        self.cache.invalidate_playlists();
+        self.refresh_mirror_playlist(None).await;
+        Ok(())
     }
 
     async fn update_playlist(
@@ -523,11 +907,115 @@ impl MusicDataSource for SubsonicDataSource {
                 &song_ids_to_add,
                 &song_indexes_to_remove,
             )
-            .await
+            .await?;
+        self.cache.invalidate_playlists();
+        self.refresh_mirror_playlist(Some(playlist_id)).await;
+        Ok(())
+    }
+
+    async fn edit_playlist(&self, playlist_id: &str, edit: &PlaylistEdit) -> Result<()> {
+        let playlist = self.client.get_playlist(playlist_id).await?;
+        let mut song_ids: Vec<String> = playlist
+            .entry
+            .unwrap_or_default()
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+
+        // 先在本地校验编辑操作, 越界时不发起请求
+        edit.apply(&mut song_ids)?;
+
+        let result = match edit {
+            PlaylistEdit::Append { song_ids: to_add } => {
+                self.client
+                    .update_playlist(playlist_id, None, None, None, to_add, &[])
+                    .await
+            }
+            PlaylistEdit::Remove { positions } => {
+                let indexes: Vec<u32> = positions.iter().map(|p| *p as u32).collect();
+                self.client
+                    .update_playlist(playlist_id, None, None, None, &[], &indexes)
+                    .await
+            }
+            // 插入和移动 Subsonic 没有对应接口, 直接替换整个列表
+            _ => self.client.replace_playlist_songs(playlist_id, &song_ids).await,
+        };
+        self.cache.invalidate_playlists();
+        self.refresh_mirror_playlist(Some(playlist_id)).await;
+        Ok(result?)
+    }
+
+    async fn set_playlist_public(&self, playlist_id: &str, public: bool) -> Result<()> {
+        self.client
+            .update_playlist(playlist_id, None, None, Some(public), &[], &[])
+            .await?;
+        self.cache.invalidate_playlists();
+        self.refresh_mirror_playlist(Some(playlist_id)).await;
+        Ok(())
+    }
+
+    async fn set_playlist_cover(&self, _playlist_id: &str, _cover: &str) -> Result<()> {
+        Err(DataSourceError::unsupported(
+            "Custom playlist covers are not supported by the Subsonic data source",
+        ))
+    }
+
+    async fn list_public_playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        // getPlaylists 会同时返回自己的歌单和其他用户公开的歌单
+        let username = self.client.username();
+        let playlists = match self.mirror() {
+            Some(mirror) => mirror.list_playlists()?,
+            None => self
+                .client
+                .get_playlists()
+                .await?
+                .into_iter()
+                .map(convert_playlist_info)
+                .collect(),
+        };
+
+        Ok(playlists
+            .into_iter()
+            .filter(|p| p.public == Some(true) && p.owner.as_deref() != Some(username))
+            .collect())
+    }
+
+    async fn subscribe_playlist(&self, _playlist_id: &str, _subscribe: bool) -> Result<()> {
+        // Subsonic 服务端会自动列出其他用户公开的歌单, 没有订阅接口
+        Err(DataSourceError::unsupported(
+            "Playlist subscription is not supported by the Subsonic data source",
+        ))
+    }
+
+    async fn create_smart_playlist(
+        &self,
+        _name: &str,
+        _description: Option<&str>,
+        _rule: &SmartPlaylistRule,
+    ) -> Result<()> {
+        // Subsonic API 没有智能歌单接口
+        Err(DataSourceError::unsupported(
+            "Smart playlists are not supported by the Subsonic data source",
+        ))
+    }
+
+    async fn update_smart_playlist(
+        &self,
+        _playlist_id: &str,
+        _rule: &SmartPlaylistRule,
+    ) -> Result<()> {
+        Err(DataSourceError::unsupported(
+            "Smart playlists are not supported by the Subsonic data source",
+        ))
     }
 
     async fn delete_playlist(&self, playlist_id: &str) -> Result<()> {
-        self.client.delete_playlist(playlist_id).await
+        self.client.delete_playlist(playlist_id).await?;
+        self.cache.invalidate_playlists();
+        if let Some(mirror) = &self.mirror {
+            mirror.remove_playlist(playlist_id)?;
+        }
+        Ok(())
     }
 
     async fn get_play_queue(&self) -> Result<Option<PlayQueueInfo>> {
@@ -541,11 +1029,7 @@ impl MusicDataSource for SubsonicDataSource {
                     .into_iter()
                     .map(|s| {
                         let mut meta: UnifiedMetadata = s.into();
-                        meta.stream_url = Some(self.client.get_stream_url(
-                            &meta.id,
-                            self.max_bitrate,
-                            &self.prefer_format,
-                        ));
+                        meta.stream_url = Some(self.client.get_stream_url(&meta.id, &StreamOptions::default()));
 
                         if Some(meta.id.clone()).eq(&q.current) {
                             current_song = Some(meta.clone());
@@ -570,9 +1054,10 @@ impl MusicDataSource for SubsonicDataSource {
         current_song_id: Option<String>,
         position: Option<u64>,
     ) -> Result<()> {
-        self.client
+        Ok(self
+            .client
             .save_play_queue(&song_ids, current_song_id.as_deref(), position)
-            .await
+            .await?)
     }
 
     async fn scrobble(
@@ -581,11 +1066,11 @@ impl MusicDataSource for SubsonicDataSource {
         submission: Option<bool>,
         timestamp: Option<u64>,
     ) -> Result<()> {
-        self.client.scrobble(song_id, submission, timestamp).await
+        Ok(self.client.scrobble(song_id, submission, timestamp).await?)
     }
 
     async fn star(&self, id: &str, item_type: StarItemType) -> Result<()> {
-        match item_type {
+        let result = match item_type {
             StarItemType::Song => {
                 self.client.star(Some(id), None, None).await
             }
@@ -595,11 +1080,16 @@ impl MusicDataSource for SubsonicDataSource {
             StarItemType::Artist => {
                 self.client.star(None, None, Some(id)).await
             }
+        };
+        self.cache.invalidate_starred(id, item_type);
+        if let (Ok(_), Some(mirror)) = (&result, &self.mirror) {
+            mirror.set_starred(id, item_type, true)?;
         }
+        Ok(result?)
     }
 
     async fn unstar(&self, id: &str, item_type: StarItemType) -> Result<()> {
-        match item_type {
+        let result = match item_type {
             StarItemType::Song => {
                 self.client.unstar(Some(id), None, None).await
             }
@@ -609,10 +1099,20 @@ impl MusicDataSource for SubsonicDataSource {
             StarItemType::Artist => {
                 self.client.unstar(None, None, Some(id)).await
             }
+        };
+        self.cache.invalidate_starred(id, item_type);
+        if let (Ok(_), Some(mirror)) = (&result, &self.mirror) {
+            mirror.set_starred(id, item_type, false)?;
         }
+        Ok(result?)
     }
 
     async fn get_starred(&self) -> Result<StarredResult> {
+        if let Some(mirror) = self.mirror() {
+            let mut result = mirror.get_starred()?;
+            result.songs = self.with_stream_url(result.songs);
+            return Ok(result);
+        }
         let starred = self.client.get_starred2().await?;
         // println!("starred: {:#?}", starred);
 
@@ -623,11 +1123,7 @@ impl MusicDataSource for SubsonicDataSource {
             .into_iter()
             .map(|s| {
                 let mut meta: UnifiedMetadata = s.into();
-                meta.stream_url = Some(self.client.get_stream_url(
-                    &meta.id,
-                    self.max_bitrate,
-                    &self.prefer_format,
-                ));
+                meta.stream_url = Some(self.client.get_stream_url(&meta.id, &StreamOptions::default()));
                 meta
             })
             .collect();
@@ -655,10 +1151,494 @@ impl MusicDataSource for SubsonicDataSource {
         })
     }
 
-    async fn is_starred(&self, _id: &str, _item_type: StarItemType) -> Result<bool> {
-        // Subsonic API 没有提供单独的检查接口
-        // 需要获取完整收藏列表并检查
-        // 这里暂时返回 false，实际使用时可以通过缓存优化
+    async fn is_starred(&self, id: &str, item_type: StarItemType) -> Result<bool> {
+        // Subsonic API 没有提供单独的检查接口, 开启镜像时从镜像中查询
+        if let Some(mirror) = self.mirror() {
+            return Ok(mirror.is_starred(id, item_type)?.unwrap_or(false));
+        }
         Ok(false)
     }
+
+    async fn set_rating(&self, id: &str, item_type: StarItemType, rating: u8) -> Result<()> {
+        self.client.set_rating(id, rating).await?;
+        // 评分变化后列表和详情中的 userRating 失效
+        self.cache.invalidate_starred(id, item_type);
+        if let Some(mirror) = &self.mirror {
+            mirror.set_rating(id, item_type, rating)?;
+        }
+        Ok(())
+    }
+
+    async fn get_rating(&self, id: &str, item_type: StarItemType) -> Result<Option<u8>> {
+        // 镜像中只有歌曲和专辑的评分
+        let mirrored = match item_type {
+            StarItemType::Song => self
+                .mirror()
+                .and_then(|m| m.get_song(id).ok().flatten())
+                .map(|song| song.rating),
+            StarItemType::Album => self
+                .mirror()
+                .and_then(|m| m.get_album(id).ok().flatten())
+                .map(|album| album.rating),
+            StarItemType::Artist => None,
+        };
+        if let Some(rating) = mirrored {
+            return Ok(rating);
+        }
+
+        let rating = match item_type {
+            StarItemType::Song => {
+                let song: UnifiedMetadata = self.client.get_song(id).await?.into();
+                song.rating
+            }
+            StarItemType::Album => {
+                let album: AlbumInfo = self.client.get_album(id).await?.into();
+                album.rating
+            }
+            StarItemType::Artist => {
+                super::mapper::user_rating(self.client.get_artist(id).await?.user_rating)
+            }
+        };
+        Ok(rating)
+    }
+
+    async fn stats_top(
+        &self,
+        _item_type: StatsItemType,
+        _range: TimeRange,
+        _limit: u32,
+    ) -> Result<Vec<StatsItem>> {
+        Err(stats_unsupported())
+    }
+
+    async fn stats_first_listened(
+        &self,
+        _item_type: StatsItemType,
+        _range: TimeRange,
+        _limit: u32,
+    ) -> Result<Vec<StatsItem>> {
+        Err(stats_unsupported())
+    }
+
+    async fn stats_listening_time(
+        &self,
+        _period: StatsPeriod,
+        _range: TimeRange,
+    ) -> Result<Vec<ListeningTime>> {
+        Err(stats_unsupported())
+    }
+
+    async fn stats_streaks(&self) -> Result<Streaks> {
+        Err(stats_unsupported())
+    }
+
+    async fn stats_heatmap(&self, _range: TimeRange) -> Result<Vec<HeatmapCell>> {
+        Err(stats_unsupported())
+    }
+
+    async fn wrapped(&self, _year: i32) -> Result<Wrapped> {
+        Err(stats_unsupported())
+    }
+
+    async fn wrapped_card(&self, _year: i32) -> Result<Vec<u8>> {
+        Err(stats_unsupported())
+    }
+}
+
+/// 收听统计依赖本地数据库中的播放记录, 播放记录保存在 Subsonic 服务器上
+fn stats_unsupported() -> DataSourceError {
+    DataSourceError::unsupported("Listening stats are not supported by the Subsonic data source")
+}
+
+fn convert_playlist_info(p: super::client::SubsonicPlaylist) -> PlaylistInfo {
+    PlaylistInfo {
+        id: p.id,
+        name: p.name,
+        description: p.comment,
+        cover: p.cover_art,
+        owner: p.owner,
+        public: p.public,
+        song_count: p.song_count.unwrap_or(0) as usize,
+        duration: p.duration,
+        created_at: p.created,
+        updated_at: p.changed,
+        smart: false,
+        daily_mix: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_subsonic::fixtures::Song;
+    use mock_subsonic::MockSubsonic;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn data_source(server: &MockSubsonic) -> SubsonicDataSource {
+        SubsonicDataSource::new(
+            server.url.clone(),
+            server.username.clone(),
+            server.password.clone(),
+            true,
+            320,
+            "raw".to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_browse_and_search() {
+        let server = MockSubsonic::start();
+        let ds = data_source(&server);
+
+        let song = ds.get_metadata("so-3").await.unwrap();
+        assert_eq!(song.title, "晴天");
+        assert_eq!(song.album, "叶惠美");
+        assert_eq!(song.stream_url.as_deref(), Some("/api/stream/so-3"));
+
+        let albums = ds
+            .list_albums(Pagination::new(1, 10), None, Some(AlbumListType::AlphabeticalByName))
+            .await
+            .unwrap();
+        assert_eq!(albums.len(), 3);
+        assert_eq!(albums[0].name, "Kind of Blue");
+
+        let result = ds.search("七里", Pagination::new(1, 10)).await.unwrap();
+        assert_eq!(result.songs.len(), 1);
+        assert_eq!(result.albums.len(), 1);
+
+        let cover = ds.get_cover("al-1", CoverSize::Small).await.unwrap();
+        assert!(cover.starts_with(b"\x89PNG"));
+
+        assert!(matches!(
+            ds.get_metadata("missing").await,
+            Err(DataSourceError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stream_forwards_range() {
+        let server = MockSubsonic::start();
+        let ds = data_source(&server);
+
+        let response = ds
+            .stream_song("so-1", Some("bytes=100-199".to_string()), &StreamOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 206);
+        let body = response.bytes().await.unwrap();
+        assert_eq!(body.to_vec(), server.library().songs[0].audio_data()[100..200].to_vec());
+
+        let request = &server.requests()[0];
+        assert_eq!(request.param("id").as_deref(), Some("so-1"));
+        assert_eq!(request.param("maxBitRate").as_deref(), Some("320"));
+        assert_eq!(request.param("format").as_deref(), Some("raw"));
+    }
+
+    #[tokio::test]
+    async fn test_write_operations() {
+        let server = MockSubsonic::start();
+        let ds = data_source(&server);
+
+        ds.star("so-2", StarItemType::Song).await.unwrap();
+        ds.star("al-2", StarItemType::Album).await.unwrap();
+        let starred = ds.get_starred().await.unwrap();
+        assert_eq!(starred.songs.len(), 1);
+        assert_eq!(starred.songs[0].starred, Some(true));
+        assert_eq!(starred.albums.len(), 1);
+        ds.unstar("so-2", StarItemType::Song).await.unwrap();
+        assert!(!server.library().starred.contains("so-2"));
+
+        ds.scrobble("so-4", Some(true), Some(1_700_000_000_000)).await.unwrap();
+        assert_eq!(server.library().scrobbles[0].id, "so-4");
+        assert_eq!(server.library().scrobbles[0].time, Some(1_700_000_000_000));
+
+        ds.create_playlist("Evening", None, &["so-5".to_string(), "so-6".to_string()])
+            .await
+            .unwrap();
+        let playlists = ds.list_playlists().await.unwrap();
+        assert_eq!(playlists.len(), 2);
+        let evening = playlists.iter().find(|p| p.name == "Evening").unwrap();
+        let detail = ds.get_playlist(&evening.id).await.unwrap();
+        assert_eq!(detail.songs.len(), 2);
+        assert_eq!(detail.songs[1].title, "借口");
+
+        ds.save_play_queue(
+            vec!["so-1".to_string(), "so-2".to_string()],
+            Some("so-2".to_string()),
+            Some(3000),
+        )
+        .await
+        .unwrap();
+        let queue = ds.get_play_queue().await.unwrap().unwrap();
+        assert_eq!(queue.songs.len(), 2);
+        assert_eq!(queue.current_song.unwrap().id, "so-2");
+        assert_eq!(queue.position, Some(3000));
+    }
+
+    #[tokio::test]
+    async fn test_ratings() {
+        let server = MockSubsonic::start();
+        let ds = data_source(&server);
+
+        ds.set_rating("so-1", StarItemType::Song, 4).await.unwrap();
+        assert_eq!(ds.get_rating("so-1", StarItemType::Song).await.unwrap(), Some(4));
+        assert_eq!(ds.get_metadata("so-1").await.unwrap().rating, Some(4));
+
+        ds.set_rating("al-1", StarItemType::Album, 5).await.unwrap();
+        ds.set_rating("al-2", StarItemType::Album, 2).await.unwrap();
+        let rated = ds
+            .list_albums(Pagination::new(1, 10), None, Some(AlbumListType::ByRating { min_rating: 3 }))
+            .await
+            .unwrap();
+        assert_eq!(rated.len(), 1);
+        assert_eq!(rated[0].id, "al-1");
+        assert_eq!(rated[0].rating, Some(5));
+
+        ds.set_rating("so-1", StarItemType::Song, 0).await.unwrap();
+        assert_eq!(ds.get_rating("so-1", StarItemType::Song).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_albums_by_rating_across_pages() {
+        let server = MockSubsonic::start();
+        server.update(|lib| {
+            let mut album = lib.albums[0].clone();
+            for (id, rating) in [("al-4", Some(3)), ("al-5", None), ("al-6", Some(1))] {
+                album.id = id.to_string();
+                lib.albums.push(album.clone());
+                if let Some(rating) = rating {
+                    lib.ratings.insert(id.to_string(), rating);
+                }
+            }
+            lib.ratings.insert("al-1".to_string(), 5);
+            lib.ratings.insert("al-2".to_string(), 2);
+            lib.ratings.insert("al-3".to_string(), 4);
+        });
+        let ds = data_source(&server);
+        let rated = |page| {
+            ds.list_albums(Pagination::new(page, 2), None, Some(AlbumListType::ByRating { min_rating: 3 }))
+        };
+
+        let first: Vec<String> = rated(1).await.unwrap().into_iter().map(|a| a.id).collect();
+        assert_eq!(first, vec!["al-1", "al-3"]);
+        assert_eq!(server.count("getAlbumList2"), 1);
+
+        // 第二页需要跳过第一页的结果, 过滤掉评分更低的专辑后继续请求下一页
+        let second: Vec<String> = rated(2).await.unwrap().into_iter().map(|a| a.id).collect();
+        assert_eq!(second, vec!["al-4"]);
+        assert_eq!(server.count("getAlbumList2"), 3);
+
+        assert!(rated(3).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_similar_songs() {
+        let server = MockSubsonic::start();
+        let ds = data_source(&server);
+
+        let songs = ds
+            .get_similar_songs(&RadioSeed::Song("so-3".into()), 2, &["so-4".to_string()])
+            .await
+            .unwrap();
+        let ids: Vec<&str> = songs.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["so-5", "so-6"]);
+        assert_eq!(server.count("getSimilarSongs2"), 1);
+        assert!(songs[0].stream_url.is_some());
+
+        let songs = ds
+            .get_similar_songs(&RadioSeed::Genre("Jazz".into()), 10, &[])
+            .await
+            .unwrap();
+        assert_eq!(songs.len(), 2);
+    }
+
+    /// 在临时目录中创建数据库, 返回数据库路径和目录
+    fn temp_db(name: &str) -> (String, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("musicflow-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("test.db");
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        conn.execute_batch(&crate::datasource::local::table::sql()).unwrap();
+        (db_path.to_string_lossy().to_string(), dir)
+    }
+
+    #[tokio::test]
+    async fn test_mirror_incremental_sync() {
+        let server = MockSubsonic::start();
+        let ds = data_source(&server);
+        let (db_path, dir) = temp_db("mirror");
+        let mirror = SubsonicMirror::new(ds.server_key(), &db_path, dir.to_str().unwrap());
+
+        mirror.sync(&ds.client, false).await.unwrap();
+        assert!(mirror.is_ready());
+        assert_eq!(server.count("getAlbum"), 3);
+        assert_eq!(mirror.get_album_songs("al-3").unwrap().len(), 2);
+        assert_eq!(mirror.get_playlist("pl-1").unwrap().unwrap().songs.len(), 2);
+
+        // 音乐库没有变化时不重新获取专辑
+        mirror.sync(&ds.client, false).await.unwrap();
+        assert_eq!(server.count("getAlbumList2"), 1);
+        assert_eq!(server.count("getAlbum"), 3);
+
+        // 删除一张专辑, 另一张专辑新增歌曲
+        server.update(|lib| {
+            lib.albums.retain(|a| a.id != "al-3");
+            lib.songs.retain(|s| s.album_id != "al-3");
+            lib.songs.push(Song {
+                id: "so-7".to_string(),
+                title: "Blue in Green".to_string(),
+                album_id: "al-1".to_string(),
+                artist_id: "ar-1".to_string(),
+                track: 3,
+                duration: 337,
+                size: 8192,
+            });
+        });
+        mirror.sync(&ds.client, false).await.unwrap();
+        // 只重新获取变化的专辑
+        assert_eq!(server.count("getAlbum"), 4);
+        assert!(mirror.get_album("al-3").unwrap().is_none());
+        assert!(mirror.get_song("so-5").unwrap().is_none());
+        let songs = mirror.get_album_songs("al-1").unwrap();
+        assert_eq!(songs.len(), 3);
+        assert!(songs.iter().any(|s| s.id == "so-7"));
+        assert_eq!(mirror.get_album_songs("al-2").unwrap().len(), 2);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_mirror_random_songs_by_bpm() {
+        let server = MockSubsonic::start();
+        server.update(|lib| {
+            lib.bpms.insert("so-1".to_string(), 90);
+            lib.bpms.insert("so-2".to_string(), 128);
+        });
+        let mut ds = data_source(&server);
+        let (db_path, dir) = temp_db("mirror-bpm");
+        let mirror = Arc::new(SubsonicMirror::new(ds.server_key(), &db_path, dir.to_str().unwrap()));
+        mirror.sync(&ds.client, false).await.unwrap();
+        ds.mirror = Some(mirror);
+
+        // 没有 BPM 限制时从镜像读取
+        let songs = ds.get_random_songs(Some(3), None, None, None, BpmRange::default()).await.unwrap();
+        assert_eq!(songs.len(), 3);
+        assert_eq!(server.count("getRandomSongs"), 0);
+
+        // 镜像不保存 BPM, 按 BPM 筛选时请求服务器
+        let bpm = BpmRange { min: Some(120.0), max: Some(140.0) };
+        let songs = ds.get_random_songs(Some(3), None, None, None, bpm).await.unwrap();
+        assert_eq!(server.count("getRandomSongs"), 1);
+        assert_eq!(songs.iter().map(|s| s.id.as_str()).collect::<Vec<_>>(), vec!["so-2"]);
+    }
+
+    #[tokio::test]
+    async fn test_mirror_ratings() {
+        let server = MockSubsonic::start();
+        server.update(|lib| {
+            lib.ratings.insert("so-1".to_string(), 4);
+            lib.ratings.insert("al-1".to_string(), 3);
+            lib.ratings.insert("al-2".to_string(), 5);
+        });
+        let mut ds = data_source(&server);
+        let (db_path, dir) = temp_db("mirror-rating");
+        let mirror = Arc::new(SubsonicMirror::new(ds.server_key(), &db_path, dir.to_str().unwrap()));
+        mirror.sync(&ds.client, false).await.unwrap();
+        ds.mirror = Some(mirror.clone());
+
+        assert_eq!(mirror.get_song("so-1").unwrap().unwrap().rating, Some(4));
+        assert_eq!(mirror.get_album("al-2").unwrap().unwrap().rating, Some(5));
+        assert_eq!(ds.get_rating("so-1", StarItemType::Song).await.unwrap(), Some(4));
+        let requests = server.requests().len();
+        assert_eq!(ds.get_metadata("so-1").await.unwrap().rating, Some(4));
+        assert_eq!(server.requests().len(), requests);
+
+        // 修改评分后同时更新镜像
+        ds.set_rating("so-1", StarItemType::Song, 2).await.unwrap();
+        ds.set_rating("al-2", StarItemType::Album, 0).await.unwrap();
+        assert_eq!(mirror.get_song("so-1").unwrap().unwrap().rating, Some(2));
+        assert_eq!(mirror.get_album("al-2").unwrap().unwrap().rating, None);
+
+        // 服务器不可用时按镜像中的评分过滤
+        let rated = mirror
+            .list_albums(&AlbumListType::ByRating { min_rating: 1 }, None, &Pagination::new(1, 10))
+            .unwrap();
+        let ids: Vec<&str> = rated.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, vec!["al-1"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// 等待所有固定的歌曲下载完成或失败
+    async fn wait_pin_downloads(ds: &SubsonicDataSource) -> PinStatus {
+        for _ in 0..100 {
+            let status = ds.pin_status().await.unwrap();
+            if status.items.iter().all(|i| i.downloaded + i.failed == i.song_count) {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("pinned songs are not downloaded");
+    }
+
+    #[tokio::test]
+    async fn test_pin_quota_and_local_stream() {
+        let server = MockSubsonic::start();
+        let (db_path, dir) = temp_db("pin");
+        // 配额只能放下 3 首歌
+        let ds = data_source(&server).with_pins(&db_path, dir.to_str().unwrap(), 8192 * 3);
+
+        ds.pin(PinItemType::Album, "al-1", true).await.unwrap();
+        let status = wait_pin_downloads(&ds).await;
+        assert_eq!(status.used, 8192 * 2);
+        assert_eq!(status.items[0].downloaded, 2);
+
+        // 已下载的歌曲直接读取本地文件
+        let requests = server.requests().len();
+        let AudioStream::LocalFile(path) = ds.get_audio_stream("so-1").await.unwrap() else {
+            panic!("pinned song is not served from the local file");
+        };
+        assert_eq!(std::fs::read(&path).unwrap(), server.library().songs[0].audio_data());
+        assert_eq!(server.requests().len(), requests);
+
+        // 超出配额的歌曲下载失败
+        ds.pin(PinItemType::Album, "al-2", true).await.unwrap();
+        let status = wait_pin_downloads(&ds).await;
+        assert_eq!(status.used, 8192 * 3);
+        let album = status.items.iter().find(|i| i.item_id == "al-2").unwrap();
+        assert_eq!((album.downloaded, album.failed), (1, 1));
+
+        // 取消固定后删除文件并释放空间, 重新固定时重试失败的歌曲
+        ds.pin(PinItemType::Album, "al-1", false).await.unwrap();
+        assert!(!path.exists());
+        assert!(matches!(
+            ds.get_audio_stream("so-1").await.unwrap(),
+            AudioStream::SubsonicStream { .. }
+        ));
+        ds.pin(PinItemType::Album, "al-2", true).await.unwrap();
+        let status = wait_pin_downloads(&ds).await;
+        assert_eq!(status.used, 8192 * 2);
+        assert_eq!(status.items[0].downloaded, 2);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_password() {
+        let server = MockSubsonic::start();
+        let ds = SubsonicDataSource::new(
+            server.url.clone(),
+            server.username.clone(),
+            "wrong".to_string(),
+            true,
+            320,
+            "mp3".to_string(),
+        );
+        assert!(matches!(
+            ds.get_metadata("so-1").await,
+            Err(DataSourceError::Unauthorized(_))
+        ));
+    }
 }
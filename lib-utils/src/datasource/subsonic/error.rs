@@ -0,0 +1,123 @@
+// Subsonic 客户端错误类型
+// 将 Subsonic 错误码和网络错误映射为具体的错误, 方便上层区分处理
+
+use std::fmt;
+
+use crate::datasource::error::DataSourceError;
+
+/// Subsonic 客户端错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubsonicClientError {
+    /// 用户名或密码错误 (错误码 40, 41)
+    Unauthorized(String),
+    /// 当前用户没有权限 (错误码 50)
+    Forbidden(String),
+    /// 请求的数据不存在 (错误码 70)
+    NotFound(String),
+    /// 客户端与服务器的 API 版本不兼容 (错误码 20, 30)
+    VersionMismatch(String),
+    /// 服务器返回的其他错误
+    Api { code: i32, message: String },
+    /// 请求超时
+    Timeout,
+    /// 无法连接服务器
+    Network(String),
+    /// 服务器返回了非成功的 HTTP 状态码
+    Http(u16),
+    /// 响应无法解析
+    InvalidResponse(String),
+    /// 连续失败次数过多, 熔断期间不再请求服务器
+    CircuitOpen,
+}
+
+impl SubsonicClientError {
+    /// 根据 Subsonic 错误码创建错误
+    pub fn from_code(code: i32, message: String) -> Self {
+        match code {
+            20 | 30 => SubsonicClientError::VersionMismatch(message),
+            40 | 41 => SubsonicClientError::Unauthorized(message),
+            50 => SubsonicClientError::Forbidden(message),
+            70 => SubsonicClientError::NotFound(message),
+            _ => SubsonicClientError::Api { code, message },
+        }
+    }
+
+    pub fn from_reqwest(e: &reqwest::Error) -> Self {
+        if e.is_timeout() {
+            SubsonicClientError::Timeout
+        } else {
+            SubsonicClientError::Network(e.to_string())
+        }
+    }
+
+    /// 是否为服务器不可用导致的错误, 这类错误会重试并计入熔断
+    pub fn is_transient(&self) -> bool {
+        match self {
+            SubsonicClientError::Timeout | SubsonicClientError::Network(_) => true,
+            SubsonicClientError::Http(status) => *status >= 500 || *status == 429,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for SubsonicClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubsonicClientError::Unauthorized(msg) => write!(f, "Subsonic authentication failed: {}", msg),
+            SubsonicClientError::Forbidden(msg) => write!(f, "Subsonic permission denied: {}", msg),
+            SubsonicClientError::NotFound(msg) => write!(f, "Subsonic data not found: {}", msg),
+            SubsonicClientError::VersionMismatch(msg) => {
+                write!(f, "Subsonic API version mismatch: {}", msg)
+            }
+            SubsonicClientError::Api { code, message } => {
+                write!(f, "Subsonic error {}: {}", code, message)
+            }
+            SubsonicClientError::Timeout => write!(f, "Subsonic request timed out"),
+            SubsonicClientError::Network(msg) => write!(f, "Subsonic server unreachable: {}", msg),
+            SubsonicClientError::Http(status) => write!(f, "Subsonic HTTP error: {}", status),
+            SubsonicClientError::InvalidResponse(msg) => {
+                write!(f, "Invalid Subsonic response: {}", msg)
+            }
+            SubsonicClientError::CircuitOpen => {
+                write!(f, "Subsonic server is temporarily unavailable")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SubsonicClientError {}
+
+/// 客户端返回前转换为数据源错误, 接口按错误类型返回对应的状态码
+impl From<SubsonicClientError> for DataSourceError {
+    fn from(e: SubsonicClientError) -> Self {
+        match e {
+            SubsonicClientError::NotFound(msg) => DataSourceError::NotFound(msg),
+            SubsonicClientError::Unauthorized(msg) | SubsonicClientError::Forbidden(msg) => {
+                DataSourceError::Unauthorized(msg)
+            }
+            SubsonicClientError::Timeout => DataSourceError::Timeout(e.to_string()),
+            SubsonicClientError::CircuitOpen => DataSourceError::Unavailable(e.to_string()),
+            e => DataSourceError::Upstream(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_data_source_error() {
+        let e: DataSourceError = anyhow::Error::new(DataSourceError::from(SubsonicClientError::from_code(
+            70,
+            "Song not found".into(),
+        )))
+        .context("get song")
+        .into();
+        assert!(matches!(e, DataSourceError::NotFound(_)));
+
+        assert!(matches!(DataSourceError::from(SubsonicClientError::Timeout), DataSourceError::Timeout(_)));
+        assert!(matches!(DataSourceError::from(SubsonicClientError::CircuitOpen), DataSourceError::Unavailable(_)));
+        assert!(matches!(DataSourceError::from(SubsonicClientError::Http(502)), DataSourceError::Upstream(_)));
+    }
+}
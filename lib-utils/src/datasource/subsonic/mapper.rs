@@ -30,6 +30,18 @@ impl From<SubsonicSong> for UnifiedMetadata {
             album_id: song.album_id.clone(),
             artist_id: song.artist_id.clone(),
             starred: song.starred,
+            play_count: song.play_count,
+            last_played: song.played.as_deref().and_then(parse_timestamp_millis),
+            skip_count: None,
+            rating: user_rating(song.user_rating),
+            replay_gain: song.replay_gain.map(|g| ReplayGain {
+                track_gain: g.track_gain,
+                track_peak: g.track_peak,
+                album_gain: g.album_gain,
+                album_peak: g.album_peak,
+            }),
+            bpm: song.bpm.filter(|bpm| *bpm > 0).map(f64::from),
+            musical_key: None,
         }
     }
 }
@@ -43,10 +55,19 @@ impl From<SubsonicAlbum> for AlbumInfo {
             year: album.year.map(|y| y.to_string()).unwrap_or_default(),
             cover_art: album.cover_art,
             song_count: album.song_count.unwrap_or(0) as usize,
+            play_count: album.play_count,
+            last_played: album.played.as_deref().and_then(parse_timestamp_millis),
+            skip_count: None,
+            rating: user_rating(album.user_rating),
         }
     }
 }
 
+/// Subsonic 的 userRating 为 1-5, 0 或缺失表示未评分
+pub(super) fn user_rating(rating: Option<u32>) -> Option<u8> {
+    rating.filter(|r| (1..=5).contains(r)).map(|r| r as u8)
+}
+
 impl From<SubsonicArtist> for ArtistInfo {
     fn from(artist: SubsonicArtist) -> Self {
         ArtistInfo {
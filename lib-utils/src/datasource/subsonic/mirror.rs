@@ -0,0 +1,914 @@
+// Subsonic 音乐库镜像
+// 将艺术家、专辑、歌曲、流派、歌单和收藏同步到本地数据库, 封面保存到磁盘,
+// 浏览和搜索直接读取镜像, 服务器不可用时仍然可以使用
+
+use anyhow::Result;
+use md5::{Digest, Md5};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use super::client::{
+    Genre, SubsonicAlbum, SubsonicArtist, SubsonicClient, SubsonicPlaylist, SubsonicSong,
+};
+use super::mapper::user_rating;
+use crate::datasource::types::*;
+use crate::log;
+
+/// 每次请求的专辑数量
+const ALBUM_PAGE_SIZE: usize = 500;
+
+const SONG_COLUMNS: &str = "s.id, s.title, s.artist, s.artist_id, s.album, s.album_id, s.year, \
+    s.genre, s.track, s.duration, s.bitrate, s.suffix, s.path, s.cover_art, s.starred, s.rating";
+
+const ALBUM_COLUMNS: &str = "id, name, artist, year, cover_art, song_count, rating";
+
+/// Subsonic 音乐库镜像
+pub struct SubsonicMirror {
+    // 区分不同服务器的镜像数据, 形如 "user@http://music.example.com"
+    server: String,
+    // 数据库路径
+    db_path: String,
+    // 封面保存目录
+    cover_dir: PathBuf,
+    // 防止同步任务重复执行
+    syncing: tokio::sync::Mutex<()>,
+    // 是否已经完成过同步
+    ready: AtomicBool,
+}
+
+impl SubsonicMirror {
+    pub fn new(server: String, db_path: &str, cover_dir: &str) -> Self {
+        Self {
+            cover_dir: server_dir(cover_dir, &server),
+            server,
+            db_path: db_path.to_string(),
+            syncing: tokio::sync::Mutex::new(()),
+            ready: AtomicBool::new(false),
+        }
+    }
+
+    /// 是否已经完成过至少一次同步 (包括之前运行时的同步)
+    pub fn is_ready(&self) -> bool {
+        if self.ready.load(Ordering::Relaxed) {
+            return true;
+        }
+        let ready = matches!(self.get_state("last_sync"), Ok(Some(_)));
+        if ready {
+            self.ready.store(true, Ordering::Relaxed);
+        }
+        ready
+    }
+
+    fn connect(&self) -> rusqlite::Result<Connection> {
+        Connection::open(&self.db_path)
+    }
+
+    fn get_state(&self, key: &str) -> Result<Option<String>> {
+        let conn = self.connect()?;
+        let value = conn
+            .query_row(
+                "SELECT value FROM subsonic_sync_state WHERE server = ?1 AND key = ?2",
+                params![self.server, key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(value)
+    }
+
+    fn set_state(&self, conn: &Connection, key: &str, value: &str) -> Result<()> {
+        conn.execute(
+            "INSERT OR REPLACE INTO subsonic_sync_state (server, key, value) VALUES (?1, ?2, ?3)",
+            params![self.server, key, value],
+        )?;
+        Ok(())
+    }
+
+    /// 同步音乐库
+    ///
+    /// 非全量同步时通过 `getIndexes` 的 `ifModifiedSince` 判断音乐库是否变化,
+    /// 没有变化时只同步歌单和收藏; 有变化时只重新获取新增或变化的专辑
+    pub async fn sync(&self, client: &SubsonicClient, full: bool) -> Result<()> {
+        let _guard = self
+            .syncing
+            .try_lock()
+            .map_err(|_| anyhow::anyhow!("Subsonic mirror sync is already running"))?;
+
+        let since = if full {
+            None
+        } else {
+            self.get_state("last_modified")?.and_then(|v| v.parse::<i64>().ok())
+        };
+        let last_modified = client.get_indexes_last_modified(since).await?;
+        let library_changed = since.is_none_or(|s| last_modified > s);
+
+        if library_changed {
+            self.sync_library(client, full).await?;
+            let conn = self.connect()?;
+            self.set_state(&conn, "last_modified", &last_modified.to_string())?;
+        }
+        self.sync_playlists(client).await?;
+        self.sync_starred(client).await?;
+
+        let conn = self.connect()?;
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        self.set_state(&conn, "last_sync", &now)?;
+        self.ready.store(true, Ordering::Relaxed);
+        log::log_info(&format!(
+            "Subsonic mirror synced: server={}, library_changed={}",
+            self.server, library_changed
+        ));
+        Ok(())
+    }
+
+    async fn sync_library(&self, client: &SubsonicClient, full: bool) -> Result<()> {
+        let artists = client.get_artists().await?;
+
+        let mut albums: Vec<SubsonicAlbum> = vec![];
+        loop {
+            let page = client
+                .get_album_list2("alphabeticalByName", ALBUM_PAGE_SIZE, albums.len(), None, None, None)
+                .await?;
+            let count = page.len();
+            albums.extend(page);
+            if count < ALBUM_PAGE_SIZE {
+                break;
+            }
+        }
+
+        // 只重新获取新增或变化的专辑的歌曲
+        let known = self.album_signatures()?;
+        let mut details: Vec<SubsonicAlbum> = vec![];
+        for album in &albums {
+            if !full && known.get(&album.id) == Some(&album_signature(album)) {
+                continue;
+            }
+            match client.get_album(&album.id).await {
+                Ok(detail) => details.push(detail),
+                Err(e) => log::log_warn(&format!("Mirror get album {} failed: {}", album.id, e)),
+            }
+        }
+
+        let genres = client.get_genres().await?;
+
+        self.write_library(&artists, &albums, &details, &genres)?;
+
+        // 下载变化专辑的封面
+        for album in &details {
+            if let Some(cover_art) = &album.cover_art {
+                self.prefetch_cover(client, cover_art).await;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_library(
+        &self,
+        artists: &[SubsonicArtist],
+        albums: &[SubsonicAlbum],
+        details: &[SubsonicAlbum],
+        genres: &[Genre],
+    ) -> Result<()> {
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM subsonic_artist WHERE server = ?1", params![self.server])?;
+        for artist in artists {
+            let Some(id) = &artist.id else { continue };
+            tx.execute(
+                "INSERT OR REPLACE INTO subsonic_artist (server, id, name, album_count, cover_art)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    self.server,
+                    id,
+                    artist.name.clone().unwrap_or_default(),
+                    artist.album_count.unwrap_or(0),
+                    artist.cover_art
+                ],
+            )?;
+        }
+
+        tx.execute("DELETE FROM subsonic_album WHERE server = ?1", params![self.server])?;
+        for album in albums {
+            insert_album(&tx, &self.server, album)?;
+        }
+        // 删除已经不存在的专辑的歌曲
+        tx.execute(
+            "DELETE FROM subsonic_song WHERE server = ?1
+             AND album_id NOT IN (SELECT id FROM subsonic_album WHERE server = ?1)",
+            params![self.server],
+        )?;
+        for album in details {
+            tx.execute(
+                "DELETE FROM subsonic_song WHERE server = ?1 AND album_id = ?2",
+                params![self.server, album.id],
+            )?;
+            for song in album.song.iter().flatten() {
+                insert_song(&tx, &self.server, song, true)?;
+            }
+        }
+
+        tx.execute("DELETE FROM subsonic_genre WHERE server = ?1", params![self.server])?;
+        for genre in genres {
+            let Some(value) = &genre.value else { continue };
+            tx.execute(
+                "INSERT OR REPLACE INTO subsonic_genre (server, value, album_count, song_count)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    self.server,
+                    value,
+                    genre.album_count.unwrap_or(0),
+                    genre.song_count.unwrap_or(0)
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn album_signatures(&self) -> Result<HashMap<String, (String, u32, u32)>> {
+        let conn = self.connect()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, name, song_count, duration FROM subsonic_album WHERE server = ?1",
+        )?;
+        let rows = stmt.query_map(params![self.server], |row| {
+            Ok((row.get(0)?, (row.get(1)?, row.get(2)?, row.get(3)?)))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    async fn sync_playlists(&self, client: &SubsonicClient) -> Result<()> {
+        let playlists = client.get_playlists().await?;
+
+        let known: HashMap<String, Option<String>> = {
+            let conn = self.connect()?;
+            let mut stmt =
+                conn.prepare("SELECT id, changed FROM subsonic_playlist WHERE server = ?1")?;
+            let rows = stmt.query_map(params![self.server], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+
+        // 只重新获取修改时间变化的歌单
+        let mut details: Vec<SubsonicPlaylist> = vec![];
+        for playlist in &playlists {
+            if playlist.changed.is_some() && known.get(&playlist.id) == Some(&playlist.changed) {
+                continue;
+            }
+            details.push(client.get_playlist(&playlist.id).await?);
+        }
+
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM subsonic_playlist WHERE server = ?1", params![self.server])?;
+        for playlist in &playlists {
+            insert_playlist(&tx, &self.server, playlist)?;
+        }
+        tx.execute(
+            "DELETE FROM subsonic_playlist_song WHERE server = ?1
+             AND playlist_id NOT IN (SELECT id FROM subsonic_playlist WHERE server = ?1)",
+            params![self.server],
+        )?;
+        for playlist in &details {
+            insert_playlist_songs(&tx, &self.server, playlist)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 重新同步单个歌单, 在修改歌单后调用
+    pub async fn refresh_playlist(&self, client: &SubsonicClient, playlist_id: &str) -> Result<()> {
+        let playlist = client.get_playlist(playlist_id).await?;
+
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        insert_playlist(&tx, &self.server, &playlist)?;
+        insert_playlist_songs(&tx, &self.server, &playlist)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 重新同步歌单列表, 在创建歌单后调用
+    pub async fn refresh_playlists(&self, client: &SubsonicClient) -> Result<()> {
+        self.sync_playlists(client).await
+    }
+
+    pub fn remove_playlist(&self, playlist_id: &str) -> Result<()> {
+        let conn = self.connect()?;
+        conn.execute(
+            "DELETE FROM subsonic_playlist WHERE server = ?1 AND id = ?2",
+            params![self.server, playlist_id],
+        )?;
+        conn.execute(
+            "DELETE FROM subsonic_playlist_song WHERE server = ?1 AND playlist_id = ?2",
+            params![self.server, playlist_id],
+        )?;
+        Ok(())
+    }
+
+    async fn sync_starred(&self, client: &SubsonicClient) -> Result<()> {
+        let starred = client.get_starred2().await?;
+
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        for table in ["subsonic_song", "subsonic_album", "subsonic_artist"] {
+            tx.execute(
+                &format!("UPDATE {} SET starred = 0 WHERE server = ?1", table),
+                params![self.server],
+            )?;
+        }
+        for song in starred.song.iter().flatten() {
+            // 收藏的歌曲可能不在已同步的专辑中, 一并写入
+            insert_song(&tx, &self.server, song, false)?;
+            set_starred(&tx, &self.server, "subsonic_song", &song.id, true)?;
+        }
+        for album in starred.album.iter().flatten() {
+            set_starred(&tx, &self.server, "subsonic_album", &album.id, true)?;
+        }
+        for artist in starred.artist.iter().flatten() {
+            if let Some(id) = &artist.id {
+                set_starred(&tx, &self.server, "subsonic_artist", id, true)?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 评分变化后更新镜像, 0 表示取消评分
+    pub fn set_rating(&self, id: &str, item_type: StarItemType, rating: u8) -> Result<()> {
+        let table = match item_type {
+            StarItemType::Song => "subsonic_song",
+            StarItemType::Album => "subsonic_album",
+            // 镜像中没有艺术家评分
+            StarItemType::Artist => return Ok(()),
+        };
+        let conn = self.connect()?;
+        conn.execute(
+            &format!("UPDATE {} SET rating = ?1 WHERE server = ?2 AND id = ?3", table),
+            params![user_rating(Some(rating as u32)), self.server, id],
+        )?;
+        Ok(())
+    }
+
+    /// 收藏状态变化后更新镜像
+    pub fn set_starred(&self, id: &str, item_type: StarItemType, starred: bool) -> Result<()> {
+        let table = match item_type {
+            StarItemType::Song => "subsonic_song",
+            StarItemType::Album => "subsonic_album",
+            StarItemType::Artist => "subsonic_artist",
+        };
+        let conn = self.connect()?;
+        set_starred(&conn, &self.server, table, id, starred)
+    }
+
+    pub fn is_starred(&self, id: &str, item_type: StarItemType) -> Result<Option<bool>> {
+        let table = match item_type {
+            StarItemType::Song => "subsonic_song",
+            StarItemType::Album => "subsonic_album",
+            StarItemType::Artist => "subsonic_artist",
+        };
+        let conn = self.connect()?;
+        let starred = conn
+            .query_row(
+                &format!("SELECT starred FROM {} WHERE server = ?1 AND id = ?2", table),
+                params![self.server, id],
+                |row| row.get::<_, bool>(0),
+            )
+            .optional()?;
+        Ok(starred)
+    }
+
+    fn query_songs(&self, condition: &str, order: &str, args: Vec<Value>) -> Result<Vec<UnifiedMetadata>> {
+        let sql = format!(
+            "SELECT {} FROM subsonic_song s WHERE s.server = ? {} {}",
+            SONG_COLUMNS, condition, order
+        );
+        let mut values = vec![Value::Text(self.server.clone())];
+        values.extend(args);
+
+        let conn = self.connect()?;
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(values), covert_row_to_song)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn query_albums(&self, condition: &str, order: &str, args: Vec<Value>) -> Result<Vec<AlbumInfo>> {
+        let sql = format!(
+            "SELECT {} FROM subsonic_album WHERE server = ? {} {}",
+            ALBUM_COLUMNS, condition, order
+        );
+        let mut values = vec![Value::Text(self.server.clone())];
+        values.extend(args);
+
+        let conn = self.connect()?;
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(values), covert_row_to_album)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn query_artists(&self, condition: &str, order: &str, args: Vec<Value>) -> Result<Vec<ArtistInfo>> {
+        let sql = format!(
+            "SELECT id, name, album_count, cover_art FROM subsonic_artist WHERE server = ? {} {}",
+            condition, order
+        );
+        let mut values = vec![Value::Text(self.server.clone())];
+        values.extend(args);
+
+        let conn = self.connect()?;
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(values), |row| {
+            Ok(ArtistInfo {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                album_count: row.get::<_, i64>(2)? as usize,
+                cover_art: row.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn get_song(&self, id: &str) -> Result<Option<UnifiedMetadata>> {
+        let songs = self.query_songs("AND s.id = ?", "", vec![Value::Text(id.to_string())])?;
+        Ok(songs.into_iter().next())
+    }
+
+    pub fn get_album(&self, id: &str) -> Result<Option<AlbumInfo>> {
+        let albums = self.query_albums("AND id = ?", "", vec![Value::Text(id.to_string())])?;
+        Ok(albums.into_iter().next())
+    }
+
+    pub fn get_album_songs(&self, album_id: &str) -> Result<Vec<UnifiedMetadata>> {
+        self.query_songs(
+            "AND s.album_id = ?",
+            "ORDER BY CAST(s.track AS INTEGER), s.title",
+            vec![Value::Text(album_id.to_string())],
+        )
+    }
+
+    /// 获取专辑列表
+    ///
+    /// 最常播放、最近播放需要服务器的播放记录, 镜像中按最新添加排序
+    pub fn list_albums(
+        &self,
+        list_type: &AlbumListType,
+        filter_text: Option<&str>,
+        pagination: &Pagination,
+    ) -> Result<Vec<AlbumInfo>> {
+        let mut condition = String::new();
+        let mut args: Vec<Value> = vec![];
+
+        if let Some(text) = filter_text.map(str::trim).filter(|t| !t.is_empty()) {
+            condition.push_str(" AND (name LIKE ? OR artist LIKE ?)");
+            let pattern = format!("%{}%", text);
+            args.push(Value::Text(pattern.clone()));
+            args.push(Value::Text(pattern));
+        }
+
+        let order = match list_type {
+            AlbumListType::Random => "ORDER BY RANDOM()",
+            AlbumListType::AlphabeticalByName => "ORDER BY name COLLATE NOCASE",
+            AlbumListType::Starred => {
+                condition.push_str(" AND starred = 1");
+                "ORDER BY name COLLATE NOCASE"
+            }
+            AlbumListType::ByGenre { genre } => {
+                condition.push_str(" AND genre = ?");
+                args.push(Value::Text(genre.clone()));
+                "ORDER BY name COLLATE NOCASE"
+            }
+            AlbumListType::ByYear { from_year, to_year } => {
+                let from = from_year.unwrap_or(0);
+                let to = to_year.unwrap_or(9999);
+                condition.push_str(" AND CAST(year AS INTEGER) BETWEEN ? AND ?");
+                args.push(Value::Integer(from.min(to) as i64));
+                args.push(Value::Integer(from.max(to) as i64));
+                // 与 Subsonic 一致, fromYear 大于 toYear 时倒序
+                if from > to {
+                    "ORDER BY CAST(year AS INTEGER) DESC"
+                } else {
+                    "ORDER BY CAST(year AS INTEGER)"
+                }
+            }
+            AlbumListType::Highest => "ORDER BY COALESCE(rating, 0) DESC, name COLLATE NOCASE",
+            AlbumListType::ByRating { min_rating } => {
+                condition.push_str(" AND rating >= ?");
+                args.push(Value::Integer(*min_rating as i64));
+                "ORDER BY rating DESC, name COLLATE NOCASE"
+            }
+            _ => "ORDER BY created DESC",
+        };
+
+        let order = format!("{} LIMIT ? OFFSET ?", order);
+        args.push(Value::Integer(pagination.page_size as i64));
+        args.push(Value::Integer(pagination.start() as i64));
+        self.query_albums(&condition, &order, args)
+    }
+
+    pub fn list_artists(&self) -> Result<Vec<ArtistInfo>> {
+        self.query_artists("", "ORDER BY name COLLATE NOCASE", vec![])
+    }
+
+    pub fn get_artist(&self, id: &str) -> Result<Option<ArtistInfo>> {
+        let artists = self.query_artists("AND id = ?", "", vec![Value::Text(id.to_string())])?;
+        Ok(artists.into_iter().next())
+    }
+
+    pub fn get_artist_songs(&self, artist_id: &str) -> Result<Vec<UnifiedMetadata>> {
+        self.query_songs(
+            "AND s.artist_id = ?",
+            "ORDER BY s.album COLLATE NOCASE, CAST(s.track AS INTEGER)",
+            vec![Value::Text(artist_id.to_string())],
+        )
+    }
+
+    pub fn list_genres(&self) -> Result<Vec<GenreInfo>> {
+        let conn = self.connect()?;
+        let mut stmt = conn.prepare(
+            "SELECT value, album_count, song_count FROM subsonic_genre
+             WHERE server = ?1 ORDER BY value COLLATE NOCASE",
+        )?;
+        let rows = stmt.query_map(params![self.server], |row| {
+            Ok(GenreInfo {
+                value: row.get(0)?,
+                album_count: row.get::<_, i64>(1)? as usize,
+                song_count: row.get::<_, i64>(2)? as usize,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn get_genre_songs(&self, genre: &str) -> Result<Vec<UnifiedMetadata>> {
+        self.query_songs(
+            "AND s.genre = ?",
+            "ORDER BY s.artist COLLATE NOCASE, s.album COLLATE NOCASE, CAST(s.track AS INTEGER)",
+            vec![Value::Text(genre.to_string())],
+        )
+    }
+
+    pub fn get_random_songs(
+        &self,
+        size: usize,
+        genre: Option<&str>,
+        from_year: Option<&str>,
+        to_year: Option<&str>,
+    ) -> Result<Vec<UnifiedMetadata>> {
+        let mut condition = String::new();
+        let mut args: Vec<Value> = vec![];
+        if let Some(genre) = genre {
+            condition.push_str(" AND s.genre = ?");
+            args.push(Value::Text(genre.to_string()));
+        }
+        if let Some(year) = from_year.and_then(|y| y.parse::<i64>().ok()) {
+            condition.push_str(" AND CAST(s.year AS INTEGER) >= ?");
+            args.push(Value::Integer(year));
+        }
+        if let Some(year) = to_year.and_then(|y| y.parse::<i64>().ok()) {
+            condition.push_str(" AND CAST(s.year AS INTEGER) <= ?");
+            args.push(Value::Integer(year));
+        }
+        args.push(Value::Integer(size as i64));
+        self.query_songs(&condition, "ORDER BY RANDOM() LIMIT ?", args)
+    }
+
+    pub fn search(&self, query: &str, pagination: &Pagination) -> Result<SearchResult> {
+        let pattern = Value::Text(format!("%{}%", query.trim()));
+        let limit = Value::Integer(pagination.page_size as i64);
+        let offset = Value::Integer(pagination.start() as i64);
+
+        let songs = self.query_songs(
+            "AND (s.title LIKE ? OR s.artist LIKE ? OR s.album LIKE ?)",
+            "ORDER BY s.title COLLATE NOCASE LIMIT ? OFFSET ?",
+            vec![pattern.clone(), pattern.clone(), pattern.clone(), limit.clone(), offset.clone()],
+        )?;
+        let albums = self.query_albums(
+            "AND (name LIKE ? OR artist LIKE ?)",
+            "ORDER BY name COLLATE NOCASE LIMIT ? OFFSET ?",
+            vec![pattern.clone(), pattern.clone(), limit.clone(), offset.clone()],
+        )?;
+        let artists = self.query_artists(
+            "AND name LIKE ?",
+            "ORDER BY name COLLATE NOCASE LIMIT ? OFFSET ?",
+            vec![pattern, limit, offset],
+        )?;
+
+        Ok(SearchResult {
+            songs,
+            albums,
+            artists,
+        })
+    }
+
+    pub fn list_playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        let conn = self.connect()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, name, comment, cover_art, owner, public, song_count, duration, created, changed
+             FROM subsonic_playlist WHERE server = ?1 ORDER BY name COLLATE NOCASE",
+        )?;
+        let rows = stmt.query_map(params![self.server], covert_row_to_playlist)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn get_playlist(&self, playlist_id: &str) -> Result<Option<PlaylistDetail>> {
+        let conn = self.connect()?;
+        let info = conn
+            .query_row(
+                "SELECT id, name, comment, cover_art, owner, public, song_count, duration, created, changed
+                 FROM subsonic_playlist WHERE server = ?1 AND id = ?2",
+                params![self.server, playlist_id],
+                covert_row_to_playlist,
+            )
+            .optional()?;
+        let Some(info) = info else {
+            return Ok(None);
+        };
+
+        let sql = format!(
+            "SELECT {} FROM subsonic_playlist_song p
+             JOIN subsonic_song s ON s.server = p.server AND s.id = p.song_id
+             WHERE p.server = ?1 AND p.playlist_id = ?2 ORDER BY p.order_num",
+            SONG_COLUMNS
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let songs = stmt
+            .query_map(params![self.server, playlist_id], covert_row_to_song)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(Some(PlaylistDetail {
+            id: info.id,
+            name: info.name,
+            description: info.description,
+            cover: songs.first().and_then(|s| s.cover_art.clone()).or(info.cover),
+            owner: info.owner,
+            public: info.public,
+            song_count: songs.len(),
+            duration: info.duration,
+            created_at: info.created_at,
+            updated_at: info.updated_at,
+            smart: false,
+            daily_mix: false,
+            songs,
+        }))
+    }
+
+    pub fn get_starred(&self) -> Result<StarredResult> {
+        Ok(StarredResult {
+            songs: self.query_songs("AND s.starred = 1", "ORDER BY s.title COLLATE NOCASE", vec![])?,
+            albums: self.query_albums("AND starred = 1", "ORDER BY name COLLATE NOCASE", vec![])?,
+            artists: self.query_artists("AND starred = 1", "ORDER BY name COLLATE NOCASE", vec![])?,
+        })
+    }
+
+    fn cover_path(&self, cover_art: &str, size: &str) -> PathBuf {
+        self.cover_dir.join(format!("{}_{}", safe_file_name(cover_art), size))
+    }
+
+    /// 读取磁盘上缓存的封面
+    pub fn read_cover(&self, cover_art: &str, size: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.cover_path(cover_art, size)).ok()
+    }
+
+    /// 保存封面到磁盘
+    pub fn save_cover(&self, cover_art: &str, size: &str, data: &[u8]) {
+        let path = self.cover_path(cover_art, size);
+        let result = std::fs::create_dir_all(&self.cover_dir).and_then(|_| std::fs::write(&path, data));
+        if let Err(e) = result {
+            log::log_warn(&format!("Save mirror cover {:?} failed: {}", path, e));
+        }
+    }
+
+    /// 下载专辑封面 (中等尺寸), 已存在时跳过
+    async fn prefetch_cover(&self, client: &SubsonicClient, cover_art: &str) {
+        if self.cover_path(cover_art, "600").exists() {
+            return;
+        }
+        let url = client.get_cover_art_url(cover_art, "600");
+        match reqwest::get(&url).await {
+            Ok(response) if response.status().is_success() => {
+                if let Ok(bytes) = response.bytes().await {
+                    self.save_cover(cover_art, "600", &bytes);
+                }
+            }
+            Ok(response) => {
+                log::log_warn(&format!("Mirror cover {} failed: {}", cover_art, response.status()))
+            }
+            Err(e) => log::log_warn(&format!("Mirror cover {} failed: {}", cover_art, e)),
+        }
+    }
+}
+
+/// 启动定时同步任务
+///
+/// `on_synced` 在每次同步成功后调用, 用于清理内存缓存
+pub fn spawn_sync_job<F>(
+    mirror: std::sync::Arc<SubsonicMirror>,
+    client: std::sync::Arc<SubsonicClient>,
+    interval_minutes: u64,
+    on_synced: F,
+) where
+    F: Fn() + Send + Sync + 'static,
+{
+    if tokio::runtime::Handle::try_current().is_err() {
+        log::log_warn("No tokio runtime, Subsonic mirror sync job is not started");
+        return;
+    }
+    let interval = Duration::from_secs(interval_minutes.max(1) * 60);
+    tokio::spawn(async move {
+        loop {
+            match mirror.sync(&client, false).await {
+                Ok(_) => on_synced(),
+                Err(e) => log::log_warn(&format!("Subsonic mirror sync failed: {}", e)),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+/// 每个服务器使用单独的子目录, 避免不同服务器的 ID 冲突
+pub(super) fn server_dir(base_dir: &str, server: &str) -> PathBuf {
+    let hash = hex::encode(Md5::digest(server.as_bytes()));
+    PathBuf::from(base_dir).join(&hash[..12])
+}
+
+/// ID 由服务器生成, 去掉可能影响路径的字符
+pub(super) fn safe_file_name(id: &str) -> String {
+    id.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+fn album_signature(album: &SubsonicAlbum) -> (String, u32, u32) {
+    (
+        album.name.clone(),
+        album.song_count.unwrap_or(0),
+        album.duration.unwrap_or(0),
+    )
+}
+
+fn insert_album(conn: &Connection, server: &str, album: &SubsonicAlbum) -> rusqlite::Result<usize> {
+    conn.execute(
+        "INSERT OR REPLACE INTO subsonic_album
+         (server, id, name, artist, artist_id, year, genre, cover_art, song_count, duration, created, rating)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            server,
+            album.id,
+            album.name,
+            album.artist.clone().unwrap_or_default(),
+            album.artist_id,
+            album.year.map(|y| y.to_string()).unwrap_or_default(),
+            album.genre.clone().unwrap_or_default(),
+            album.cover_art,
+            album.song_count.unwrap_or(0),
+            album.duration.unwrap_or(0),
+            album.created.clone().unwrap_or_default(),
+            user_rating(album.user_rating),
+        ],
+    )
+}
+
+/// 写入歌曲, `replace` 为 false 时不覆盖已存在的歌曲
+fn insert_song(conn: &Connection, server: &str, song: &SubsonicSong, replace: bool) -> rusqlite::Result<usize> {
+    let sql = format!(
+        "INSERT OR {} INTO subsonic_song
+         (server, id, title, artist, artist_id, album, album_id, year, genre, track,
+          duration, bitrate, suffix, path, cover_art, starred, rating)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+        if replace { "REPLACE" } else { "IGNORE" }
+    );
+    conn.execute(
+        &sql,
+        params![
+            server,
+            song.id,
+            song.title,
+            song.artist.clone().unwrap_or_default(),
+            song.artist_id,
+            song.album.clone().unwrap_or_default(),
+            song.album_id,
+            song.year.map(|y| y.to_string()).unwrap_or_default(),
+            song.genre.clone().unwrap_or_default(),
+            song.track.map(|t| t.to_string()).unwrap_or_default(),
+            song.duration.unwrap_or(0) as f64,
+            song.bit_rate.map(|b| b.to_string()).unwrap_or_default(),
+            song.suffix,
+            song.path,
+            song.cover_art,
+            song.starred.unwrap_or(false),
+            user_rating(song.user_rating),
+        ],
+    )
+}
+
+fn insert_playlist(conn: &Connection, server: &str, playlist: &SubsonicPlaylist) -> rusqlite::Result<usize> {
+    conn.execute(
+        "INSERT OR REPLACE INTO subsonic_playlist
+         (server, id, name, comment, owner, public, song_count, duration, created, changed, cover_art)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            server,
+            playlist.id,
+            playlist.name,
+            playlist.comment,
+            playlist.owner,
+            playlist.public,
+            playlist.song_count.unwrap_or(0),
+            playlist.duration,
+            playlist.created,
+            playlist.changed,
+            playlist.cover_art,
+        ],
+    )
+}
+
+fn insert_playlist_songs(conn: &Connection, server: &str, playlist: &SubsonicPlaylist) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM subsonic_playlist_song WHERE server = ?1 AND playlist_id = ?2",
+        params![server, playlist.id],
+    )?;
+    for (index, song) in playlist.entry.iter().flatten().enumerate() {
+        insert_song(conn, server, song, false)?;
+        conn.execute(
+            "INSERT INTO subsonic_playlist_song (server, playlist_id, song_id, order_num)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![server, playlist.id, song.id, index as i64],
+        )?;
+    }
+    Ok(())
+}
+
+fn set_starred(conn: &Connection, server: &str, table: &str, id: &str, starred: bool) -> Result<()> {
+    conn.execute(
+        &format!("UPDATE {} SET starred = ?1 WHERE server = ?2 AND id = ?3", table),
+        params![starred, server, id],
+    )?;
+    Ok(())
+}
+
+fn covert_row_to_song(row: &Row) -> rusqlite::Result<UnifiedMetadata> {
+    let id: String = row.get(0)?;
+    Ok(UnifiedMetadata {
+        id: id.clone(),
+        title: row.get(1)?,
+        artist: row.get(2)?,
+        artist_id: row.get(3)?,
+        album: row.get(4)?,
+        album_id: row.get(5)?,
+        year: row.get(6)?,
+        genre: row.get(7)?,
+        track: row.get(8)?,
+        duration: row.get(9)?,
+        bitrate: row.get(10)?,
+        file_name: row.get(11)?,
+        file_path: row.get(12)?,
+        cover_art: row.get(13)?,
+        starred: Some(row.get(14)?),
+        disc: String::new(),
+        language: String::new(),
+        comment: String::new(),
+        samplerate: String::new(),
+        source: DataSourceType::Subsonic,
+        file_url: None,
+        subsonic_id: Some(id),
+        stream_url: None,
+        play_count: None,
+        last_played: None,
+        skip_count: None,
+        rating: row.get(15)?,
+        replay_gain: None,
+        bpm: None,
+        musical_key: None,
+    })
+}
+
+fn covert_row_to_album(row: &Row) -> rusqlite::Result<AlbumInfo> {
+    Ok(AlbumInfo {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        artist: row.get(2)?,
+        year: row.get(3)?,
+        cover_art: row.get(4)?,
+        song_count: row.get::<_, i64>(5)? as usize,
+        play_count: None,
+        last_played: None,
+        skip_count: None,
+        rating: row.get(6)?,
+    })
+}
+
+fn covert_row_to_playlist(row: &Row) -> rusqlite::Result<PlaylistInfo> {
+    Ok(PlaylistInfo {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        cover: row.get(3)?,
+        owner: row.get(4)?,
+        public: row.get(5)?,
+        song_count: row.get::<_, i64>(6)? as usize,
+        duration: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        smart: false,
+        daily_mix: false,
+    })
+}
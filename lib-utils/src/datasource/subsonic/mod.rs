@@ -2,8 +2,13 @@
 // 实现 Subsonic REST API 的调用和数据源适配
 
 pub mod auth;
+pub mod cache;
 pub mod client;
+pub mod error;
 pub mod mapper;
+pub mod mirror;
+pub mod pin;
+pub mod policy;
 pub mod datasource;
 
 // 重新导出核心类型
@@ -0,0 +1,291 @@
+// Subsonic 离线下载
+// 将固定的歌曲、专辑、歌单的原始文件下载到本地目录, 播放时直接读取本地文件
+
+use anyhow::Result;
+use futures::StreamExt;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+
+use super::client::{SubsonicClient, SubsonicSong};
+use super::mirror::{safe_file_name, server_dir};
+use crate::datasource::types::*;
+use crate::log;
+
+const STATUS_PENDING: i64 = 0;
+const STATUS_DONE: i64 = 1;
+const STATUS_FAILED: i64 = 2;
+
+/// 离线下载管理
+pub struct SubsonicPinStore {
+    server: String,
+    // 数据库路径
+    db_path: String,
+    // 下载文件的保存目录
+    dir: PathBuf,
+    // 空间配额 (字节)
+    quota: u64,
+    // 防止下载任务重复执行
+    downloading: tokio::sync::Mutex<()>,
+}
+
+impl SubsonicPinStore {
+    pub fn new(server: String, db_path: &str, dir: &str, quota: u64) -> Self {
+        Self {
+            dir: server_dir(dir, &server),
+            server,
+            db_path: db_path.to_string(),
+            quota,
+            downloading: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    fn connect(&self) -> rusqlite::Result<Connection> {
+        Connection::open(&self.db_path)
+    }
+
+    fn now() -> String {
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+
+    /// 固定一个项目, 已固定时更新歌曲列表并重试下载失败的歌曲
+    pub fn pin(&self, item_type: PinItemType, item_id: &str, songs: &[SubsonicSong]) -> Result<()> {
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR IGNORE INTO subsonic_pin (server, item_type, item_id, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![self.server, item_type.as_str(), item_id, Self::now()],
+        )?;
+        tx.execute(
+            "DELETE FROM subsonic_pin_song WHERE server = ?1 AND item_type = ?2 AND item_id = ?3",
+            params![self.server, item_type.as_str(), item_id],
+        )?;
+        for song in songs {
+            tx.execute(
+                "INSERT INTO subsonic_pin_song (server, item_type, item_id, song_id)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![self.server, item_type.as_str(), item_id, song.id],
+            )?;
+            let file_name = format!(
+                "{}.{}",
+                safe_file_name(&song.id),
+                song.suffix.as_deref().map(safe_file_name).unwrap_or_else(|| "bin".to_string())
+            );
+            tx.execute(
+                "INSERT OR IGNORE INTO subsonic_pin_file (server, song_id, file_name, status, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![self.server, song.id, file_name, STATUS_PENDING, Self::now()],
+            )?;
+            tx.execute(
+                "UPDATE subsonic_pin_file SET status = ?1, error = NULL
+                 WHERE server = ?2 AND song_id = ?3 AND status = ?4",
+                params![STATUS_PENDING, self.server, song.id, STATUS_FAILED],
+            )?;
+        }
+        tx.commit()?;
+        self.remove_orphan_files(&conn)?;
+        Ok(())
+    }
+
+    /// 取消固定, 删除不再被任何项目引用的文件
+    pub fn unpin(&self, item_type: PinItemType, item_id: &str) -> Result<()> {
+        let conn = self.connect()?;
+        conn.execute(
+            "DELETE FROM subsonic_pin WHERE server = ?1 AND item_type = ?2 AND item_id = ?3",
+            params![self.server, item_type.as_str(), item_id],
+        )?;
+        conn.execute(
+            "DELETE FROM subsonic_pin_song WHERE server = ?1 AND item_type = ?2 AND item_id = ?3",
+            params![self.server, item_type.as_str(), item_id],
+        )?;
+        self.remove_orphan_files(&conn)
+    }
+
+    fn remove_orphan_files(&self, conn: &Connection) -> Result<()> {
+        let mut stmt = conn.prepare(
+            "SELECT song_id, file_name FROM subsonic_pin_file WHERE server = ?1
+             AND song_id NOT IN (SELECT song_id FROM subsonic_pin_song WHERE server = ?1)",
+        )?;
+        let orphans = stmt
+            .query_map(params![self.server], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        for (song_id, file_name) in orphans {
+            let path = self.dir.join(&file_name);
+            if path.exists() {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    log::log_warn(&format!("Remove pinned file {:?} failed: {}", path, e));
+                }
+            }
+            conn.execute(
+                "DELETE FROM subsonic_pin_file WHERE server = ?1 AND song_id = ?2",
+                params![self.server, song_id],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// 已下载歌曲的本地文件路径
+    pub fn local_path(&self, song_id: &str) -> Option<PathBuf> {
+        let conn = self.connect().ok()?;
+        let file_name: String = conn
+            .query_row(
+                "SELECT file_name FROM subsonic_pin_file WHERE server = ?1 AND song_id = ?2 AND status = ?3",
+                params![self.server, song_id, STATUS_DONE],
+                |row| row.get(0),
+            )
+            .optional()
+            .ok()??;
+        let path = self.dir.join(file_name);
+        path.is_file().then_some(path)
+    }
+
+    fn used(&self, conn: &Connection) -> Result<u64> {
+        let used: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(size), 0) FROM subsonic_pin_file WHERE server = ?1 AND status = ?2",
+            params![self.server, STATUS_DONE],
+            |row| row.get(0),
+        )?;
+        Ok(used as u64)
+    }
+
+    pub fn status(&self) -> Result<PinStatus> {
+        let conn = self.connect()?;
+        let mut stmt = conn.prepare(
+            "SELECT p.item_type, p.item_id, p.created_at,
+                COUNT(s.song_id),
+                COALESCE(SUM(CASE WHEN f.status = ?2 THEN 1 ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN f.status = ?3 THEN 1 ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN f.status = ?2 THEN f.size ELSE 0 END), 0)
+             FROM subsonic_pin p
+             LEFT JOIN subsonic_pin_song s
+               ON s.server = p.server AND s.item_type = p.item_type AND s.item_id = p.item_id
+             LEFT JOIN subsonic_pin_file f ON f.server = s.server AND f.song_id = s.song_id
+             WHERE p.server = ?1
+             GROUP BY p.item_type, p.item_id
+             ORDER BY p.created_at DESC",
+        )?;
+        let items = stmt
+            .query_map(params![self.server, STATUS_DONE, STATUS_FAILED], |row| {
+                let item_type: String = row.get(0)?;
+                Ok(PinItem {
+                    item_type: PinItemType::parse(&item_type).unwrap_or(PinItemType::Song),
+                    item_id: row.get(1)?,
+                    created_at: row.get(2)?,
+                    song_count: row.get::<_, i64>(3)? as usize,
+                    downloaded: row.get::<_, i64>(4)? as usize,
+                    failed: row.get::<_, i64>(5)? as usize,
+                    size: row.get::<_, i64>(6)? as u64,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(PinStatus {
+            quota: self.quota,
+            used: self.used(&conn)?,
+            items,
+        })
+    }
+
+    fn next_pending(&self) -> Result<Option<(String, String)>> {
+        let conn = self.connect()?;
+        let next = conn
+            .query_row(
+                "SELECT song_id, file_name FROM subsonic_pin_file
+                 WHERE server = ?1 AND status = ?2 ORDER BY updated_at LIMIT 1",
+                params![self.server, STATUS_PENDING],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        Ok(next)
+    }
+
+    fn set_file_status(&self, song_id: &str, status: i64, size: u64, error: Option<&str>) -> Result<()> {
+        let conn = self.connect()?;
+        conn.execute(
+            "UPDATE subsonic_pin_file SET status = ?1, size = ?2, error = ?3, updated_at = ?4
+             WHERE server = ?5 AND song_id = ?6",
+            params![status, size as i64, error, Self::now(), self.server, song_id],
+        )?;
+        Ok(())
+    }
+
+    /// 依次下载所有等待中的歌曲, 超出配额时停止
+    pub async fn download_pending(&self, client: &SubsonicClient) -> Result<()> {
+        let Ok(_guard) = self.downloading.try_lock() else {
+            // 正在下载, 新增的歌曲会被正在运行的任务处理
+            return Ok(());
+        };
+        tokio::fs::create_dir_all(&self.dir).await?;
+
+        while let Some((song_id, file_name)) = self.next_pending()? {
+            let used = self.used(&self.connect()?)?;
+            match self.download_file(client, &song_id, &file_name, used).await {
+                Ok(size) => self.set_file_status(&song_id, STATUS_DONE, size, None)?,
+                Err(e) => {
+                    log::log_warn(&format!("Download pinned song {} failed: {}", song_id, e));
+                    self.set_file_status(&song_id, STATUS_FAILED, 0, Some(&e.to_string()))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn download_file(
+        &self,
+        client: &SubsonicClient,
+        song_id: &str,
+        file_name: &str,
+        used: u64,
+    ) -> Result<u64> {
+        let quota_exceeded = || anyhow::anyhow!("Pin quota exceeded ({} bytes)", self.quota);
+
+        let response = client.download(song_id).await?;
+        if response.content_length().is_some_and(|len| used + len > self.quota) {
+            return Err(quota_exceeded());
+        }
+
+        // 先写入临时文件, 下载完成后再重命名
+        let path = self.dir.join(file_name);
+        let part_path = self.dir.join(format!("{}.part", file_name));
+        let mut file = tokio::fs::File::create(&part_path).await?;
+        let mut size: u64 = 0;
+        let mut stream = response.bytes_stream();
+        let result: Result<()> = async {
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk?;
+                size += chunk.len() as u64;
+                if used + size > self.quota {
+                    return Err(quota_exceeded());
+                }
+                file.write_all(&chunk).await?;
+            }
+            file.flush().await?;
+            Ok(())
+        }
+        .await;
+
+        drop(file);
+        if let Err(e) = result {
+            let _ = tokio::fs::remove_file(&part_path).await;
+            return Err(e);
+        }
+        tokio::fs::rename(&part_path, &path).await?;
+        Ok(size)
+    }
+}
+
+/// 在后台下载等待中的歌曲
+pub fn spawn_download_job(store: Arc<SubsonicPinStore>, client: Arc<SubsonicClient>) {
+    if tokio::runtime::Handle::try_current().is_err() {
+        log::log_warn("No tokio runtime, pinned songs are not downloaded");
+        return;
+    }
+    tokio::spawn(async move {
+        if let Err(e) = store.download_pending(&client).await {
+            log::log_warn(&format!("Download pinned songs failed: {}", e));
+        }
+    });
+}
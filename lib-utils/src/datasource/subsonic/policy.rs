@@ -0,0 +1,129 @@
+// Subsonic 请求策略
+// 超时、指数退避重试和熔断
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::error::SubsonicClientError;
+
+/// 请求策略
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientPolicy {
+    /// 单次请求超时
+    pub timeout: Duration,
+    /// 幂等请求失败后的重试次数
+    pub retry_count: u32,
+    /// 第一次重试前的等待时间, 之后每次翻倍
+    pub retry_backoff: Duration,
+    /// 连续失败多少次后熔断, 为 0 时不熔断
+    pub circuit_breaker_threshold: u32,
+    /// 熔断持续时间
+    pub circuit_breaker_cooldown: Duration,
+}
+
+impl Default for ClientPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(15),
+            retry_count: 2,
+            retry_backoff: Duration::from_millis(500),
+            circuit_breaker_threshold: 5,
+            circuit_breaker_cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ClientPolicy {
+    /// 第 `attempt` 次重试 (从 1 开始) 前的等待时间
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.retry_backoff * 2u32.saturating_pow(attempt.saturating_sub(1))
+    }
+}
+
+struct BreakerState {
+    // 连续失败次数
+    failures: u32,
+    // 熔断结束时间
+    open_until: Option<Instant>,
+}
+
+/// 熔断器
+///
+/// 连续失败达到阈值后熔断, 熔断期间直接返回错误; 熔断结束后放行请求,
+/// 成功则恢复, 失败则再次熔断
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            state: Mutex::new(BreakerState {
+                failures: 0,
+                open_until: None,
+            }),
+        }
+    }
+
+    /// 请求前检查, 熔断期间返回错误
+    pub fn check(&self) -> Result<(), SubsonicClientError> {
+        let state = self.state.lock().unwrap();
+        match state.open_until {
+            Some(until) if Instant::now() < until => Err(SubsonicClientError::CircuitOpen),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures = 0;
+        state.open_until = None;
+    }
+
+    pub fn record_failure(&self) {
+        if self.threshold == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        if state.failures >= self.threshold {
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = ClientPolicy {
+            retry_backoff: Duration::from_millis(100),
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new(2, Duration::from_millis(30));
+        breaker.record_failure();
+        assert!(breaker.check().is_ok());
+        breaker.record_failure();
+        assert_eq!(breaker.check(), Err(SubsonicClientError::CircuitOpen));
+
+        // 熔断结束后放行, 成功后计数清零
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(breaker.check().is_ok());
+        breaker.record_success();
+        breaker.record_failure();
+        assert!(breaker.check().is_ok());
+    }
+}
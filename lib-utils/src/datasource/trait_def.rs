@@ -1,6 +1,6 @@
-use anyhow::Result;
 use async_trait::async_trait;
 
+use super::error::Result;
 use super::types::*;
 
 /// 音乐数据源抽象接口
@@ -61,10 +61,16 @@ pub trait MusicDataSource: Send + Sync {
     /// # 参数
     /// * `song_id` - 歌曲ID
     /// * `range` - 可选的 HTTP Range 请求头(用于断点续传)
+    /// * `options` - 音质参数(最大比特率、转码格式), 未指定时使用配置中的默认值
     ///
     /// # 返回
     /// * `Ok(reqwest::Response)` - HTTP 响应流
-    async fn stream_song(&self, song_id: &str, range: Option<String>) -> Result<reqwest::Response>;
+    async fn stream_song(
+        &self,
+        song_id: &str,
+        range: Option<String>,
+        options: &StreamOptions,
+    ) -> Result<reqwest::Response>;
 
 
     /// 扫描音乐库
@@ -111,6 +117,16 @@ pub trait MusicDataSource: Send + Sync {
     /// * `Ok(Vec<UnifiedMetadata>)` - 专辑的歌曲列表
     async fn get_album_songs(&self, album_id: &str) -> Result<Vec<UnifiedMetadata>>;
 
+    /// 根据文件路径查找歌曲
+    ///
+    /// # 参数
+    /// * `path` - 文件路径(绝对路径, 或相对音乐库根目录的路径)
+    ///
+    /// # 返回
+    /// * `Ok(Some(UnifiedMetadata))` - 匹配的歌曲
+    /// * `Ok(None)` - 没有唯一匹配的歌曲
+    async fn find_song_by_path(&self, path: &str) -> Result<Option<UnifiedMetadata>>;
+
     /// 获取艺术家列表
     ///
     /// # 返回
@@ -156,6 +172,7 @@ pub trait MusicDataSource: Send + Sync {
     /// * `genre` - 可选,按流派筛选
     /// * `from_year` - 可选,只返回此年份之后(含)发布的歌曲
     /// * `to_year` - 可选,只返回此年份之前(含)发布的歌曲
+    /// * `bpm` - BPM 范围, 有限制时只返回已知 BPM 的歌曲
     ///
     /// # 返回
     /// * `Ok(Vec<UnifiedMetadata>)` - 随机歌曲列表
@@ -165,6 +182,23 @@ pub trait MusicDataSource: Send + Sync {
         genre: Option<&str>,
         from_year: Option<&str>,
         to_year: Option<&str>,
+        bpm: BpmRange,
+    ) -> Result<Vec<UnifiedMetadata>>;
+
+    /// 相似歌曲电台
+    ///
+    /// # 参数
+    /// * `seed` - 种子歌曲、艺术家或风格
+    /// * `count` - 返回的最大歌曲数量
+    /// * `exclude` - 不返回的歌曲 ID (如播放队列中已有的歌曲), 用于连续获取下一批
+    ///
+    /// # 返回
+    /// * `Ok(Vec<UnifiedMetadata>)` - 按相似程度排序的歌曲列表
+    async fn get_similar_songs(
+        &self,
+        seed: &RadioSeed,
+        count: usize,
+        exclude: &[String],
     ) -> Result<Vec<UnifiedMetadata>>;
 
     /// 搜索音乐
@@ -182,6 +216,12 @@ pub trait MusicDataSource: Send + Sync {
     /// * `DataSourceType` - 本地或Subsonic
     fn source_type(&self) -> DataSourceType;
 
+    /// 获取数据源支持的功能
+    ///
+    /// - 本地模式: 支持编辑标签和歌词、智能歌单等, 不支持转码
+    /// - Subsonic模式: 支持转码和播放队列同步, 收藏状态查询和离线下载取决于是否开启镜像和离线下载
+    fn capabilities(&self) -> Capabilities;
+
     /// 健康检查
     ///
     /// 检查数据源是否可用
@@ -196,6 +236,31 @@ pub trait MusicDataSource: Send + Sync {
     /// 扫描音乐
     async fn scan_music(&self) -> Result<()>;
 
+    /// 清空元数据缓存
+    ///
+    /// - 本地模式: 没有缓存, 直接返回
+    /// - Subsonic模式: 清空歌曲、专辑、艺术家等缓存, 下次访问时重新请求服务器
+    async fn clear_cache(&self) -> Result<()>;
+
+    /// 同步音乐库镜像
+    ///
+    /// - 本地模式: 没有镜像, 直接返回
+    /// - Subsonic模式: 将服务器的音乐库同步到本地数据库, `full` 为 false 时只同步变化的部分
+    async fn sync_library(&self, full: bool) -> Result<()>;
+
+    /// 固定/取消固定离线下载项目
+    ///
+    /// 固定后在后台下载原始文件, 播放时直接读取本地文件
+    ///
+    /// # 参数
+    /// * `item_type` - 歌曲、专辑或歌单
+    /// * `id` - 项目 ID
+    /// * `pin` - true 固定, false 取消固定并删除不再需要的文件
+    async fn pin(&self, item_type: PinItemType, id: &str, pin: bool) -> Result<()>;
+
+    /// 获取离线下载状态和已使用空间
+    async fn pin_status(&self) -> Result<PinStatus>;
+
     /// 获取扫描状态
     ///
     /// # 返回
@@ -251,6 +316,71 @@ pub trait MusicDataSource: Send + Sync {
         song_ids: Option<&[String]>,
     ) -> Result<()>;
 
+    /// 编辑播放列表中的歌曲(追加、插入、移动、按位置删除、整体替换)
+    ///
+    /// # 参数
+    /// * `playlist_id` - 播放列表 ID
+    /// * `edit` - 编辑操作
+    ///
+    /// # 返回
+    /// * `Ok(())` - 编辑成功, 任何一步失败时歌单保持不变
+    async fn edit_playlist(&self, playlist_id: &str, edit: &PlaylistEdit) -> Result<()>;
+
+    /// 设置播放列表是否公开
+    ///
+    /// # 参数
+    /// * `playlist_id` - 播放列表 ID
+    /// * `public` - 是否公开
+    async fn set_playlist_public(&self, playlist_id: &str, public: bool) -> Result<()>;
+
+    /// 设置播放列表的自定义封面
+    ///
+    /// # 参数
+    /// * `playlist_id` - 播放列表 ID
+    /// * `cover` - 重新编码后的 webp data URL, 为空时恢复自动拼接封面
+    async fn set_playlist_cover(&self, playlist_id: &str, cover: &str) -> Result<()>;
+
+    /// 浏览其他用户公开的播放列表
+    ///
+    /// 本地模式目前只有默认用户 (user_id = 1), 自己的歌单不会出现在结果中
+    async fn list_public_playlists(&self) -> Result<Vec<PlaylistInfo>>;
+
+    /// 订阅/取消订阅公开的播放列表, 订阅后会出现在 list_playlists 中
+    ///
+    /// 不能订阅自己的歌单, 本地模式只有默认用户时没有可订阅的歌单
+    ///
+    /// # 参数
+    /// * `playlist_id` - 播放列表 ID
+    /// * `subscribe` - true 订阅, false 取消订阅
+    async fn subscribe_playlist(&self, playlist_id: &str, subscribe: bool) -> Result<()>;
+
+    /// 创建智能歌单
+    ///
+    /// # 参数
+    /// * `name` - 歌单名称
+    /// * `description` - 描述(可选)
+    /// * `rule` - 歌单规则, 每次获取歌单时按规则重新计算歌曲
+    ///
+    /// # 返回
+    /// * `Ok(())` - 创建成功
+    async fn create_smart_playlist(
+        &self,
+        name: &str,
+        description: Option<&str>,
+        rule: &SmartPlaylistRule,
+    ) -> Result<()>;
+
+    /// 更新智能歌单规则
+    ///
+    /// # 参数
+    /// * `playlist_id` - 播放列表 ID
+    /// * `rule` - 新的歌单规则
+    ///
+    /// # 返回
+    /// * `Ok(())` - 更新成功
+    async fn update_smart_playlist(&self, playlist_id: &str, rule: &SmartPlaylistRule)
+        -> Result<()>;
+
     /// 删除播放列表
     ///
     /// # 参数
@@ -327,4 +457,67 @@ pub trait MusicDataSource: Send + Sync {
     /// # 返回
     /// * `Ok(bool)` - true 表示已收藏，false 表示未收藏
     async fn is_starred(&self, id: &str, item_type: StarItemType) -> Result<bool>;
+
+    /// 设置评分
+    ///
+    /// # 参数
+    /// * `id` - 项目 ID
+    /// * `item_type` - 项目类型（歌曲/专辑）
+    /// * `rating` - 评分 1-5, 0 表示取消评分
+    ///
+    /// # 返回
+    /// * `Ok(())` - 设置成功
+    async fn set_rating(&self, id: &str, item_type: StarItemType, rating: u8) -> Result<()>;
+
+    /// 获取评分
+    ///
+    /// # 返回
+    /// * `Ok(Some(u8))` - 评分 1-5
+    /// * `Ok(None)` - 未评分
+    async fn get_rating(&self, id: &str, item_type: StarItemType) -> Result<Option<u8>>;
+
+    /// 时间窗口内播放次数最多的项目
+    ///
+    /// 收听统计基于本地数据库中的播放记录, 没有本地数据库的数据源返回 `Unsupported`
+    ///
+    /// # 参数
+    /// * `item_type` - 统计对象（歌曲/专辑/艺术家/风格）
+    /// * `range` - 时间窗口
+    /// * `limit` - 返回数量
+    async fn stats_top(
+        &self,
+        item_type: StatsItemType,
+        range: TimeRange,
+        limit: u32,
+    ) -> Result<Vec<StatsItem>>;
+
+    /// 第一次收听发生在时间窗口内的项目, 按首次收听时间倒序
+    async fn stats_first_listened(
+        &self,
+        item_type: StatsItemType,
+        range: TimeRange,
+        limit: u32,
+    ) -> Result<Vec<StatsItem>>;
+
+    /// 按天/周/月统计收听时长
+    async fn stats_listening_time(
+        &self,
+        period: StatsPeriod,
+        range: TimeRange,
+    ) -> Result<Vec<ListeningTime>>;
+
+    /// 连续收听天数
+    async fn stats_streaks(&self) -> Result<Streaks>;
+
+    /// 按星期和小时统计的收听热力图
+    async fn stats_heatmap(&self, range: TimeRange) -> Result<Vec<HeatmapCell>>;
+
+    /// 年度报告
+    ///
+    /// # 参数
+    /// * `year` - 年份
+    async fn wrapped(&self, year: i32) -> Result<Wrapped>;
+
+    /// 年度报告分享卡片 (PNG)
+    async fn wrapped_card(&self, year: i32) -> Result<Vec<u8>>;
 }
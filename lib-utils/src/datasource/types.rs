@@ -46,6 +46,10 @@ pub enum DataSourceType {
     Local,
     /// Subsonic 服务器
     Subsonic,
+    /// Jellyfin 服务器
+    Jellyfin,
+    /// 聚合多个数据源
+    Aggregate,
 }
 
 /// 统一的音乐元数据结构
@@ -79,6 +83,21 @@ pub struct UnifiedMetadata {
     pub artist_id: Option<String>,
     pub starred: Option<bool>,
 
+    // 播放统计, 数据源不支持时为 None
+    pub play_count: Option<u32>,
+    /// 最后播放时间 (毫秒时间戳)
+    pub last_played: Option<i64>,
+    /// 跳过次数: 记录了"正在播放"但没有播放完
+    pub skip_count: Option<u32>,
+    /// 用户评分 (1-5), 未评分时为 None
+    pub rating: Option<u8>,
+    /// 响度归一化信息, 没有标签且尚未分析时为 None
+    pub replay_gain: Option<ReplayGain>,
+    /// 每分钟节拍数, 没有标签且尚未分析时为 None
+    pub bpm: Option<f64>,
+    /// 调性, 如 "Am"、"F#"
+    pub musical_key: Option<String>,
+
     // 本地文件模式专有字段
     pub file_name: Option<String>,
     pub file_path: Option<String>,
@@ -89,6 +108,40 @@ pub struct UnifiedMetadata {
     pub stream_url: Option<String>,
 }
 
+/// 响度归一化信息 (ReplayGain 2.0, 参考响度 -18 LUFS)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub struct ReplayGain {
+    /// 单曲增益 (dB)
+    pub track_gain: Option<f64>,
+    /// 单曲峰值 (线性幅度, 1.0 为满幅)
+    pub track_peak: Option<f64>,
+    /// 专辑增益 (dB)
+    pub album_gain: Option<f64>,
+    /// 专辑峰值 (线性幅度)
+    pub album_peak: Option<f64>,
+}
+
+/// BPM 范围(含), 两端都为空时不限制
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub struct BpmRange {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl BpmRange {
+    pub fn is_unbounded(&self) -> bool {
+        self.min.is_none() && self.max.is_none()
+    }
+
+    /// 是否在范围内, 有限制时没有 BPM 的歌曲不匹配
+    pub fn contains(&self, bpm: Option<f64>) -> bool {
+        if self.is_unbounded() {
+            return true;
+        }
+        bpm.is_some_and(|bpm| self.min.is_none_or(|min| bpm >= min) && self.max.is_none_or(|max| bpm <= max))
+    }
+}
+
 /// 音频流类型
 #[derive(Debug, Clone)]
 pub enum AudioStream {
@@ -101,6 +154,52 @@ pub enum AudioStream {
     },
 }
 
+/// 音频流参数 (转发给 Subsonic 的 maxBitRate / format)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamOptions {
+    /// 最大比特率 (kbps), 0 表示不限制
+    pub max_bitrate: Option<u32>,
+    /// 转码格式, 如 "mp3" / "opus", "raw" 表示不转码
+    pub format: Option<String>,
+}
+
+impl StreamOptions {
+    /// 预设的音质档位
+    /// - mobile: 128 kbps mp3
+    /// - standard: 192 kbps mp3
+    /// - high: 320 kbps mp3
+    /// - lossless: 原始文件, 不转码
+    pub fn from_profile(profile: &str) -> Option<Self> {
+        let (max_bitrate, format) = match profile.to_lowercase().as_str() {
+            "mobile" => (128, "mp3"),
+            "standard" => (192, "mp3"),
+            "high" => (320, "mp3"),
+            "lossless" => (0, "raw"),
+            _ => return None,
+        };
+        Some(Self {
+            max_bitrate: Some(max_bitrate),
+            format: Some(format.to_string()),
+        })
+    }
+
+    /// 转换为 /api/stream/{id} 的查询字符串, 没有参数时为空
+    pub fn to_query(&self) -> String {
+        let mut params = Vec::new();
+        if let Some(max_bitrate) = self.max_bitrate {
+            params.push(format!("max_bitrate={}", max_bitrate));
+        }
+        if let Some(format) = &self.format {
+            params.push(format!("format={}", format));
+        }
+        if params.is_empty() {
+            String::new()
+        } else {
+            format!("?{}", params.join("&"))
+        }
+    }
+}
+
 /// 封面图片尺寸
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CoverSize {
@@ -137,6 +236,58 @@ pub struct MetadataFilter {
     pub album_ids: Option<Vec<String>>,
     /// 关键字搜索
     pub keyword: Option<String>,
+    /// BPM 范围过滤
+    pub bpm: BpmRange,
+    /// 调性过滤 (音名或 Camelot 记号)
+    pub musical_key: Option<String>,
+    /// 排序字段, 为空时保持数据源的默认顺序
+    pub sort: Option<MetadataSort>,
+    /// 是否倒序
+    pub descending: bool,
+}
+
+/// 歌曲列表排序字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataSort {
+    Title,
+    Artist,
+    Album,
+    Year,
+    Duration,
+    PlayCount,
+    LastPlayed,
+    SkipCount,
+    Rating,
+}
+
+/// 解析 ISO 8601 时间 (如 "2024-03-05T12:00:00.000Z") 为毫秒时间戳
+pub fn parse_timestamp_millis(time: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|t| t.timestamp_millis())
+}
+
+/// 按指定字段排序歌曲, 没有播放统计或评分的歌曲视为 0
+pub fn sort_metadata(list: &mut [UnifiedMetadata], sort: MetadataSort, descending: bool) {
+    list.sort_by(|a, b| {
+        let ordering = match sort {
+            MetadataSort::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            MetadataSort::Artist => a.artist.to_lowercase().cmp(&b.artist.to_lowercase()),
+            MetadataSort::Album => a.album.to_lowercase().cmp(&b.album.to_lowercase()),
+            MetadataSort::Year => a.year.cmp(&b.year),
+            MetadataSort::Duration => a.duration.total_cmp(&b.duration),
+            MetadataSort::PlayCount => a.play_count.unwrap_or(0).cmp(&b.play_count.unwrap_or(0)),
+            MetadataSort::LastPlayed => a.last_played.unwrap_or(0).cmp(&b.last_played.unwrap_or(0)),
+            MetadataSort::SkipCount => a.skip_count.unwrap_or(0).cmp(&b.skip_count.unwrap_or(0)),
+            MetadataSort::Rating => a.rating.unwrap_or(0).cmp(&b.rating.unwrap_or(0)),
+        };
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
 }
 
 /// 音乐库扫描进度
@@ -186,6 +337,13 @@ pub struct AlbumInfo {
     pub year: String,
     pub cover_art: Option<String>,
     pub song_count: usize,
+    /// 专辑中歌曲的播放次数之和
+    pub play_count: Option<u32>,
+    /// 最后播放时间 (毫秒时间戳)
+    pub last_played: Option<i64>,
+    pub skip_count: Option<u32>,
+    /// 用户评分 (1-5), 未评分时为 None
+    pub rating: Option<u8>,
 }
 
 /// 艺术家信息
@@ -234,6 +392,12 @@ pub struct PlaylistInfo {
     pub duration: Option<u32>,
     pub created_at: Option<String>,
     pub updated_at: Option<String>,
+    /// 是否为智能歌单(由规则动态生成, 歌曲列表只读)
+    #[serde(default)]
+    pub smart: bool,
+    /// 是否为每日推荐歌单(每天自动生成, 只读)
+    #[serde(default)]
+    pub daily_mix: bool,
 }
 
 /// 播放列表详情(包含歌曲列表)
@@ -249,6 +413,10 @@ pub struct PlaylistDetail {
     pub duration: Option<u32>,
     pub created_at: Option<String>,
     pub updated_at: Option<String>,
+    #[serde(default)]
+    pub smart: bool,
+    #[serde(default)]
+    pub daily_mix: bool,
     pub songs: Vec<UnifiedMetadata>,
 }
 
@@ -298,6 +466,8 @@ pub enum AlbumListType {
     Recent,
     /// 已收藏
     Starred,
+    /// 评分不低于 min_rating 的专辑, 按评分倒序
+    ByRating { min_rating: u8 },
     /// 按流派
     ByGenre { genre: String },
     /// 按年份
@@ -319,6 +489,8 @@ impl AlbumListType {
             AlbumListType::Frequent => "frequent",
             AlbumListType::Recent => "recent",
             AlbumListType::Starred => "starred",
+            // Subsonic 没有按评分过滤的列表, 取评分最高的列表后再过滤
+            AlbumListType::ByRating { .. } => "highest",
             AlbumListType::ByGenre { .. } => "byGenre",
             AlbumListType::ByYear { .. } => "byYear",
             AlbumListType::AlphabeticalByName => "alphabeticalByName",
@@ -362,6 +534,20 @@ impl StarItemType {
     }
 }
 
+/// 电台 (相似歌曲) 的种子
+///
+/// 序列化为 `{"song": "id"}` / `{"artist": "id"}` / `{"genre": "Rock"}`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RadioSeed {
+    /// 歌曲 ID
+    Song(String),
+    /// 艺术家 ID
+    Artist(String),
+    /// 风格名称
+    Genre(String),
+}
+
 /// 收藏列表结果
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct StarredResult {
@@ -372,3 +558,455 @@ pub struct StarredResult {
     /// 收藏的艺术家
     pub artists: Vec<ArtistInfo>,
 }
+
+/// 离线下载项目类型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PinItemType {
+    /// 歌曲
+    Song,
+    /// 专辑
+    Album,
+    /// 歌单
+    Playlist,
+}
+
+impl PinItemType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PinItemType::Song => "song",
+            PinItemType::Album => "album",
+            PinItemType::Playlist => "playlist",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "song" => Some(PinItemType::Song),
+            "album" => Some(PinItemType::Album),
+            "playlist" => Some(PinItemType::Playlist),
+            _ => None,
+        }
+    }
+}
+
+/// 单个离线下载项目的状态
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PinItem {
+    pub item_type: PinItemType,
+    pub item_id: String,
+    /// 包含的歌曲数量
+    pub song_count: usize,
+    /// 已下载的歌曲数量
+    pub downloaded: usize,
+    /// 下载失败的歌曲数量
+    pub failed: usize,
+    /// 已下载文件的总大小 (字节)
+    pub size: u64,
+    pub created_at: String,
+}
+
+/// 离线下载状态
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PinStatus {
+    /// 空间配额 (字节)
+    pub quota: u64,
+    /// 已使用空间 (字节)
+    pub used: u64,
+    pub items: Vec<PinItem>,
+}
+
+/// 数据源支持的功能
+///
+/// 前端根据这些开关隐藏当前数据源不支持的功能
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Capabilities {
+    /// 修改歌曲风格、封面, 删除歌曲
+    pub tag_editing: bool,
+    /// 删除歌词
+    pub lyrics_editing: bool,
+    /// 扫描音乐库
+    pub scanning: bool,
+    /// 服务端转码, 支持切换音质档位
+    pub transcoding: bool,
+    /// 播放队列同步
+    pub play_queue_sync: bool,
+    /// 评分
+    pub ratings: bool,
+    /// 查询单个歌曲/专辑/艺术家是否已收藏
+    pub starred_status: bool,
+    /// 歌曲列表按艺术家、专辑过滤
+    pub metadata_filtering: bool,
+    /// 智能歌单
+    pub smart_playlists: bool,
+    /// 订阅其他用户的公开歌单
+    pub playlist_subscription: bool,
+    /// 离线下载
+    pub offline_pinning: bool,
+    /// 音乐库镜像同步
+    pub library_sync: bool,
+    /// 收听统计 (/api/stats)
+    pub listening_stats: bool,
+    /// 相似歌曲电台 (/api/radio)
+    pub radio: bool,
+    /// 每日推荐歌单
+    pub daily_mixes: bool,
+    /// 按声学指纹检测重复歌曲 (/api/duplicates)
+    pub duplicate_detection: bool,
+}
+
+impl Capabilities {
+    /// 合并两个数据源的功能, 任一支持即为支持
+    pub fn union(self, other: Capabilities) -> Capabilities {
+        Capabilities {
+            tag_editing: self.tag_editing || other.tag_editing,
+            lyrics_editing: self.lyrics_editing || other.lyrics_editing,
+            scanning: self.scanning || other.scanning,
+            transcoding: self.transcoding || other.transcoding,
+            play_queue_sync: self.play_queue_sync || other.play_queue_sync,
+            ratings: self.ratings || other.ratings,
+            starred_status: self.starred_status || other.starred_status,
+            metadata_filtering: self.metadata_filtering || other.metadata_filtering,
+            smart_playlists: self.smart_playlists || other.smart_playlists,
+            playlist_subscription: self.playlist_subscription || other.playlist_subscription,
+            offline_pinning: self.offline_pinning || other.offline_pinning,
+            library_sync: self.library_sync || other.library_sync,
+            listening_stats: self.listening_stats || other.listening_stats,
+            radio: self.radio || other.radio,
+            daily_mixes: self.daily_mixes || other.daily_mixes,
+            duplicate_detection: self.duplicate_detection || other.duplicate_detection,
+        }
+    }
+}
+
+/// 智能歌单规则
+///
+/// 规则树序列化为 JSON 存储, 例如:
+/// `{"all": [{"genre_contains": "Rock"}, {"year_range": {"from": 1990, "to": 1999}}]}`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SmartRule {
+    /// 同时满足所有子规则
+    All(Vec<SmartRule>),
+    /// 满足任意一条子规则
+    Any(Vec<SmartRule>),
+    /// 不满足子规则
+    Not(Box<SmartRule>),
+    /// 风格包含指定文本
+    GenreContains(String),
+    /// 年份范围(含)
+    YearRange { from: Option<u32>, to: Option<u32> },
+    /// 艺术家在列表中
+    ArtistIn(Vec<String>),
+    /// 播放次数大于 N
+    PlayCountGt(u32),
+    /// 最近 N 天内没有播放过
+    NotPlayedInDays(u32),
+    /// 已收藏
+    Starred,
+    /// 评分不低于 N (1-5)
+    RatingAtLeast(u8),
+    /// 最近 N 天内添加
+    AddedInLastDays(u32),
+    /// 时长范围(秒)
+    DurationRange { min: Option<f64>, max: Option<f64> },
+    /// 比特率范围
+    BitrateRange { min: Option<u32>, max: Option<u32> },
+    /// BPM 范围
+    BpmRange { min: Option<f64>, max: Option<f64> },
+    /// 调性在列表中 (音名或 Camelot 记号, 如 "Am"、"8A")
+    KeyIn(Vec<String>),
+}
+
+/// 智能歌单排序字段
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SmartSort {
+    #[default]
+    Random,
+    Title,
+    Artist,
+    Album,
+    Year,
+    Duration,
+    /// 添加时间
+    Added,
+    /// 播放次数
+    PlayCount,
+    /// 最后播放时间
+    LastPlayed,
+    /// 评分
+    Rating,
+}
+
+/// 智能歌单定义: 规则 + 排序 + 数量限制
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SmartPlaylistRule {
+    pub rule: SmartRule,
+    #[serde(default)]
+    pub sort: SmartSort,
+    /// 是否倒序
+    #[serde(default)]
+    pub descending: bool,
+    /// 最多返回的歌曲数量, None 表示不限制
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// 歌单编辑操作
+///
+/// 位置均从 0 开始, 同一首歌可以在歌单中出现多次, 因此删除和移动都按位置而不是歌曲 ID
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PlaylistEdit {
+    /// 追加到末尾
+    Append { song_ids: Vec<String> },
+    /// 插入到指定位置, 超出长度时追加到末尾
+    Insert { index: usize, song_ids: Vec<String> },
+    /// 将 [from, from + count) 区间的歌曲移动到 to 位置(以移除区间后的列表计算)
+    Move { from: usize, count: usize, to: usize },
+    /// 按位置删除
+    Remove { positions: Vec<usize> },
+    /// 整体替换
+    Replace { song_ids: Vec<String> },
+}
+
+impl PlaylistEdit {
+    /// 对歌曲 ID 列表应用编辑操作
+    pub fn apply(&self, songs: &mut Vec<String>) -> anyhow::Result<()> {
+        match self {
+            PlaylistEdit::Append { song_ids } => songs.extend(song_ids.iter().cloned()),
+            PlaylistEdit::Insert { index, song_ids } => {
+                let index = (*index).min(songs.len());
+                songs.splice(index..index, song_ids.iter().cloned());
+            }
+            PlaylistEdit::Move { from, count, to } => {
+                let end = from.checked_add(*count).filter(|end| *end <= songs.len());
+                let Some(end) = end else {
+                    return Err(anyhow::anyhow!(
+                        "Move range out of bounds: from={}, count={}, len={}",
+                        from,
+                        count,
+                        songs.len()
+                    ));
+                };
+                if *to > songs.len() - count {
+                    return Err(anyhow::anyhow!(
+                        "Move target out of bounds: to={}, len={}",
+                        to,
+                        songs.len() - count
+                    ));
+                }
+                let moved: Vec<String> = songs.drain(*from..end).collect();
+                songs.splice(*to..*to, moved);
+            }
+            PlaylistEdit::Remove { positions } => {
+                if let Some(p) = positions.iter().find(|p| **p >= songs.len()) {
+                    return Err(anyhow::anyhow!(
+                        "Remove position out of bounds: {}, len={}",
+                        p,
+                        songs.len()
+                    ));
+                }
+                let mut index = 0;
+                songs.retain(|_| {
+                    let keep = !positions.contains(&index);
+                    index += 1;
+                    keep
+                });
+            }
+            PlaylistEdit::Replace { song_ids } => *songs = song_ids.clone(),
+        }
+        Ok(())
+    }
+}
+
+/// 时间窗口 [from, to), 为空表示不限制
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+impl TimeRange {
+    pub(crate) fn bounds(&self) -> (i64, i64) {
+        (self.from.unwrap_or(i64::MIN), self.to.unwrap_or(i64::MAX))
+    }
+}
+
+/// 统计对象
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsItemType {
+    Song,
+    Album,
+    Artist,
+    Genre,
+}
+
+impl StatsItemType {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "song" | "songs" => Ok(Self::Song),
+            "album" | "albums" => Ok(Self::Album),
+            "artist" | "artists" => Ok(Self::Artist),
+            "genre" | "genres" => Ok(Self::Genre),
+            _ => Err(anyhow::anyhow!("Unknown stats type: {}", value)),
+        }
+    }
+}
+
+/// 排行榜或首次收听的一项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsItem {
+    pub id: String,
+    pub name: String,
+    pub artist: Option<String>,
+    pub plays: i64,
+    /// 收听时长 (秒)
+    pub seconds: f64,
+    pub first_listened: i64,
+    pub last_listened: i64,
+}
+
+/// 收听时长的统计周期
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+impl StatsPeriod {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.to_lowercase().as_str() {
+            "day" => Ok(Self::Day),
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            _ => Err(anyhow::anyhow!("Unknown stats period: {}", value)),
+        }
+    }
+}
+
+/// 一个周期内的收听时长
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListeningTime {
+    pub period: String,
+    pub plays: i64,
+    pub seconds: f64,
+}
+
+/// 连续收听的天数
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Streaks {
+    /// 截止到今天 (或昨天) 的连续天数
+    pub current: i64,
+    pub current_start: Option<String>,
+    pub longest: i64,
+    pub longest_start: Option<String>,
+    pub longest_end: Option<String>,
+    /// 有收听记录的总天数
+    pub days: i64,
+}
+
+/// 热力图的一格
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeatmapCell {
+    /// 0 = 周日, 6 = 周六
+    pub weekday: i64,
+    /// 0 ~ 23
+    pub hour: i64,
+    pub plays: i64,
+    pub seconds: f64,
+}
+
+/// 一个月内播放最多的风格
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonthGenres {
+    /// "2024-03"
+    pub month: String,
+    pub genres: Vec<StatsItem>,
+}
+
+/// 一次连续收听
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListeningSession {
+    pub start: i64,
+    pub end: i64,
+    pub plays: i64,
+    pub seconds: f64,
+}
+
+/// 年度报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Wrapped {
+    pub year: i32,
+    pub total_plays: i64,
+    pub total_minutes: i64,
+    pub top_songs: Vec<StatsItem>,
+    pub top_albums: Vec<StatsItem>,
+    pub top_artists: Vec<StatsItem>,
+    pub top_genres: Vec<StatsItem>,
+    /// 今年第一次听的艺术家数量
+    pub new_artist_count: usize,
+    /// 今年第一次听的艺术家中播放最多的几位
+    pub new_artists: Vec<StatsItem>,
+    /// 播放次数最多的一天
+    pub most_played_day: Option<ListeningTime>,
+    /// 每个月的收听时长
+    pub monthly: Vec<ListeningTime>,
+    /// 每个月播放最多的风格
+    pub genre_shift: Vec<MonthGenres>,
+    pub longest_session: Option<ListeningSession>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_playlist_edit() {
+        let mut songs = ids(&["a", "b", "c"]);
+
+        PlaylistEdit::Append { song_ids: ids(&["a"]) }.apply(&mut songs).unwrap();
+        assert_eq!(songs, ids(&["a", "b", "c", "a"]));
+
+        PlaylistEdit::Insert { index: 1, song_ids: ids(&["x", "y"]) }
+            .apply(&mut songs)
+            .unwrap();
+        assert_eq!(songs, ids(&["a", "x", "y", "b", "c", "a"]));
+
+        PlaylistEdit::Move { from: 1, count: 2, to: 3 }.apply(&mut songs).unwrap();
+        assert_eq!(songs, ids(&["a", "b", "c", "x", "y", "a"]));
+
+        // 重复歌曲只删除指定位置
+        PlaylistEdit::Remove { positions: vec![5, 3] }.apply(&mut songs).unwrap();
+        assert_eq!(songs, ids(&["a", "b", "c", "y"]));
+
+        assert!(PlaylistEdit::Remove { positions: vec![4] }.apply(&mut songs).is_err());
+        assert!(PlaylistEdit::Move { from: 3, count: 2, to: 0 }.apply(&mut songs).is_err());
+        assert_eq!(songs, ids(&["a", "b", "c", "y"]));
+
+        PlaylistEdit::Replace { song_ids: ids(&["z"]) }.apply(&mut songs).unwrap();
+        assert_eq!(songs, ids(&["z"]));
+    }
+
+    #[test]
+    fn test_stream_options() {
+        assert_eq!(StreamOptions::default().to_query(), "");
+        assert_eq!(
+            StreamOptions::from_profile("Mobile").unwrap().to_query(),
+            "?max_bitrate=128&format=mp3"
+        );
+        assert_eq!(
+            StreamOptions::from_profile("lossless").unwrap().format.as_deref(),
+            Some("raw")
+        );
+        assert!(StreamOptions::from_profile("ultra").is_none());
+    }
+}
@@ -0,0 +1,295 @@
+// WebDAV HTTP 客户端
+// PROPFIND 遍历目录, GET 读取文件 (支持 Range)
+
+use std::collections::VecDeque;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use regex::Regex;
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use url::Url;
+
+use crate::comm::is_music_file;
+use crate::datasource::error::{DataSourceError, Result};
+
+/// PROPFIND 请求体, 只请求扫描需要的属性
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop><d:resourcetype/><d:getetag/><d:getcontentlength/><d:getlastmodified/></d:prop>
+</d:propfind>"#;
+
+static RESPONSE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)<(?:[\w-]+:)?response(?:\s[^>]*)?>(.*?)</(?:[\w-]+:)?response>").unwrap()
+});
+
+static COLLECTION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<(?:[\w-]+:)?collection\b").unwrap());
+
+/// 目录中的文件或子目录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DavEntry {
+    /// 服务器上的绝对路径 (已解码), 目录不以 '/' 结尾
+    pub path: String,
+    pub is_dir: bool,
+    pub etag: Option<String>,
+    pub size: u64,
+    pub last_modified: Option<String>,
+}
+
+impl DavEntry {
+    /// 用于判断文件是否变化, 服务器不返回 ETag 时使用大小和修改时间
+    pub fn version(&self) -> String {
+        match &self.etag {
+            Some(etag) => etag.clone(),
+            None => format!("{}:{}", self.size, self.last_modified.as_deref().unwrap_or_default()),
+        }
+    }
+}
+
+/// WebDAV 客户端
+pub struct WebDavClient {
+    // 服务器地址, 请求时替换路径部分
+    base: Url,
+    // 音乐目录在服务器上的路径 (已解码), 不以 '/' 结尾
+    root: String,
+    username: String,
+    password: String,
+    client: Client,
+    timeout: Duration,
+}
+
+impl WebDavClient {
+    /// 创建新的 WebDAV 客户端
+    ///
+    /// # 参数
+    /// * `server_url` - 音乐目录的地址 (如 "https://nas.example.com/dav/music")
+    /// * `username` - 用户名, 为空时不认证
+    /// * `password` - 密码
+    pub fn new(server_url: &str, username: String, password: String) -> anyhow::Result<Self> {
+        let base = Url::parse(server_url)?;
+        let root = percent_decode(base.path()).trim_end_matches('/').to_string();
+        Ok(Self {
+            base,
+            root,
+            username,
+            password,
+            client: Client::new(),
+            timeout: Duration::from_secs(15),
+        })
+    }
+
+    /// 设置单次请求超时 (音频流不受影响)
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// 音乐目录在服务器上的路径
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    pub fn server_url(&self) -> &str {
+        self.base.as_str()
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let mut url = self.base.clone();
+        // set_path 会对空格等字符编码
+        url.set_path(path);
+        let request = self.client.request(method, url);
+        if self.username.is_empty() {
+            request
+        } else {
+            request.basic_auth(&self.username, Some(&self.password))
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder, path: &str) -> Result<Response> {
+        let response = request.send().await.map_err(upstream)?;
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(DataSourceError::Unauthorized(
+                format!("WebDAV server rejected request: {}", path),
+            )),
+            StatusCode::NOT_FOUND => Err(DataSourceError::not_found(format!(
+                "WebDAV file not found: {}",
+                path
+            ))),
+            status => Err(DataSourceError::Upstream(anyhow::anyhow!(
+                "WebDAV HTTP {} for {}",
+                status,
+                path
+            ))),
+        }
+    }
+
+    /// 列出目录下的文件和子目录 (Depth: 1), 不包含目录本身
+    pub async fn list(&self, dir: &str) -> Result<Vec<DavEntry>> {
+        let dir = dir.trim_end_matches('/');
+        let request = self
+            .request(Method::from_bytes(b"PROPFIND").unwrap(), &format!("{}/", dir))
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(PROPFIND_BODY)
+            .timeout(self.timeout);
+        let body = self
+            .send(request, dir)
+            .await?
+            .text()
+            .await
+            .map_err(upstream)?;
+        Ok(parse_multistatus(&body)
+            .into_iter()
+            .filter(|e| e.path != dir)
+            .collect())
+    }
+
+    /// 递归列出目录下的所有音乐文件
+    pub async fn walk(&self, dir: &str) -> Result<Vec<DavEntry>> {
+        let mut files = vec![];
+        let mut dirs = VecDeque::from([dir.to_string()]);
+        while let Some(dir) = dirs.pop_front() {
+            for entry in self.list(&dir).await? {
+                if entry.is_dir {
+                    dirs.push_back(entry.path);
+                } else if is_music_file(&entry.path) {
+                    files.push(entry);
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// 读取文件的一部分
+    ///
+    /// # 返回
+    /// * `(data, partial)` - 服务器不支持 Range 时 `partial` 为 false, `data` 为整个文件
+    pub async fn get_range(&self, path: &str, start: u64, end: u64) -> Result<(Vec<u8>, bool)> {
+        let request = self
+            .request(Method::GET, path)
+            .header("Range", format!("bytes={}-{}", start, end))
+            .timeout(self.timeout);
+        let response = self.send(request, path).await?;
+        let partial = response.status() == StatusCode::PARTIAL_CONTENT;
+        let data = response.bytes().await.map_err(upstream)?;
+        Ok((data.to_vec(), partial))
+    }
+
+    /// 获取文件流, 转发 Range 请求头
+    pub async fn stream(&self, path: &str, range: Option<String>) -> Result<Response> {
+        // 音频流可能很长, 不设置整体超时
+        let mut request = self.request(Method::GET, path);
+        if let Some(range) = range {
+            request = request.header("Range", range);
+        }
+        self.send(request, path).await
+    }
+}
+
+fn upstream(e: reqwest::Error) -> DataSourceError {
+    DataSourceError::Upstream(anyhow::Error::new(e).context("WebDAV request failed"))
+}
+
+/// 解析 PROPFIND 返回的 multistatus, 兼容不同的命名空间前缀
+pub fn parse_multistatus(xml: &str) -> Vec<DavEntry> {
+    RESPONSE_RE
+        .captures_iter(xml)
+        .filter_map(|caps| {
+            let response = caps.get(1)?.as_str();
+            let href = xml_value(response, "href")?;
+            // href 可能是完整 URL, 也可能是绝对路径
+            let href = match Url::parse(&href) {
+                Ok(url) => url.path().to_string(),
+                Err(_) => href,
+            };
+            Some(DavEntry {
+                path: percent_decode(&href).trim_end_matches('/').to_string(),
+                is_dir: COLLECTION_RE.is_match(response),
+                etag: xml_value(response, "getetag")
+                    .map(|e| e.trim_start_matches("W/").trim_matches('"').to_string())
+                    .filter(|e| !e.is_empty()),
+                size: xml_value(response, "getcontentlength")
+                    .and_then(|s| s.trim().parse().ok())
+                    .unwrap_or(0),
+                last_modified: xml_value(response, "getlastmodified"),
+            })
+        })
+        .collect()
+}
+
+/// 读取元素的文本内容, 忽略命名空间前缀
+fn xml_value(xml: &str, name: &str) -> Option<String> {
+    let re = Regex::new(&format!(
+        r"(?s)<(?:[\w-]+:)?{0}(?:\s[^>]*)?>(.*?)</(?:[\w-]+:)?{0}>",
+        name
+    ))
+    .ok()?;
+    let value = re.captures(xml)?.get(1)?.as_str().trim();
+    Some(
+        value
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
+/// URL 路径解码 (%20 等)
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Ok(b) = u8::from_str_radix(&s[i + 1..i + 3], 16) {
+                decoded.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_multistatus() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<D:multistatus xmlns:D="DAV:">
+  <D:response>
+    <D:href>/dav/music/</D:href>
+    <D:propstat><D:prop><D:resourcetype><D:collection/></D:resourcetype></D:prop></D:propstat>
+  </D:response>
+  <D:response>
+    <D:href>https://nas.example.com/dav/music/Rock%20%26%20Roll/</D:href>
+    <D:propstat><D:prop><D:resourcetype><D:collection/></D:resourcetype></D:prop></D:propstat>
+  </D:response>
+  <D:response>
+    <D:href>/dav/music/01%20%E5%A4%A9%E5%8F%B0.flac</D:href>
+    <D:propstat>
+      <D:prop>
+        <D:resourcetype/>
+        <D:getetag>W/"abc-123"</D:getetag>
+        <D:getcontentlength>2048</D:getcontentlength>
+      </D:prop>
+    </D:propstat>
+  </D:response>
+</D:multistatus>"#;
+        let entries = parse_multistatus(xml);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].path, "/dav/music");
+        assert!(entries[1].is_dir);
+        assert_eq!(entries[1].path, "/dav/music/Rock & Roll");
+        assert_eq!(entries[2].path, "/dav/music/01 天台.flac");
+        assert!(!entries[2].is_dir);
+        assert_eq!(entries[2].etag.as_deref(), Some("abc-123"));
+        assert_eq!(entries[2].size, 2048);
+        assert_eq!(entries[2].version(), "abc-123");
+    }
+}
@@ -0,0 +1,298 @@
+// WebDAV 音乐库索引
+// 遍历 WebDAV 目录, 按 ETag 判断文件变化, 元数据保存到本地数据库 (与本地模式相同的表)
+
+use anyhow::{anyhow, Result};
+use rusqlite::params;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::client::{DavEntry, WebDavClient};
+use super::reader::RemoteFile;
+use crate::datasource::local::{connect_db, service};
+use crate::datasource::types::*;
+use crate::{log, readmeta};
+
+/// WebDAV 音乐库
+pub struct WebDavLibrary {
+    client: Arc<WebDavClient>,
+    progress: Mutex<ScanProgress>,
+    // 防止多个扫描任务同时运行
+    scanning: tokio::sync::Mutex<()>,
+}
+
+impl WebDavLibrary {
+    pub fn new(client: WebDavClient) -> Self {
+        Self {
+            client: Arc::new(client),
+            progress: Mutex::new(ScanProgress {
+                status: ScanStatus::Idle,
+                processed: 0,
+                total: 0,
+                current_file: None,
+                error: None,
+            }),
+            scanning: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn client(&self) -> &WebDavClient {
+        &self.client
+    }
+
+    /// 扫描进度
+    pub fn progress(&self) -> ScanProgress {
+        self.progress.lock().unwrap().clone()
+    }
+
+    fn update_progress(&self, update: impl FnOnce(&mut ScanProgress)) {
+        update(&mut self.progress.lock().unwrap());
+    }
+
+    /// 扫描 WebDAV 目录, 更新数据库中的元数据
+    ///
+    /// # 参数
+    /// * `full` - 为 true 时重新读取所有文件, 否则只读取新增和变化的文件
+    pub async fn scan(&self, full: bool) -> Result<()> {
+        let _guard = self
+            .scanning
+            .try_lock()
+            .map_err(|_| anyhow!("WebDAV scan is already running"))?;
+        self.update_progress(|p| {
+            *p = ScanProgress {
+                status: ScanStatus::Scanning,
+                processed: 0,
+                total: 0,
+                current_file: None,
+                error: None,
+            }
+        });
+
+        let result = self.scan_files(full).await;
+        self.update_progress(|p| {
+            p.current_file = None;
+            match &result {
+                Ok(_) => p.status = ScanStatus::Completed,
+                Err(e) => {
+                    p.status = ScanStatus::Failed;
+                    p.error = Some(e.to_string());
+                }
+            }
+        });
+        result
+    }
+
+    async fn scan_files(&self, full: bool) -> Result<()> {
+        let files = self.client.walk(self.client.root()).await?;
+        let known = load_indexed_files()?;
+        self.update_progress(|p| p.total = files.len());
+        log::log_info(&format!("WebDAV scan found {} music files", files.len()));
+
+        for (i, file) in files.iter().enumerate() {
+            self.update_progress(|p| {
+                p.processed = i;
+                p.current_file = Some(file.path.clone());
+            });
+            let indexed = known.get(&file.path);
+            if !full && indexed.is_some_and(|(version, _)| *version == file.version()) {
+                continue;
+            }
+            let old_song_id = indexed.map(|(_, song_id)| song_id.clone());
+            match self.index_file(file, old_song_id).await {
+                Ok(song_id) => save_indexed_file(&file.path, &file.version(), &song_id)?,
+                Err(e) => log::log_warn(&format!("WebDAV read metadata failed: {}, {}", file.path, e)),
+            }
+        }
+        self.update_progress(|p| p.processed = files.len());
+
+        // 服务器上已删除的文件
+        let present: HashSet<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        for (path, (_, song_id)) in known.iter().filter(|(p, _)| !present.contains(p.as_str())) {
+            log::log_info(&format!("WebDAV file removed: {}", path));
+            service::del_metadata_by_id(song_id)?;
+            connect_db()?.execute("DELETE FROM webdav_file WHERE path = ?1", params![path])?;
+        }
+        Ok(())
+    }
+
+    /// 通过 Range 请求读取文件标签, 保存到数据库, 返回歌曲 ID
+    async fn index_file(&self, file: &DavEntry, old_song_id: Option<String>) -> Result<String> {
+        let client = self.client.clone();
+        let file = file.clone();
+        tokio::task::spawn_blocking(move || {
+            let extension = file.path.rsplit('.').next().map(|e| e.to_lowercase());
+            let media = RemoteFile::new(
+                client.clone(),
+                file.path.clone(),
+                file.size,
+                tokio::runtime::Handle::current(),
+            );
+            let mut premetadata =
+                readmeta::read_metadata_from(Box::new(media), extension.as_deref(), true)?;
+            if premetadata.title.is_empty() {
+                return Err(anyhow!("title is empty"));
+            }
+            premetadata.build_genre(&file.path, client.root());
+
+            let mut metadata = premetadata.build_metadata();
+            metadata.file_name = file.path.rsplit('/').next().unwrap_or_default().to_string();
+            metadata.file_url = file.path[client.root().len()..].to_string();
+            metadata.file_path = file.path;
+            // 文件变化时保留原来的歌曲 ID, 收藏和歌单不受影响
+            if let Some(song_id) = old_song_id {
+                metadata.id = song_id;
+                service::set_metadata_by_id(&metadata)?;
+            }
+            readmeta::save_metadata_into_db(&premetadata, &metadata)
+        })
+        .await?
+    }
+
+    /// 获取歌曲的音频流, 转发 Range 请求头
+    pub async fn stream(
+        &self,
+        song_id: &str,
+        range: Option<String>,
+    ) -> crate::datasource::error::Result<reqwest::Response> {
+        let metadata = service::get_metadata_by_id(song_id)?.ok_or_else(|| {
+            crate::datasource::error::DataSourceError::not_found(format!(
+                "Song not found: {}",
+                song_id
+            ))
+        })?;
+        self.client.stream(&metadata.file_path, range).await
+    }
+}
+
+/// 已索引的文件, path -> (version, song_id)
+fn load_indexed_files() -> Result<HashMap<String, (String, String)>> {
+    let conn = connect_db()?;
+    let mut stmt = conn.prepare("SELECT path, version, song_id FROM webdav_file")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+fn save_indexed_file(path: &str, version: &str, song_id: &str) -> Result<()> {
+    connect_db()?.execute(
+        "INSERT OR REPLACE INTO webdav_file (path, version, song_id, updated_at) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            path,
+            version,
+            song_id,
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+        ],
+    )?;
+    Ok(())
+}
+
+/// 启动定时扫描任务, 启动时先扫描一次
+///
+/// `interval_minutes` 为 0 时只在启动时扫描
+pub fn spawn_scan_job(library: Arc<WebDavLibrary>, interval_minutes: u64) {
+    if tokio::runtime::Handle::try_current().is_err() {
+        log::log_warn("No tokio runtime, WebDAV scan job is not started");
+        return;
+    }
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = library.scan(false).await {
+                log::log_warn(&format!("WebDAV scan failed: {}", e));
+            }
+            if interval_minutes == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_secs(interval_minutes * 60)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_subsonic::{MockResponse, MockServer};
+    use std::io::{Read, Seek, SeekFrom};
+
+    const LISTING: &str = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:">
+  <d:response><d:href>/dav/music/</d:href>
+    <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat></d:response>
+  <d:response><d:href>/dav/music/Jazz/</d:href>
+    <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat></d:response>
+  <d:response><d:href>/dav/music/cover.jpg</d:href>
+    <d:propstat><d:prop><d:resourcetype/><d:getcontentlength>10</d:getcontentlength></d:prop></d:propstat></d:response>
+</d:multistatus>"#;
+
+    const JAZZ: &str = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:">
+  <d:response><d:href>/dav/music/Jazz/</d:href>
+    <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat></d:response>
+  <d:response><d:href>/dav/music/Jazz/Take%20Five.flac</d:href>
+    <d:propstat><d:prop><d:resourcetype/><d:getetag>"v1"</d:getetag>
+    <d:getcontentlength>300000</d:getcontentlength></d:prop></d:propstat></d:response>
+</d:multistatus>"#;
+
+    fn file_server() -> MockServer {
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        MockServer::start(move |req| match (req.method.as_str(), req.route()) {
+            ("PROPFIND", "/dav/music/") => MockResponse::bytes(207, LISTING.into()),
+            ("PROPFIND", "/dav/music/Jazz/") => MockResponse::bytes(207, JAZZ.into()),
+            ("GET", "/dav/music/Jazz/Take%20Five.flac") => {
+                let range = req.header("range").unwrap().trim_start_matches("bytes=");
+                let (start, end) = range.split_once('-').unwrap();
+                let (start, end): (usize, usize) = (start.parse().unwrap(), end.parse().unwrap());
+                MockResponse::bytes(206, data[start..=end].to_vec()).with_header(
+                    "Content-Range",
+                    &format!("bytes {}-{}/{}", start, end, data.len()),
+                )
+            }
+            _ => MockResponse::status(404),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_walk_music_files() {
+        let server = file_server();
+        let client =
+            WebDavClient::new(&format!("{}/dav/music", server.url), String::new(), String::new())
+                .unwrap();
+        let files = client.walk(client.root()).await.unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "/dav/music/Jazz/Take Five.flac");
+        assert_eq!(files[0].version(), "v1");
+        assert_eq!(server.count("PROPFIND"), 2);
+        let body = String::from_utf8(server.requests()[0].body.clone()).unwrap();
+        assert!(body.contains("getetag"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_remote_file_reads_only_needed_ranges() {
+        let server = file_server();
+        let client = Arc::new(
+            WebDavClient::new(&format!("{}/dav/music", server.url), String::new(), String::new())
+                .unwrap(),
+        );
+        let handle = tokio::runtime::Handle::current();
+        let (head, tail) = tokio::task::spawn_blocking(move || {
+            let mut file =
+                RemoteFile::new(client, "/dav/music/Jazz/Take Five.flac".into(), 300_000, handle);
+            let mut head = [0u8; 16];
+            file.read_exact(&mut head).unwrap();
+            file.seek(SeekFrom::End(-4)).unwrap();
+            let mut tail = vec![];
+            file.read_to_end(&mut tail).unwrap();
+            (head, tail)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(head[..4], [0, 1, 2, 3]);
+        assert_eq!(tail.len(), 4);
+        assert_eq!(tail[3], (299_999 % 251) as u8);
+        let ranges: Vec<_> = server
+            .requests()
+            .iter()
+            .filter_map(|r| r.header("range").map(|r| r.to_string()))
+            .collect();
+        assert_eq!(ranges, vec!["bytes=0-65535", "bytes=262144-299999"]);
+    }
+}
@@ -0,0 +1,9 @@
+// WebDAV 数据源
+// 索引 WebDAV 上的音乐目录, 元数据保存在本地数据库, 由 LocalDataSource 使用
+
+pub mod client;
+pub mod library;
+pub mod reader;
+
+pub use client::WebDavClient;
+pub use library::WebDavLibrary;
@@ -0,0 +1,107 @@
+// WebDAV 远程文件读取
+// 按需通过 Range 请求读取文件的一部分, 读取标签时只下载 symphonia 需要的数据块
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+use symphonia::core::io::MediaSource;
+use tokio::runtime::Handle;
+
+use super::client::WebDavClient;
+
+/// 每次 Range 请求的大小
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+/// 远程文件, 实现 Read + Seek 供 symphonia 读取
+///
+/// 内部使用 `Handle::block_on` 发送请求, 必须在 `spawn_blocking` 等阻塞线程中使用
+pub struct RemoteFile {
+    client: Arc<WebDavClient>,
+    path: String,
+    len: u64,
+    pos: u64,
+    handle: Handle,
+    // 已下载的数据块, key 为块序号
+    chunks: HashMap<u64, Vec<u8>>,
+    // 服务器不支持 Range 时下载的整个文件
+    full: Option<Vec<u8>>,
+}
+
+impl RemoteFile {
+    pub fn new(client: Arc<WebDavClient>, path: String, len: u64, handle: Handle) -> Self {
+        Self {
+            client,
+            path,
+            len,
+            pos: 0,
+            handle,
+            chunks: HashMap::new(),
+            full: None,
+        }
+    }
+
+    fn load_chunk(&mut self, index: u64) -> std::io::Result<()> {
+        if self.full.is_some() || self.chunks.contains_key(&index) {
+            return Ok(());
+        }
+        let start = index * CHUNK_SIZE;
+        let end = (start + CHUNK_SIZE).min(self.len) - 1;
+        let (data, partial) = self
+            .handle
+            .block_on(self.client.get_range(&self.path, start, end))
+            .map_err(|e| Error::other(e.to_string()))?;
+        if partial {
+            self.chunks.insert(index, data);
+        } else {
+            self.full = Some(data);
+        }
+        Ok(())
+    }
+}
+
+impl Read for RemoteFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let index = self.pos / CHUNK_SIZE;
+        self.load_chunk(index)?;
+        let (data, offset) = match &self.full {
+            Some(full) => (full, self.pos as usize),
+            None => (&self.chunks[&index], (self.pos - index * CHUNK_SIZE) as usize),
+        };
+        if offset >= data.len() {
+            return Ok(0);
+        }
+        let n = buf.len().min(data.len() - offset);
+        buf[..n].copy_from_slice(&data[offset..offset + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for RemoteFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(p) => p as i64,
+            SeekFrom::End(p) => self.len as i64 + p,
+            SeekFrom::Current(p) => self.pos as i64 + p,
+        };
+        if pos < 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "seek before start of file"));
+        }
+        self.pos = pos as u64;
+        Ok(self.pos)
+    }
+}
+
+impl MediaSource for RemoteFile {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        Some(self.len)
+    }
+}
@@ -53,6 +53,124 @@ pub fn compress_img(img: &DynamicImage, qulity: i8) -> Result<Vec<u8>, String> {
     }
 }
 
+// 解码上传的图片, 缩小到 size 以内并重新编码为 webp
+pub fn reencode_webp(bytes: &[u8], size: u32, qulity: i8) -> Result<Vec<u8>, String> {
+    let img = image::load_from_memory(bytes).map_err(|e| e.to_string())?;
+    let (width, height) = img.dimensions();
+    let img = if width.min(height) > size {
+        *resize_image(Box::new(img), size)
+    } else {
+        img
+    };
+    compress_img(&DynamicImage::ImageRgba8(img.to_rgba8()), qulity)
+}
+
+// 拼接歌单封面: 4 张及以上拼成 2x2, 否则使用第一张, 输出 webp
+pub fn collage_cover(covers: &[Vec<u8>], size: u32, qulity: i8) -> Result<Vec<u8>, String> {
+    let images: Vec<DynamicImage> = covers
+        .iter()
+        .filter_map(|c| image::load_from_memory(c).ok())
+        .take(4)
+        .collect();
+    if images.is_empty() {
+        return Err("no cover image".to_string());
+    }
+
+    let collage = if images.len() < 4 {
+        let img = images[0].resize_to_fill(size, size, image::imageops::FilterType::Triangle);
+        DynamicImage::ImageRgba8(img.to_rgba8())
+    } else {
+        let half = size / 2;
+        let mut canvas = image::RgbaImage::new(half * 2, half * 2);
+        for (i, img) in images.iter().enumerate() {
+            let tile = img.resize_to_fill(half, half, image::imageops::FilterType::Triangle);
+            let x = (i as u32 % 2) * half;
+            let y = (i as u32 / 2) * half;
+            image::imageops::overlay(&mut canvas, &tile.to_rgba8(), x as i64, y as i64);
+        }
+        DynamicImage::ImageRgba8(canvas)
+    };
+
+    compress_img(&collage, qulity)
+}
+
+// 5x7 点阵字体, 用于在生成的图片上绘制数字和英文标签 (不支持中文)
+const FONT_CHARS: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ-:.? ";
+const FONT_GLYPHS: [[u8; 7]; 41] = [
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+    [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11],
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+];
+
+// 文字宽度 (像素), 每个字符 5 列加 1 列间距
+pub fn text_width(text: &str, scale: u32) -> u32 {
+    (text.chars().count() as u32 * 6).saturating_sub(1) * scale
+}
+
+// 绘制文字, 小写字母转为大写, 不支持的字符显示为 '?'
+pub fn draw_text(canvas: &mut image::RgbaImage, text: &str, x: u32, y: u32, scale: u32, color: image::Rgba<u8>) {
+    for (i, c) in text.to_ascii_uppercase().chars().enumerate() {
+        let index = FONT_CHARS.find(c).unwrap_or(FONT_CHARS.len() - 2);
+        let left = x + i as u32 * 6 * scale;
+        for (row, bits) in FONT_GLYPHS[index].iter().enumerate() {
+            for col in 0..5 {
+                if bits & (0x10 >> col) != 0 {
+                    fill_rect(canvas, left + col * scale, y + row as u32 * scale, scale, scale, color);
+                }
+            }
+        }
+    }
+}
+
+// 填充矩形, 超出画布的部分忽略
+pub fn fill_rect(canvas: &mut image::RgbaImage, x: u32, y: u32, width: u32, height: u32, color: image::Rgba<u8>) {
+    let right = (x + width).min(canvas.width());
+    let bottom = (y + height).min(canvas.height());
+    for py in y..bottom {
+        for px in x..right {
+            canvas.put_pixel(px, py, color);
+        }
+    }
+}
+
 // 保存图片到指定路径
 pub fn save_img(buf: &[u8], file_path: &str) -> Result<usize, String> {
     let start_time = std::time::Instant::now();
@@ -4,13 +4,18 @@ use regex::Regex;
 use walkdir::WalkDir;
 use std::collections::HashMap;
 use std::fs::File;
+use std::sync::atomic::{AtomicBool, Ordering};
 use symphonia::core::formats::FormatOptions;
-use symphonia::core::io::MediaSourceStream;
+use symphonia::core::io::{MediaSource, MediaSourceStream};
 use symphonia::core::meta::{MetadataOptions, Tag, Value, Visual};
 use symphonia::core::probe::{Hint, ProbeResult};
 
 use crate::comm::is_music_file;
+use crate::audio::key::{Key, KeyDetector};
+use crate::audio::tempo::{self, TempoDetector};
+use crate::audio::{self, fingerprint, loudness, waveform};
 use crate::config::get_config;
+use crate::datasource::types::ReplayGain;
 use crate::datasource::local::service::{self, Metadata};
 use crate::datasource::local::service::*;
 use crate::image::{compress_img, resize_image};
@@ -34,6 +39,10 @@ pub struct PreMetadata {
     pub samplerate: String,
     pub disc: String,
     pub comment: String,
+    pub replay_gain: ReplayGain,
+    pub bpm: Option<f64>,
+    /// 调性, 已转换为音名记号
+    pub musical_key: Option<String>,
     pub covers: Vec<Cover>,
     pub lyrics: Vec<Lyric>,
 }
@@ -61,11 +70,25 @@ pub struct Pair {
 
 pub fn read_metadata(file_path: &str, is_proc_cover: bool) -> Result<PreMetadata, Error> {
     let file = File::open(file_path)?;
-    let source = MediaSourceStream::new(Box::new(file), Default::default());
+    read_metadata_from(Box::new(file), None, is_proc_cover)
+}
+
+/// 从任意数据流读取元数据 (本地文件或 WebDAV 上的远程文件)
+///
+/// `extension` 为文件扩展名, 用于提示 symphonia 文件格式
+pub fn read_metadata_from(
+    media: Box<dyn MediaSource>,
+    extension: Option<&str>,
+    is_proc_cover: bool,
+) -> Result<PreMetadata, Error> {
+    let source = MediaSourceStream::new(media, Default::default());
     let format_options = FormatOptions::default();
     let metadata_options = MetadataOptions::default();
 
-    let hint = Hint::new();
+    let mut hint = Hint::new();
+    if let Some(extension) = extension {
+        hint.with_extension(extension);
+    }
     let mut probed = symphonia::default::get_probe().format(
         &hint,
         source,
@@ -210,6 +233,12 @@ fn get_metadata(probed: &mut ProbeResult, is_proc_cover: bool) -> Option<PreMeta
             "language" => metadata.language = value,
             "comment" => metadata.comment = value,
             "lyrics" => metadata.lyrics = proc_lyrics(value),
+            "replaygaintrackgain" => metadata.replay_gain.track_gain = parse_gain(&value),
+            "replaygaintrackpeak" => metadata.replay_gain.track_peak = parse_gain(&value),
+            "replaygainalbumgain" => metadata.replay_gain.album_gain = parse_gain(&value),
+            "replaygainalbumpeak" => metadata.replay_gain.album_peak = parse_gain(&value),
+            "bpm" => metadata.bpm = tempo::parse_bpm(&value),
+            "initialkey" => metadata.musical_key = Key::parse(&value).map(|k| k.to_string()),
             _ => {
                 // if value.len() > 30 {
                 //     let value = value.chars().take(30).collect::<String>();
@@ -241,6 +270,17 @@ fn get_metadata(probed: &mut ProbeResult, is_proc_cover: bool) -> Option<PreMeta
     Some(metadata)
 }
 
+// ReplayGain 标签的值, 如 "-6.54 dB" 或 "0.988547"
+fn parse_gain(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let value = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .or_else(|| value.strip_suffix("DB"))
+        .unwrap_or(value);
+    value.trim().parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
 fn proc_tags(tags: &[Tag]) -> Vec<Pair> {
     let mut pairs = Vec::new();
     for tag in tags.iter() {
@@ -250,11 +290,18 @@ fn proc_tags(tags: &[Tag]) -> Vec<Pair> {
                 continue;
             }
             pairs.push(pair);
+        } else if is_initial_key(&tag.key) {
+            pairs.push(get_key_tag_value("InitialKey", &tag.value));
         }
     }
     pairs
 }
 
+// 调性标签没有标准 key: ID3v2 为 TKEY, Vorbis 为 INITIALKEY 或 KEY
+fn is_initial_key(key: &str) -> bool {
+    ["TKEY", "INITIALKEY", "KEY"].iter().any(|k| key.eq_ignore_ascii_case(k))
+}
+
 fn get_key_tag_value(key: &str, value: &Value) -> Pair {
     match value {
         Value::String(s) => Pair {
@@ -423,10 +470,16 @@ pub fn read_metadata_into_db(file_path: &str, music_dir: &str) -> Result<(), Err
     } else {
         file_path
     };
+    // 从音乐库移除的文件 (如重复歌曲的副本) 不再写入
+    if is_excluded_file(file_path)? {
+        return Err(Error::msg("excluded file"));
+    }
     // 处理meta数据
     let (premetadata, metadata) = proc_metadata(&file_path, &music_dir, false)?;
     // 开始写入数据
-    let song_id = insert_meta(&metadata)?;
+    let song_id = insert_meta(&metadata, |path| Path::new(&local_file_path(path.to_string())).exists())?;
+    insert_replay_gain(&premetadata, &song_id)?;
+    insert_bpm_key(&premetadata, &song_id)?;
     let (album_name, album_id, album_song_size) = insert_album(&premetadata, &song_id)?;
     let (artist_len, artist_song_size) = insert_artist(&premetadata, &song_id)?;
     let cover_size = insert_cover(&file_path, &music_dir, album_id)?;
@@ -450,6 +503,21 @@ pub fn read_metadata_into_db(file_path: &str, music_dir: &str) -> Result<(), Err
     Ok(())
 }
 
+/// 将已经读取的元数据写入数据库, 返回歌曲 ID
+///
+/// 与 `read_metadata_into_db` 相同, 但封面使用 `premetadata.covers`, 不再重新读取文件
+pub fn save_metadata_into_db(premetadata: &PreMetadata, metadata: &Metadata) -> Result<String, Error> {
+    // WebDAV 文件按路径建立索引, 文件移动时由索引删除旧的歌曲, 同名的歌曲总是作为新歌曲加入
+    let song_id = insert_meta(metadata, |_| true)?;
+    insert_replay_gain(premetadata, &song_id)?;
+    insert_bpm_key(premetadata, &song_id)?;
+    let (_, album_id, _) = insert_album(premetadata, &song_id)?;
+    insert_artist(premetadata, &song_id)?;
+    insert_covers(premetadata, album_id)?;
+    insert_lyrics(premetadata, &song_id)?;
+    Ok(song_id)
+}
+
 fn proc_metadata(
     file_path: &str,
     music_dir: &str,
@@ -499,30 +567,54 @@ fn proc_metadata(
     Ok((premetadata, metadata))
 }
 
-fn insert_meta(metadata: &Metadata) -> Result<String, Error> {
-    let exist = get_metadata_by_title_artist(&metadata.title, &metadata.artist)?;
-    let song_id = if exist.is_none() {
-        let _ = add_metadata(&metadata)?;
-        metadata.id.clone()
-    } else {
-        let exist = exist.unwrap();
-        // metadata.id = exist.id.clone();
-        if exist.file_path != metadata.file_path {
-            let size = set_metadata_by_id(&metadata)?;
-            let _ = log_file(
-                LOG_PATH,
-                "info",
-                &format!(
-                    "Already exist, but file path is different, {} -> {}, update rows: {}",
-                    exist.file_path, metadata.file_path, size
-                ),
-            );
-        }
+// 写入歌曲, 返回歌曲 ID
+//
+// 同一个文件沿用原来的 ID; 标题和艺术家相同、且原来的文件已经不存在 (`file_exists` 为 false) 时
+// 认为文件被移动, 更新路径并沿用原来的 ID; 其他情况 (重复的副本或同名的不同录音) 作为新歌曲加入,
+// 重复的副本由声学指纹检测
+fn insert_meta(metadata: &Metadata, file_exists: impl Fn(&str) -> bool) -> Result<String, Error> {
+    if let Some(exist) = get_metadata_by_file_path(&metadata.file_path)? {
+        return Ok(exist.id);
+    }
+    let moved = get_metadata_list_by_title_artist(&metadata.title, &metadata.artist)?
+        .into_iter()
+        .find(|exist| !file_exists(&exist.file_path));
+    let song_id = if let Some(exist) = moved {
+        let mut metadata = metadata.clone();
+        metadata.id = exist.id.clone();
+        let size = set_metadata_by_id(&metadata)?;
+        let _ = log_file(
+            LOG_PATH,
+            "info",
+            &format!(
+                "File moved, {} -> {}, update rows: {}",
+                exist.file_path, metadata.file_path, size
+            ),
+        );
         exist.id
+    } else {
+        let _ = add_metadata(metadata)?;
+        metadata.id.clone()
     };
     Ok(song_id)
 }
 
+// 写入 ReplayGain 标签, 没有标签的歌曲由后台任务分析
+fn insert_replay_gain(premetadata: &PreMetadata, song_id: &str) -> Result<usize, Error> {
+    if premetadata.replay_gain == ReplayGain::default() {
+        return Ok(0);
+    }
+    Ok(set_replay_gain(song_id, &premetadata.replay_gain)?)
+}
+
+// 写入 BPM 和调性标签, 没有标签的歌曲在开启 analyze_tempo 时由后台任务估计
+fn insert_bpm_key(premetadata: &PreMetadata, song_id: &str) -> Result<usize, Error> {
+    if premetadata.bpm.is_none() && premetadata.musical_key.is_none() {
+        return Ok(0);
+    }
+    Ok(set_bpm_key(song_id, premetadata.bpm, premetadata.musical_key.as_deref())?)
+}
+
 // 写入 artist 和 artist_song 数据
 fn insert_album(premetadata: &PreMetadata, song_id: &str) -> Result<(String, i64, usize), Error> {
     let album = premetadata.build_album();
@@ -574,6 +666,11 @@ fn insert_cover(file_path: &str, music_dir: &str, album_id: i64) -> Result<usize
     }
 
     let (premetadata, _) = proc_metadata(&file_path, &music_dir, true)?;
+    insert_covers(&premetadata, album_id)
+}
+
+// 写入已读取的封面数据, 跳过已存在的尺寸
+fn insert_covers(premetadata: &PreMetadata, album_id: i64) -> Result<usize, Error> {
     let mut covers = premetadata.build_covers(album_id, "album");
     let mut exist_cover = vec![];
     for cover in &covers {
@@ -625,6 +722,8 @@ pub async fn check_lost_file(music_dir: &str) {
         path_metadata_map.insert(metadata.file_path.to_string(), metadata);
     }
 
+    let excluded = service::get_excluded_files().unwrap_or_default();
+
     let mut lost_files = Vec::new();
     // 第一种情况：遍历音乐目录，如果文件路径在metas中不存在，则认为是缺失文件
     for entry in WalkDir::new(music_dir).into_iter().filter_map(|e| e.ok()) {
@@ -634,6 +733,9 @@ pub async fn check_lost_file(music_dir: &str) {
                 continue;
             }
             let path2 = path.clone().replace(music_dir, "/mnt/data/music");
+            if excluded.contains(&path) || excluded.contains(&path2) {
+                continue;
+            }
             let metadata = path_metadata_map.get(&path);
             let metadata2 = path_metadata_map.get(&path2);
             if metadata.is_none() && metadata2.is_none() {
@@ -645,7 +747,7 @@ pub async fn check_lost_file(music_dir: &str) {
     }
 
     // 第二种情况：对应路径存在metadata，但是关联的封面、歌词等缺失，也需要重新扫描
-    let album_list = if let Ok(list) = service::get_album_list(None) {
+    let album_list = if let Ok(list) = service::get_album_list(1, None) {
         list.iter().map(|album| album.name.to_string()).collect()
     } else {
         Vec::new()
@@ -720,6 +822,195 @@ pub async fn check_lost_file(music_dir: &str) {
             }
             let elapsed = start.elapsed();
             log::log_info(&format!("Scan lost files done. Elapsed: {:.2?}", elapsed));
+            spawn_analysis_job();
+        });
+    } else {
+        spawn_analysis_job();
+    }
+}
+
+static ANALYSIS_JOB_RUNNING: AtomicBool = AtomicBool::new(false);
+
+// 后台分析音频: 先分析没有 ReplayGain 标签的歌曲的响度并计算专辑增益, 再生成波形峰值数据,
+// 开启 analyze_tempo 时最后估计 BPM 和调性
+pub fn spawn_analysis_job() {
+    if ANALYSIS_JOB_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let _ = std::thread::spawn(|| {
+        analyze_loudness();
+        generate_waveforms();
+        generate_fingerprints();
+        if get_config().analyze_tempo {
+            analyze_tempo();
+        }
+        ANALYSIS_JOB_RUNNING.store(false, Ordering::SeqCst);
+    });
+}
+
+// 本地文件路径, 调试模式下替换为本机的音乐目录
+fn local_file_path(file_path: String) -> String {
+    let config = get_config();
+    if config.debug {
+        file_path.replace("/mnt/data/music", &config.music_dir)
+    } else {
+        file_path
+    }
+}
+
+// 分析缺少单曲增益的歌曲响度; 分析失败时标记 loudness_failed, 避免重复分析
+fn analyze_loudness() {
+    let songs = match service::get_songs_without_replay_gain() {
+        Ok(songs) => songs,
+        Err(e) => {
+            log::log_err(&format!("get songs without replay gain error: {e}"));
+            return;
+        }
+    };
+    if !songs.is_empty() {
+        log::log_info(&format!("Start analyze loudness, songs: {}", songs.len()));
+    }
+
+    let start = std::time::Instant::now();
+    for (song_id, file_path) in songs {
+        let file_path = local_file_path(file_path);
+        match loudness::analyze_file(&file_path) {
+            Ok(result) => {
+                let gain = ReplayGain {
+                    track_gain: Some((result.gain() * 100.0).round() / 100.0),
+                    track_peak: Some(result.true_peak),
+                    ..Default::default()
+                };
+                if let Err(e) = set_replay_gain(&song_id, &gain) {
+                    log::log_err(&format!("save replay gain error: {song_id}, {e}"));
+                }
+            }
+            Err(e) => {
+                log::log_info(&format!("path: {file_path}, analyze loudness error: {e}"));
+                if let Err(e) = service::set_loudness_failed(&song_id) {
+                    log::log_err(&format!("save replay gain error: {song_id}, {e}"));
+                }
+            }
+        }
+    }
+
+    match service::update_album_replay_gains() {
+        Ok(count) => log::log_info(&format!(
+            "Analyze loudness done, albums: {count}. Elapsed: {:.2?}",
+            start.elapsed()
+        )),
+        Err(e) => log::log_err(&format!("update album replay gain error: {e}")),
+    }
+}
+
+// 为没有缓存的本地歌曲生成波形峰值数据, 缓存 key 为文件路径
+fn generate_waveforms() {
+    let waveform_dir = get_config().waveform_dir;
+    let songs = service::get_metadata_list().unwrap_or_default();
+    let start = std::time::Instant::now();
+    let mut count = 0;
+    for metadata in songs {
+        if waveform::cache_path(&waveform_dir, &metadata.file_path).exists() {
+            continue;
+        }
+        let file_path = local_file_path(metadata.file_path.clone());
+        let result = waveform::analyze_file(&file_path)
+            .and_then(|w| waveform::save_cached(&waveform_dir, &metadata.file_path, &w));
+        match result {
+            Ok(()) => count += 1,
+            Err(e) => log::log_info(&format!("path: {file_path}, generate waveform error: {e}")),
+        }
+    }
+    if count > 0 {
+        log::log_info(&format!("Generate waveforms done, songs: {count}. Elapsed: {:.2?}", start.elapsed()));
+    }
+}
+
+// 计算声学指纹, 同时记录格式、码率和采样率, 用于重复歌曲检测
+fn generate_fingerprints() {
+    let songs = match service::get_songs_without_fingerprint() {
+        Ok(songs) => songs,
+        Err(e) => {
+            log::log_err(&format!("get songs without fingerprint error: {e}"));
+            return;
+        }
+    };
+    if songs.is_empty() {
+        return;
+    }
+    log::log_info(&format!("Start generate fingerprints, songs: {}", songs.len()));
+
+    let start = std::time::Instant::now();
+    for (song_id, file_path, duration) in songs {
+        let file_path = local_file_path(file_path);
+        let format = Path::new(&file_path)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let size = std::fs::metadata(&file_path).map(|m| m.len()).unwrap_or(0);
+        let bitrate = if duration > 0.0 { (size as f64 * 8.0 / duration / 1000.0).round() as u32 } else { 0 };
+        // 音频太短、静音或解码失败时记录为失败, 避免重复分析
+        let (data, samplerate) = match fingerprint::analyze_file(&file_path) {
+            Ok((print, samplerate)) => (print.map(|p| p.to_bytes()), samplerate),
+            Err(e) => {
+                log::log_info(&format!("path: {file_path}, generate fingerprint error: {e}"));
+                (None, 0)
+            }
+        };
+        if let Err(e) = set_fingerprint(&song_id, data.as_deref(), &format, bitrate, samplerate) {
+            log::log_err(&format!("save fingerprint error: {song_id}, {e}"));
+        }
+    }
+    log::log_info(&format!("Generate fingerprints done. Elapsed: {:.2?}", start.elapsed()));
+}
+
+// 估计缺少 BPM 或调性的歌曲, 只补充缺少的字段; 无法估计时标记 tempo_failed, 避免重复分析
+fn analyze_tempo() {
+    let songs = match service::get_songs_without_bpm_key() {
+        Ok(songs) => songs,
+        Err(e) => {
+            log::log_err(&format!("get songs without bpm or key error: {e}"));
+            return;
+        }
+    };
+    if songs.is_empty() {
+        return;
+    }
+    log::log_info(&format!("Start analyze tempo and key, songs: {}", songs.len()));
+
+    let start = std::time::Instant::now();
+    for (song_id, file_path, has_bpm, has_key) in songs {
+        let file_path = local_file_path(file_path);
+        let mut detectors: Option<(TempoDetector, KeyDetector)> = None;
+        let result = audio::decode_file(&file_path, |samples, channels, rate| {
+            let (tempo, key) = detectors
+                .get_or_insert_with(|| (TempoDetector::new(channels, rate), KeyDetector::new(channels, rate)));
+            if !has_bpm {
+                tempo.add_samples(samples);
+            }
+            if !has_key {
+                key.add_samples(samples);
+            }
         });
+        if let Err(e) = result {
+            log::log_info(&format!("path: {file_path}, analyze tempo error: {e}"));
+            continue;
+        }
+
+        let (bpm, key) = match &detectors {
+            Some((tempo, key)) => (tempo.finish(), key.finish().map(|k| k.to_string())),
+            None => (None, None),
+        };
+        let bpm = bpm.filter(|_| !has_bpm);
+        let key = key.filter(|_| !has_key);
+        if let Err(e) = set_bpm_key(&song_id, bpm, key.as_deref()) {
+            log::log_err(&format!("save bpm and key error: {song_id}, {e}"));
+        }
+        if (!has_bpm && bpm.is_none()) || (!has_key && key.is_none()) {
+            if let Err(e) = service::set_tempo_failed(&song_id) {
+                log::log_err(&format!("save bpm and key error: {song_id}, {e}"));
+            }
+        }
     }
+    log::log_info(&format!("Analyze tempo and key done. Elapsed: {:.2?}", start.elapsed()));
 }
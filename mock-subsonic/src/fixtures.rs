@@ -0,0 +1,167 @@
+// 模拟服务器的音乐库数据
+
+use std::collections::{HashMap, HashSet};
+
+/// 1x1 的 PNG 图片, 作为所有封面返回
+pub const COVER_PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x00, 0x00, 0x00, 0x90, 0x77, 0x53,
+    0xde, 0x00, 0x00, 0x00, 0x0c, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0xf8, 0xcf, 0xc0, 0x00,
+    0x00, 0x03, 0x01, 0x01, 0x00, 0xc9, 0xfe, 0x92, 0xef, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e,
+    0x44, 0xae, 0x42, 0x60, 0x82,
+];
+
+#[derive(Debug, Clone)]
+pub struct Artist {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Album {
+    pub id: String,
+    pub name: String,
+    pub artist_id: String,
+    pub year: u32,
+    pub genre: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Song {
+    pub id: String,
+    pub title: String,
+    pub album_id: String,
+    pub artist_id: String,
+    pub track: u32,
+    /// 时长 (秒)
+    pub duration: u32,
+    /// 音频数据的字节数, 内容由 `audio_data` 生成
+    pub size: usize,
+}
+
+impl Song {
+    /// 歌曲的音频数据, 每首歌的内容不同, 便于校验 Range 请求
+    pub fn audio_data(&self) -> Vec<u8> {
+        let seed = self.id.bytes().fold(0u32, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u32));
+        (0..self.size)
+            .map(|i| ((i as u32).wrapping_add(seed) % 251) as u8)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Playlist {
+    pub id: String,
+    pub name: String,
+    pub comment: String,
+    pub public: bool,
+    pub song_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayQueue {
+    pub song_ids: Vec<String>,
+    pub current: Option<String>,
+    pub position: u64,
+}
+
+/// 收到的 scrobble 请求
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scrobble {
+    pub id: String,
+    pub submission: bool,
+    pub time: Option<u64>,
+}
+
+/// 模拟服务器的状态, 写接口会修改这里的数据
+#[derive(Debug, Clone, Default)]
+pub struct Library {
+    pub artists: Vec<Artist>,
+    pub albums: Vec<Album>,
+    pub songs: Vec<Song>,
+    pub playlists: Vec<Playlist>,
+    pub play_queue: Option<PlayQueue>,
+    /// 已收藏的歌曲、专辑、艺术家 ID
+    pub starred: HashSet<String>,
+    /// 歌曲、专辑、艺术家 ID -> 评分 (1-5)
+    pub ratings: HashMap<String, u32>,
+    /// 歌曲 ID -> BPM
+    pub bpms: HashMap<String, u32>,
+    pub scrobbles: Vec<Scrobble>,
+    pub next_playlist_id: u32,
+    /// `getIndexes` 返回的音乐库修改时间 (毫秒)
+    pub last_modified: i64,
+}
+
+impl Library {
+    pub fn song(&self, id: &str) -> Option<&Song> {
+        self.songs.iter().find(|s| s.id == id)
+    }
+
+    pub fn album(&self, id: &str) -> Option<&Album> {
+        self.albums.iter().find(|a| a.id == id)
+    }
+
+    pub fn artist(&self, id: &str) -> Option<&Artist> {
+        self.artists.iter().find(|a| a.id == id)
+    }
+
+    pub fn playlist(&self, id: &str) -> Option<&Playlist> {
+        self.playlists.iter().find(|p| p.id == id)
+    }
+}
+
+/// 默认的音乐库: 2 位艺术家, 3 张专辑, 6 首歌曲, 1 个歌单
+pub fn library() -> Library {
+    let artist = |id: &str, name: &str| Artist {
+        id: id.to_string(),
+        name: name.to_string(),
+    };
+    let album = |id: &str, name: &str, artist_id: &str, year, genre: &str| Album {
+        id: id.to_string(),
+        name: name.to_string(),
+        artist_id: artist_id.to_string(),
+        year,
+        genre: genre.to_string(),
+    };
+    let song = |id: &str, title: &str, album_id: &str, artist_id: &str, track, duration| Song {
+        id: id.to_string(),
+        title: title.to_string(),
+        album_id: album_id.to_string(),
+        artist_id: artist_id.to_string(),
+        track,
+        duration,
+        size: 8192,
+    };
+
+    Library {
+        artists: vec![artist("ar-1", "Miles Davis"), artist("ar-2", "周杰伦")],
+        albums: vec![
+            album("al-1", "Kind of Blue", "ar-1", 1959, "Jazz"),
+            album("al-2", "叶惠美", "ar-2", 2003, "Pop"),
+            album("al-3", "七里香", "ar-2", 2004, "Pop"),
+        ],
+        songs: vec![
+            song("so-1", "So What", "al-1", "ar-1", 1, 562),
+            song("so-2", "Freddie Freeloader", "al-1", "ar-1", 2, 589),
+            song("so-3", "晴天", "al-2", "ar-2", 3, 269),
+            song("so-4", "东风破", "al-2", "ar-2", 5, 315),
+            song("so-5", "七里香", "al-3", "ar-2", 1, 299),
+            song("so-6", "借口", "al-3", "ar-2", 2, 295),
+        ],
+        playlists: vec![Playlist {
+            id: "pl-1".to_string(),
+            name: "Morning".to_string(),
+            comment: String::new(),
+            public: false,
+            song_ids: vec!["so-1".to_string(), "so-3".to_string()],
+        }],
+        play_queue: None,
+        starred: HashSet::new(),
+        ratings: HashMap::new(),
+        bpms: HashMap::new(),
+        scrobbles: vec![],
+        next_playlist_id: 2,
+        last_modified: 1_700_000_000_000,
+    }
+}
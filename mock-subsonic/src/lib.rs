@@ -0,0 +1,9 @@
+// 测试用的模拟服务器
+// 在测试进程内启动 HTTP 服务器, 不需要网络和真实的服务器账号
+
+pub mod fixtures;
+pub mod http;
+pub mod subsonic;
+
+pub use http::{MockRequest, MockResponse, MockServer};
+pub use subsonic::MockSubsonic;
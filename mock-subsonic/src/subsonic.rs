@@ -0,0 +1,564 @@
+// 模拟 Subsonic 服务器
+// 实现 SubsonicClient 调用的接口, 校验 token 认证, 返回 JSON 格式的响应
+
+use md5::{Digest, Md5};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+use crate::fixtures::{self, Library, PlayQueue, Playlist, Scrobble, COVER_PNG};
+use crate::http::{MockRequest, MockResponse, MockServer};
+
+const API_VERSION: &str = "1.16.1";
+
+/// 收藏时间, 所有收藏的项目使用相同的时间
+const STARRED_AT: &str = "2024-01-01T00:00:00.000Z";
+
+pub struct MockSubsonic {
+    pub url: String,
+    pub username: String,
+    pub password: String,
+    server: MockServer,
+    library: Arc<Mutex<Library>>,
+}
+
+impl MockSubsonic {
+    /// 使用默认音乐库启动, 用户名 "admin", 密码 "sesame"
+    pub fn start() -> Self {
+        Self::with_library(fixtures::library())
+    }
+
+    pub fn with_library(library: Library) -> Self {
+        let (username, password) = ("admin".to_string(), "sesame".to_string());
+        let library = Arc::new(Mutex::new(library));
+        let state = library.clone();
+        let (user, pass) = (username.clone(), password.clone());
+        let server = MockServer::start(move |req| {
+            let params = req.params();
+            if let Err(response) = check_auth(&params, &user, &pass) {
+                return response;
+            }
+            handle(req, &params, &mut state.lock().unwrap())
+        });
+        Self {
+            url: server.url.clone(),
+            username,
+            password,
+            server,
+            library,
+        }
+    }
+
+    /// 当前的音乐库状态 (收藏、歌单、播放队列、scrobble 等)
+    pub fn library(&self) -> Library {
+        self.library.lock().unwrap().clone()
+    }
+
+    /// 修改音乐库, 并更新 `getIndexes` 返回的修改时间, 模拟服务器重新扫描
+    pub fn update(&self, f: impl FnOnce(&mut Library)) {
+        let mut library = self.library.lock().unwrap();
+        f(&mut library);
+        library.last_modified += 1000;
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.server.requests()
+    }
+
+    /// 某个接口收到的请求数量, 如 `count("getSong")`
+    pub fn count(&self, endpoint: &str) -> usize {
+        self.requests()
+            .iter()
+            .filter(|r| endpoint_name(r) == endpoint)
+            .count()
+    }
+}
+
+fn endpoint_name(req: &MockRequest) -> &str {
+    req.route()
+        .trim_start_matches("/rest/")
+        .trim_end_matches(".view")
+}
+
+/// 校验 u + t + s (token) 或 u + p (明文 / enc: 十六进制) 认证参数
+fn check_auth(params: &[(String, String)], username: &str, password: &str) -> Result<(), MockResponse> {
+    let get = |name: &str| params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+    let valid = match (get("u"), get("t"), get("s"), get("p")) {
+        (Some(u), Some(t), Some(s), _) => {
+            u == username && hex::encode(Md5::digest(format!("{}{}", password, s))) == t
+        }
+        (Some(u), _, _, Some(p)) => {
+            let p = match p.strip_prefix("enc:") {
+                Some(encoded) => hex::decode(encoded)
+                    .ok()
+                    .and_then(|b| String::from_utf8(b).ok())
+                    .unwrap_or_default(),
+                None => p.to_string(),
+            };
+            u == username && p == password
+        }
+        _ => return Err(error(10, "Required parameter is missing.")),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(error(40, "Wrong username or password."))
+    }
+}
+
+fn ok(body: Value) -> MockResponse {
+    let mut response = json!({ "status": "ok", "version": API_VERSION });
+    if let (Some(response), Value::Object(body)) = (response.as_object_mut(), body) {
+        response.extend(body);
+    }
+    MockResponse::json(200, json!({ "subsonic-response": response }).to_string())
+}
+
+/// Subsonic 的错误也返回 HTTP 200
+fn error(code: i32, message: &str) -> MockResponse {
+    MockResponse::json(
+        200,
+        json!({
+            "subsonic-response": {
+                "status": "failed",
+                "version": API_VERSION,
+                "error": { "code": code, "message": message }
+            }
+        })
+        .to_string(),
+    )
+}
+
+fn not_found(what: &str) -> MockResponse {
+    error(70, &format!("{} not found", what))
+}
+
+fn handle(req: &MockRequest, params: &[(String, String)], lib: &mut Library) -> MockResponse {
+    let get = |name: &str| params.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone());
+    let all = |name: &str| -> Vec<String> {
+        params.iter().filter(|(k, _)| k == name).map(|(_, v)| v.clone()).collect()
+    };
+    let number = |name: &str, default: usize| get(name).and_then(|v| v.parse().ok()).unwrap_or(default);
+    let id = get("id").unwrap_or_default();
+
+    match endpoint_name(req) {
+        "ping" | "startScan" => ok(json!({})),
+        "getScanStatus" => ok(json!({ "scanStatus": { "scanning": false, "count": lib.songs.len() } })),
+        "getSong" => match lib.song(&id) {
+            Some(song) => ok(json!({ "song": song_json(lib, song) })),
+            None => not_found("Song"),
+        },
+        "getAlbum" => match lib.album(&id) {
+            Some(album) => ok(json!({ "album": album_json(lib, album, true) })),
+            None => not_found("Album"),
+        },
+        "getAlbumList2" => {
+            let mut albums: Vec<_> = lib.albums.iter().collect();
+            match get("type").as_deref() {
+                Some("alphabeticalByName") => albums.sort_by(|a, b| a.name.cmp(&b.name)),
+                Some("newest") => albums.sort_by_key(|a| std::cmp::Reverse(a.year)),
+                Some("starred") => albums.retain(|a| lib.starred.contains(&a.id)),
+                Some("highest") => albums.sort_by_key(|a| std::cmp::Reverse(lib.ratings.get(&a.id).copied())),
+                Some("byGenre") => albums.retain(|a| Some(&a.genre) == get("genre").as_ref()),
+                Some(_) => {}
+                None => return error(10, "Required parameter is missing: type"),
+            }
+            let albums: Vec<_> = page(albums, number("offset", 0), number("size", 10))
+                .into_iter()
+                .map(|a| album_json(lib, a, false))
+                .collect();
+            ok(json!({ "albumList2": { "album": albums } }))
+        }
+        "getArtists" => {
+            let artists: Vec<_> = lib.artists.iter().map(|a| artist_json(lib, a)).collect();
+            ok(json!({ "artists": { "index": [{ "name": "#", "artist": artists }] } }))
+        }
+        "getArtist" => match lib.artist(&id) {
+            Some(artist) => {
+                let mut value = artist_json(lib, artist);
+                value["album"] = lib
+                    .albums
+                    .iter()
+                    .filter(|a| a.artist_id == artist.id)
+                    .map(|a| album_json(lib, a, false))
+                    .collect();
+                ok(json!({ "artist": value }))
+            }
+            None => not_found("Artist"),
+        },
+        "getIndexes" => ok(json!({ "indexes": { "lastModified": lib.last_modified } })),
+        "getGenres" => {
+            let mut genres: Vec<&str> = lib.albums.iter().map(|a| a.genre.as_str()).collect();
+            genres.dedup();
+            let genres: Vec<_> = genres
+                .into_iter()
+                .map(|genre| {
+                    let albums: Vec<_> = lib.albums.iter().filter(|a| a.genre == genre).collect();
+                    let songs = lib
+                        .songs
+                        .iter()
+                        .filter(|s| albums.iter().any(|a| a.id == s.album_id))
+                        .count();
+                    json!({ "value": genre, "albumCount": albums.len(), "songCount": songs })
+                })
+                .collect();
+            ok(json!({ "genres": { "genre": genres } }))
+        }
+        "getRandomSongs" | "getSongsByGenre" | "getTopSongs" => {
+            let genre = get("genre");
+            let artist = get("artist");
+            let songs: Vec<_> = lib
+                .songs
+                .iter()
+                .filter(|s| {
+                    let album = lib.album(&s.album_id);
+                    genre.as_ref().is_none_or(|g| album.is_some_and(|a| &a.genre == g))
+                        && artist
+                            .as_ref()
+                            .is_none_or(|n| lib.artist(&s.artist_id).is_some_and(|a| &a.name == n))
+                })
+                .take(number("size", number("count", 50)))
+                .map(|s| song_json(lib, s))
+                .collect();
+            let key = match endpoint_name(req) {
+                "getRandomSongs" => "randomSongs",
+                "getSongsByGenre" => "songsByGenre",
+                _ => "topSongs",
+            };
+            ok(json!({ key: { "song": songs } }))
+        }
+        // 同一风格的艺术家视为相似, 没有相似歌曲时返回空列表
+        "getSimilarSongs2" => {
+            let genres: Vec<&str> = lib
+                .albums
+                .iter()
+                .filter(|a| a.artist_id == id)
+                .map(|a| a.genre.as_str())
+                .collect();
+            let songs: Vec<_> = lib
+                .songs
+                .iter()
+                .filter(|s| lib.album(&s.album_id).is_some_and(|a| genres.contains(&a.genre.as_str())))
+                .take(number("count", 50))
+                .map(|s| song_json(lib, s))
+                .collect();
+            ok(json!({ "similarSongs2": { "song": songs } }))
+        }
+        "search3" => {
+            let query = get("query").unwrap_or_default().trim_matches('"').to_lowercase();
+            let matches = |name: &str| name.to_lowercase().contains(&query);
+            let songs = page(
+                lib.songs.iter().filter(|s| matches(&s.title)).collect(),
+                number("songOffset", 0),
+                number("songCount", 20),
+            );
+            let albums = page(
+                lib.albums.iter().filter(|a| matches(&a.name)).collect(),
+                number("albumOffset", 0),
+                number("albumCount", 20),
+            );
+            let artists = page(
+                lib.artists.iter().filter(|a| matches(&a.name)).collect(),
+                number("artistOffset", 0),
+                number("artistCount", 20),
+            );
+            ok(json!({
+                "searchResult3": {
+                    "song": songs.into_iter().map(|s| song_json(lib, s)).collect::<Vec<_>>(),
+                    "album": albums.into_iter().map(|a| album_json(lib, a, false)).collect::<Vec<_>>(),
+                    "artist": artists.into_iter().map(|a| artist_json(lib, a)).collect::<Vec<_>>(),
+                }
+            }))
+        }
+        "getLyrics" => ok(json!({ "lyrics": {} })),
+        "stream" | "download" => match lib.song(&id) {
+            Some(song) => audio_response(song.audio_data(), req.header("range")),
+            None => not_found("Song"),
+        },
+        "getCoverArt" => {
+            if lib.song(&id).is_some() || lib.album(&id).is_some() || lib.artist(&id).is_some() {
+                MockResponse::bytes(200, COVER_PNG.to_vec()).with_header("Content-Type", "image/png")
+            } else {
+                not_found("Cover art")
+            }
+        }
+        "getPlaylists" => {
+            let playlists: Vec<_> = lib.playlists.iter().map(|p| playlist_json(lib, p, false)).collect();
+            ok(json!({ "playlists": { "playlist": playlists } }))
+        }
+        "getPlaylist" => match lib.playlist(&id) {
+            Some(playlist) => ok(json!({ "playlist": playlist_json(lib, playlist, true) })),
+            None => not_found("Playlist"),
+        },
+        "createPlaylist" => {
+            let song_ids = all("songId");
+            let playlist_id = match get("playlistId") {
+                // 传入 playlistId 时替换歌单中的歌曲
+                Some(playlist_id) => match lib.playlists.iter_mut().find(|p| p.id == playlist_id) {
+                    Some(playlist) => {
+                        playlist.song_ids = song_ids;
+                        playlist_id
+                    }
+                    None => return not_found("Playlist"),
+                },
+                None => {
+                    let Some(name) = get("name") else {
+                        return error(10, "Required parameter is missing: name");
+                    };
+                    let playlist_id = format!("pl-{}", lib.next_playlist_id);
+                    lib.next_playlist_id += 1;
+                    lib.playlists.push(Playlist {
+                        id: playlist_id.clone(),
+                        name,
+                        comment: String::new(),
+                        public: false,
+                        song_ids,
+                    });
+                    playlist_id
+                }
+            };
+            let playlist = lib.playlist(&playlist_id).unwrap();
+            ok(json!({ "playlist": playlist_json(lib, playlist, true) }))
+        }
+        "updatePlaylist" => {
+            let playlist_id = get("playlistId").unwrap_or_default();
+            let Some(playlist) = lib.playlists.iter_mut().find(|p| p.id == playlist_id) else {
+                return not_found("Playlist");
+            };
+            if let Some(name) = get("name") {
+                playlist.name = name;
+            }
+            if let Some(comment) = get("comment") {
+                playlist.comment = comment;
+            }
+            if let Some(public) = get("public") {
+                playlist.public = public == "true";
+            }
+            // 先按原来的位置删除, 再追加
+            let mut remove: Vec<usize> = all("songIndexToRemove")
+                .iter()
+                .filter_map(|i| i.parse().ok())
+                .collect();
+            remove.sort_unstable_by_key(|i| std::cmp::Reverse(*i));
+            for index in remove {
+                if index < playlist.song_ids.len() {
+                    playlist.song_ids.remove(index);
+                }
+            }
+            playlist.song_ids.extend(all("songIdToAdd"));
+            ok(json!({}))
+        }
+        "deletePlaylist" => {
+            let before = lib.playlists.len();
+            lib.playlists.retain(|p| p.id != id);
+            if lib.playlists.len() == before {
+                return not_found("Playlist");
+            }
+            ok(json!({}))
+        }
+        "getPlayQueue" => match &lib.play_queue {
+            Some(queue) => {
+                let entry: Vec<_> = queue
+                    .song_ids
+                    .iter()
+                    .filter_map(|id| lib.song(id))
+                    .map(|s| song_json(lib, s))
+                    .collect();
+                ok(json!({
+                    "playQueue": {
+                        "current": queue.current,
+                        "position": queue.position,
+                        "username": "admin",
+                        "changed": STARRED_AT,
+                        "changedBy": "MusicFlow",
+                        "entry": entry,
+                    }
+                }))
+            }
+            None => ok(json!({})),
+        },
+        "savePlayQueue" => {
+            let song_ids = all("id");
+            lib.play_queue = match song_ids.is_empty() {
+                true => None,
+                false => Some(PlayQueue {
+                    song_ids,
+                    current: get("current"),
+                    position: number("position", 0) as u64,
+                }),
+            };
+            ok(json!({}))
+        }
+        "star" | "unstar" => {
+            let ids: Vec<_> = ["id", "albumId", "artistId"].iter().flat_map(|name| all(name)).collect();
+            if ids.is_empty() {
+                return error(10, "Required parameter is missing: id");
+            }
+            for id in ids {
+                if endpoint_name(req) == "star" {
+                    lib.starred.insert(id);
+                } else {
+                    lib.starred.remove(&id);
+                }
+            }
+            ok(json!({}))
+        }
+        "setRating" => {
+            let rating = match get("rating").and_then(|r| r.parse::<u32>().ok()) {
+                Some(rating) if rating <= 5 => rating,
+                _ => return error(0, "Invalid rating"),
+            };
+            if rating == 0 {
+                lib.ratings.remove(&id);
+            } else {
+                lib.ratings.insert(id, rating);
+            }
+            ok(json!({}))
+        }
+        "getStarred2" => ok(json!({
+            "starred2": {
+                "song": lib.songs.iter().filter(|s| lib.starred.contains(&s.id)).map(|s| song_json(lib, s)).collect::<Vec<_>>(),
+                "album": lib.albums.iter().filter(|a| lib.starred.contains(&a.id)).map(|a| album_json(lib, a, false)).collect::<Vec<_>>(),
+                "artist": lib.artists.iter().filter(|a| lib.starred.contains(&a.id)).map(|a| artist_json(lib, a)).collect::<Vec<_>>(),
+            }
+        })),
+        "scrobble" => {
+            let submission = get("submission").is_none_or(|s| s == "true");
+            let times = all("time");
+            for (i, id) in all("id").into_iter().enumerate() {
+                lib.scrobbles.push(Scrobble {
+                    id,
+                    submission,
+                    time: times.get(i).and_then(|t| t.parse().ok()),
+                });
+            }
+            ok(json!({}))
+        }
+        _ => MockResponse::status(404),
+    }
+}
+
+fn page<T>(items: Vec<T>, offset: usize, size: usize) -> Vec<T> {
+    items.into_iter().skip(offset).take(size).collect()
+}
+
+/// 支持单个 Range (bytes=start-end / bytes=start-) 的音频响应
+fn audio_response(data: Vec<u8>, range: Option<&str>) -> MockResponse {
+    let total = data.len();
+    let range = range
+        .and_then(|r| r.strip_prefix("bytes="))
+        .and_then(|r| r.split_once('-'))
+        .and_then(|(start, end)| {
+            let start: usize = start.parse().ok()?;
+            let end = match end {
+                "" => total - 1,
+                end => end.parse::<usize>().ok()?.min(total - 1),
+            };
+            (start <= end).then_some((start, end))
+        });
+    let response = match range {
+        Some((start, end)) => MockResponse::bytes(206, data[start..=end].to_vec())
+            .with_header("Content-Range", &format!("bytes {}-{}/{}", start, end, total)),
+        None => MockResponse::bytes(200, data),
+    };
+    response
+        .with_header("Content-Type", "audio/mpeg")
+        .with_header("Accept-Ranges", "bytes")
+}
+
+/// 收藏时间和评分
+fn user_data(lib: &Library, id: &str, value: &mut Value) {
+    if lib.starred.contains(id) {
+        value["starred"] = json!(STARRED_AT);
+    }
+    if let Some(rating) = lib.ratings.get(id) {
+        value["userRating"] = json!(rating);
+    }
+}
+
+fn song_json(lib: &Library, song: &fixtures::Song) -> Value {
+    let album = lib.album(&song.album_id);
+    let artist = lib.artist(&song.artist_id);
+    let mut value = json!({
+        "id": song.id,
+        "parent": song.album_id,
+        "isDir": false,
+        "title": song.title,
+        "album": album.map(|a| a.name.as_str()),
+        "albumId": song.album_id,
+        "artist": artist.map(|a| a.name.as_str()),
+        "artistId": song.artist_id,
+        "track": song.track,
+        "year": album.map(|a| a.year),
+        "genre": album.map(|a| a.genre.as_str()),
+        "coverArt": song.album_id,
+        "size": song.size,
+        "contentType": "audio/mpeg",
+        "suffix": "mp3",
+        "duration": song.duration,
+        "bitRate": 320,
+        "path": format!(
+            "{}/{}/{:02} - {}.mp3",
+            artist.map(|a| a.name.as_str()).unwrap_or_default(),
+            album.map(|a| a.name.as_str()).unwrap_or_default(),
+            song.track,
+            song.title
+        ),
+        "type": "music",
+    });
+    if let Some(bpm) = lib.bpms.get(&song.id) {
+        value["bpm"] = json!(bpm);
+    }
+    user_data(lib, &song.id, &mut value);
+    value
+}
+
+fn album_json(lib: &Library, album: &fixtures::Album, with_songs: bool) -> Value {
+    let songs: Vec<_> = lib.songs.iter().filter(|s| s.album_id == album.id).collect();
+    let mut value = json!({
+        "id": album.id,
+        "name": album.name,
+        "artist": lib.artist(&album.artist_id).map(|a| a.name.as_str()),
+        "artistId": album.artist_id,
+        "coverArt": album.id,
+        "songCount": songs.len(),
+        "duration": songs.iter().map(|s| s.duration).sum::<u32>(),
+        "created": "2024-01-01T00:00:00.000Z",
+        "year": album.year,
+        "genre": album.genre,
+    });
+    if with_songs {
+        value["song"] = songs.into_iter().map(|s| song_json(lib, s)).collect();
+    }
+    user_data(lib, &album.id, &mut value);
+    value
+}
+
+fn artist_json(lib: &Library, artist: &fixtures::Artist) -> Value {
+    let mut value = json!({
+        "id": artist.id,
+        "name": artist.name,
+        "coverArt": artist.id,
+        "albumCount": lib.albums.iter().filter(|a| a.artist_id == artist.id).count(),
+    });
+    user_data(lib, &artist.id, &mut value);
+    value
+}
+
+fn playlist_json(lib: &Library, playlist: &Playlist, with_entries: bool) -> Value {
+    let songs: Vec<_> = playlist.song_ids.iter().filter_map(|id| lib.song(id)).collect();
+    let mut value = json!({
+        "id": playlist.id,
+        "name": playlist.name,
+        "comment": playlist.comment,
+        "owner": "admin",
+        "public": playlist.public,
+        "songCount": songs.len(),
+        "duration": songs.iter().map(|s| s.duration).sum::<u32>(),
+        "created": "2024-01-01T00:00:00.000Z",
+        "changed": "2024-01-01T00:00:00.000Z",
+    });
+    if with_entries {
+        value["entry"] = songs.into_iter().map(|s| song_json(lib, s)).collect();
+    }
+    value
+}
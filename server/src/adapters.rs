@@ -8,7 +8,16 @@ use std::collections::HashMap;
 /// 将 UnifiedMetadata 转换为 MetadataVo
 pub fn unified_to_vo(metadata: UnifiedMetadata) -> MetadataVo {
     // 获取 album_id 和 artist_id
-    let (album_id, artist_id, cover_art) = if metadata.source == DataSourceType::Local {
+    let (album_id, artist_id, cover_art) = if let (DataSourceType::Local, Some(album_id)) =
+        (&metadata.source, &metadata.album_id)
+    {
+        // 聚合模式下已补全带命名空间的 ID
+        (
+            album_id.clone(),
+            metadata.artist_id.clone().unwrap_or_default(),
+            metadata.cover_art.clone().unwrap_or_default(),
+        )
+    } else if metadata.source == DataSourceType::Local {
         let album_id = service::album_song_by_song_ids(&vec![metadata.id.clone()])
             .ok()
             .and_then(|albums| albums.first().map(|a| a.album_id))
@@ -36,7 +45,7 @@ pub fn unified_to_vo(metadata: UnifiedMetadata) -> MetadataVo {
         DataSourceType::Local => {
             metadata.file_url.unwrap_or_default()
         },
-        DataSourceType::Subsonic => {
+        DataSourceType::Subsonic | DataSourceType::Jellyfin | DataSourceType::Aggregate => {
             // Subsonic 使用 stream_url
             metadata.stream_url.unwrap_or_default()
         },
@@ -72,6 +81,13 @@ pub fn unified_to_vo(metadata: UnifiedMetadata) -> MetadataVo {
         artist_id,
         cover_art,
         starred: metadata.starred.unwrap_or(false),
+        play_count: metadata.play_count,
+        last_played: metadata.last_played,
+        skip_count: metadata.skip_count,
+        rating: metadata.rating,
+        replay_gain: metadata.replay_gain,
+        bpm: metadata.bpm,
+        musical_key: metadata.musical_key,
     }
 }
 
@@ -84,7 +100,10 @@ pub fn unified_list_to_vo(metadata_list: Vec<UnifiedMetadata>) -> Vec<MetadataVo
     // 检查数据源类型
     let source_type = metadata_list.first().map(|m| m.source.clone()).unwrap_or(DataSourceType::Local);
 
-    if source_type == DataSourceType::Local {
+    // 聚合模式下各歌曲已带 album_id, 逐条转换
+    let has_ids = metadata_list.iter().any(|m| m.album_id.is_some());
+
+    if source_type == DataSourceType::Local && !has_ids {
         // 本地模式: 批量查询 album_id 和 artist_id
         let ids: Vec<String> = metadata_list.iter().map(|m| m.id.clone()).collect();
 
@@ -142,6 +161,13 @@ pub fn unified_list_to_vo(metadata_list: Vec<UnifiedMetadata>) -> Vec<MetadataVo
                     artist_id: artist_id.to_string(),
                     cover_art: album_id.to_string(),
                     starred: m.starred.unwrap_or(false),
+                    play_count: m.play_count,
+                    last_played: m.last_played,
+                    skip_count: m.skip_count,
+                    rating: m.rating,
+                    replay_gain: m.replay_gain,
+                    bpm: m.bpm,
+                    musical_key: m.musical_key,
                 }
             })
             .collect()
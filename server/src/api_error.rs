@@ -0,0 +1,61 @@
+// 接口错误
+// 将数据源错误转换为统一的 HTTP 状态码和 JsonResult
+
+use std::fmt;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use lib_utils::datasource::DataSourceError;
+
+use crate::JsonResult;
+
+/// 数据源错误的接口响应
+///
+/// 响应体为 `JsonResult`, `code` 与 HTTP 状态码一致
+#[derive(Debug)]
+pub struct ApiError(pub DataSourceError);
+
+impl From<DataSourceError> for ApiError {
+    fn from(e: DataSourceError) -> Self {
+        ApiError(e)
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        ApiError(e.into())
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match &self.0 {
+            DataSourceError::NotFound(_) => StatusCode::NOT_FOUND,
+            DataSourceError::InvalidId(_) => StatusCode::BAD_REQUEST,
+            DataSourceError::Unsupported(_) => StatusCode::NOT_IMPLEMENTED,
+            DataSourceError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            DataSourceError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            DataSourceError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            DataSourceError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            DataSourceError::Database(_) | DataSourceError::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        HttpResponse::build(status).json(JsonResult::<()>::new(
+            status.as_u16() as i32,
+            false,
+            &self.0.to_string(),
+            None,
+        ))
+    }
+}
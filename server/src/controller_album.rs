@@ -1,6 +1,7 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpResponse, Responder, ResponseError};
 use serde::{Deserialize, Serialize};
 
+use crate::api_error::ApiError;
 use crate::{AppState, JsonResult};
 use lib_utils::datasource::types::{AlbumInfo, AlbumListType, Pagination};
 use crate::adapters::unified_list_to_vo;
@@ -11,6 +12,8 @@ pub struct AlbumsBody {
     page_size: Option<usize>,
     filter_text: Option<String>,
     list_type: Option<String>,
+    /// list_type 为 rated 时的最低评分 (默认 1)
+    min_rating: Option<u8>,
 }
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct ListAlbumResponse {
@@ -32,6 +35,9 @@ pub async fn handle_get_album(app_data: web::Data<AppState>, albums_body: web::J
             "frequent" => Some(AlbumListType::Frequent),
             "recent" => Some(AlbumListType::Recent),
             "starred" => Some(AlbumListType::Starred),
+            "rated" => Some(AlbumListType::ByRating {
+                min_rating: albums_body.min_rating.unwrap_or(1),
+            }),
             _ => None,
         }
     });
@@ -42,8 +48,7 @@ pub async fn handle_get_album(app_data: web::Data<AppState>, albums_body: web::J
             let total = list.len();
             HttpResponse::Ok().json(JsonResult::success(ListAlbumResponse { list, total }))
         }
-        Err(e) => HttpResponse::InternalServerError()
-            .json(JsonResult::<()>::error(&format!("Error: {}", e))),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
@@ -61,8 +66,7 @@ pub async fn handle_get_album_songs(
             let vo_list = unified_list_to_vo(metadata_list);
             HttpResponse::Ok().json(JsonResult::success(vo_list))
         }
-        Err(e) => HttpResponse::InternalServerError()
-            .json(JsonResult::<()>::error(&format!("Error: {}", e))),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
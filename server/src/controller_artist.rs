@@ -1,6 +1,7 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpResponse, Responder, ResponseError};
 use serde::{Deserialize, Serialize};
 
+use crate::api_error::ApiError;
 use crate::{adapters::unified_list_to_vo, AppState, JsonResult};
 use lib_utils::datasource::types::ArtistInfo;
 
@@ -40,8 +41,7 @@ pub async fn handle_get_artist(
 
             HttpResponse::Ok().json(JsonResult::success(ListArtistResponse { list, total }))
         }
-        Err(e) => HttpResponse::InternalServerError()
-            .json(JsonResult::<()>::error(&format!("Error: {}", e))),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
@@ -59,8 +59,7 @@ pub async fn handle_get_artist_songs(
             let vo_list = unified_list_to_vo(metadata_list);
             HttpResponse::Ok().json(JsonResult::success(vo_list))
         }
-        Err(e) => HttpResponse::InternalServerError()
-            .json(JsonResult::<()>::error(&format!("Error: {}", e))),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
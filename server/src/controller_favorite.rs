@@ -2,6 +2,7 @@ use actix_web::{web, Responder};
 use lib_utils::datasource::types::StarItemType;
 use serde::{Deserialize, Serialize};
 
+use crate::api_error::ApiError;
 use crate::{AppState, JsonResult};
 
 /// 收藏请求参数
@@ -22,7 +23,7 @@ pub async fn handle_star(
     data.data_source
         .star(&req.id, item_type)
         .await
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+        .map_err(ApiError::from)?;
 
     Ok(web::Json(JsonResult::success(())))
 }
@@ -38,7 +39,7 @@ pub async fn handle_unstar(
     data.data_source
         .unstar(&req.id, item_type)
         .await
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+        .map_err(ApiError::from)?;
 
     Ok(web::Json(JsonResult::success(())))
 }
@@ -51,7 +52,7 @@ pub async fn handle_get_starred(
         .data_source
         .get_starred()
         .await
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+        .map_err(ApiError::from)?;
 
     Ok(web::Json(JsonResult::success(starred)))
 }
@@ -74,7 +75,7 @@ pub async fn handle_is_starred(
         .data_source
         .is_starred(&query.id, item_type)
         .await
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+        .map_err(ApiError::from)?;
 
     #[derive(Serialize)]
     struct IsStarredResponse {
@@ -85,3 +86,59 @@ pub async fn handle_is_starred(
         is_starred,
     })))
 }
+
+/// 评分请求参数
+#[derive(Debug, Deserialize)]
+pub struct RatingRequest {
+    pub id: String,
+    pub item_type: String, // "song", "album"
+    /// 1-5, 0 表示取消评分
+    pub rating: u8,
+}
+
+/// 解析评分的项目类型, 只支持歌曲和专辑
+fn rating_item_type(item_type: &str) -> Result<StarItemType, actix_web::Error> {
+    match StarItemType::from_str(item_type) {
+        Some(t @ (StarItemType::Song | StarItemType::Album)) => Ok(t),
+        _ => Err(actix_web::error::ErrorBadRequest("Invalid item_type")),
+    }
+}
+
+/// 设置评分
+pub async fn handle_set_rating(
+    data: web::Data<AppState>,
+    req: web::Json<RatingRequest>,
+) -> Result<impl Responder, actix_web::Error> {
+    let item_type = rating_item_type(&req.item_type)?;
+    if req.rating > 5 {
+        return Err(actix_web::error::ErrorBadRequest("rating must be between 0 and 5"));
+    }
+
+    data.data_source
+        .set_rating(&req.id, item_type, req.rating)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(web::Json(JsonResult::success(())))
+}
+
+/// 获取评分
+pub async fn handle_get_rating(
+    data: web::Data<AppState>,
+    query: web::Query<IsStarredQuery>,
+) -> Result<impl Responder, actix_web::Error> {
+    let item_type = rating_item_type(&query.item_type)?;
+
+    let rating = data
+        .data_source
+        .get_rating(&query.id, item_type)
+        .await
+        .map_err(ApiError::from)?;
+
+    #[derive(Serialize)]
+    struct RatingResponse {
+        rating: Option<u8>,
+    }
+
+    Ok(web::Json(JsonResult::success(RatingResponse { rating })))
+}
@@ -0,0 +1,58 @@
+use actix_web::{web, Responder};
+use lib_utils::datasource::types::PinItemType;
+use serde::Deserialize;
+
+use crate::api_error::ApiError;
+use crate::{AppState, JsonResult};
+
+/// 离线下载请求参数
+#[derive(Debug, Deserialize)]
+pub struct PinRequest {
+    pub id: String,
+    pub item_type: String, // "song", "album", "playlist"
+}
+
+async fn set_pinned(
+    data: web::Data<AppState>,
+    req: web::Json<PinRequest>,
+    pin: bool,
+) -> Result<impl Responder, actix_web::Error> {
+    let item_type = PinItemType::parse(&req.item_type)
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("Invalid item_type"))?;
+
+    data.data_source
+        .pin(item_type, &req.id, pin)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(web::Json(JsonResult::success(())))
+}
+
+/// 固定离线下载, 文件在后台下载
+pub async fn handle_pin(
+    data: web::Data<AppState>,
+    req: web::Json<PinRequest>,
+) -> Result<impl Responder, actix_web::Error> {
+    set_pinned(data, req, true).await
+}
+
+/// 取消固定并删除不再需要的文件
+pub async fn handle_unpin(
+    data: web::Data<AppState>,
+    req: web::Json<PinRequest>,
+) -> Result<impl Responder, actix_web::Error> {
+    set_pinned(data, req, false).await
+}
+
+/// 获取离线下载状态和已使用空间
+pub async fn handle_pin_status(
+    data: web::Data<AppState>,
+) -> Result<impl Responder, actix_web::Error> {
+    let status = data
+        .data_source
+        .pin_status()
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(web::Json(JsonResult::success(status)))
+}
@@ -1,7 +1,8 @@
 /// 和播放列表相关的接口，注意与歌单的接口区别，歌单接口在 controller_songlist.rs 中
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpResponse, Responder, ResponseError};
 use serde::{Deserialize, Serialize};
 
+use crate::api_error::ApiError;
 use crate::{adapters, controller_song::MetadataVo, AppState, JsonResult};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -28,10 +29,7 @@ pub async fn handle_get_play_queue(app_state: web::Data<AppState>) -> impl Respo
         Ok(None) => {
             HttpResponse::InternalServerError().json(JsonResult::<()>::error("PlayList not found"))
         }
-        Err(e) => HttpResponse::InternalServerError().json(JsonResult::<()>::error(&format!(
-            "get play queue failed, error: {:?}",
-            e
-        ))),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
@@ -57,9 +55,6 @@ pub async fn handle_save_play_queue(
         .await
     {
         Ok(()) => HttpResponse::Ok().json(JsonResult::success("ok")),
-        Err(e) => HttpResponse::InternalServerError().json(JsonResult::<()>::error(&format!(
-            "save play queue failed, error: {:?}",
-            e
-        ))),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
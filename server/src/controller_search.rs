@@ -1,6 +1,7 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpResponse, Responder, ResponseError};
 use serde::{Deserialize, Serialize};
 
+use crate::api_error::ApiError;
 use crate::{AppState, JsonResult, adapters, controller_song::MetadataVo};
 use lib_utils::datasource::types::Pagination;
 
@@ -56,7 +57,6 @@ pub async fn handle_search(
 
             HttpResponse::Ok().json(JsonResult::success(response))
         }
-        Err(e) => HttpResponse::InternalServerError()
-            .json(JsonResult::<()>::error(&e.to_string())),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
@@ -1,14 +1,15 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpResponse, Responder, ResponseError};
 use base64::Engine;
 use lib_utils::{
     config::get_config,
     datasource::local::service,
-    datasource::{types::MetadataFilter, CoverSize},
+    datasource::{types::{BpmRange, MetadataFilter, MetadataSort, RadioSeed, ReplayGain}, CoverSize},
     log::log_err,
     readmeta,
 };
 use serde::{Deserialize, Serialize};
 
+use crate::api_error::ApiError;
 use crate::{adapters, AppState, JsonResult};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -39,6 +40,20 @@ pub struct MetadataVo {
     pub artist_id: String,
     pub cover_art: String,
     pub starred: bool,
+    /// 播放次数, 数据源不支持时为 null
+    pub play_count: Option<u32>,
+    /// 最后播放时间 (毫秒时间戳)
+    pub last_played: Option<i64>,
+    /// 跳过次数 (仅本地模式)
+    pub skip_count: Option<u32>,
+    /// 用户评分 (1-5), 未评分时为 null
+    pub rating: Option<u8>,
+    /// 响度归一化信息 (ReplayGain), 没有标签且尚未分析时为 null
+    pub replay_gain: Option<ReplayGain>,
+    /// 每分钟节拍数, 没有标签且尚未分析时为 null
+    pub bpm: Option<f64>,
+    /// 调性, 如 "Am"、"F#"
+    pub musical_key: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -49,6 +64,15 @@ pub struct MusicListQuery {
     artist: Option<Vec<i64>>,
     album: Option<Vec<i64>>,
     any: Option<String>,
+    /// 排序字段: title / artist / album / year / duration / play_count / last_played / skip_count / rating
+    sort: Option<MetadataSort>,
+    /// 是否倒序 (默认 false)
+    descending: Option<bool>,
+    /// BPM 范围(含), 仅本地模式
+    bpm_min: Option<f64>,
+    bpm_max: Option<f64>,
+    /// 调性, 音名或 Camelot 记号 (如 "Am"、"8A"), 仅本地模式
+    key: Option<String>,
 }
 
 /// 获取服务器上所有音乐文件 (使用 DataSource)
@@ -62,13 +86,19 @@ pub async fn handle_get_metadatas(
         genres: query.genres.clone(),
         page: query.page,
         page_size: query.page_size,
+        sort: query.sort,
+        descending: query.descending.unwrap_or(false),
+        bpm: BpmRange {
+            min: query.bpm_min,
+            max: query.bpm_max,
+        },
+        musical_key: query.key.clone(),
         ..Default::default()
     };
 
     let result = app_state.data_source.list_metadata(filter.clone()).await;
     if let Err(e) = result {
-        return HttpResponse::InternalServerError()
-            .json(JsonResult::<ListMusic>::error(&e.to_string()));
+        return ApiError::from(e).error_response();
     }
 
     let mut metadata_list = result.unwrap();
@@ -110,7 +140,7 @@ pub async fn handle_get_metadata(
             let vo = adapters::unified_to_vo(metadata);
             HttpResponse::Ok().json(JsonResult::success(vo))
         }
-        Err(e) => HttpResponse::InternalServerError().json(JsonResult::<()>::error(&e.to_string())),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
@@ -255,6 +285,12 @@ pub struct RandomSongsQuery {
     /// 只返回此年份之前(含)发布的歌曲
     #[serde(rename = "toYear")]
     pub to_year: Option<String>,
+    /// 只返回 BPM 不低于此值的歌曲
+    #[serde(rename = "bpmMin")]
+    pub bpm_min: Option<f64>,
+    /// 只返回 BPM 不高于此值的歌曲
+    #[serde(rename = "bpmMax")]
+    pub bpm_max: Option<f64>,
     /// 按音乐文件夹筛选(暂不支持)
     #[serde(rename = "musicFolderId")]
     pub music_folder_id: Option<String>,
@@ -269,39 +305,31 @@ pub struct RandomSongsQuery {
 /// - genre: 按流派筛选
 /// - fromYear: 只返回此年份之后(含)发布的歌曲
 /// - toYear: 只返回此年份之前(含)发布的歌曲
+/// - bpmMin / bpmMax: BPM 范围(含), 有限制时只返回已知 BPM 的歌曲
 ///
 /// 返回: 随机歌曲列表
 pub async fn handle_get_random_songs(
     query: web::Query<RandomSongsQuery>,
     app_state: web::Data<AppState>,
 ) -> impl Responder {
-    // 从每日缓存获取数据
+    // 由数据源按条件随机选取, 每日推荐见歌单列表中的每日推荐歌单
+    let size = query.size.unwrap_or(150).min(500);
     let result = app_state
-        .daily_random_songs
-        .get_or_refresh(&app_state.data_source)
+        .data_source
+        .get_random_songs(
+            Some(size),
+            query.genre.as_deref(),
+            query.from_year.as_deref(),
+            query.to_year.as_deref(),
+            BpmRange {
+                min: query.bpm_min,
+                max: query.bpm_max,
+            },
+        )
         .await;
 
     match result {
-        Ok(mut metadata_list) => {
-            // 应用查询参数过滤
-            if let Some(genre) = &query.genre {
-                metadata_list.retain(|m| {
-                    !m.genre.is_empty() && m.genre.to_lowercase().contains(&genre.to_lowercase())
-                });
-            }
-
-            if let Some(from_year) = &query.from_year {
-                metadata_list.retain(|m| !m.year.is_empty() && &m.year >= from_year);
-            }
-
-            if let Some(to_year) = &query.to_year {
-                metadata_list.retain(|m| !m.year.is_empty() && &m.year <= to_year);
-            }
-
-            // 限制返回数量
-            let size = query.size.unwrap_or(150).min(500);
-            metadata_list.truncate(size);
-
+        Ok(metadata_list) => {
             let total = metadata_list.len() as u32;
             // 转换为 VO
             let list = adapters::unified_list_to_vo(metadata_list);
@@ -347,7 +375,47 @@ pub async fn handle_scrobble(
         Ok(()) => HttpResponse::Ok().json(JsonResult::success(())),
         Err(e) => {
             log_err(&format!("scrobble error: {}", e));
-            HttpResponse::Ok().json(JsonResult::<()>::error(&e.to_string()))
+            ApiError::from(e).error_response()
+        }
+    }
+}
+
+/// 电台请求参数
+#[derive(Debug, Deserialize)]
+pub struct RadioRequest {
+    /// 种子: {"song": "id"} / {"artist": "id"} / {"genre": "Rock"}
+    pub seed: RadioSeed,
+    /// 返回数量, 默认 50, 最大 200
+    pub count: Option<usize>,
+    /// 不返回的歌曲 ID (如播放队列中已有的歌曲), 用于连续获取下一批
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// 相似歌曲电台
+///
+/// 路由: POST /api/radio
+///
+/// 返回: 按相似程度排序的歌曲列表, 最近播放过的歌曲不返回
+pub async fn handle_radio(
+    app_state: web::Data<AppState>,
+    req: web::Json<RadioRequest>,
+) -> impl Responder {
+    let count = req.count.unwrap_or(50).min(200);
+    let result = app_state
+        .data_source
+        .get_similar_songs(&req.seed, count, &req.exclude)
+        .await;
+
+    match result {
+        Ok(metadata_list) => {
+            let total = metadata_list.len() as u32;
+            let list = adapters::unified_list_to_vo(metadata_list);
+            HttpResponse::Ok().json(JsonResult::success(ListMusic { list, total }))
+        }
+        Err(e) => {
+            log_err(&format!("radio error: {}", e));
+            ApiError::from(e).error_response()
         }
     }
 }
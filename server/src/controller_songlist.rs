@@ -3,9 +3,16 @@
 /// 歌单相关接口
 use std::str;
 
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::http::header::{
+    Charset, ContentDisposition, DispositionParam, DispositionType, ExtendedValue,
+};
+use actix_web::{web, HttpRequest, HttpResponse, Responder, ResponseError};
+use lib_utils::datasource::playlist_io::{self, PathMode, PlaylistFormat};
+use base64::Engine;
+use lib_utils::datasource::types::{CoverSize, PlaylistEdit, SmartPlaylistRule};
 use serde::Deserialize;
 
+use crate::api_error::ApiError;
 use crate::{AppState, JsonResult};
 
 pub async fn handle_song_list(data: web::Data<AppState>) -> impl Responder {
@@ -15,8 +22,7 @@ pub async fn handle_song_list(data: web::Data<AppState>) -> impl Responder {
 
     match result {
         Ok(list) => HttpResponse::Ok().json(JsonResult::success(list)),
-        Err(e) => HttpResponse::InternalServerError()
-            .json(JsonResult::<()>::error(&format!("Error: {}", e))),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
@@ -31,8 +37,7 @@ pub async fn handle_song_list_songs(
         Ok(playlist_detail) => {
             HttpResponse::Ok().json(JsonResult::success(playlist_detail))
         }
-        Err(e) => HttpResponse::InternalServerError()
-            .json(JsonResult::<()>::error(&format!("Error: {}", e))),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
@@ -45,7 +50,7 @@ pub async fn handle_delete_song_list(
 
     match result {
         Ok(()) => HttpResponse::Ok().json(JsonResult::success(())),
-        Err(e) => HttpResponse::Ok().json(JsonResult::<()>::error(&format!("Error: {}", e))),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
@@ -68,7 +73,7 @@ pub async fn handle_create_song_list(
 
     match result {
         Ok(playlist) => HttpResponse::Ok().json(JsonResult::success(playlist)),
-        Err(e) => HttpResponse::Ok().json(JsonResult::<()>::error(&format!("Error: {}", e))),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
@@ -97,7 +102,239 @@ pub async fn handle_update_song_list(
 
     match result {
         Ok(()) => HttpResponse::Ok().json(JsonResult::success(())),
-        Err(e) => HttpResponse::Ok().json(JsonResult::<()>::error(&format!("Error: {}", e))),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+/// 自定义歌单封面解码后的最大字节数
+const MAX_COVER_BYTES: usize = 5 * 1024 * 1024;
+/// 允许上传的图片格式
+const COVER_MIME_TYPES: [&str; 5] = ["image/png", "image/jpeg", "image/webp", "image/gif", "image/bmp"];
+/// 保存的自定义封面边长
+const COVER_SIZE: u32 = 600;
+
+/// 上传封面接口的请求体大小限制, base64 编码后约为原图的 4/3
+pub fn cover_json_config() -> web::JsonConfig {
+    web::JsonConfig::default().limit(MAX_COVER_BYTES * 2)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SetSongListCoverBody {
+    /// data URL 格式的图片 (data:image/png;base64,...), 为空时恢复自动拼接封面
+    cover: String,
+}
+
+/// 校验上传的封面并重新编码为 webp data URL, 为空时返回空字符串
+fn encode_cover(cover: &str) -> Result<String, HttpResponse> {
+    if cover.is_empty() {
+        return Ok(String::new());
+    }
+    // 先按 base64 长度粗略判断, 避免解码过大的数据
+    if cover.len() > MAX_COVER_BYTES / 3 * 4 + 64 {
+        return Err(HttpResponse::PayloadTooLarge().json(JsonResult::<()>::error("封面图片过大")));
+    }
+    let Some((mime, bytes)) = decode_data_url(cover) else {
+        return Err(HttpResponse::BadRequest().json(JsonResult::<()>::error("封面必须是 data URL 格式的图片")));
+    };
+    if !COVER_MIME_TYPES.contains(&mime.to_lowercase().as_str()) {
+        return Err(HttpResponse::UnsupportedMediaType().json(JsonResult::<()>::error("不支持的封面图片格式")));
+    }
+    if bytes.len() > MAX_COVER_BYTES {
+        return Err(HttpResponse::PayloadTooLarge().json(JsonResult::<()>::error("封面图片过大")));
+    }
+    let webp = lib_utils::image::reencode_webp(&bytes, COVER_SIZE, 80)
+        .map_err(|e| HttpResponse::BadRequest().json(JsonResult::<()>::error(&format!("无法解析图片: {}", e))))?;
+    Ok(format!(
+        "data:image/webp;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(webp)
+    ))
+}
+
+/// 上传自定义歌单封面
+pub async fn handle_set_song_list_cover(
+    song_list_id: web::Path<String>,
+    body: web::Json<SetSongListCoverBody>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let cover = match encode_cover(&body.cover) {
+        Ok(cover) => cover,
+        Err(response) => return response,
+    };
+    match data.data_source.set_playlist_cover(&song_list_id.into_inner(), &cover).await {
+        Ok(()) => HttpResponse::Ok().json(JsonResult::success(())),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+/// 获取歌单封面: 优先使用自定义封面, 否则拼接前四张专辑封面
+pub async fn handle_song_list_cover(
+    song_list_id: web::Path<String>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let data_source = &data.data_source;
+    let playlist = match data_source.get_playlist(&song_list_id.into_inner()).await {
+        Ok(playlist) => playlist,
+        Err(e) => {
+            return ApiError::from(e).error_response()
+        }
+    };
+
+    if let Some(cover) = playlist.cover.as_deref() {
+        if cover.starts_with("data:") {
+            // 上传时已经重新编码为 webp, 其他类型 (旧数据) 不返回, 避免以上传者指定的 MIME 类型输出内容
+            match decode_data_url(cover) {
+                Some((mime, bytes)) if mime == "image/webp" => {
+                    return HttpResponse::Ok().content_type("image/webp").body(bytes);
+                }
+                _ => {}
+            }
+        } else if let Ok(bytes) = data_source.get_cover(cover, CoverSize::Medium).await {
+            // 其他情况视为数据源的封面 ID
+            return HttpResponse::Ok().content_type("image/webp").body(bytes);
+        }
+    }
+
+    // 取前四张不同专辑的封面
+    let mut cover_ids: Vec<String> = Vec::new();
+    let mut albums = Vec::new();
+    for song in &playlist.songs {
+        if let Some(cover_art) = &song.cover_art {
+            let album = song.album_id.clone().unwrap_or_else(|| song.album.clone());
+            if !albums.contains(&album) && !cover_ids.contains(cover_art) {
+                albums.push(album);
+                cover_ids.push(cover_art.clone());
+            }
+        }
+        if cover_ids.len() >= 4 {
+            break;
+        }
+    }
+
+    let mut covers = Vec::new();
+    for cover_id in cover_ids {
+        if let Ok(bytes) = data_source.get_cover(&cover_id, CoverSize::Medium).await {
+            covers.push(bytes);
+        }
+    }
+
+    match lib_utils::image::collage_cover(&covers, 600, 80) {
+        Ok(bytes) => HttpResponse::Ok().content_type("image/webp").body(bytes),
+        Err(_) => {
+            let bytes = crate::controller_song::get_cover_size("", CoverSize::Medium, &data).await;
+            HttpResponse::Ok().content_type("image/webp").body(bytes)
+        }
+    }
+}
+
+/// 解析 data:image/png;base64,xxx 格式的图片
+fn decode_data_url(url: &str) -> Option<(String, Vec<u8>)> {
+    let rest = url.strip_prefix("data:")?;
+    let (header, content) = rest.split_once(',')?;
+    let mime = header.strip_suffix(";base64")?;
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(content)
+        .ok()?;
+    Some((mime.to_string(), bytes))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SetSongListPublicBody {
+    public: bool,
+}
+
+/// 设置歌单是否公开
+pub async fn handle_set_song_list_public(
+    song_list_id: web::Path<String>,
+    body: web::Json<SetSongListPublicBody>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let result = data
+        .data_source
+        .set_playlist_public(&song_list_id.into_inner(), body.public)
+        .await;
+
+    match result {
+        Ok(()) => HttpResponse::Ok().json(JsonResult::success(())),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+/// 浏览其他用户公开的歌单
+///
+/// 本地模式目前只有默认用户 (user_id = 1), 自己的歌单不会出现在结果中, 所以总是返回空列表
+pub async fn handle_public_song_list(data: web::Data<AppState>) -> impl Responder {
+    match data.data_source.list_public_playlists().await {
+        Ok(list) => HttpResponse::Ok().json(JsonResult::success(list)),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SubscribeSongListBody {
+    subscribe: bool,
+}
+
+/// 订阅/取消订阅公开歌单
+///
+/// 不能订阅自己的歌单; 本地模式只有默认用户时没有可订阅的歌单, 会返回 404
+pub async fn handle_subscribe_song_list(
+    song_list_id: web::Path<String>,
+    body: web::Json<SubscribeSongListBody>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let result = data
+        .data_source
+        .subscribe_playlist(&song_list_id.into_inner(), body.subscribe)
+        .await;
+
+    match result {
+        Ok(()) => HttpResponse::Ok().json(JsonResult::success(())),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateSmartSongListBody {
+    name: String,
+    description: Option<String>,
+    rule: SmartPlaylistRule,
+}
+
+pub async fn handle_create_smart_song_list(
+    body: web::Json<CreateSmartSongListBody>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let data_source = &data.data_source;
+    let body = body.into_inner();
+
+    let result = data_source
+        .create_smart_playlist(&body.name, body.description.as_deref(), &body.rule)
+        .await;
+
+    match result {
+        Ok(()) => HttpResponse::Ok().json(JsonResult::success(())),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateSmartSongListBody {
+    id: String,
+    rule: SmartPlaylistRule,
+}
+
+pub async fn handle_update_smart_song_list(
+    body: web::Json<UpdateSmartSongListBody>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let data_source = &data.data_source;
+    let body = body.into_inner();
+
+    let result = data_source.update_smart_playlist(&body.id, &body.rule).await;
+
+    match result {
+        Ok(()) => HttpResponse::Ok().json(JsonResult::success(())),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
@@ -111,8 +348,7 @@ pub async fn handle_remove_song_from_songlist(
     // 获取现有播放列表
     let playlist_result = data_source.get_playlist(&song_list_id).await;
     if let Err(e) = playlist_result {
-        return HttpResponse::InternalServerError()
-            .json(JsonResult::<()>::error(&format!("Error: {}", e)));
+        return ApiError::from(e).error_response();
     }
 
     let playlist = playlist_result.unwrap();
@@ -130,7 +366,7 @@ pub async fn handle_remove_song_from_songlist(
 
     match result {
         Ok(()) => HttpResponse::Ok().json(JsonResult::success(())),
-        Err(e) => HttpResponse::Ok().json(JsonResult::<()>::error(&format!("Error: {}", e))),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
@@ -147,13 +383,202 @@ pub async fn handle_add_song_list_song(
     let data_source = &data.data_source;
     let body = body.into_inner();
 
-    // 使用新的歌曲列表更新播放列表
+    let size = body.song_ids.len();
+
+    // 追加到歌单末尾
+    let edit = PlaylistEdit::Append {
+        song_ids: body.song_ids,
+    };
+    let result = data_source.edit_playlist(&body.song_list_id, &edit).await;
+
+    match result {
+        Ok(()) => HttpResponse::Ok().json(JsonResult::success(size)),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+/// 编辑歌单歌曲: 追加 / 插入 / 移动 / 按位置删除 / 整体替换
+pub async fn handle_edit_song_list(
+    song_list_id: web::Path<String>,
+    body: web::Json<PlaylistEdit>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let data_source = &data.data_source;
     let result = data_source
-        .update_playlist(&body.song_list_id, None, None, Some(&body.song_ids))
+        .edit_playlist(&song_list_id.into_inner(), &body.into_inner())
         .await;
 
     match result {
-        Ok(()) => HttpResponse::Ok().json(JsonResult::success(body.song_ids.len())),
-        Err(e) => HttpResponse::Ok().json(JsonResult::<()>::error(&format!("Error: {}", e))),
+        Ok(()) => HttpResponse::Ok().json(JsonResult::success(())),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ExportSongListQuery {
+    /// 导出格式: m3u8 (默认) / xspf
+    format: Option<String>,
+    /// 歌曲位置写法: relative (默认) / absolute / stream
+    path_mode: Option<String>,
+}
+
+/// 导出歌单为 M3U8 或 XSPF 文件
+pub async fn handle_export_song_list(
+    song_list_id: web::Path<String>,
+    query: web::Query<ExportSongListQuery>,
+    req: HttpRequest,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let format = match query.format.as_deref() {
+        None => PlaylistFormat::M3u8,
+        Some(f) => match PlaylistFormat::parse(f) {
+            Some(f) => f,
+            None => {
+                return HttpResponse::BadRequest()
+                    .json(JsonResult::<()>::error(&format!("不支持的格式: {}", f)))
+            }
+        },
+    };
+    let path_mode = match query.path_mode.as_deref() {
+        None => PathMode::default(),
+        Some(m) => match PathMode::parse(m) {
+            Some(m) => m,
+            None => {
+                return HttpResponse::BadRequest()
+                    .json(JsonResult::<()>::error(&format!("不支持的路径模式: {}", m)))
+            }
+        },
+    };
+
+    let playlist = match data.data_source.get_playlist(&song_list_id.into_inner()).await {
+        Ok(playlist) => playlist,
+        Err(e) => {
+            return ApiError::from(e).error_response()
+        }
+    };
+
+    let conn = req.connection_info();
+    let base_url = format!("{}://{}", conn.scheme(), conn.host());
+    let body = playlist_io::export_playlist(&playlist, format, path_mode, &base_url);
+
+    let file_name = format!("{}.{}", playlist.name, format.extension());
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::FilenameExt(ExtendedValue {
+                charset: Charset::Ext("UTF-8".to_string()),
+                language_tag: None,
+                value: file_name.into_bytes(),
+            })],
+        })
+        .body(body)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ImportSongListBody {
+    /// 歌单名称, 为空时使用文件中的标题
+    name: Option<String>,
+    /// 文件格式: m3u8 / xspf, 为空时根据内容自动识别
+    format: Option<String>,
+    /// 歌单文件内容
+    content: String,
+}
+
+/// 从 M3U8 或 XSPF 文件导入歌单
+pub async fn handle_import_song_list(
+    body: web::Json<ImportSongListBody>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let body = body.into_inner();
+    let format = match body.format.as_deref() {
+        None | Some("") => None,
+        Some(f) => match PlaylistFormat::parse(f) {
+            Some(f) => Some(f),
+            None => {
+                return HttpResponse::BadRequest()
+                    .json(JsonResult::<()>::error(&format!("不支持的格式: {}", f)))
+            }
+        },
+    };
+
+    let result = playlist_io::import_playlist(
+        data.data_source.as_ref(),
+        body.name.as_deref(),
+        &body.content,
+        format,
+    )
+    .await;
+
+    match result {
+        Ok(report) => HttpResponse::Ok().json(JsonResult::success(report)),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use lib_utils::datasource::subsonic::SubsonicDataSource;
+    use mock_subsonic::MockSubsonic;
+    use std::sync::Arc;
+
+    fn data_url(mime: &str, bytes: &[u8]) -> String {
+        format!("data:{};base64,{}", mime, base64::engine::general_purpose::STANDARD.encode(bytes))
+    }
+
+    #[actix_web::test]
+    async fn test_set_song_list_cover_rejects_invalid_images() {
+        let server = MockSubsonic::start();
+        let data_source = SubsonicDataSource::new(
+            server.url.clone(),
+            server.username.clone(),
+            server.password.clone(),
+            true,
+            320,
+            "raw".to_string(),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::with_data_source(Arc::new(data_source))))
+                .app_data(cover_json_config())
+                .route("/api/set_songlist_cover/{id}", web::put().to(handle_set_song_list_cover)),
+        )
+        .await;
+
+        let cases = [
+            (data_url("text/html", b"<script>alert(1)</script>"), 415),
+            (data_url("image/svg+xml", b"<svg onload=\"alert(1)\"/>"), 415),
+            ("https://example.com/cover.png".to_string(), 400),
+            (data_url("image/png", &vec![0u8; MAX_COVER_BYTES + 1]), 413),
+            (data_url("image/png", b"not an image"), 400),
+        ];
+        for (cover, status) in cases {
+            let req = test::TestRequest::put()
+                .uri("/api/set_songlist_cover/1")
+                .set_json(serde_json::json!({ "cover": cover }))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), status, "{}", &cover[..cover.len().min(40)]);
+        }
+
+        // 有效的图片交给数据源, Subsonic 不支持自定义歌单封面
+        let req = test::TestRequest::put()
+            .uri("/api/set_songlist_cover/pl-1")
+            .set_json(serde_json::json!({ "cover": "" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 501);
+    }
+
+    #[actix_web::test]
+    async fn test_encode_cover_reencodes_to_webp() {
+        // 1x1 的 PNG
+        let png = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAQAAAC1HAwCAAAAC0lEQVR42mNkYAAAAAYAAjCB0C8AAAAASUVORK5CYII=";
+        let cover = encode_cover(png).unwrap();
+        let (mime, bytes) = decode_data_url(&cover).unwrap();
+        assert_eq!(mime, "image/webp");
+        assert_eq!(&bytes[8..12], b"WEBP");
+        assert_eq!(encode_cover("").unwrap(), "");
     }
 }
@@ -0,0 +1,191 @@
+use actix_web::error::ErrorBadRequest;
+use actix_web::{web, HttpResponse, Responder};
+use lib_utils::datasource::types::{StatsItemType, StatsPeriod, TimeRange};
+use serde::Deserialize;
+
+use crate::api_error::ApiError;
+use crate::{AppState, JsonResult};
+
+/// 统计接口的查询参数
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    /// 统计对象: song / album / artist / genre (默认 song)
+    #[serde(rename = "type")]
+    pub item_type: Option<String>,
+    /// 时间窗口开始 (毫秒时间戳, 包含)
+    pub from: Option<i64>,
+    /// 时间窗口结束 (毫秒时间戳, 不包含)
+    pub to: Option<i64>,
+    /// 返回数量 (默认 50, 最多 500)
+    pub limit: Option<u32>,
+    /// 统计周期: day / week / month (默认 day)
+    pub period: Option<String>,
+}
+
+impl StatsQuery {
+    fn range(&self) -> TimeRange {
+        TimeRange {
+            from: self.from,
+            to: self.to,
+        }
+    }
+
+    fn limit(&self) -> u32 {
+        self.limit.unwrap_or(50).min(500)
+    }
+
+    fn item_type(&self) -> anyhow::Result<StatsItemType> {
+        StatsItemType::parse(self.item_type.as_deref().unwrap_or("song"))
+    }
+}
+
+/// 播放次数排行
+///
+/// 路由: GET /api/stats/top?type=song&from=&to=&limit=
+pub async fn handle_stats_top(
+    app_state: web::Data<AppState>,
+    query: web::Query<StatsQuery>,
+) -> Result<impl Responder, actix_web::Error> {
+    let item_type = query.item_type().map_err(ErrorBadRequest)?;
+    let items = app_state
+        .data_source
+        .stats_top(item_type, query.range(), query.limit())
+        .await
+        .map_err(ApiError::from)?;
+    Ok(web::Json(JsonResult::success(items)))
+}
+
+/// 第一次收听发生在时间窗口内的歌曲 / 专辑 / 艺术家 / 风格
+///
+/// 路由: GET /api/stats/first_listened?type=artist&from=&to=&limit=
+pub async fn handle_stats_first_listened(
+    app_state: web::Data<AppState>,
+    query: web::Query<StatsQuery>,
+) -> Result<impl Responder, actix_web::Error> {
+    let item_type = query.item_type().map_err(ErrorBadRequest)?;
+    let items = app_state
+        .data_source
+        .stats_first_listened(item_type, query.range(), query.limit())
+        .await
+        .map_err(ApiError::from)?;
+    Ok(web::Json(JsonResult::success(items)))
+}
+
+/// 按天 / 周 / 月统计收听时长
+///
+/// 路由: GET /api/stats/listening_time?period=day&from=&to=
+pub async fn handle_stats_listening_time(
+    app_state: web::Data<AppState>,
+    query: web::Query<StatsQuery>,
+) -> Result<impl Responder, actix_web::Error> {
+    let period = StatsPeriod::parse(query.period.as_deref().unwrap_or("day")).map_err(ErrorBadRequest)?;
+    let times = app_state
+        .data_source
+        .stats_listening_time(period, query.range())
+        .await
+        .map_err(ApiError::from)?;
+    Ok(web::Json(JsonResult::success(times)))
+}
+
+/// 连续收听天数
+///
+/// 路由: GET /api/stats/streaks
+pub async fn handle_stats_streaks(
+    app_state: web::Data<AppState>,
+) -> Result<impl Responder, actix_web::Error> {
+    let streaks = app_state
+        .data_source
+        .stats_streaks()
+        .await
+        .map_err(ApiError::from)?;
+    Ok(web::Json(JsonResult::success(streaks)))
+}
+
+/// 按星期和小时统计的收听热力图
+///
+/// 路由: GET /api/stats/heatmap?from=&to=
+pub async fn handle_stats_heatmap(
+    app_state: web::Data<AppState>,
+    query: web::Query<StatsQuery>,
+) -> Result<impl Responder, actix_web::Error> {
+    let cells = app_state
+        .data_source
+        .stats_heatmap(query.range())
+        .await
+        .map_err(ApiError::from)?;
+    Ok(web::Json(JsonResult::success(cells)))
+}
+
+/// 年度报告
+///
+/// 路由: GET /api/wrapped/{year}
+pub async fn handle_wrapped(
+    app_state: web::Data<AppState>,
+    year: web::Path<i32>,
+) -> Result<impl Responder, actix_web::Error> {
+    let report = app_state
+        .data_source
+        .wrapped(*year)
+        .await
+        .map_err(ApiError::from)?;
+    Ok(web::Json(JsonResult::success(report)))
+}
+
+/// 年度报告分享卡片 (PNG)
+///
+/// 路由: GET /api/wrapped/{year}/card
+pub async fn handle_wrapped_card(
+    app_state: web::Data<AppState>,
+    year: web::Path<i32>,
+) -> Result<impl Responder, actix_web::Error> {
+    let png = app_state
+        .data_source
+        .wrapped_card(*year)
+        .await
+        .map_err(ApiError::from)?;
+    Ok(HttpResponse::Ok().content_type("image/png").body(png))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use lib_utils::datasource::subsonic::SubsonicDataSource;
+    use mock_subsonic::MockSubsonic;
+    use std::sync::Arc;
+
+    #[actix_web::test]
+    async fn test_stats_errors() {
+        let server = MockSubsonic::start();
+        let data_source = SubsonicDataSource::new(
+            server.url.clone(),
+            server.username.clone(),
+            server.password.clone(),
+            true,
+            320,
+            "raw".to_string(),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::with_data_source(Arc::new(data_source))))
+                .route("/api/stats/top", web::get().to(handle_stats_top))
+                .route("/api/wrapped/{year}/card", web::get().to(handle_wrapped_card)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/api/stats/top?type=mood").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+
+        // 数据源没有收听记录时返回 ApiError
+        let req = test::TestRequest::get().uri("/api/stats/top?type=artist").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 501);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], 501);
+
+        let req = test::TestRequest::get().uri("/api/wrapped/2024/card").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 501);
+    }
+}
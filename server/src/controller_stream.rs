@@ -1,17 +1,59 @@
+use actix_files::NamedFile;
 use actix_web::{web, HttpRequest, HttpResponse, Result as ActixResult};
 use futures::StreamExt;
+use lib_utils::audio::waveform;
+use lib_utils::datasource::types::{AudioStream, StreamOptions};
 use lib_utils::log;
+use serde::Deserialize;
+use std::path::Path;
+use tokio::io::AsyncWriteExt;
 
-use crate::AppState;
+use crate::api_error::ApiError;
+use crate::{AppState, JsonResult};
+
+#[derive(Deserialize, Debug)]
+pub struct StreamQuery {
+    /// 音质档位: mobile / standard / high / lossless
+    profile: Option<String>,
+    /// 最大比特率 (kbps), 优先于 profile
+    max_bitrate: Option<u32>,
+    /// 转码格式, 优先于 profile
+    format: Option<String>,
+}
 
 /// 代理音频流请求到数据源
 pub async fn stream_song(
     song_id: web::Path<String>,
+    query: web::Query<StreamQuery>,
     req: HttpRequest,
     app_state: web::Data<AppState>,
 ) -> ActixResult<HttpResponse> {
     log::log_info(&format!("Stream request for song: {}", song_id));
 
+    // 已离线下载的歌曲直接返回本地文件 (支持 Range)
+    if let Ok(AudioStream::LocalFile(path)) = app_state.data_source.get_audio_stream(&song_id).await {
+        if path.is_file() {
+            log::log_info(&format!("Stream from local file: {:?}", path));
+            let file = NamedFile::open_async(&path).await?;
+            return Ok(file.into_response(&req));
+        }
+    }
+
+    // 音质参数: 先取档位预设, 再用显式参数覆盖, 未指定的由数据源使用配置默认值
+    let query = query.into_inner();
+    let mut options = match query.profile.as_deref() {
+        Some(profile) => StreamOptions::from_profile(profile).ok_or_else(|| {
+            actix_web::error::ErrorBadRequest(format!("Unknown stream profile: {}", profile))
+        })?,
+        None => StreamOptions::default(),
+    };
+    if query.max_bitrate.is_some() {
+        options.max_bitrate = query.max_bitrate;
+    }
+    if query.format.is_some() {
+        options.format = query.format;
+    }
+
     // 获取客户端的 Range 请求头(用于断点续传)
     let range_header = req
         .headers()
@@ -26,11 +68,11 @@ pub async fn stream_song(
     // 从数据源获取音频流
     let response = app_state
         .data_source
-        .stream_song(&song_id, range_header)
+        .stream_song(&song_id, range_header, &options)
         .await
         .map_err(|e| {
             log::log_err(&format!("Failed to stream song {}: {:?}", song_id, e));
-            actix_web::error::ErrorInternalServerError(format!("Failed to stream song: {}", e))
+            ApiError::from(e)
         })?;
 
     // 获取响应状态码
@@ -67,3 +109,198 @@ pub async fn stream_song(
 
     Ok(builder.streaming(stream))
 }
+
+#[derive(Deserialize, Debug)]
+pub struct WaveformQuery {
+    /// 返回格式: bin (默认) / json
+    format: Option<String>,
+}
+
+/// 生成波形时下载音频流的大小上限
+const MAX_WAVEFORM_DOWNLOAD: u64 = 256 * 1024 * 1024;
+
+/// 把音频流写入临时文件, 超过大小上限或下载失败时删除临时文件
+async fn download_to_file<S, B, E>(mut stream: S, content_length: Option<u64>, path: &Path) -> ActixResult<()>
+where
+    S: futures::Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    E: std::fmt::Debug + std::fmt::Display + 'static,
+{
+    let too_large = || actix_web::error::ErrorPayloadTooLarge("Audio stream too large for waveform");
+    if content_length.is_some_and(|len| len > MAX_WAVEFORM_DOWNLOAD) {
+        return Err(too_large());
+    }
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+
+    let mut file = tokio::fs::File::create(path).await?;
+    let mut size: u64 = 0;
+    let result: ActixResult<()> = async {
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(actix_web::error::ErrorBadGateway)?;
+            size += chunk.as_ref().len() as u64;
+            if size > MAX_WAVEFORM_DOWNLOAD {
+                return Err(too_large());
+            }
+            file.write_all(chunk.as_ref()).await?;
+        }
+        file.flush().await?;
+        Ok(())
+    }
+    .await;
+
+    drop(file);
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(path).await;
+    }
+    result
+}
+
+/// 获取歌曲的波形峰值数据
+///
+/// 路由: GET /api/waveform/{song_id}
+///
+/// 查询参数:
+/// - format: `bin` 返回字节数组 (每个区间的最小值、最大值交替排列, 为 -127~127 的 i8),
+///   `json` 返回 `{"buckets": 1000, "min": [...], "max": [...]}`
+///
+/// 本地文件由后台任务预先生成; 其他数据源第一次请求时把音频流下载到临时文件后分析, 结果缓存在 `waveform_dir` 中
+pub async fn handle_waveform(
+    song_id: web::Path<String>,
+    query: web::Query<WaveformQuery>,
+    app_state: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let waveform_dir = app_state.config.waveform_dir.clone();
+    let data_source = &app_state.data_source;
+
+    // 本地文件 (包括已离线下载的歌曲) 以文件路径作为缓存 key, 与后台任务一致
+    let local_path = match data_source.get_audio_stream(&song_id).await {
+        Ok(AudioStream::LocalFile(path)) if path.is_file() => Some(path.to_string_lossy().to_string()),
+        _ => None,
+    };
+    let key = match &local_path {
+        Some(path) => path.clone(),
+        None => format!("{:?}:{}", data_source.source_type(), song_id),
+    };
+
+    let waveform = match waveform::load_cached(&waveform_dir, &key) {
+        Some(waveform) => waveform,
+        None => {
+            let result = match local_path {
+                Some(path) => web::block(move || waveform::analyze_file(&path)).await?,
+                None => {
+                    let response = data_source
+                        .stream_song(&song_id, None, &StreamOptions::default())
+                        .await
+                        .map_err(ApiError::from)?;
+                    if !response.status().is_success() {
+                        return Err(actix_web::error::ErrorBadGateway(format!(
+                            "Stream request failed: {}",
+                            response.status()
+                        )));
+                    }
+                    // 音频流先写入临时文件再解码, 避免把整首歌读入内存
+                    let part_path = waveform::cache_path(&waveform_dir, &key).with_extension("part");
+                    let content_length = response.content_length();
+                    download_to_file(response.bytes_stream(), content_length, &part_path).await?;
+                    let path = part_path.to_string_lossy().to_string();
+                    let result = web::block(move || waveform::analyze_file(&path)).await;
+                    let _ = tokio::fs::remove_file(&part_path).await;
+                    result?
+                }
+            };
+            let waveform = result.map_err(|e| {
+                log::log_err(&format!("Failed to generate waveform {}: {}", song_id, e));
+                actix_web::error::ErrorUnprocessableEntity(e.to_string())
+            })?;
+            if let Err(e) = waveform::save_cached(&waveform_dir, &key, &waveform) {
+                log::log_err(&format!("Failed to save waveform {}: {}", song_id, e));
+            }
+            waveform
+        }
+    };
+
+    match query.format.as_deref() {
+        Some("json") => Ok(HttpResponse::Ok().json(JsonResult::success(waveform))),
+        _ => Ok(HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .insert_header(("Cache-Control", "max-age=86400"))
+            .body(waveform.to_bytes())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use lib_utils::datasource::subsonic::SubsonicDataSource;
+    use mock_subsonic::MockSubsonic;
+    use std::sync::Arc;
+
+    #[actix_web::test]
+    async fn test_stream_proxies_subsonic_range() {
+        let server = MockSubsonic::start();
+        let data_source = SubsonicDataSource::new(
+            server.url.clone(),
+            server.username.clone(),
+            server.password.clone(),
+            true,
+            320,
+            "raw".to_string(),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::with_data_source(Arc::new(data_source))))
+                .route("/api/stream/{id}", web::get().to(stream_song)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/stream/so-2?profile=mobile")
+            .insert_header(("Range", "bytes=0-9"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 206);
+        assert_eq!(resp.headers().get("content-range").unwrap(), "bytes 0-9/8192");
+        let body = test::read_body(resp).await;
+        assert_eq!(body.to_vec(), server.library().songs[1].audio_data()[..10].to_vec());
+
+        let request = &server.requests()[0];
+        assert_eq!(request.param("maxBitRate").as_deref(), Some("128"));
+        assert_eq!(request.param("format").as_deref(), Some("mp3"));
+    }
+
+    #[actix_web::test]
+    async fn test_waveform_remote_temp_file() {
+        let server = MockSubsonic::start();
+        let data_source = SubsonicDataSource::new(
+            server.url.clone(),
+            server.username.clone(),
+            server.password.clone(),
+            true,
+            320,
+            "raw".to_string(),
+        );
+        let dir = std::env::temp_dir().join(format!("musicflow-waveform-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut state = AppState::with_data_source(Arc::new(data_source));
+        state.config.waveform_dir = dir.to_string_lossy().to_string();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .route("/api/waveform/{id}", web::get().to(handle_waveform)),
+        )
+        .await;
+
+        // 模拟服务器返回的不是有效音频, 解码失败后临时文件被删除
+        let req = test::TestRequest::get().uri("/api/waveform/so-2").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 422);
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        assert!(files.is_empty());
+        assert!(server.requests().iter().any(|r| r.route().ends_with("/stream")));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,25 +1,56 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpResponse, Responder, ResponseError};
+use lib_utils::audio::fingerprint;
+use lib_utils::datasource::local::{connect_db, duplicates};
 use lib_utils::log;
 use serde::{Deserialize, Serialize};
 
+use crate::api_error::ApiError;
 use crate::{AppState, JsonResult};
 
 pub async fn handle_scan_music(app_state: web::Data<AppState>) -> impl Responder {
     match app_state.data_source.scan_music().await {
         Ok(_) => HttpResponse::Ok().json(JsonResult::success(0)),
-        Err(e) => HttpResponse::InternalServerError()
-            .json(JsonResult::<()>::error(&format!("Error: {}", e))),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+pub async fn handle_clear_cache(app_state: web::Data<AppState>) -> impl Responder {
+    log::log_info("clear data source cache");
+    match app_state.data_source.clear_cache().await {
+        Ok(_) => HttpResponse::Ok().json(JsonResult::success(0)),
+        Err(e) => ApiError::from(e).error_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SyncLibraryQuery {
+    #[serde(default)]
+    full: bool,
+}
+
+pub async fn handle_sync_library(
+    query: web::Query<SyncLibraryQuery>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    log::log_info(&format!("sync library, full={}", query.full));
+    match app_state.data_source.sync_library(query.full).await {
+        Ok(_) => HttpResponse::Ok().json(JsonResult::success(0)),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
 pub async fn handle_scan_status(app_state: web::Data<AppState>) -> impl Responder {
     match app_state.data_source.scan_status().await {
         Ok(progress) => HttpResponse::Ok().json(JsonResult::success(progress)),
-        Err(e) => HttpResponse::InternalServerError()
-            .json(JsonResult::<()>::error(&format!("Error: {}", e))),
+        Err(e) => ApiError::from(e).error_response(),
     }
 }
 
+/// 当前数据源支持的功能, 前端据此隐藏不可用的功能
+pub async fn handle_capabilities(app_state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(JsonResult::success(app_state.data_source.capabilities()))
+}
+
 // 前端日志记录
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct FrontendLog {
@@ -51,3 +82,63 @@ pub async fn handle_delete_meta(
             .json(JsonResult::<()>::error(&format!("Error: {}", e))),
     }
 }
+
+/// 重复歌曲报告, 按声学指纹相似度分组, 每组按音质从高到低排序
+///
+/// 路由: GET /api/duplicates
+pub async fn handle_duplicates(app_state: web::Data<AppState>) -> impl Responder {
+    if !app_state.config.is_local_mode() {
+        return HttpResponse::Forbidden().json(JsonResult::<()>::error("仅本地模式下可检测重复歌曲"));
+    }
+    let result = web::block(|| {
+        let conn = connect_db()?;
+        duplicates::find_duplicates(&conn, fingerprint::DUPLICATE_SIMILARITY)
+    })
+    .await;
+    match result {
+        Ok(Ok(groups)) => HttpResponse::Ok().json(JsonResult::success(groups)),
+        Ok(Err(e)) => {
+            log::log_err(&format!("find duplicates error: {}", e));
+            HttpResponse::InternalServerError().json(JsonResult::<()>::error(&e.to_string()))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(JsonResult::<()>::error(&e.to_string())),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeDuplicatesRequest {
+    /// 同一组的歌曲
+    song_ids: Vec<String>,
+    /// 保留的歌曲, 不传时保留音质最好的
+    keep: Option<String>,
+}
+
+/// 保留一组重复歌曲中的一首, 其他副本的播放记录、收藏和歌单引用转到保留的歌曲上, 然后从音乐库移除
+///
+/// 路由: POST /api/duplicates/merge
+pub async fn handle_merge_duplicates(
+    app_state: web::Data<AppState>,
+    body: web::Json<MergeDuplicatesRequest>,
+) -> impl Responder {
+    if !app_state.config.is_local_mode() {
+        return HttpResponse::Forbidden().json(JsonResult::<()>::error("仅本地模式下可移除重复歌曲"));
+    }
+    if body.song_ids.len() < 2 {
+        return HttpResponse::BadRequest().json(JsonResult::<()>::error("至少需要两首歌曲"));
+    }
+    log::log_info(&format!("merge duplicates: {:?}, keep: {:?}", body.song_ids, body.keep));
+    let body = body.into_inner();
+    let result = web::block(move || {
+        let mut conn = connect_db()?;
+        duplicates::merge(&mut conn, &body.song_ids, body.keep.as_deref())
+    })
+    .await;
+    match result {
+        Ok(Ok(result)) => HttpResponse::Ok().json(JsonResult::success(result)),
+        Ok(Err(e)) => {
+            log::log_err(&format!("merge duplicates error: {}", e));
+            HttpResponse::BadRequest().json(JsonResult::<()>::error(&e.to_string()))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(JsonResult::<()>::error(&e.to_string())),
+    }
+}
@@ -14,8 +14,7 @@ use lib_utils::{log, readmeta};
 use serde::{Deserialize, Serialize};
 use std::io;
 use std::path::Path;
-use std::sync::{Arc, RwLock};
-use chrono::Local;
+use std::sync::Arc;
 
 mod controller_album;
 mod controller_artist;
@@ -23,12 +22,15 @@ mod controller_genre;
 mod controller_playlist;
 mod controller_song;
 mod controller_songlist;
+mod controller_stats;
 mod controller_stream;
 mod controller_tool;
 mod controller_user;
 mod controller_search;
 mod controller_favorite;
+mod controller_pin;
 mod adapters;
+mod api_error;
 
 use controller_album::*;
 use controller_artist::*;
@@ -37,64 +39,12 @@ use controller_playlist::*;
 use controller_song::*;
 use controller_search::*;
 use controller_songlist::*;
+use controller_stats::*;
 use controller_stream::*;
 use controller_tool::*;
 use controller_user::*;
 use controller_favorite::*;
-
-// 每日随机歌曲缓存结构
-struct DailyRandomCache {
-    // 缓存数据
-    data: RwLock<Vec<lib_utils::datasource::UnifiedMetadata>>,
-    // 缓存日期（用于判断是否需要刷新）
-    cached_date: RwLock<String>, // 格式: "2026-01-05"
-}
-
-impl DailyRandomCache {
-    fn new() -> Self {
-        Self {
-            data: RwLock::new(Vec::new()),
-            cached_date: RwLock::new(String::new()),
-        }
-    }
-
-    // 获取今天的日期字符串
-    fn today() -> String {
-        Local::now().format("%Y-%m-%d").to_string()
-    }
-
-    // 检查缓存是否过期
-    fn is_expired(&self) -> bool {
-        let cached = self.cached_date.read().unwrap();
-        *cached != Self::today()
-    }
-
-    // 更新缓存
-    async fn refresh(&self, data_source: &Arc<dyn MusicDataSource>) -> Result<(), Box<dyn std::error::Error>> {
-        // 从数据源获取随机歌曲（最大 200 首）
-        let songs = data_source.get_random_songs(Some(200), None, None, None).await?;
-
-        // 更新缓存
-        *self.data.write().unwrap() = songs;
-        *self.cached_date.write().unwrap() = Self::today();
-
-        Ok(())
-    }
-
-    // 获取缓存数据（如果过期则刷新）
-    async fn get_or_refresh(
-        &self,
-        data_source: &Arc<dyn MusicDataSource>,
-    ) -> Result<Vec<lib_utils::datasource::UnifiedMetadata>, Box<dyn std::error::Error>> {
-        // 如果缓存过期，刷新缓存
-        if self.is_expired() {
-            self.refresh(data_source).await?;
-        }
-
-        // 返回缓存数据的克隆
-        Ok(self.data.read().unwrap().clone())
-    }
-}
+use controller_pin::*;
 
 // 应用状态
 #[derive(Clone)]
@@ -103,8 +53,27 @@ struct AppState {
     web_path: String,
     music_path: String,
     data_source: Arc<dyn MusicDataSource>,
-    // 每日随机歌曲缓存
-    daily_random_songs: Arc<DailyRandomCache>,
+}
+
+#[cfg(test)]
+impl AppState {
+    /// 测试用的应用状态, 不读取 conf/config.json
+    fn with_data_source(data_source: Arc<dyn MusicDataSource>) -> Self {
+        let config: lib_utils::config::Config = serde_json::from_value(serde_json::json!({
+            "ip": "127.0.0.1",
+            "port": 0,
+            "web_dir": "",
+            "db_path": "",
+            "debug": false,
+        }))
+        .unwrap();
+        Self {
+            config,
+            web_path: String::new(),
+            music_path: String::new(),
+            data_source,
+        }
+    }
 }
 
 #[actix_web::main]
@@ -120,14 +89,16 @@ async fn main() -> io::Result<()> {
     let port = config.port.clone();
     let music_dir = config.music_dir.clone().replace("\\", "/");
 
-    if config.is_local_mode() {
-        // 本地模式，初始化数据库
+    if config.uses_database() {
+        // 本地模式(或聚合模式包含本地数据源)、开启了 Subsonic 镜像或离线下载时，初始化数据库
         let result = table::init();
         if result.is_err() {
             let _ = log::log_err(&format!("init table error: {}", result.err().unwrap()));
             return Err(io::Error::new(io::ErrorKind::Other, "init table error"));
         }
+    }
 
+    if config.has_local_source() {
         // 扫描音乐文件，构建音乐 ID 到 Music 实例的映射表
         log::log_info(&format!("Music dir: {}, Web dir: {}", music_dir, web_dir));
 
@@ -138,15 +109,6 @@ async fn main() -> io::Result<()> {
     let data_source = create_data_source(&config);
     log::log_info(&format!("Data source created: {:?}", data_source.source_type()));
 
-    // 初始化每日随机歌曲缓存
-    let daily_cache = Arc::new(DailyRandomCache::new());
-    // 预热缓存（异步加载，不阻塞启动）
-    if let Err(e) = daily_cache.refresh(&data_source).await {
-        log::log_err(&format!("Failed to initialize daily random cache: {}", e));
-    } else {
-        log::log_info("Daily random cache initialized successfully");
-    }
-
     // 映射音乐文件的静态路径
     let music_path = "/music";
 
@@ -164,7 +126,6 @@ async fn main() -> io::Result<()> {
             web_path: web_dir.to_string(),
             music_path: music_path.to_string(),
             data_source: data_source.clone(),
-            daily_random_songs: daily_cache.clone(),
         });
 
         App::new()
@@ -178,7 +139,9 @@ async fn main() -> io::Result<()> {
             .route("/api/single/{song_id}", get().to(handle_get_metadata))
             .route("/api/scrobble", post().to(handle_scrobble))
             .route("/api/random_songs", get().to(handle_get_random_songs))
+            .route("/api/radio", post().to(handle_radio))
             .route("/api/stream/{song_id}", get().to(stream_song))
+            .route("/api/waveform/{song_id}", get().to(handle_waveform))
             .route("/api/cover/small/{song_id}", get().to(get_cover_small))
             .route("/api/cover/medium/{song_id}", get().to(get_cover_medium))
             .route("/api/cover/large/{song_id}", get().to(get_cover_large))
@@ -199,9 +162,23 @@ async fn main() -> io::Result<()> {
             .route("/api/delete_songlist/{songlist_id}", delete().to(handle_delete_song_list))
             .route("/api/create_songlist", post().to(handle_create_song_list))
             .route("/api/update_songlist", put().to(handle_update_song_list))
+            .route("/api/create_smart_songlist", post().to(handle_create_smart_song_list))
+            .route("/api/update_smart_songlist", put().to(handle_update_smart_song_list))
             .route("/api/remove_song_from_songlist/{songlist_id}/{song_id}",
                 delete().to(handle_remove_song_from_songlist))
             .route("/api/add_song_to_songlist", put().to(handle_add_song_list_song))
+            .route("/api/edit_songlist/{songlist_id}", put().to(handle_edit_song_list))
+            .route("/api/songlist_cover/{songlist_id}", get().to(handle_song_list_cover))
+            .service(
+                web::resource("/api/set_songlist_cover/{songlist_id}")
+                    .app_data(cover_json_config())
+                    .route(put().to(handle_set_song_list_cover)),
+            )
+            .route("/api/set_songlist_public/{songlist_id}", put().to(handle_set_song_list_public))
+            .route("/api/public_songlists", get().to(handle_public_song_list))
+            .route("/api/subscribe_songlist/{songlist_id}", put().to(handle_subscribe_song_list))
+            .route("/api/export_songlist/{songlist_id}", get().to(handle_export_song_list))
+            .route("/api/import_songlist", post().to(handle_import_song_list))
 
             // 专辑相关接口
             .route("/api/album", post().to(handle_get_album))
@@ -231,6 +208,11 @@ async fn main() -> io::Result<()> {
             .route("/api/del/{song_id}", get().to(handle_delete_meta))
             .route("/api/scan_music", post().to(handle_scan_music))
             .route("/api/scan_status", get().to(handle_scan_status))
+            .route("/api/clear_cache", post().to(handle_clear_cache))
+            .route("/api/sync_library", post().to(handle_sync_library))
+            .route("/api/capabilities", get().to(handle_capabilities))
+            .route("/api/duplicates", get().to(handle_duplicates))
+            .route("/api/duplicates/merge", post().to(handle_merge_duplicates))
 
             // 搜索相关接口
             .route("/api/search", get().to(handle_search))
@@ -241,6 +223,24 @@ async fn main() -> io::Result<()> {
             .route("/api/starred", get().to(handle_get_starred))
             .route("/api/is_starred", get().to(handle_is_starred))
 
+            // 评分相关接口
+            .route("/api/rating", post().to(handle_set_rating))
+            .route("/api/rating", get().to(handle_get_rating))
+
+            // 离线下载相关接口
+            .route("/api/pin", post().to(handle_pin))
+            .route("/api/unpin", post().to(handle_unpin))
+            .route("/api/pin_status", get().to(handle_pin_status))
+
+            // 收听统计相关接口
+            .route("/api/stats/top", get().to(handle_stats_top))
+            .route("/api/stats/first_listened", get().to(handle_stats_first_listened))
+            .route("/api/stats/listening_time", get().to(handle_stats_listening_time))
+            .route("/api/stats/streaks", get().to(handle_stats_streaks))
+            .route("/api/stats/heatmap", get().to(handle_stats_heatmap))
+            .route("/api/wrapped/{year}", get().to(handle_wrapped))
+            .route("/api/wrapped/{year}/card", get().to(handle_wrapped_card))
+
             // 添加静态文件服务
             .service(actix_files::Files::new(music_path, &music_dir).show_files_listing())
             .service(actix_files::Files::new("/", &web_dir).index_file("index.html"))